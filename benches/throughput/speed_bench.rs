@@ -0,0 +1,66 @@
+#[path = "../support/mod.rs"]
+#[allow(dead_code)]
+mod support;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use scah::{Query, Save, parse};
+use std::hint::black_box;
+use support::SPEED_BENCH_SIZES;
+
+// To check a change against the parser it started from, run
+// `just bench-rust-throughput-save` on the starting commit, then
+// `just bench-rust-throughput-compare` on the change: criterion reports the
+// throughput of each group relative to the saved baseline.
+
+// Plain selectors, which need none of the sibling counts or `:has()`
+// bookkeeping, next to ones that do, so both paths of the parser are timed.
+const PLAIN_SELECTORS: [&str; 2] = ["div.item a", "h2"];
+const STRUCTURAL_SELECTORS: [&str; 2] = ["div.item:has(a) li:nth-of-type(2)", "h2:last-child"];
+
+fn generate_html(count: usize) -> String {
+    let mut html = String::with_capacity(count * 150);
+    html.push_str("<html><body>");
+    for i in 0..count {
+        html.push_str(&format!(
+            r#"<div class="item c{i}"><h2>Title {i}</h2><p>Some <b>text</b> &amp; more</p><ul><li><a href="/x/{i}">link</a></li><li>two</li></ul></div>"#
+        ));
+    }
+    html.push_str("</body></html>");
+    html
+}
+
+fn bench_selectors(c: &mut Criterion, group_name: &str, selectors: [&str; 2]) {
+    let mut group = c.benchmark_group(group_name);
+
+    for size in SPEED_BENCH_SIZES.iter() {
+        let content = generate_html(*size);
+        group.throughput(Throughput::Bytes(content.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("scah", size), &content, |b, html| {
+            let queries = &[
+                Query::all(selectors[0], Save::all())
+                    .expect("throughput bench selector should parse")
+                    .build(),
+                Query::all(selectors[1], Save::only_text_content())
+                    .expect("throughput bench selector should parse")
+                    .build(),
+            ];
+            b.iter(|| {
+                let store = parse(black_box(html), queries);
+                black_box(store.elements.len());
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_plain(c: &mut Criterion) {
+    bench_selectors(c, "throughput_plain_selectors", PLAIN_SELECTORS);
+}
+
+fn bench_structural(c: &mut Criterion) {
+    bench_selectors(c, "throughput_structural_selectors", STRUCTURAL_SELECTORS);
+}
+
+criterion_group!(benches, bench_plain, bench_structural);
+criterion_main!(benches);
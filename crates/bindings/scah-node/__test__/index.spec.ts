@@ -1,6 +1,6 @@
 import { test, expect } from 'bun:test'
 
-import { parse, Query } from '../index'
+import { parse, parseBytes, Query } from '../index'
 
 test('Basic selection', () => {
   const html = `
@@ -28,7 +28,11 @@ test('Basic selection', () => {
     Hello World
     <a href="https://example.com">Example Website</a>
   `,
+    outerHtml: undefined,
     textContent: 'Hello World Example Website',
+    startTagRange: { start: 3, end: 8 },
+    outerRange: { start: 3, end: 87 },
+    location: { line: 2, column: 3 },
   })
 
   let a = div?.get('a').at(0)
@@ -39,7 +43,11 @@ test('Basic selection', () => {
     id: undefined,
     attributes: { href: 'https://example.com' },
     innerHtml: `Example Website`,
+    outerHtml: undefined,
     textContent: 'Example Website',
+    startTagRange: { start: 29, end: 59 },
+    outerRange: { start: 29, end: 78 },
+    location: { line: 4, column: 5 },
   })
 })
 
@@ -141,14 +149,21 @@ test('find 5_000 anchor tags', () => {
 
   const links = store.get('a')?.map((e) => e.toJson())
 
-  const generated_links = Array.from({ length: 5000 }, (_, i) => ({
-    name: 'a',
-    id: undefined,
-    class: undefined,
-    attributes: { href: `/post/${i}` },
-    innerHtml: `<b>Post</b> &lt;${i}&gt;`,
-    textContent: `Post &lt;${i}&gt;`,
-  }))
+  const generated_links = Array.from({ length: 5000 }, (_, i) => {
+    const start = html.indexOf(`<a href="/post/${i}">`)
+    return {
+      name: 'a',
+      id: undefined,
+      class: undefined,
+      attributes: { href: `/post/${i}` },
+      innerHtml: `<b>Post</b> &lt;${i}&gt;`,
+      outerHtml: undefined,
+      textContent: `Post &lt;${i}&gt;`,
+      startTagRange: { start, end: html.indexOf('>', start) + 1 },
+      outerRange: { start, end: html.indexOf('</a>', start) + 4 },
+      location: { line: 1, column: start + 1 },
+    }
+  })
 
   expect(links).toEqual(generated_links)
 })
@@ -180,3 +195,58 @@ test('Save defaults omitted object to false', () => {
   expect(span?.innerHtml).toBeNull()
   expect(span?.textContent).toBeNull()
 })
+
+test('Elements know where they are in the source', () => {
+  const html = `<main>\n  <a href="/one">One</a>\n</main>`
+  const query = Query.all('a').build()
+  const store = parse(html, [query])
+
+  const a = store.get('a')?.at(0)
+  const source = Buffer.from(html)
+  const startTag = a?.startTagRange
+  expect(source.subarray(startTag?.start, startTag?.end).toString()).toBe('<a href="/one">')
+  const outer = a?.outerRange
+  expect(source.subarray(outer?.start, outer?.end).toString()).toBe('<a href="/one">One</a>')
+  expect(a?.location).toEqual({ line: 2, column: 3 })
+})
+
+test('Source ranges count UTF-8 bytes', () => {
+  const html = `<p>Café ✓</p>\n<a href="/ü">Ü</a>`
+  const store = parse(html, [Query.all('a').build()])
+
+  const a = store.get('a')?.at(0)
+  const outer = a?.outerRange
+  expect(outer?.start).toBe(Buffer.byteLength('<p>Café ✓</p>\n'))
+  expect(Buffer.from(html).subarray(outer?.start, outer?.end).toString()).toBe('<a href="/ü">Ü</a>')
+  expect(a?.location).toEqual({ line: 2, column: 1 })
+})
+
+test('Save outer html', () => {
+  const html = `<ul><li>One<li><a href="/two">Two</a><br></ul>`
+  const store = parse(html, [
+    Query.all('li', { outerHtml: true }).build(),
+    Query.all('br', { outerHtml: true }).build(),
+    Query.all('a', { innerHtml: true }).build(),
+  ])
+
+  expect(store.get('li')?.map((li) => li.outerHtml)).toEqual(['<li>One', '<li><a href="/two">Two</a><br>'])
+  expect(store.get('br')?.at(0)?.outerHtml).toBe('<br>')
+  expect(store.get('a')?.at(0)?.outerHtml).toBeNull()
+})
+
+test('Parse bytes in other encodings', () => {
+  // `<meta charset=shift_jis><p>日本語</p>` in Shift_JIS.
+  const shiftJis = Buffer.concat([
+    Buffer.from('<meta charset=shift_jis><p>'),
+    Buffer.from([0x93, 0xfa, 0x96, 0x7b, 0x8c, 0xea]),
+    Buffer.from('</p>'),
+  ])
+  let store = parseBytes(shiftJis, [Query.all('p', { textContent: true }).build()])
+  expect(store.encoding).toBe('Shift_JIS')
+  expect(store.get('p')?.at(0)?.textContent).toBe('日本語')
+
+  store = parseBytes(Buffer.from('<p>caf\xe9</p>', 'latin1'), [Query.all('p', { textContent: true }).build()], 'windows-1252')
+  expect(store.encoding).toBe('windows-1252')
+  expect(store.get('p')?.at(0)?.textContent).toBe('café')
+  expect(parse('<p>café</p>', [Query.all('p', { textContent: true }).build()]).encoding).toBe('UTF-8')
+})
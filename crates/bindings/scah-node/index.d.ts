@@ -8,7 +8,11 @@ export declare class Element {
   getAttribute(key: string): string | null
   get attributes(): object
   get innerHtml(): string | null
+  get outerHtml(): string | null
   get textContent(): string | null
+  get startTagRange(): SourceRange | null
+  get outerRange(): SourceRange | null
+  get location(): SourceLocation | null
   get(query: string): Array<Element>
 }
 export type JsElement = Element
@@ -40,6 +44,11 @@ export type JsQueryFactory = QueryFactory
 export declare class Store {
   get(query: string): Array<JsElement> | null
   get length(): number
+  /**
+   * The name of the encoding the document was decoded from, such as
+   * `"UTF-8"` or `"Shift_JIS"`.
+   */
+  get encoding(): string
 }
 export type JSStore = Store
 
@@ -49,12 +58,39 @@ export interface JsonElement {
   class?: string
   attributes: object
   innerHtml?: string
+  outerHtml?: string
   textContent?: string
+  startTagRange: SourceRange
+  outerRange: SourceRange
+  location: SourceLocation
 }
 
 export declare function parse(html: string, queries: Array<JsQuery>): Store
 
+/**
+ * Parses an HTML document given as bytes, decoded from `encoding` (the
+ * `charset` it was served with) or else the encoding the document declares.
+ */
+export declare function parseBytes(data: Buffer, queries: Array<JsQuery>, encoding?: string | undefined | null): Store
+
 export interface Save {
   innerHtml?: boolean
+  outerHtml?: boolean
   textContent?: boolean
+  templateContent?: boolean
+}
+
+/** A 1-based line and column of the source. */
+export interface SourceLocation {
+  line: number
+  column: number
+}
+
+/**
+ * A range of the source in UTF-8 bytes, so it slices `Buffer.from(html)`
+ * rather than the string once the source has non-ASCII characters.
+ */
+export interface SourceRange {
+  start: number
+  end: number
 }
@@ -1,4 +1,5 @@
-use ::scah::{Attribute, ElementId, Store};
+use ::scah::{ElementId, OwnedAttribute, OwnedStore};
+use std::ops::Range;
 
 use napi::bindgen_prelude::*;
 use napi::{Env, Error, Result, Status};
@@ -11,12 +12,62 @@ pub struct JsonElement<'a> {
     pub class: Option<String>,
     pub attributes: Object<'a>,
     pub inner_html: Option<String>,
+    pub outer_html: Option<String>,
     pub text_content: Option<String>,
+    pub start_tag_range: SourceRange,
+    pub outer_range: SourceRange,
+    pub location: SourceLocation,
+}
+
+/// A range of the source in UTF-8 bytes, so it slices `Buffer.from(html)`
+/// rather than the string once the source has non-ASCII characters.
+#[napi(object)]
+pub struct SourceRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl TryFrom<&Range<usize>> for SourceRange {
+    type Error = Error;
+
+    fn try_from(range: &Range<usize>) -> Result<Self> {
+        Ok(Self {
+            start: to_u32(range.start)?,
+            end: to_u32(range.end)?,
+        })
+    }
+}
+
+/// A 1-based line and column of the source.
+#[napi(object)]
+pub struct SourceLocation {
+    pub line: u32,
+    pub column: u32,
+}
+
+impl TryFrom<::scah::SourceLocation> for SourceLocation {
+    type Error = Error;
+
+    fn try_from(location: ::scah::SourceLocation) -> Result<Self> {
+        Ok(Self {
+            line: to_u32(location.line)?,
+            column: to_u32(location.column)?,
+        })
+    }
+}
+
+fn to_u32(value: usize) -> Result<u32> {
+    u32::try_from(value).map_err(|_| {
+        Error::new(
+            Status::GenericFailure,
+            format!("Source position {value} is too large for a u32"),
+        )
+    })
 }
 
 #[napi(js_name = "Element")]
 pub struct JsElement {
-    pub(super) store: std::sync::Arc<Store<'static, 'static>>,
+    pub(super) store: std::sync::Arc<OwnedStore>,
     pub(super) id: ElementId,
 }
 
@@ -31,12 +82,16 @@ impl JsElement {
             .expect("The Element ID should be valid");
 
         let json = JsonElement {
-            name: element.name.to_string(),
-            id: element.id.map(|s| s.to_string()),
-            class: element.class.map(|s| s.to_string()),
+            name: element.name.clone(),
+            id: element.id.clone(),
+            class: element.class.clone(),
             attributes: self.attributes(env)?,
-            inner_html: element.inner_html.map(|s| s.to_string()),
+            inner_html: element.inner_html(&self.store).map(|s| s.to_string()),
+            outer_html: element.outer_html(&self.store).map(|s| s.to_string()),
             text_content: element.text_content(&self.store).map(|s| s.to_string()),
+            start_tag_range: (&element.start_tag_range).try_into()?,
+            outer_range: (&element.outer_range).try_into()?,
+            location: element.location.try_into()?,
         };
 
         Ok(json)
@@ -44,7 +99,10 @@ impl JsElement {
 
     #[napi(getter)]
     pub fn name(&self) -> Option<&str> {
-        self.store.elements.get(self.id.index()).map(|e| e.name)
+        self.store
+            .elements
+            .get(self.id.index())
+            .map(|e| e.name.as_str())
     }
 
     #[napi(getter)]
@@ -52,12 +110,15 @@ impl JsElement {
         self.store
             .elements
             .get(self.id.index())
-            .and_then(|e| e.class)
+            .and_then(|e| e.class.as_deref())
     }
 
     #[napi(getter)]
     pub fn id(&self) -> Option<&str> {
-        self.store.elements.get(self.id.index()).and_then(|e| e.id)
+        self.store
+            .elements
+            .get(self.id.index())
+            .and_then(|e| e.id.as_deref())
     }
 
     #[napi]
@@ -78,8 +139,8 @@ impl JsElement {
             .and_then(|e| e.attributes(&self.store));
 
         if let Some(attrs) = attributes {
-            for Attribute { key, value } in attrs {
-                object.set(*key, *value)?
+            for OwnedAttribute { key, value, .. } in attrs {
+                object.set(key, value.as_deref())?
             }
         }
         Ok(object)
@@ -90,7 +151,15 @@ impl JsElement {
         self.store
             .elements
             .get(self.id.index())
-            .and_then(|e| e.inner_html)
+            .and_then(|e| e.inner_html(&self.store))
+    }
+
+    #[napi(getter)]
+    pub fn outer_html(&self) -> Option<&str> {
+        self.store
+            .elements
+            .get(self.id.index())
+            .and_then(|e| e.outer_html(&self.store))
     }
 
     #[napi(getter)]
@@ -101,6 +170,33 @@ impl JsElement {
             .and_then(|e| e.text_content(&self.store))
     }
 
+    #[napi(getter)]
+    pub fn start_tag_range(&self) -> Result<Option<SourceRange>> {
+        self.store
+            .elements
+            .get(self.id.index())
+            .map(|e| (&e.start_tag_range).try_into())
+            .transpose()
+    }
+
+    #[napi(getter)]
+    pub fn outer_range(&self) -> Result<Option<SourceRange>> {
+        self.store
+            .elements
+            .get(self.id.index())
+            .map(|e| (&e.outer_range).try_into())
+            .transpose()
+    }
+
+    #[napi(getter)]
+    pub fn location(&self) -> Result<Option<SourceLocation>> {
+        self.store
+            .elements
+            .get(self.id.index())
+            .map(|e| e.location.try_into())
+            .transpose()
+    }
+
     #[napi]
     pub fn get(&self, query: String) -> Result<Vec<JsElement>> {
         let element = self
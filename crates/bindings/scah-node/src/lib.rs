@@ -2,7 +2,7 @@ use napi::Result;
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
 
-use ::scah::{Query, QueryMultiplexer, Reader, XHtmlParser};
+use ::scah::{Query, QueryMultiplexer, Reader, XHtmlParser, decode_html};
 
 use std::sync::Arc;
 
@@ -23,21 +23,44 @@ fn parse(html: String, queries: Vec<Reference<JsQuery>>) -> Result<JSStore> {
         ));
     }
 
-    let html = Arc::new(html);
-    let html_bytes = html.as_ref().as_bytes();
-    let html_bytes = unsafe { std::slice::from_raw_parts(html_bytes.as_ptr(), html_bytes.len()) };
     let queries_rs = queries
         .iter()
         .map(|q| q.query.clone())
         .collect::<Vec<Query>>();
 
-    let slice = unsafe { std::slice::from_raw_parts(queries_rs.as_ptr(), queries_rs.len()) };
-    let selectors = QueryMultiplexer::new(slice);
-    let mut parser = XHtmlParser::with_capacity(selectors, html_bytes.len());
-    let mut reader = Reader::from_bytes(html_bytes);
+    let selectors = QueryMultiplexer::new(&queries_rs);
+    let mut parser = XHtmlParser::with_capacity(selectors, html.len());
+    let mut reader = Reader::new(&html);
     while parser.next(&mut reader) {}
 
-    let store = std::sync::Arc::new(parser.matches());
+    let store = Arc::new(parser.matches().into_owned());
 
-    Ok(JSStore { store, _html: html })
+    Ok(JSStore { store })
+}
+
+/// Parses an HTML document given as bytes, decoded from `encoding` (the
+/// `charset` it was served with) or else the encoding the document declares.
+#[napi]
+#[allow(dead_code)]
+fn parse_bytes(
+    data: Buffer,
+    queries: Vec<Reference<JsQuery>>,
+    encoding: Option<String>,
+) -> Result<JSStore> {
+    if queries.is_empty() {
+        return Err(napi::Error::new(
+            napi::Status::ArrayExpected,
+            "No queries where passed".to_owned(),
+        ));
+    }
+
+    let queries_rs = queries
+        .iter()
+        .map(|q| q.query.clone())
+        .collect::<Vec<Query>>();
+
+    let document = decode_html(&data, encoding.as_deref());
+    let store = Arc::new(document.parse(&queries_rs).into_owned());
+
+    Ok(JSStore { store })
 }
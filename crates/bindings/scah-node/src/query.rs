@@ -9,7 +9,9 @@ use napi_derive::napi;
 #[derive(Clone, Copy, Debug)]
 pub struct JsSave {
     pub inner_html: Option<bool>,
+    pub outer_html: Option<bool>,
     pub text_content: Option<bool>,
+    pub template_content: Option<bool>,
 }
 
 #[napi]
@@ -18,7 +20,19 @@ impl JsSave {
     pub fn only_inner_html() -> Self {
         Self {
             inner_html: Some(true),
+            outer_html: Some(false),
             text_content: Some(false),
+            template_content: None,
+        }
+    }
+
+    #[napi]
+    pub fn only_outer_html() -> Self {
+        Self {
+            inner_html: Some(false),
+            outer_html: Some(true),
+            text_content: Some(false),
+            template_content: None,
         }
     }
 
@@ -26,7 +40,9 @@ impl JsSave {
     pub fn only_text_content() -> Self {
         Self {
             inner_html: Some(false),
+            outer_html: Some(false),
             text_content: Some(true),
+            template_content: None,
         }
     }
 
@@ -34,7 +50,9 @@ impl JsSave {
     pub fn all() -> Self {
         Self {
             inner_html: Some(true),
+            outer_html: Some(true),
             text_content: Some(true),
+            template_content: None,
         }
     }
 
@@ -42,22 +60,33 @@ impl JsSave {
     pub fn none() -> Self {
         Self {
             inner_html: Some(false),
+            outer_html: Some(false),
             text_content: Some(false),
+            template_content: None,
         }
     }
 
     #[napi]
-    pub fn new(inner_html: Option<bool>, text_content: Option<bool>) -> Self {
+    pub fn new(
+        inner_html: Option<bool>,
+        text_content: Option<bool>,
+        template_content: Option<bool>,
+        outer_html: Option<bool>,
+    ) -> Self {
         Self {
             inner_html,
+            outer_html,
             text_content,
+            template_content,
         }
     }
 
     fn to_save(self) -> Save {
         Save {
             inner_html: self.inner_html.unwrap_or(false),
+            outer_html: self.outer_html.unwrap_or(false),
             text_content: self.text_content.unwrap_or(false),
+            template_content: self.template_content.unwrap_or(false),
         }
     }
 }
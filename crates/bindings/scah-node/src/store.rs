@@ -1,14 +1,13 @@
 use napi_derive::napi;
 
 use super::elements::JsElement;
-use scah::Store;
+use scah::OwnedStore;
 
 use std::sync::Arc;
 
 #[napi(js_name = "Store")]
 pub struct JSStore {
-    pub(crate) store: Arc<Store<'static, 'static>>,
-    pub(crate) _html: Arc<String>,
+    pub(crate) store: Arc<OwnedStore>,
 }
 
 #[napi]
@@ -29,4 +28,11 @@ impl JSStore {
     pub fn length(&self) -> i64 {
         self.store.elements.len() as i64
     }
+
+    /// The name of the encoding the document was decoded from, such as
+    /// `"UTF-8"` or `"Shift_JIS"`.
+    #[napi(getter)]
+    pub fn encoding(&self) -> &'static str {
+        self.store.encoding
+    }
 }
@@ -27,7 +27,23 @@ class Element:
     @property
     def inner_html(self) -> typing.Optional[builtins.str]: ...
     @property
+    def outer_html(self) -> typing.Optional[builtins.str]: ...
+    @property
     def text_content(self) -> typing.Optional[builtins.str]: ...
+    @property
+    def start_tag_range(self) -> typing.Optional[tuple[builtins.int, builtins.int]]:
+        r"""
+        The `(start, end)` of the start tag in the source, in UTF-8 bytes:
+        slice `html.encode()` with them, not `html`.
+        """
+    @property
+    def outer_range(self) -> typing.Optional[tuple[builtins.int, builtins.int]]:
+        r"""
+        The `(start, end)` of the whole element in the source, in UTF-8
+        bytes, like `start_tag_range`.
+        """
+    @property
+    def location(self) -> typing.Optional[tuple[builtins.int, builtins.int]]: ...
     def get_attribute(self, key: builtins.str) -> typing.Optional[builtins.str]: ...
     def get(self, query: builtins.str) -> builtins.list[Element]: ...
     def keys(self) -> builtins.list[builtins.str]: ...
@@ -62,17 +78,33 @@ class Save:
     @staticmethod
     def only_inner_html() -> Save: ...
     @staticmethod
+    def only_outer_html() -> Save: ...
+    @staticmethod
     def only_text_content() -> Save: ...
     @staticmethod
     def all() -> Save: ...
     @staticmethod
     def none() -> Save: ...
-    def __new__(cls, inner_html: builtins.bool = False, text_content: builtins.bool = False) -> Save: ...
+    def __new__(cls, inner_html: builtins.bool = False, text_content: builtins.bool = False, template_content: builtins.bool = False, outer_html: builtins.bool = False) -> Save: ...
+    def with_outer_html(self) -> Save: ...
+    def with_template_content(self) -> Save: ...
 
 @typing.final
 class Store:
     def get(self, query: builtins.str) -> typing.Optional[builtins.list[Element]]: ...
     def __len__(self) -> builtins.int: ...
+    @property
+    def encoding(self) -> builtins.str:
+        r"""
+        The name of the encoding the document was decoded from, such as
+        `"UTF-8"` or `"Shift_JIS"`.
+        """
 
 def parse(html: builtins.str, queries: typing.Sequence[PyQuery]) -> Store: ...
 
+def parse_bytes(data: bytes, queries: typing.Sequence[PyQuery], encoding: typing.Optional[builtins.str] = None) -> Store:
+    r"""
+    Parses an HTML document given as bytes, decoded from `encoding` (the
+    `charset` it was served with) or else the encoding the document declares.
+    """
+
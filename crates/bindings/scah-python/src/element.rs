@@ -2,13 +2,13 @@ use pyo3::exceptions::PyValueError;
 use pyo3::types::PyDict;
 use pyo3::{Bound, IntoPyObjectExt, prelude::*};
 use pyo3_stub_gen::derive::{gen_stub_pyclass, gen_stub_pymethods};
-use scah_core::{Attribute, ElementId, Store};
+use scah_core::{ElementId, OwnedAttribute, OwnedStore};
 use std::sync::Arc;
 
 #[gen_stub_pyclass]
 #[pyclass(module = "scah", name = "Element")]
 pub struct PyElement {
-    pub(crate) store: Arc<Store<'static, 'static>>,
+    pub(crate) store: Arc<OwnedStore>,
     pub(crate) id: ElementId,
 }
 
@@ -17,7 +17,10 @@ pub struct PyElement {
 impl PyElement {
     #[getter]
     pub fn name(&self) -> Option<&str> {
-        self.store.elements.get(self.id.index()).map(|e| e.name)
+        self.store
+            .elements
+            .get(self.id.index())
+            .map(|e| e.name.as_str())
     }
 
     #[getter]
@@ -25,12 +28,15 @@ impl PyElement {
         self.store
             .elements
             .get(self.id.index())
-            .and_then(|e| e.class)
+            .and_then(|e| e.class.as_deref())
     }
 
     #[getter]
     pub fn id(&self) -> Option<&str> {
-        self.store.elements.get(self.id.index()).and_then(|e| e.id)
+        self.store
+            .elements
+            .get(self.id.index())
+            .and_then(|e| e.id.as_deref())
     }
 
     pub fn get_attribute(&self, key: String) -> Option<&str> {
@@ -50,8 +56,8 @@ impl PyElement {
             .and_then(|e| e.attributes(&self.store));
 
         if let Some(attrs) = attributes {
-            for Attribute { key, value } in attrs {
-                object.set_item(*key, *value)?
+            for OwnedAttribute { key, value, .. } in attrs {
+                object.set_item(key, value.as_deref())?
             }
         }
         Ok(object)
@@ -62,7 +68,15 @@ impl PyElement {
         self.store
             .elements
             .get(self.id.index())
-            .and_then(|e| e.inner_html)
+            .and_then(|e| e.inner_html(&self.store))
+    }
+
+    #[getter]
+    pub fn outer_html(&self) -> Option<&str> {
+        self.store
+            .elements
+            .get(self.id.index())
+            .and_then(|e| e.outer_html(&self.store))
     }
 
     #[getter]
@@ -73,6 +87,34 @@ impl PyElement {
             .and_then(|e| e.text_content(&self.store))
     }
 
+    /// The `(start, end)` of the start tag in the source, in UTF-8 bytes:
+    /// slice `html.encode()` with them, not `html`.
+    #[getter]
+    pub fn start_tag_range(&self) -> Option<(usize, usize)> {
+        self.store
+            .elements
+            .get(self.id.index())
+            .map(|e| (e.start_tag_range.start, e.start_tag_range.end))
+    }
+
+    /// The `(start, end)` of the whole element in the source, in UTF-8
+    /// bytes, like `start_tag_range`.
+    #[getter]
+    pub fn outer_range(&self) -> Option<(usize, usize)> {
+        self.store
+            .elements
+            .get(self.id.index())
+            .map(|e| (e.outer_range.start, e.outer_range.end))
+    }
+
+    #[getter]
+    pub fn location(&self) -> Option<(usize, usize)> {
+        self.store
+            .elements
+            .get(self.id.index())
+            .map(|e| (e.location.line, e.location.column))
+    }
+
     pub fn get(&self, query: String) -> PyResult<Vec<PyElement>> {
         let element = self
             .store
@@ -100,7 +142,11 @@ impl PyElement {
             "class",
             "attributes",
             "inner_html",
+            "outer_html",
             "text_content",
+            "start_tag_range",
+            "outer_range",
+            "location",
         ]
     }
 
@@ -111,7 +157,11 @@ impl PyElement {
             "class" => self.class_name().into_bound_py_any(py),
             "attributes" => self.attributes(py).and_then(|a| a.into_bound_py_any(py)),
             "inner_html" => self.inner_html().into_bound_py_any(py),
+            "outer_html" => self.outer_html().into_bound_py_any(py),
             "text_content" => self.text_content().into_bound_py_any(py),
+            "start_tag_range" => self.start_tag_range().into_bound_py_any(py),
+            "outer_range" => self.outer_range().into_bound_py_any(py),
+            "location" => self.location().into_bound_py_any(py),
             _ => Err(pyo3::exceptions::PyKeyError::new_err(key.to_string())),
         }
     }
@@ -120,8 +170,7 @@ impl PyElement {
 #[gen_stub_pyclass]
 #[pyclass(module = "scah", name = "Store")]
 pub(crate) struct PyStore {
-    pub(crate) store: Arc<Store<'static, 'static>>,
-    pub(crate) _html: Arc<String>,
+    pub(crate) store: Arc<OwnedStore>,
 }
 
 #[gen_stub_pymethods]
@@ -141,4 +190,11 @@ impl PyStore {
     fn __len__(&self) -> usize {
         self.store.elements.len()
     }
+
+    /// The name of the encoding the document was decoded from, such as
+    /// `"UTF-8"` or `"Shift_JIS"`.
+    #[getter]
+    fn encoding(&self) -> &'static str {
+        self.store.encoding
+    }
 }
@@ -1,6 +1,6 @@
 use pyo3::prelude::*;
 use pyo3_stub_gen::{define_stub_info_gatherer, derive::gen_stub_pyfunction};
-use scah_core::{Query, QueryMultiplexer, Reader, XHtmlParser};
+use scah_core::{Query, QueryMultiplexer, Reader, XHtmlParser, decode_html};
 
 use std::sync::Arc;
 
@@ -15,32 +15,47 @@ use element::{PyElement, PyStore};
 #[gen_stub_pyfunction]
 #[pyfunction]
 fn parse(html: String, queries: Vec<PyRef<PyQuery>>) -> PyResult<PyStore> {
-    let html = Arc::new(html);
-    let html_bytes = html.as_ref().as_bytes();
-    let html_bytes = unsafe { std::slice::from_raw_parts(html_bytes.as_ptr(), html_bytes.len()) };
-
     let queries_rs = queries
         .iter()
         .map(|q| q.query.clone())
         .collect::<Box<[Query]>>();
 
-    let slice = unsafe {
-        std::slice::from_raw_parts(queries_rs.as_ref().as_ptr(), queries_rs.as_ref().len())
-    };
-    let selectors = QueryMultiplexer::new(slice);
-    let mut parser = XHtmlParser::with_capacity(selectors, html_bytes.len());
+    let selectors = QueryMultiplexer::new(&queries_rs);
+    let mut parser = XHtmlParser::with_capacity(selectors, html.len());
 
-    let mut reader = Reader::from_bytes(html_bytes);
+    let mut reader = Reader::new(&html);
     while parser.next(&mut reader) {}
 
-    let store = std::sync::Arc::new(parser.matches());
+    let store = Arc::new(parser.matches().into_owned());
+
+    Ok(PyStore { store })
+}
+
+/// Parses an HTML document given as bytes, decoded from `encoding` (the
+/// `charset` it was served with) or else the encoding the document declares.
+#[gen_stub_pyfunction]
+#[pyfunction]
+#[pyo3(signature = (data, queries, encoding=None))]
+fn parse_bytes(
+    data: &[u8],
+    queries: Vec<PyRef<PyQuery>>,
+    encoding: Option<&str>,
+) -> PyResult<PyStore> {
+    let queries_rs = queries
+        .iter()
+        .map(|q| q.query.clone())
+        .collect::<Box<[Query]>>();
+
+    let document = decode_html(data, encoding);
+    let store = Arc::new(document.parse(&queries_rs).into_owned());
 
-    Ok(PyStore { store, _html: html })
+    Ok(PyStore { store })
 }
 
 #[pymodule]
 fn scah(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(parse, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_bytes, m)?)?;
     m.add_class::<PySave>()?;
     m.add_class::<PyQuery>()?;
     m.add_class::<PyQueryBuilder>()?;
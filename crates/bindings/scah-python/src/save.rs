@@ -19,6 +19,13 @@ impl PySave {
         }
     }
 
+    #[staticmethod]
+    pub fn only_outer_html() -> Self {
+        Self {
+            save: Save::only_outer_html(),
+        }
+    }
+
     #[staticmethod]
     pub fn only_text_content() -> Self {
         Self {
@@ -37,13 +44,32 @@ impl PySave {
     }
 
     #[new]
-    #[pyo3(signature = (inner_html=false, text_content=false))]
-    pub fn new(inner_html: bool, text_content: bool) -> Self {
+    #[pyo3(signature = (inner_html=false, text_content=false, template_content=false, outer_html=false))]
+    pub fn new(
+        inner_html: bool,
+        text_content: bool,
+        template_content: bool,
+        outer_html: bool,
+    ) -> Self {
         Self {
             save: Save {
                 inner_html,
+                outer_html,
                 text_content,
+                template_content,
             },
         }
     }
+
+    pub fn with_outer_html(&self) -> Self {
+        Self {
+            save: self.save.with_outer_html(),
+        }
+    }
+
+    pub fn with_template_content(&self) -> Self {
+        Self {
+            save: self.save.with_template_content(),
+        }
+    }
 }
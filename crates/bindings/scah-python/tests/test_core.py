@@ -1,6 +1,6 @@
 import pytest
 
-from scah import Query, Save, parse
+from scah import Query, Save, parse, parse_bytes
 HTML = """
 <span class="hello" id="world" hello="world">
     Hello <a href="https://www.example.com">World</a>
@@ -143,4 +143,55 @@ def test_multiple_root_queries():
     ]
 
     # The QueryMultiplexer evaluates all of these against the token stream simultaneously
-    store_api = parse(html_api, queries)
\ No newline at end of file
+    store_api = parse(html_api, queries)
+
+
+def test_source_spans():
+    html = "<main>\n  <a href='/one'>One</a>\n</main>"
+    q = Query.all("a", Save.none()).build()
+    store = parse(html, [q])
+
+    a = store.get("a")[0]
+    source = html.encode()
+    start, end = a.start_tag_range
+    assert source[start:end] == b"<a href='/one'>"
+    start, end = a.outer_range
+    assert source[start:end] == b"<a href='/one'>One</a>"
+    assert a.location == (2, 3)
+
+
+def test_source_spans_count_utf8_bytes():
+    html = "<p>Café ✓</p>\n<a href='/ü'>Ü</a>"
+    store = parse(html, [Query.all("a", Save.none()).build()])
+
+    a = store.get("a")[0]
+    start, end = a.outer_range
+    assert start == len("<p>Café ✓</p>\n".encode())
+    assert html.encode()[start:end].decode() == "<a href='/ü'>Ü</a>"
+    assert a.location == (2, 1)
+
+
+def test_outer_html():
+    html = "<ul><li>One<li><a href='/two'>Two</a><br></ul>"
+    store = parse(html, [
+        Query.all("li", Save.only_outer_html()).build(),
+        Query.all("br", Save.none().with_outer_html()).build(),
+        Query.all("a", Save(inner_html=True)).build(),
+    ])
+
+    assert [li.outer_html for li in store.get("li")] == ["<li>One", "<li><a href='/two'>Two</a><br>"]
+    assert store.get("br")[0].outer_html == "<br>"
+    assert store.get("a")[0].outer_html is None
+
+
+def test_parse_bytes():
+    data = "<meta charset=shift_jis><p>日本語</p>".encode("shift_jis")
+    store = parse_bytes(data, [Query.all("p", Save.all()).build()])
+
+    assert store.encoding == "Shift_JIS"
+    assert store.get("p")[0].text_content == "日本語"
+
+    store = parse_bytes(b"<p>caf\xe9</p>", [Query.all("p", Save.all()).build()], encoding="windows-1252")
+    assert store.encoding == "windows-1252"
+    assert store.get("p")[0].text_content == "café"
+    assert parse("<p>café</p>", [Query.all("p", Save.all()).build()]).encoding == "UTF-8"
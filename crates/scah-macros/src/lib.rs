@@ -2,8 +2,9 @@ use proc_macro::TokenStream;
 use proc_macro2::Span;
 use quote::quote;
 use scah_query_ir::{
-    AttributeSelectionKind, Combinator, ElementPredicate, Query, QueryBuilder, QuerySection, Save,
-    SelectionKind, Transition,
+    AttributeSelectionKind, CaseSensitivity, Combinator, ElementPredicate, Nth,
+    PredicateSelections, PseudoClass, Query, QueryBuilder, QuerySection, Save, SelectionKind,
+    Transition,
 };
 use syn::parse::{Parse, ParseStream};
 use syn::{Expr, LitStr, Result, Token, braced, parenthesized};
@@ -94,10 +95,22 @@ fn parse_kind(input: ParseStream<'_>) -> Result<SelectionKind> {
 }
 
 fn parse_save_expr(expr: &Expr) -> Result<Save> {
+    if let Expr::MethodCall(call) = expr {
+        let save = parse_save_expr(&call.receiver)?;
+        return match call.method.to_string().as_str() {
+            "with_outer_html" if call.args.is_empty() => Ok(save.with_outer_html()),
+            "with_template_content" if call.args.is_empty() => Ok(save.with_template_content()),
+            _ => Err(syn::Error::new_spanned(
+                &call.method,
+                "unsupported save method in query!, expected with_outer_html() or with_template_content()",
+            )),
+        };
+    }
+
     let Expr::Call(call) = expr else {
         return Err(syn::Error::new_spanned(
             expr,
-            "expected Save::all(), Save::none(), Save::only_inner_html(), or Save::only_text_content()",
+            "expected Save::all(), Save::none(), Save::only_inner_html(), Save::only_outer_html(), or Save::only_text_content()",
         ));
     };
     if !call.args.is_empty() {
@@ -125,6 +138,7 @@ fn parse_save_expr(expr: &Expr) -> Result<Save> {
             ["Save", "all"] => Ok(Save::all()),
             ["Save", "none"] => Ok(Save::none()),
             ["Save", "only_inner_html"] => Ok(Save::only_inner_html()),
+            ["Save", "only_outer_html"] => Ok(Save::only_outer_html()),
             ["Save", "only_text_content"] => Ok(Save::only_text_content()),
             _ => Err(syn::Error::new_spanned(
                 expr,
@@ -163,23 +177,13 @@ fn expand_query(node: &QueryNode) -> Result<proc_macro2::TokenStream> {
         .map(QueryBuilder::build)
         .map_err(|err| syn::Error::new(node.selector.span(), err.to_string()))?;
 
-    let attribute_consts = compiled
-        .states
-        .iter()
-        .enumerate()
-        .map(attribute_const_tokens)
-        .collect::<Vec<_>>();
-    let class_consts = compiled
-        .states
-        .iter()
-        .enumerate()
-        .map(class_const_tokens)
-        .collect::<Vec<_>>();
+    let mut consts = Vec::new();
     let states = compiled
         .states
         .iter()
         .enumerate()
-        .map(|(index, transition)| transition_tokens(index, transition));
+        .map(|(index, transition)| transition_tokens(index, transition, &mut consts))
+        .collect::<Vec<_>>();
     let sections = compiled.queries.iter().map(query_section_tokens);
     let num_states = compiled.states.len();
     let num_sections = compiled.queries.len();
@@ -187,8 +191,7 @@ fn expand_query(node: &QueryNode) -> Result<proc_macro2::TokenStream> {
 
     Ok(quote! {
         {
-            #(#attribute_consts)*
-            #(#class_consts)*
+            #(#consts)*
             ::scah::StaticQuery::<#num_states, #num_sections>::new(
                 [#(#states),*],
                 [#(#sections),*],
@@ -198,63 +201,175 @@ fn expand_query(node: &QueryNode) -> Result<proc_macro2::TokenStream> {
     })
 }
 
-fn class_const_tokens((index, transition): (usize, &Transition<'_>)) -> proc_macro2::TokenStream {
-    let ident = syn::Ident::new(&format!("__SCAH_CLASSES_{index}"), Span::call_site());
-    let classes = transition
-        .predicate
-        .classes
-        .as_slice()
-        .iter()
-        .map(|class| quote! { #class });
-    quote! {
-        const #ident: &[&'static str] = &[#(#classes),*];
+fn transition_tokens(
+    index: usize,
+    transition: &Transition<'_>,
+    consts: &mut Vec<proc_macro2::TokenStream>,
+) -> proc_macro2::TokenStream {
+    let guard = combinator_tokens(&transition.guard);
+    let predicate = predicate_tokens(&index.to_string(), &transition.predicate, consts);
+    if transition.alternative {
+        quote! { ::scah::Transition::new_const(#guard, #predicate).into_alternative() }
+    } else {
+        quote! { ::scah::Transition::new_const(#guard, #predicate) }
     }
 }
 
-fn attribute_const_tokens(
-    (index, transition): (usize, &Transition<'_>),
+/// Builds the `new_const` call for a predicate, pushing its slices into `consts`
+/// as named items (nested pseudo-class predicates are suffixed onto `path`).
+fn predicate_tokens(
+    path: &str,
+    predicate: &ElementPredicate<'_>,
+    consts: &mut Vec<proc_macro2::TokenStream>,
 ) -> proc_macro2::TokenStream {
-    let ident = syn::Ident::new(&format!("__SCAH_ATTRS_{index}"), Span::call_site());
-    let attrs = transition
-        .predicate
+    let name = option_str_tokens(predicate.name.as_deref());
+    let namespace = option_str_tokens(predicate.namespace);
+    let id = option_str_tokens(predicate.id.as_deref());
+
+    let classes_ident = syn::Ident::new(&format!("__SCAH_CLASSES_{path}"), Span::call_site());
+    let classes = predicate
+        .classes
+        .as_slice()
+        .iter()
+        .map(AsRef::<str>::as_ref);
+    consts.push(quote! {
+        const #classes_ident: &[::std::borrow::Cow<'static, str>] =
+            &[#(::std::borrow::Cow::Borrowed(#classes)),*];
+    });
+
+    let attrs_ident = syn::Ident::new(&format!("__SCAH_ATTRS_{path}"), Span::call_site());
+    let attrs = predicate
         .attributes
         .as_slice()
         .iter()
         .map(attribute_selection_tokens);
-    quote! {
-        const #ident: &[::scah::AttributeSelection<'static>] = &[#(#attrs),*];
-    }
-}
+    consts.push(quote! {
+        const #attrs_ident: &[::scah::AttributeSelection<'static>] = &[#(#attrs),*];
+    });
 
-fn transition_tokens(index: usize, transition: &Transition<'_>) -> proc_macro2::TokenStream {
-    let guard = combinator_tokens(&transition.guard);
-    let predicate = predicate_tokens(index, &transition.predicate);
-    quote! { ::scah::Transition::new_const(#guard, #predicate) }
-}
+    let pseudo_ident = syn::Ident::new(&format!("__SCAH_PSEUDO_{path}"), Span::call_site());
+    let pseudo_classes = predicate
+        .pseudo_classes
+        .as_slice()
+        .iter()
+        .enumerate()
+        .map(|(index, pseudo_class)| {
+            pseudo_class_tokens(&format!("{path}_{index}"), pseudo_class, consts)
+        })
+        .collect::<Vec<_>>();
+    consts.push(quote! {
+        const #pseudo_ident: &[::scah::PseudoClass<'static>] = &[#(#pseudo_classes),*];
+    });
 
-fn predicate_tokens(index: usize, predicate: &ElementPredicate<'_>) -> proc_macro2::TokenStream {
-    let name = option_str_tokens(predicate.name);
-    let id = option_str_tokens(predicate.id);
-    let classes_ident = syn::Ident::new(&format!("__SCAH_CLASSES_{index}"), Span::call_site());
-    let attrs_ident = syn::Ident::new(&format!("__SCAH_ATTRS_{index}"), Span::call_site());
     quote! {
         ::scah::ElementPredicate::new_const(
             #name,
+            #namespace,
             #id,
             ::scah::ClassSelections::from_static(#classes_ident),
             ::scah::AttributeSelections::from_static(#attrs_ident),
+            ::scah::PseudoClassSelections::from_static(#pseudo_ident),
         )
     }
 }
 
+fn pseudo_class_tokens(
+    path: &str,
+    pseudo_class: &PseudoClass<'_>,
+    consts: &mut Vec<proc_macro2::TokenStream>,
+) -> proc_macro2::TokenStream {
+    match pseudo_class {
+        PseudoClass::Not(predicates) => {
+            let predicates = predicate_selections_tokens(path, predicates, consts);
+            quote! { ::scah::PseudoClass::Not(#predicates) }
+        }
+        PseudoClass::Is(predicates) => {
+            let predicates = predicate_selections_tokens(path, predicates, consts);
+            quote! { ::scah::PseudoClass::Is(#predicates) }
+        }
+        PseudoClass::NthChild { nth, of } => {
+            let nth = nth_tokens(nth);
+            let of = nth_of_tokens(path, of, consts);
+            quote! { ::scah::PseudoClass::NthChild { nth: #nth, of: #of } }
+        }
+        PseudoClass::NthOfType(nth) => {
+            let nth = nth_tokens(nth);
+            quote! { ::scah::PseudoClass::NthOfType(#nth) }
+        }
+        PseudoClass::NthLastChild { nth, of } => {
+            let nth = nth_tokens(nth);
+            let of = nth_of_tokens(path, of, consts);
+            quote! { ::scah::PseudoClass::NthLastChild { nth: #nth, of: #of } }
+        }
+        PseudoClass::NthLastOfType(nth) => {
+            let nth = nth_tokens(nth);
+            quote! { ::scah::PseudoClass::NthLastOfType(#nth) }
+        }
+        PseudoClass::Has {
+            children,
+            descendants,
+        } => {
+            let children =
+                predicate_selections_tokens(&format!("{path}_CHILDREN"), children, consts);
+            let descendants =
+                predicate_selections_tokens(&format!("{path}_DESCENDANTS"), descendants, consts);
+            quote! {
+                ::scah::PseudoClass::Has {
+                    children: #children,
+                    descendants: #descendants,
+                }
+            }
+        }
+    }
+}
+
+fn nth_tokens(nth: &Nth) -> proc_macro2::TokenStream {
+    let a = nth.a;
+    let b = nth.b;
+    quote! { ::scah::Nth::new(#a, #b) }
+}
+
+fn nth_of_tokens(
+    path: &str,
+    of: &Option<PredicateSelections<'_>>,
+    consts: &mut Vec<proc_macro2::TokenStream>,
+) -> proc_macro2::TokenStream {
+    match of {
+        Some(of) => {
+            let of = predicate_selections_tokens(path, of, consts);
+            quote! { Some(#of) }
+        }
+        None => quote! { None },
+    }
+}
+
+fn predicate_selections_tokens(
+    path: &str,
+    predicates: &PredicateSelections<'_>,
+    consts: &mut Vec<proc_macro2::TokenStream>,
+) -> proc_macro2::TokenStream {
+    let ident = syn::Ident::new(&format!("__SCAH_PREDICATES_{path}"), Span::call_site());
+    let predicates = predicates
+        .as_slice()
+        .iter()
+        .enumerate()
+        .map(|(index, predicate)| predicate_tokens(&format!("{path}_{index}"), predicate, consts))
+        .collect::<Vec<_>>();
+    consts.push(quote! {
+        const #ident: &[::scah::ElementPredicate<'static>] = &[#(#predicates),*];
+    });
+    quote! { ::scah::PredicateSelections::from_static(#ident) }
+}
+
 fn attribute_selection_tokens(
     attribute: &scah_query_ir::AttributeSelection<'_>,
 ) -> proc_macro2::TokenStream {
-    let name = attribute.name;
-    let value = option_str_tokens(attribute.value);
+    let name = attribute.name.as_ref();
+    let value = option_str_tokens(attribute.value.as_deref());
     let kind = attribute_selection_kind_tokens(&attribute.kind);
+    let case = case_sensitivity_tokens(attribute.case);
     quote! {
-        ::scah::AttributeSelection::new_const(#name, #value, #kind)
+        ::scah::AttributeSelection::new_const(#name, #value, #kind, #case)
     }
 }
 
@@ -280,8 +395,17 @@ fn query_section_tokens(section: &QuerySection<'_>) -> proc_macro2::TokenStream
 
 fn save_tokens(save: Save) -> proc_macro2::TokenStream {
     let inner_html = save.inner_html;
+    let outer_html = save.outer_html;
     let text_content = save.text_content;
-    quote! { ::scah::Save { inner_html: #inner_html, text_content: #text_content } }
+    let template_content = save.template_content;
+    quote! {
+        ::scah::Save {
+            inner_html: #inner_html,
+            outer_html: #outer_html,
+            text_content: #text_content,
+            template_content: #template_content,
+        }
+    }
 }
 
 fn selection_kind_tokens(kind: SelectionKind) -> proc_macro2::TokenStream {
@@ -297,7 +421,6 @@ fn combinator_tokens(kind: &Combinator) -> proc_macro2::TokenStream {
         Combinator::Descendant => quote! { ::scah::Combinator::Descendant },
         Combinator::NextSibling => quote! { ::scah::Combinator::NextSibling },
         Combinator::SubsequentSibling => quote! { ::scah::Combinator::SubsequentSibling },
-        Combinator::Namespace => quote! { ::scah::Combinator::Namespace },
     }
 }
 
@@ -317,6 +440,13 @@ fn attribute_selection_kind_tokens(kind: &AttributeSelectionKind) -> proc_macro2
     }
 }
 
+fn case_sensitivity_tokens(case: CaseSensitivity) -> proc_macro2::TokenStream {
+    match case {
+        CaseSensitivity::Sensitive => quote! { ::scah::CaseSensitivity::Sensitive },
+        CaseSensitivity::Insensitive => quote! { ::scah::CaseSensitivity::Insensitive },
+    }
+}
+
 fn option_str_tokens(value: Option<&str>) -> proc_macro2::TokenStream {
     match value {
         Some(value) => quote! { Some(#value) },
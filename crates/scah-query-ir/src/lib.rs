@@ -6,7 +6,9 @@ pub use query::compiler::{
     SelectionKind, SelectorParseError, StaticQuery, Transition, TransitionId,
 };
 pub use query::selector::{
-    Attribute, AttributeSelection, AttributeSelectionKind, AttributeSelections, ClassSelections,
-    Combinator, ElementPredicate, IElement,
+    Attribute, AttributeSelection, AttributeSelectionKind, AttributeSelections, CaseSensitivity,
+    ClassSelections, Combinator, ElementPredicate, HTML_NAMESPACE, IElement, MATHML_NAMESPACE,
+    Namespaces, Nth, PredicateSelections, PseudoClass, PseudoClassSelections, SVG_NAMESPACE,
+    SiblingFilter,
 };
 pub use scah_reader::Reader;
@@ -1,16 +1,18 @@
 use super::SelectorParseError;
 use super::query::{Query, QuerySection, QuerySectionId, TransitionId};
 use super::transition::Transition;
+use crate::query::selector::Namespaces;
 
 /// Controls which pieces of content to capture for matched elements.
 ///
 /// When an element matches a CSS selector, scah can optionally capture its
-/// **inner HTML** (the raw markup between the opening and closing tags) and/or
-/// its **text content** (the concatenated, whitespace-trimmed text nodes).
+/// **inner HTML** (the raw markup between the opening and closing tags), its
+/// **outer HTML** (the same markup including the tags themselves) and/or its
+/// **text content** (the concatenated, whitespace-trimmed text nodes).
 ///
 /// Use the convenience constructors [`Save::all`], [`Save::none`],
-/// [`Save::only_inner_html`], and [`Save::only_text_content`] to create
-/// common configurations.
+/// [`Save::only_inner_html`], [`Save::only_outer_html`], and
+/// [`Save::only_text_content`] to create common configurations.
 ///
 /// # Example
 ///
@@ -20,6 +22,7 @@ use super::transition::Transition;
 /// // Capture everything
 /// let save = Save::all();
 /// assert!(save.inner_html);
+/// assert!(save.outer_html);
 /// assert!(save.text_content);
 ///
 /// // Capture only text content (lighter weight)
@@ -32,9 +35,17 @@ pub struct Save {
     /// When `true`, the raw HTML between the element's opening and closing
     /// tags is stored as [`Element::inner_html`](crate::Element::inner_html).
     pub inner_html: bool,
+    /// When `true`, the raw HTML from the `<` of the element's start tag to
+    /// the end of its end tag is stored as
+    /// [`Element::outer_html`](crate::Element::outer_html).
+    pub outer_html: bool,
     /// When `true`, the concatenated text content of the element is stored
     /// and retrievable via [`Element::text_content()`](crate::Element::text_content).
     pub text_content: bool,
+    /// When `true`, the selector also matches inside the contents of
+    /// `<template>` elements, which aren't part of the rendered document and
+    /// are skipped otherwise.
+    pub template_content: bool,
 }
 
 impl Save {
@@ -42,7 +53,19 @@ impl Save {
     pub fn only_inner_html() -> Self {
         Self {
             inner_html: true,
+            outer_html: false,
             text_content: false,
+            template_content: false,
+        }
+    }
+
+    /// Capture only the raw outer HTML of matched elements.
+    pub fn only_outer_html() -> Self {
+        Self {
+            inner_html: false,
+            outer_html: true,
+            text_content: false,
+            template_content: false,
         }
     }
 
@@ -50,28 +73,61 @@ impl Save {
     pub fn only_text_content() -> Self {
         Self {
             inner_html: false,
+            outer_html: false,
             text_content: true,
+            template_content: false,
         }
     }
 
-    /// Capture both inner HTML and text content.
+    /// Capture inner HTML, outer HTML and text content.
     pub fn all() -> Self {
         Self {
             inner_html: true,
+            outer_html: true,
             text_content: true,
+            template_content: false,
         }
     }
 
-    /// Capture neither inner HTML nor text content.
+    /// Capture neither inner HTML, outer HTML nor text content.
     ///
     /// The matched element's tag name, id, class, and attributes are still
     /// stored; only the heavier content extraction is skipped.
     pub fn none() -> Self {
         Self {
             inner_html: false,
+            outer_html: false,
             text_content: false,
+            template_content: false,
         }
     }
+
+    /// Also capture the raw outer HTML of matched elements.
+    ///
+    /// ```rust
+    /// use scah_query_ir::Save;
+    ///
+    /// let save = Save::only_text_content().with_outer_html();
+    /// assert!(save.outer_html);
+    /// assert!(save.text_content);
+    /// ```
+    pub fn with_outer_html(mut self) -> Self {
+        self.outer_html = true;
+        self
+    }
+
+    /// Also match the selector inside `<template>` contents.
+    ///
+    /// ```rust
+    /// use scah_query_ir::Save;
+    ///
+    /// let save = Save::all().with_template_content();
+    /// assert!(save.template_content);
+    /// ```
+    pub fn with_template_content(mut self) -> Self {
+        self.template_content = true;
+        self
+    }
 }
 
 /// Whether a query section should match **all** occurrences or only the
@@ -116,6 +172,9 @@ pub struct QueryBuilder<'query> {
     pub states: Vec<Transition<'query>>,
     /// Internal ordered list of query sections.
     pub selection: Vec<QuerySection<'query>>,
+    /// Prefix map that the selectors added with `.all()`, `.first()` and
+    /// `.then()` resolve `ns|name` against.
+    pub namespaces: Namespaces<'query>,
 }
 
 impl<'query> QueryBuilder<'query> {
@@ -123,7 +182,7 @@ impl<'query> QueryBuilder<'query> {
         assert!(!self.selection.is_empty());
 
         let current_state_len = self.states.len();
-        let mut states = Transition::generate_transitions_from_string(query)?;
+        let mut states = Transition::generate_transitions_from_string(query, &self.namespaces)?;
 
         let parent_index = QuerySectionId(self.selection.len() - 1);
         let range = TransitionId(current_state_len)..TransitionId(current_state_len + states.len());
@@ -140,16 +199,18 @@ impl<'query> QueryBuilder<'query> {
         Ok(self)
     }
 
-    /// Append a child selector that matches **all** occurrences.
+    /// Append a child selector that matches only the **first** occurrence.
     ///
     /// The new selector is scoped to elements that already matched
-    /// the previous selector in the chain.
+    /// the previous selector in the chain. Like [`Query::first`], it
+    /// rejects `:has()` and end-relative pseudo-classes.
     ///
     pub fn first(mut self, query: &'query str, save: Save) -> Result<Self, SelectorParseError> {
         assert!(!self.selection.is_empty());
 
         let current_state_len = self.states.len();
-        let mut states = Transition::generate_transitions_from_string(query)?;
+        let mut states = Transition::generate_transitions_from_string(query, &self.namespaces)?;
+        Transition::reject_deferred(&states)?;
 
         let parent_index = QuerySectionId(self.selection.len() - 1);
         let range = TransitionId(current_state_len)..TransitionId(current_state_len + states.len());
@@ -242,10 +303,10 @@ impl<'query> QueryBuilder<'query> {
     /// ```
     pub fn then<F, I>(mut self, func: F) -> Result<Self, SelectorParseError>
     where
-        F: FnOnce(QueryFactory) -> Result<I, SelectorParseError>,
+        F: FnOnce(QueryFactory<'query>) -> Result<I, SelectorParseError>,
         I: IntoIterator<Item = Self>,
     {
-        let factory = QueryFactory {};
+        let factory = QueryFactory::new(self.namespaces.clone());
         let children = func(factory)?;
 
         let current_index = QuerySectionId(self.selection.len() - 1);
@@ -341,31 +402,66 @@ impl<'query> QueryBuilder<'query> {
 /// A factory for creating child [`QueryBuilder`]s inside a
 /// [`QueryBuilder::then`] closure.
 ///
-/// You never construct this directly; it is provided as the argument to
-/// the closure passed to `.then()`.
-pub struct QueryFactory {}
-impl<'query> QueryFactory {
+/// It is provided as the argument to the closure passed to `.then()`, and
+/// by [`Query::with_namespaces`] to start a query with its own prefix map.
+pub struct QueryFactory<'query> {
+    namespaces: Namespaces<'query>,
+}
+impl<'query> QueryFactory<'query> {
+    pub(crate) fn new(namespaces: Namespaces<'query>) -> Self {
+        Self { namespaces }
+    }
+
     /// Create a child query that matches **all** occurrences of the selector.
     pub fn all(
         &self,
         query: &'query str,
         save: Save,
     ) -> Result<QueryBuilder<'query>, SelectorParseError> {
-        Query::all(query, save)
+        self.start(query, save, SelectionKind::All)
     }
 
     /// Create a child query that matches only the **first** occurrence.
+    /// Like [`Query::first`], it rejects `:has()` and end-relative
+    /// pseudo-classes.
     pub fn first(
         &self,
         query: &'query str,
         save: Save,
     ) -> Result<QueryBuilder<'query>, SelectorParseError> {
-        Query::first(query, save)
+        self.start(query, save, SelectionKind::First)
+    }
+
+    fn start(
+        &self,
+        query: &'query str,
+        save: Save,
+        kind: SelectionKind,
+    ) -> Result<QueryBuilder<'query>, SelectorParseError> {
+        let states = Transition::generate_transitions_from_string(query, &self.namespaces)?;
+        if kind == SelectionKind::First {
+            Transition::reject_deferred(&states)?;
+        }
+        let queries = vec![QuerySection::new(
+            query,
+            save,
+            kind,
+            TransitionId(0)..TransitionId(states.len()),
+            None,
+        )];
+
+        Ok(QueryBuilder {
+            states,
+            selection: queries,
+            namespaces: self.namespaces.clone(),
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::borrow::Cow;
+
     use crate::{ClassSelections, Query, QuerySectionId, Save, SelectionKind};
 
     #[test]
@@ -373,7 +469,7 @@ mod tests {
         let query = Query::all("a.blue.exit", Save::all()).unwrap().build();
         assert_eq!(
             query.states[0].predicate.classes,
-            ClassSelections::from_static(&["blue", "exit"])
+            ClassSelections::from_static(&[Cow::Borrowed("blue"), Cow::Borrowed("exit")])
         );
     }
 
@@ -423,11 +519,17 @@ mod tests {
             "a > ",
             ".",
             "#",
-            " a ~ b",
-            "a + b",
+            " a ~ ",
+            "a + ",
             "a[]",
-            "*",
             "a[123=\"321\"]",
+            "a:hover",
+            "a:not(div p)",
+            "a:not()",
+            "a:first-child()",
+            "a:nth-child(2n+)",
+            "li:last-child > a",
+            "li:nth-last-of-type(2) a",
         ];
 
         for selector in invalid {
@@ -435,6 +537,43 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_end_relative_pseudo_class_errors() {
+        let error = Query::all("ul li:last-child > a", Save::none()).unwrap_err();
+        assert_eq!(
+            error.message(),
+            "end-relative pseudo-classes must be in the last compound"
+        );
+        assert_eq!(error.position(), 2);
+
+        let error = Query::first("li:last-child", Save::none()).unwrap_err();
+        assert_eq!(
+            error.message(),
+            "end-relative pseudo-classes are not supported in `first` selections"
+        );
+
+        let error = Query::all("ul", Save::none())
+            .unwrap()
+            .then(|ul| Ok([ul.first("li:nth-last-child(2)", Save::none())?]))
+            .unwrap_err();
+        assert_eq!(
+            error.message(),
+            "end-relative pseudo-classes are not supported in `first` selections"
+        );
+
+        let error = Query::all("li:has(a) > b", Save::none()).unwrap_err();
+        assert_eq!(error.message(), "`:has()` must be in the last compound");
+        let error = Query::first("li:has(> a)", Save::none()).unwrap_err();
+        assert_eq!(
+            error.message(),
+            "`:has()` is not supported in `first` selections"
+        );
+
+        assert!(Query::all("ul > li:last-child", Save::none()).is_ok());
+        assert!(Query::all("ul > li:has(> a)", Save::none()).is_ok());
+        assert!(Query::first("ul > li:first-child", Save::none()).is_ok());
+    }
+
     #[test]
     fn test_then_with_all_and_first() {
         let query = Query::all("article", Save::none())
@@ -460,12 +599,12 @@ mod tests {
             .then(|article| {
                 Ok([
                     article.all("a[href]", Save::all())?,
-                    article.first("a + b", Save::only_text_content())?,
+                    article.first("a > ", Save::only_text_content())?,
                 ])
             })
             .unwrap_err();
 
-        assert_eq!(error.message(), "unsupported combinator '+'");
+        assert_eq!(error.message(), "missing selector element");
     }
 
     #[test]
@@ -524,7 +663,7 @@ mod tests {
             .unwrap()
             .then(|article| {
                 let first = article.all("section", Save::none())?;
-                let second = article.first("a + b", Save::all());
+                let second = article.first("a > ", Save::all());
                 match second {
                     Ok(second) => Ok([first, second]),
                     Err(err) => Err(err),
@@ -533,6 +672,6 @@ mod tests {
 
         assert!(builder.is_err());
         let error = builder.unwrap_err();
-        assert_eq!(error.message(), "unsupported combinator '+'");
+        assert_eq!(error.message(), "missing selector element");
     }
 }
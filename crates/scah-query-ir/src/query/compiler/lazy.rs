@@ -1,5 +1,5 @@
 use super::transition::Transition;
-use crate::{Query, QueryBuilder, QuerySection, QuerySectionId, TransitionId};
+use crate::{Namespaces, Query, QueryBuilder, QuerySection, QuerySectionId, TransitionId};
 
 use super::SelectorParseError;
 use super::builder::{Save, SelectionKind};
@@ -182,7 +182,8 @@ impl<S: AsRef<str>> LazyQueryBuilder<S> {
                 }
             };
 
-            let mut string_states = Transition::generate_transitions_from_string(source)?;
+            let mut string_states =
+                Transition::generate_transitions_from_string(source, &Namespaces::default())?;
             let range = {
                 let start = states.len();
                 states.append(&mut string_states);
@@ -208,6 +209,7 @@ impl<S: AsRef<str>> LazyQueryBuilder<S> {
             QueryBuilder {
                 states,
                 selection: queries,
+                namespaces: Namespaces::default(),
             }
             .build(),
         ))
@@ -233,12 +235,16 @@ impl LazyQueryFactory {
 
 #[cfg(test)]
 mod tests {
+    use std::borrow::Cow;
+
     use pretty_assertions::assert_eq;
 
     use super::Transition;
     use super::*;
     use crate::AttributeSelections;
-    use crate::query::selector::{ClassSelections, Combinator, ElementPredicate};
+    use crate::query::selector::{
+        ClassSelections, Combinator, ElementPredicate, PseudoClassSelections,
+    };
     use crate::{Query, QuerySectionId, Save, SelectionKind, TransitionId};
 
     #[test]
@@ -393,28 +399,34 @@ mod tests {
                     Transition::new(
                         Combinator::Descendant,
                         ElementPredicate {
-                            name: Some("div"),
+                            name: Some(Cow::Borrowed("div")),
+                            namespace: None,
                             id: None,
                             classes: ClassSelections::from_static(&[]),
-                            attributes: AttributeSelections::from_static(&[])
+                            attributes: AttributeSelections::from_static(&[]),
+                            pseudo_classes: PseudoClassSelections::from_static(&[])
                         }
                     ),
                     Transition::new(
                         Combinator::Descendant,
                         ElementPredicate {
-                            name: Some("a"),
+                            name: Some(Cow::Borrowed("a")),
+                            namespace: None,
                             id: None,
                             classes: ClassSelections::from_static(&[]),
-                            attributes: AttributeSelections::from_static(&[])
+                            attributes: AttributeSelections::from_static(&[]),
+                            pseudo_classes: PseudoClassSelections::from_static(&[])
                         }
                     ),
                     Transition::new(
                         Combinator::Descendant,
                         ElementPredicate {
-                            name: Some("a"),
+                            name: Some(Cow::Borrowed("a")),
+                            namespace: None,
                             id: None,
                             classes: ClassSelections::from_static(&[]),
-                            attributes: AttributeSelections::from_static(&[])
+                            attributes: AttributeSelections::from_static(&[]),
+                            pseudo_classes: PseudoClassSelections::from_static(&[])
                         }
                     ),
                 ]
@@ -490,4 +502,27 @@ mod tests {
         };
         assert_eq!(query.exit_at_section_end, Some(QuerySectionId(1)));
     }
+
+    #[test]
+    fn test_selector_list_shares_one_section() {
+        let (_tape, query) = unsafe {
+            LazyQuery::all(String::from("h1, h2"), Save::all())
+                .all(String::from("a, b"), Save::none())
+                .to_query()
+        };
+
+        assert_eq!(query.queries.len(), 2);
+        assert_eq!(query.queries[0].source, "h1, h2");
+        assert_eq!(query.queries[0].range, TransitionId(0)..TransitionId(2));
+        assert_eq!(query.queries[1].source, "a, b");
+        assert_eq!(query.queries[1].range, TransitionId(2)..TransitionId(4));
+        assert_eq!(
+            query
+                .states
+                .iter()
+                .map(|state| state.alternative)
+                .collect::<Vec<_>>(),
+            vec![false, true, false, true]
+        );
+    }
 }
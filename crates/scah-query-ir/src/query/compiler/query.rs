@@ -1,9 +1,9 @@
 use std::ops::Range;
 
-use super::builder::{QueryBuilder, Save, SelectionKind};
+use super::builder::{QueryBuilder, QueryFactory, Save, SelectionKind};
 use super::error::SelectorParseError;
 use super::transition::Transition;
-use crate::query::selector::Combinator;
+use crate::query::selector::{Combinator, Namespaces};
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
 pub struct TransitionId(pub usize);
@@ -70,21 +70,39 @@ pub trait QuerySpec<'query> {
         self.get_transition(state).guard == Combinator::Descendant
     }
 
+    fn is_sibling(&self, state: TransitionId) -> bool {
+        matches!(
+            self.get_transition(state).guard,
+            Combinator::NextSibling | Combinator::SubsequentSibling
+        )
+    }
+
     fn is_save_point(&self, position: &Position) -> bool {
         debug_assert!(
             self.get_selection(position.selection)
                 .range
                 .contains(&position.state)
         );
-        self.get_selection(position.selection).range.end.index() - 1 == position.state.index()
+        position.next_transition(self).is_none()
     }
 
     fn is_last_save_point(&self, position: &Position) -> bool {
         debug_assert!(position.selection.index() < self.queries().len());
         let is_last_query = self.queries().len() - 1 == position.selection.index();
-        let is_last_state =
-            self.get_selection(position.selection).range.end.index() - 1 == position.state.index();
-        is_last_query && is_last_state
+        is_last_query && self.is_save_point(position)
+    }
+
+    /// Start positions of the other selectors of a section's selector list
+    /// (`h1, h2`); cursors entering the section only follow the first one.
+    fn alternatives(&self, selection: QuerySectionId) -> impl Iterator<Item = Position> + '_
+    where
+        Self: Sized,
+    {
+        let range = self.get_selection(selection).range.clone();
+        (range.start.index() + 1..range.end.index())
+            .map(TransitionId)
+            .filter(|state| self.get_transition(*state).alternative)
+            .map(move |state| Position { selection, state })
     }
 
     fn children(&'query self, position: &Position) -> Option<impl Iterator<Item = Position>>
@@ -118,8 +136,9 @@ impl Position {
         );
 
         let selection_range = &query.get_selection(self.selection).range;
-        if self.state.index() + 1 < selection_range.end.index() {
-            Some(TransitionId(self.state.index() + 1))
+        let next = TransitionId(self.state.index() + 1);
+        if next < selection_range.end && !query.get_transition(next).alternative {
+            Some(next)
         } else {
             None
         }
@@ -173,11 +192,18 @@ impl Position {
         debug_assert!(self.state < query.get_selection(self.selection).range.end);
 
         let selection = query.get_selection(self.selection);
-        if self.state.index() > selection.range.start.index() {
+        if self.state.index() > selection.range.start.index()
+            && !query.get_transition(self.state).alternative
+        {
             self.state = TransitionId(self.state.index() - 1);
         } else if let Some(parent) = selection.parent {
+            // Back to the end of the parent's first selector, the only one
+            // the main cursor follows.
             self.selection = parent;
-            self.state = TransitionId(query.get_selection(self.selection).range.end.index() - 1);
+            self.state = query.get_selection(parent).range.start;
+            while let Some(next) = self.next_transition(query) {
+                self.state = next;
+            }
         }
     }
 }
@@ -290,52 +316,47 @@ impl<'query, const N_STATES: usize, const N_SECTIONS: usize> QuerySpec<'query>
 }
 
 impl<'query> Query<'query> {
+    /// Starts a query that stops at the **first** element matching `query`.
+    ///
+    /// Parsing stops at that element, before a `:has()` or a pseudo-class
+    /// that counts the siblings after it (`:last-child`,
+    /// `:nth-last-of-type()`, ...) could be decided, so selectors using one
+    /// are rejected. Use [`Self::all`] and take its first match instead.
     pub fn first(
         query: &'query str,
         save: Save,
     ) -> Result<QueryBuilder<'query>, SelectorParseError> {
-        let states = Transition::generate_transitions_from_string(query)?;
-        let queries = vec![QuerySection::new(
-            query,
-            save,
-            SelectionKind::First,
-            TransitionId(0)..TransitionId(states.len()),
-            None,
-        )];
-
-        Ok(QueryBuilder {
-            states,
-            selection: queries,
-        })
+        Self::with_namespaces(Namespaces::default()).first(query, save)
     }
 
     pub fn all(query: &'query str, save: Save) -> Result<QueryBuilder<'query>, SelectorParseError> {
-        let states = Transition::generate_transitions_from_string(query)?;
-        let queries = vec![QuerySection::new(
-            query,
-            save,
-            SelectionKind::All,
-            TransitionId(0)..TransitionId(states.len()),
-            None,
-        )];
+        Self::with_namespaces(Namespaces::default()).all(query, save)
+    }
 
-        Ok(QueryBuilder {
-            states,
-            selection: queries,
-        })
+    /// Starts a query whose `ns|name` type selectors, including the ones
+    /// added later with `.all()`, `.first()` and `.then()`, resolve against
+    /// `namespaces`.
+    pub fn with_namespaces(namespaces: Namespaces<'query>) -> QueryFactory<'query> {
+        QueryFactory::new(namespaces)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::borrow::Cow;
+
     use crate::query::compiler::transition::Transition;
     use crate::query::selector::AttributeSelection;
     use crate::query::selector::AttributeSelectionKind;
     use crate::query::selector::AttributeSelections;
+    use crate::query::selector::CaseSensitivity;
     use crate::query::selector::ClassSelections;
     use crate::query::selector::Combinator;
     use crate::query::selector::ElementPredicate;
-    use crate::{Query, QuerySection, QuerySectionId, Save, SelectionKind, TransitionId};
+    use crate::query::selector::PseudoClassSelections;
+    use crate::{
+        Position, Query, QuerySection, QuerySectionId, QuerySpec, Save, SelectionKind, TransitionId,
+    };
 
     #[test]
     fn test_query_builder_one_selection() {
@@ -345,12 +366,15 @@ mod tests {
             query.states.iter().as_slice(),
             [Transition {
                 predicate: ElementPredicate {
-                    name: Some("a"),
+                    name: Some(Cow::Borrowed("a")),
+                    namespace: None,
                     id: None,
                     classes: ClassSelections::from_static(&[]),
-                    attributes: AttributeSelections::from_static(&[])
+                    attributes: AttributeSelections::from_static(&[]),
+                    pseudo_classes: PseudoClassSelections::from_static(&[])
                 },
                 guard: Combinator::Descendant,
+                alternative: false,
             }]
         );
 
@@ -380,21 +404,27 @@ mod tests {
             [
                 Transition {
                     predicate: ElementPredicate {
-                        name: Some("span"),
+                        name: Some(Cow::Borrowed("span")),
+                        namespace: None,
                         id: None,
                         classes: ClassSelections::from_static(&[]),
-                        attributes: AttributeSelections::from_static(&[])
+                        attributes: AttributeSelections::from_static(&[]),
+                        pseudo_classes: PseudoClassSelections::from_static(&[])
                     },
                     guard: Combinator::Descendant,
+                    alternative: false,
                 },
                 Transition {
                     predicate: ElementPredicate {
-                        name: Some("a"),
+                        name: Some(Cow::Borrowed("a")),
+                        namespace: None,
                         id: None,
                         classes: ClassSelections::from_static(&[]),
-                        attributes: AttributeSelections::from_static(&[])
+                        attributes: AttributeSelections::from_static(&[]),
+                        pseudo_classes: PseudoClassSelections::from_static(&[])
                     },
                     guard: Combinator::Descendant,
+                    alternative: false,
                 }
             ]
         );
@@ -413,14 +443,17 @@ mod tests {
         assert_eq!(
             query.states[1].predicate,
             ElementPredicate {
-                name: Some("a"),
-                id: Some("link1"),
-                classes: ClassSelections::from_static(&["foo"]),
+                name: Some(Cow::Borrowed("a")),
+                namespace: None,
+                id: Some(Cow::Borrowed("link1")),
+                classes: ClassSelections::from_static(&[Cow::Borrowed("foo")]),
                 attributes: AttributeSelections::from(vec![AttributeSelection {
-                    name: "href",
-                    value: Some("https"),
+                    name: Cow::Borrowed("href"),
+                    value: Some(Cow::Borrowed("https")),
                     kind: AttributeSelectionKind::Prefix,
+                    case: CaseSensitivity::Sensitive,
                 }]),
+                pseudo_classes: PseudoClassSelections::from_static(&[]),
             }
         );
     }
@@ -442,4 +475,49 @@ mod tests {
         assert_eq!(query.queries[1].next_sibling, Some(QuerySectionId(2)));
         assert_eq!(query.queries[2].next_sibling, None);
     }
+
+    #[test]
+    fn test_selector_list_shares_one_section() {
+        let query = Query::all("h1, section > h2", Save::all())
+            .unwrap()
+            .all("a", Save::none())
+            .unwrap()
+            .build();
+
+        assert_eq!(query.queries.len(), 2);
+        assert_eq!(query.queries[0].source, "h1, section > h2");
+        assert_eq!(query.queries[0].range, TransitionId(0)..TransitionId(3));
+        assert_eq!(
+            query.alternatives(QuerySectionId(0)).collect::<Vec<_>>(),
+            vec![Position {
+                selection: QuerySectionId(0),
+                state: TransitionId(1),
+            }]
+        );
+
+        let h1 = Position {
+            selection: QuerySectionId(0),
+            state: TransitionId(0),
+        };
+        let section = Position {
+            selection: QuerySectionId(0),
+            state: TransitionId(1),
+        };
+        let h2 = Position {
+            selection: QuerySectionId(0),
+            state: TransitionId(2),
+        };
+        assert!(query.is_save_point(&h1));
+        assert!(!query.is_save_point(&section));
+        assert!(query.is_save_point(&h2));
+        assert_eq!(h1.next_transition(&query), None);
+        assert_eq!(section.next_transition(&query), Some(TransitionId(2)));
+
+        let mut a = Position {
+            selection: QuerySectionId(1),
+            state: TransitionId(3),
+        };
+        a.back(&query);
+        assert_eq!(a, h1);
+    }
 }
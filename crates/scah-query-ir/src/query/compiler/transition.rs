@@ -1,40 +1,110 @@
 use crate::Reader;
 use crate::query::compiler::SelectorParseError;
-use crate::query::selector::{Combinator, ElementPredicate, IElement, Lexer};
+use crate::query::selector::{Combinator, ElementPredicate, IElement, Lexer, Namespaces};
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct Transition<'query> {
     pub guard: Combinator,
     pub predicate: ElementPredicate<'query>,
+    /// Starts another selector of a selector list (`h1, h2`); the chains of
+    /// a list sit back to back in the same section.
+    pub alternative: bool,
 }
 
 impl<'query> Transition<'query> {
     pub fn new(guard: Combinator, predicate: ElementPredicate<'query>) -> Self {
-        Self { guard, predicate }
+        Self {
+            guard,
+            predicate,
+            alternative: false,
+        }
     }
 
     pub const fn new_const(guard: Combinator, predicate: ElementPredicate<'query>) -> Self {
-        Self { guard, predicate }
+        Self {
+            guard,
+            predicate,
+            alternative: false,
+        }
+    }
+
+    pub const fn into_alternative(mut self) -> Self {
+        self.alternative = true;
+        self
     }
 
     pub fn generate_transitions_from_string(
         query: &'query str,
+        namespaces: &Namespaces<'query>,
     ) -> Result<Vec<Self>, SelectorParseError> {
         let reader = &mut Reader::new(query);
-        let mut states = Vec::new();
-        let mut seen_selector = false;
-        while let Some((combinator, element)) = Lexer::try_next(reader, seen_selector)? {
-            seen_selector = true;
-            states.push(Self::new(combinator, element));
-        }
+        let mut states: Vec<Self> = Vec::new();
+        loop {
+            let chain_start = states.len();
+            let mut seen_selector = false;
+            // Only a saved element can wait for its parent or itself to
+            // close, so `:last-child` and `:has()` can't lead into another
+            // compound.
+            let mut compound_start = reader.get_position();
+            let mut deferred_at = None;
+            while let Some((combinator, element)) =
+                Lexer::try_next(reader, seen_selector, namespaces)?
+            {
+                if let Some((message, position)) = deferred_at {
+                    return Err(SelectorParseError::new(message, position));
+                }
+                if element.is_end_relative() {
+                    deferred_at = Some((
+                        "end-relative pseudo-classes must be in the last compound",
+                        compound_start,
+                    ));
+                } else if element.is_relational() {
+                    deferred_at = Some(("`:has()` must be in the last compound", compound_start));
+                }
+                seen_selector = true;
+                states.push(Self::new(combinator, element));
+                compound_start = reader.get_position();
+            }
+
+            if states.len() == chain_start {
+                if chain_start == 0 && reader.eof() {
+                    return Err(SelectorParseError::new("empty selector", 0));
+                }
+                return Err(SelectorParseError::new(
+                    "missing selector in selector list",
+                    reader.get_position(),
+                ));
+            }
+            if chain_start > 0 {
+                states[chain_start].alternative = true;
+            }
 
-        if states.is_empty() {
-            return Err(SelectorParseError::new("empty selector", 0));
+            if !Lexer::next_alternative(reader) {
+                break;
+            }
         }
 
         Ok(states)
     }
 
+    /// `first` selections stop at their first save, so they can't wait for
+    /// an element or its parent to close to confirm it.
+    pub(crate) fn reject_deferred(states: &[Self]) -> Result<(), SelectorParseError> {
+        if states.iter().any(|state| state.predicate.is_end_relative()) {
+            return Err(SelectorParseError::new(
+                "end-relative pseudo-classes are not supported in `first` selections",
+                0,
+            ));
+        }
+        if states.iter().any(|state| state.predicate.is_relational()) {
+            return Err(SelectorParseError::new(
+                "`:has()` is not supported in `first` selections",
+                0,
+            ));
+        }
+        Ok(())
+    }
+
     pub fn next<'html, E: IElement<'html>>(
         &self,
         element: &E,
@@ -57,7 +127,11 @@ impl<'query> Transition<'query> {
 
 #[cfg(test)]
 mod tests {
-    use crate::query::selector::{Attribute, AttributeSelections, ClassSelections, IElement};
+    use std::borrow::Cow;
+
+    use crate::query::selector::{
+        Attribute, AttributeSelections, ClassSelections, IElement, PseudoClassSelections,
+    };
 
     use super::*;
 
@@ -92,10 +166,12 @@ mod tests {
         let state = Transition::new(
             Combinator::Descendant,
             ElementPredicate {
-                name: Some("a"),
+                name: Some(Cow::Borrowed("a")),
+                namespace: None,
                 id: None,
                 classes: ClassSelections::from_static(&[]),
                 attributes: AttributeSelections::from_static(&[]),
+                pseudo_classes: PseudoClassSelections::from_static(&[]),
             },
         );
         assert!(state.next(
@@ -115,10 +191,12 @@ mod tests {
         let state = Transition::new(
             Combinator::Child,
             ElementPredicate {
-                name: Some("a"),
+                name: Some(Cow::Borrowed("a")),
+                namespace: None,
                 id: None,
                 classes: ClassSelections::from_static(&[]),
                 attributes: AttributeSelections::from_static(&[]),
+                pseudo_classes: PseudoClassSelections::from_static(&[]),
             },
         );
         assert!(state.next(
@@ -138,10 +216,12 @@ mod tests {
         let state = Transition::new(
             Combinator::Child,
             ElementPredicate {
-                name: Some("a"),
+                name: Some(Cow::Borrowed("a")),
+                namespace: None,
                 id: None,
                 classes: ClassSelections::from_static(&[]),
                 attributes: AttributeSelections::from_static(&[]),
+                pseudo_classes: PseudoClassSelections::from_static(&[]),
             },
         );
         assert!(!state.next(
@@ -155,4 +235,65 @@ mod tests {
             1,
         ));
     }
+
+    #[test]
+    fn test_selector_list_marks_alternative_chains() {
+        let states = Transition::generate_transitions_from_string(
+            "h1, div > h2 ,h3",
+            &Namespaces::default(),
+        )
+        .unwrap();
+
+        let names = states
+            .iter()
+            .map(|state| {
+                (
+                    state.predicate.name.as_deref(),
+                    state.guard.clone(),
+                    state.alternative,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                (Some("h1"), Combinator::Descendant, false),
+                (Some("div"), Combinator::Descendant, true),
+                (Some("h2"), Combinator::Child, false),
+                (Some("h3"), Combinator::Descendant, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_selector_list_keeps_commas_inside_attribute_values() {
+        let states = Transition::generate_transitions_from_string(
+            "a[title=\"x, y\"], b",
+            &Namespaces::default(),
+        )
+        .unwrap();
+
+        assert_eq!(states.len(), 2);
+        assert_eq!(
+            states[0].predicate.attributes.as_slice()[0]
+                .value
+                .as_deref(),
+            Some("x, y")
+        );
+        assert!(states[1].alternative);
+    }
+
+    #[test]
+    fn test_selector_list_rejects_empty_selectors() {
+        for selector in [", a", "a,", "a, ,b", "a,,b"] {
+            let error =
+                Transition::generate_transitions_from_string(selector, &Namespaces::default())
+                    .unwrap_err();
+            assert_eq!(
+                error.message(),
+                "missing selector in selector list",
+                "{selector}"
+            );
+        }
+    }
 }
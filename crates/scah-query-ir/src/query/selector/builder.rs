@@ -1,24 +1,42 @@
-use super::string_search::AttributeSelectionKind;
+use std::borrow::Cow;
+
+use super::ident::{is_identifier_byte, next_identifier, next_quoted, next_until_unescaped};
+use super::namespace::{HTML_NAMESPACE, Namespaces};
+use super::nth::Nth;
+use super::string_search::{AttributeSelectionKind, CaseSensitivity};
 use crate::Reader;
 use crate::query::compiler::SelectorParseError;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
-enum QuoteKind {
-    DoubleQuoted,
-    SingleQuoted,
-}
-
 #[derive(Debug, PartialEq, Clone)]
 pub struct Attribute<'html> {
     pub key: &'html str,
-    pub value: Option<&'html str>,
+    /// The value with its character references decoded; borrowed from the
+    /// source unless it had any.
+    pub value: Option<Cow<'html, str>>,
+    /// The value as written in the source.
+    pub raw_value: Option<&'html str>,
+}
+
+impl<'html> Attribute<'html> {
+    /// An attribute whose value is used as written.
+    pub const fn new(key: &'html str, value: Option<&'html str>) -> Self {
+        Self {
+            key,
+            value: match value {
+                Some(value) => Some(Cow::Borrowed(value)),
+                None => None,
+            },
+            raw_value: value,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct AttributeSelection<'query> {
-    pub name: &'query str,
-    pub value: Option<&'query str>,
+    pub name: Cow<'query, str>,
+    pub value: Option<Cow<'query, str>>,
     pub kind: AttributeSelectionKind,
+    pub case: CaseSensitivity,
 }
 
 impl<'query> AttributeSelection<'query> {
@@ -26,12 +44,21 @@ impl<'query> AttributeSelection<'query> {
         name: &'query str,
         value: Option<&'query str>,
         kind: AttributeSelectionKind,
+        case: CaseSensitivity,
     ) -> Self {
-        Self { name, value, kind }
+        Self {
+            name: Cow::Borrowed(name),
+            value: match value {
+                Some(value) => Some(Cow::Borrowed(value)),
+                None => None,
+            },
+            kind,
+            case,
+        }
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
 pub enum AttributeSelections<'query> {
     Static(&'query [AttributeSelection<'query>]),
     Owned(Box<[AttributeSelection<'query>]>),
@@ -62,18 +89,24 @@ impl<'query> From<Vec<AttributeSelection<'query>>> for AttributeSelections<'quer
     }
 }
 
+impl<'query> PartialEq for AttributeSelections<'query> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ClassSelections<'query> {
-    Static(&'query [&'query str]),
-    Owned(Box<[&'query str]>),
+    Static(&'query [Cow<'query, str>]),
+    Owned(Box<[Cow<'query, str>]>),
 }
 
 impl<'query> ClassSelections<'query> {
-    pub const fn from_static(classes: &'query [&'query str]) -> Self {
+    pub const fn from_static(classes: &'query [Cow<'query, str>]) -> Self {
         Self::Static(classes)
     }
 
-    pub fn as_slice(&self) -> &[&'query str] {
+    pub fn as_slice(&self) -> &[Cow<'query, str>] {
         match self {
             Self::Static(classes) => classes,
             Self::Owned(classes) => classes,
@@ -87,8 +120,8 @@ impl<'query> Default for ClassSelections<'query> {
     }
 }
 
-impl<'query> From<Vec<&'query str>> for ClassSelections<'query> {
-    fn from(value: Vec<&'query str>) -> Self {
+impl<'query> From<Vec<Cow<'query, str>>> for ClassSelections<'query> {
+    fn from(value: Vec<Cow<'query, str>>) -> Self {
         Self::Owned(value.into_boxed_slice())
     }
 }
@@ -99,22 +132,243 @@ impl<'query> PartialEq for ClassSelections<'query> {
     }
 }
 
+#[derive(Debug, Clone)]
+pub enum PredicateSelections<'query> {
+    Static(&'query [ElementPredicate<'query>]),
+    Owned(Box<[ElementPredicate<'query>]>),
+}
+
+impl<'query> PredicateSelections<'query> {
+    pub const fn from_static(predicates: &'query [ElementPredicate<'query>]) -> Self {
+        Self::Static(predicates)
+    }
+
+    pub fn as_slice(&self) -> &[ElementPredicate<'query>] {
+        match self {
+            Self::Static(predicates) => predicates,
+            Self::Owned(predicates) => predicates,
+        }
+    }
+}
+
+impl<'query> From<Vec<ElementPredicate<'query>>> for PredicateSelections<'query> {
+    fn from(value: Vec<ElementPredicate<'query>>) -> Self {
+        Self::Owned(value.into_boxed_slice())
+    }
+}
+
+impl<'query> PartialEq for PredicateSelections<'query> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum PseudoClass<'query> {
+    /// `:not(...)`: none of the compound selectors may match.
+    Not(PredicateSelections<'query>),
+    /// `:is(...)` or `:where(...)`: one of the compound selectors must match.
+    /// Specificity is never used, so both parse to this variant.
+    Is(PredicateSelections<'query>),
+    /// `:nth-child(an+b [of S])`, or `:first-child`.
+    NthChild {
+        nth: Nth,
+        of: Option<PredicateSelections<'query>>,
+    },
+    /// `:nth-of-type(an+b)`, or `:first-of-type`.
+    NthOfType(Nth),
+    /// `:nth-last-child(an+b [of S])`, or `:last-child`.
+    ///
+    /// Counting from the end needs the siblings that follow the element, so
+    /// the match is only confirmed once the parent closes (see
+    /// [`ElementPredicate::is_end_relative`]).
+    NthLastChild {
+        nth: Nth,
+        of: Option<PredicateSelections<'query>>,
+    },
+    /// `:nth-last-of-type(an+b)`, or `:last-of-type`; resolved like
+    /// [`PseudoClass::NthLastChild`].
+    NthLastOfType(Nth),
+    /// `:has(> a, img)`: a child matches one of `children`, or a descendant
+    /// matches one of `descendants`.
+    ///
+    /// Only confirmed once the element closes (see
+    /// [`ElementPredicate::is_relational`]).
+    Has {
+        children: PredicateSelections<'query>,
+        descendants: PredicateSelections<'query>,
+    },
+}
+
+impl<'query> PseudoClass<'query> {
+    fn is_end_relative(&self) -> bool {
+        match self {
+            Self::Not(predicates) | Self::Is(predicates) => predicates
+                .as_slice()
+                .iter()
+                .any(ElementPredicate::is_end_relative),
+            Self::NthChild { .. } | Self::NthOfType(_) | Self::Has { .. } => false,
+            Self::NthLastChild { .. } | Self::NthLastOfType(_) => true,
+        }
+    }
+
+    fn is_relational(&self) -> bool {
+        match self {
+            Self::Not(predicates) | Self::Is(predicates) => predicates
+                .as_slice()
+                .iter()
+                .any(ElementPredicate::is_relational),
+            Self::Has { .. } => true,
+            _ => false,
+        }
+    }
+
+    fn numbers_siblings(&self) -> bool {
+        match self {
+            Self::Not(predicates) | Self::Is(predicates) => predicates
+                .as_slice()
+                .iter()
+                .any(ElementPredicate::numbers_siblings),
+            Self::NthChild { .. }
+            | Self::NthLastChild { .. }
+            | Self::NthOfType(_)
+            | Self::NthLastOfType(_) => true,
+            Self::Has {
+                children,
+                descendants,
+            } => children
+                .as_slice()
+                .iter()
+                .chain(descendants.as_slice())
+                .any(ElementPredicate::numbers_siblings),
+        }
+    }
+
+    fn is_of_type(&self) -> bool {
+        match self {
+            Self::Not(predicates) | Self::Is(predicates) => predicates
+                .as_slice()
+                .iter()
+                .any(ElementPredicate::is_of_type),
+            Self::NthChild { of, .. } | Self::NthLastChild { of, .. } => of
+                .iter()
+                .flat_map(PredicateSelections::as_slice)
+                .any(ElementPredicate::is_of_type),
+            Self::NthOfType(_) | Self::NthLastOfType(_) => true,
+            Self::Has {
+                children,
+                descendants,
+            } => children
+                .as_slice()
+                .iter()
+                .chain(descendants.as_slice())
+                .any(ElementPredicate::is_of_type),
+        }
+    }
+}
+
+/// Which siblings an `an+b` index counts.
+#[derive(Debug, Clone, Copy)]
+pub enum SiblingFilter<'a, 'query> {
+    /// Every element sibling (`:nth-child`).
+    All,
+    /// Siblings with the same tag name (`:nth-of-type`).
+    SameType,
+    /// Siblings matching the selector list `S` of `:nth-child(an+b of S)`.
+    Matching(&'a [ElementPredicate<'query>]),
+}
+
+#[derive(Debug, Clone)]
+pub enum PseudoClassSelections<'query> {
+    Static(&'query [PseudoClass<'query>]),
+    Owned(Box<[PseudoClass<'query>]>),
+}
+
+impl<'query> PseudoClassSelections<'query> {
+    pub const fn from_static(pseudo_classes: &'query [PseudoClass<'query>]) -> Self {
+        Self::Static(pseudo_classes)
+    }
+
+    pub fn as_slice(&self) -> &[PseudoClass<'query>] {
+        match self {
+            Self::Static(pseudo_classes) => pseudo_classes,
+            Self::Owned(pseudo_classes) => pseudo_classes,
+        }
+    }
+}
+
+impl<'query> Default for PseudoClassSelections<'query> {
+    fn default() -> Self {
+        Self::Static(&[])
+    }
+}
+
+impl<'query> From<Vec<PseudoClass<'query>>> for PseudoClassSelections<'query> {
+    fn from(value: Vec<PseudoClass<'query>>) -> Self {
+        Self::Owned(value.into_boxed_slice())
+    }
+}
+
+impl<'query> PartialEq for PseudoClassSelections<'query> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
 /// Element Interface
 pub trait IElement<'html> {
     fn name(&self) -> &'html str;
     fn id(&self) -> Option<&'html str>;
     fn class(&self) -> Option<&'html str>;
     fn attributes(&self) -> &[Attribute<'html>];
+
+    /// Namespace URI of the element; elements outside `<svg>` and `<math>`
+    /// are HTML.
+    fn namespace(&self) -> &str {
+        HTML_NAMESPACE
+    }
+
+    /// How tag and attribute names compare: ASCII case-insensitively in
+    /// HTML, exactly in XML and inside `<svg>` and `<math>`.
+    fn name_case(&self) -> CaseSensitivity {
+        CaseSensitivity::Insensitive
+    }
+
+    /// 1-based position of the element among the siblings counted by `filter`.
+    ///
+    /// `None` when positions are not tracked, or when the element itself is
+    /// not counted by `filter`; structural pseudo-classes never match then.
+    fn sibling_index(&self, _filter: SiblingFilter<'_, '_>) -> Option<u32> {
+        None
+    }
+
+    /// Number of siblings counted by `filter`, this element included.
+    ///
+    /// Only known once the parent has closed; until then end-relative
+    /// pseudo-classes (`:last-child`) stay undecided.
+    fn sibling_count(&self, _filter: SiblingFilter<'_, '_>) -> Option<u32> {
+        None
+    }
+
+    /// Whether one of the elements `selector` looks for, from a `:has()`
+    /// list, was found inside the element.
+    ///
+    /// Only known once the element has closed; until then `:has()` stays
+    /// undecided.
+    fn has_relative_match(&self, _selector: &[ElementPredicate<'_>]) -> Option<bool> {
+        None
+    }
 }
 
 struct KeyValueAttributeSelection<'query> {
-    name: Option<&'query str>,
+    name: Option<Cow<'query, str>>,
     selection_kind: AttributeSelectionKind,
-    value: Option<&'query str>,
+    value: Option<Cow<'query, str>>,
+    case: Option<CaseSensitivity>,
 }
 
 impl<'query> KeyValueAttributeSelection<'query> {
-    fn push(&mut self, content_inside_quotes: &'query str) {
+    fn push(&mut self, content_inside_quotes: Cow<'query, str>) {
         if self.name.is_none() {
             self.name = Some(content_inside_quotes);
         } else if self.value.is_none() {
@@ -139,43 +393,45 @@ impl<'query> From<&mut Reader<'query>> for AttributeSelection<'query> {
 
 impl<'query> AttributeSelection<'query> {
     fn try_from(reader: &mut Reader<'query>) -> Result<Self, SelectorParseError> {
-        let mut position = reader.get_position();
-
-        let mut opened_quote: Option<QuoteKind> = None;
         let mut equal = false;
 
         let mut kv = KeyValueAttributeSelection {
             name: None,
             selection_kind: AttributeSelectionKind::Presence,
             value: None,
+            case: None,
         };
 
-        while let Some(token) = SelectionAttributeToken::next(reader)? {
-            match token {
-                SelectionAttributeToken::Quote(kind) => {
-                    if opened_quote.is_none() {
-                        opened_quote = Some(kind);
-                        position = reader.get_position();
-                        continue;
-                    }
-
-                    if let Some(quote_kind) = &opened_quote
-                        && *quote_kind != kind
-                    {
-                        continue;
-                    }
-
-                    opened_quote = None;
+        loop {
+            reader.next_while(b' ');
+            let token_start = reader.get_position();
+            let Some(token) = SelectionAttributeToken::next(reader)? else {
+                break;
+            };
 
-                    const SIZE_OF_QUOTE: usize = 1;
-                    let end_position = reader.get_position() - SIZE_OF_QUOTE;
-                    let content_inside_quotes = reader.slice(position..end_position);
-
-                    kv.push(content_inside_quotes);
+            match token {
+                SelectionAttributeToken::Quote(quote) => {
+                    kv.push(next_quoted(reader, quote)?);
                 }
 
                 SelectionAttributeToken::String(string_value) => {
-                    if opened_quote.is_some() {
+                    if kv.value.is_some() {
+                        let case = match string_value.as_ref() {
+                            "i" | "I" => CaseSensitivity::Insensitive,
+                            "s" | "S" => CaseSensitivity::Sensitive,
+                            _ => {
+                                return Err(SelectorParseError::new(
+                                    "attribute selector has an unknown modifier",
+                                    token_start,
+                                ));
+                            }
+                        };
+                        if kv.case.replace(case).is_some() {
+                            return Err(SelectorParseError::new(
+                                "attribute selector has multiple modifiers",
+                                token_start,
+                            ));
+                        }
                         continue;
                     }
 
@@ -210,26 +466,22 @@ impl<'query> AttributeSelection<'query> {
             }
         }
 
-        if kv.name.is_none() {
+        let Some(name) = kv.name.take() else {
             return Err(SelectorParseError::new(
                 "attribute selector is missing a key",
                 reader.get_position(),
             ));
-        }
-        if !is_valid_attribute_name(kv.name.unwrap()) {
+        };
+        // Escaped names can hold any character.
+        if let Cow::Borrowed(name) = name
+            && !is_valid_attribute_name(name)
+        {
             return Err(SelectorParseError::new(
                 "attribute selector key is invalid",
                 reader.get_position(),
             ));
         }
 
-        if opened_quote.is_some() {
-            return Err(SelectorParseError::new(
-                "attribute selector has an unclosed quoted value",
-                reader.get_position(),
-            ));
-        }
-
         if equal && kv.value.is_none() {
             return Err(SelectorParseError::new(
                 "attribute selector is missing a value",
@@ -240,50 +492,61 @@ impl<'query> AttributeSelection<'query> {
         kv.refresh_equal();
 
         Ok(AttributeSelection {
-            name: kv.name.unwrap(),
+            name,
             value: kv.value,
             kind: kv.selection_kind,
+            case: kv.case.unwrap_or_default(),
         })
     }
 }
 
 enum SelectionKeyWords<'query> {
-    String(&'query str),
+    String(Cow<'query, str>),
+    /// Text that can't start an identifier.
+    Invalid,
     ID,
     Class,
     Quote,
     OpenAttribute,
     CloseAttribute,
+    PseudoClass,
 }
 
 impl<'a> SelectionKeyWords<'a> {
-    pub fn next(reader: &mut Reader<'a>) -> Option<Self> {
-        let start_pos = reader.get_position();
-
-        if let Some(token) = reader.peek()
-            && matches!(token, b'>' | b' ' | b'+' | b'~' | b'|')
-        {
-            return None;
-        }
+    pub fn next(reader: &mut Reader<'a>) -> Result<Option<Self>, SelectorParseError> {
+        let Some(token) = reader.peek() else {
+            return Ok(None);
+        };
 
-        match reader.next()? {
-            b'#' => Some(Self::ID),
-            b'.' => Some(Self::Class),
-            b'"' => Some(Self::Quote),
-            b'\'' => Some(Self::Quote),
-            b'[' => Some(Self::OpenAttribute),
-            b']' => Some(Self::CloseAttribute),
+        let keyword = match token {
+            b'>' | b' ' | b'+' | b'~' | b'|' | b',' | b')' => return Ok(None),
+            b'#' => Self::ID,
+            b'.' => Self::Class,
+            b'"' => Self::Quote,
+            b'\'' => Self::Quote,
+            b'[' => Self::OpenAttribute,
+            b']' => Self::CloseAttribute,
+            b':' => Self::PseudoClass,
+            b'\\' => return Ok(Some(Self::String(next_identifier(reader)?))),
+            token if is_identifier_byte(token) => {
+                return Ok(Some(Self::String(next_identifier(reader)?)));
+            }
             _ => {
-                reader.next_until_list(&[b' ', b'#', b'.', b'[']);
-                Some(Self::String(reader.slice(start_pos..reader.get_position())))
+                reader.skip();
+                reader.next_until_list(&[
+                    b' ', b'#', b'.', b'[', b'>', b'+', b'~', b'|', b',', b':', b'(', b')',
+                ]);
+                return Ok(Some(Self::Invalid));
             }
-        }
+        };
+        reader.skip();
+        Ok(Some(keyword))
     }
 }
 
 enum SelectionAttributeToken<'a> {
-    String(&'a str),
-    Quote(QuoteKind),
+    String(Cow<'a, str>),
+    Quote(u8),
     Equal,
     StringMatchSelector(AttributeSelectionKind),
 }
@@ -292,9 +555,7 @@ impl<'a> SelectionAttributeToken<'a> {
     pub fn next(reader: &mut Reader<'a>) -> Result<Option<Self>, SelectorParseError> {
         reader.next_while(b' ');
 
-        let start_pos = reader.get_position();
-
-        let token = match reader.next() {
+        let token = match reader.peek() {
             None => {
                 return Err(SelectorParseError::new(
                     "attribute selector is missing a closing ']'",
@@ -304,9 +565,8 @@ impl<'a> SelectionAttributeToken<'a> {
             Some(token) => token,
         };
 
-        Ok(match token {
-            b'"' => Some(Self::Quote(QuoteKind::DoubleQuoted)),
-            b'\'' => Some(Self::Quote(QuoteKind::SingleQuoted)),
+        let token = match token {
+            b'"' | b'\'' => Some(Self::Quote(token)),
             b'=' => Some(Self::Equal),
             b'~' => Some(Self::StringMatchSelector(
                 AttributeSelectionKind::WhitespaceSeparated,
@@ -319,39 +579,55 @@ impl<'a> SelectionAttributeToken<'a> {
             b'*' => Some(Self::StringMatchSelector(AttributeSelectionKind::Substring)),
             b']' => None,
             _ => {
-                reader.next_until_list(&[
-                    b' ', b'"', b'\'', b'=', b']', b'~', b'|', b'^', b'$', b'*',
-                ]);
-                Some(Self::String(reader.slice(start_pos..reader.get_position())))
+                return Ok(Some(Self::String(next_until_unescaped(
+                    reader,
+                    &[b' ', b'"', b'\'', b'=', b']', b'~', b'|', b'^', b'$', b'*'],
+                )?)));
             }
-        })
+        };
+        reader.skip();
+        Ok(token)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ElementPredicate<'a> {
-    pub name: Option<&'a str>,
-    pub id: Option<&'a str>,
+    pub name: Option<Cow<'a, str>>,
+    /// The namespace URI a type selector is limited to; `Some("")` for
+    /// `|name`, which only matches elements without a namespace.
+    pub namespace: Option<&'a str>,
+    pub id: Option<Cow<'a, str>>,
     pub classes: ClassSelections<'a>,
     pub attributes: AttributeSelections<'a>,
+    pub pseudo_classes: PseudoClassSelections<'a>,
 }
 
 impl<'a> ElementPredicate<'a> {
     pub const fn new_const(
         name: Option<&'a str>,
+        namespace: Option<&'a str>,
         id: Option<&'a str>,
         classes: ClassSelections<'a>,
         attributes: AttributeSelections<'a>,
+        pseudo_classes: PseudoClassSelections<'a>,
     ) -> Self {
         Self {
-            name,
-            id,
+            name: match name {
+                Some(name) => Some(Cow::Borrowed(name)),
+                None => None,
+            },
+            namespace,
+            id: match id {
+                Some(id) => Some(Cow::Borrowed(id)),
+                None => None,
+            },
             classes,
             attributes,
+            pseudo_classes,
         }
     }
 
-    fn push_class(&mut self, class_name: &'a str) {
+    fn push_class(&mut self, class_name: Cow<'a, str>) {
         let mut classes = self.classes.as_slice().to_vec();
         classes.push(class_name);
         self.classes = ClassSelections::from(classes);
@@ -365,54 +641,455 @@ impl<'a> ElementPredicate<'a> {
         Ok(())
     }
 
+    fn try_parse_pseudo_class(
+        &mut self,
+        reader: &mut Reader<'a>,
+        namespaces: &Namespaces<'a>,
+    ) -> Result<(), SelectorParseError> {
+        let start = reader.get_position();
+        while let Some(token) = reader.peek()
+            && (token.is_ascii_alphanumeric() || token == b'-')
+        {
+            reader.skip();
+        }
+
+        let pseudo_class = match reader.slice(start..reader.get_position()) {
+            "" => {
+                return Err(SelectorParseError::new(
+                    "missing pseudo-class name",
+                    reader.get_position(),
+                ));
+            }
+            "not" => PseudoClass::Not(Self::try_parse_compound_list(reader, namespaces)?.into()),
+            "is" | "where" => {
+                PseudoClass::Is(Self::try_parse_compound_list(reader, namespaces)?.into())
+            }
+            "first-child" => PseudoClass::NthChild {
+                nth: Nth::new(0, 1),
+                of: None,
+            },
+            "last-child" => PseudoClass::NthLastChild {
+                nth: Nth::new(0, 1),
+                of: None,
+            },
+            "first-of-type" => PseudoClass::NthOfType(Nth::new(0, 1)),
+            "last-of-type" => PseudoClass::NthLastOfType(Nth::new(0, 1)),
+            "nth-child" => {
+                let (nth, of) = Self::try_parse_nth_argument(reader, true, namespaces)?;
+                PseudoClass::NthChild { nth, of }
+            }
+            "nth-last-child" => {
+                let (nth, of) = Self::try_parse_nth_argument(reader, true, namespaces)?;
+                PseudoClass::NthLastChild { nth, of }
+            }
+            "nth-of-type" => {
+                PseudoClass::NthOfType(Self::try_parse_nth_argument(reader, false, namespaces)?.0)
+            }
+            "nth-last-of-type" => PseudoClass::NthLastOfType(
+                Self::try_parse_nth_argument(reader, false, namespaces)?.0,
+            ),
+            "has" => Self::try_parse_relative_list(reader, namespaces)?,
+            _ => {
+                return Err(SelectorParseError::new("unsupported pseudo-class", start));
+            }
+        };
+
+        let mut pseudo_classes = self.pseudo_classes.as_slice().to_vec();
+        pseudo_classes.push(pseudo_class);
+        self.pseudo_classes = PseudoClassSelections::from(pseudo_classes);
+        Ok(())
+    }
+
+    fn try_open_argument(reader: &mut Reader<'a>) -> Result<(), SelectorParseError> {
+        if reader.peek() != Some(b'(') {
+            return Err(SelectorParseError::new(
+                "pseudo-class is missing its argument",
+                reader.get_position(),
+            ));
+        }
+        reader.skip();
+        Ok(())
+    }
+
+    /// Parses the `(an+b)` argument of a structural pseudo-class, and its
+    /// `of S` selector list when `allow_of` is set.
+    fn try_parse_nth_argument(
+        reader: &mut Reader<'a>,
+        allow_of: bool,
+        namespaces: &Namespaces<'a>,
+    ) -> Result<(Nth, Option<PredicateSelections<'a>>), SelectorParseError> {
+        Self::try_open_argument(reader)?;
+        reader.next_while_list(b" \t\n\r\x0c");
+        let nth = Nth::try_from(reader)?;
+
+        reader.next_while_list(b" \t\n\r\x0c");
+        match reader.peek() {
+            Some(b')') => {
+                reader.skip();
+                Ok((nth, None))
+            }
+            Some(_) if allow_of && reader.match_ignore_case("of ") => {
+                reader.skip();
+                reader.skip();
+                reader.next_while_list(b" \t\n\r\x0c");
+                let start = reader.get_position();
+                let of = Self::try_parse_compound_items(reader, namespaces)?;
+                // Siblings are counted against `S` as they open.
+                if of.iter().any(ElementPredicate::is_end_relative) {
+                    return Err(SelectorParseError::new(
+                        "end-relative pseudo-classes are not supported in `of` selectors",
+                        start,
+                    ));
+                }
+                if of.iter().any(ElementPredicate::is_relational) {
+                    return Err(SelectorParseError::new(
+                        "`:has()` is not supported in `of` selectors",
+                        start,
+                    ));
+                }
+                Ok((nth, Some(of.into())))
+            }
+            None => Err(SelectorParseError::new(
+                "pseudo-class is missing a closing ')'",
+                reader.get_position(),
+            )),
+            Some(_) => Err(SelectorParseError::new(
+                "invalid an+b argument",
+                reader.get_position(),
+            )),
+        }
+    }
+
+    /// Parses the `(> a, img)` argument of `:has()`. Each relative selector
+    /// is a compound, optionally preceded by the child combinator.
+    fn try_parse_relative_list(
+        reader: &mut Reader<'a>,
+        namespaces: &Namespaces<'a>,
+    ) -> Result<PseudoClass<'a>, SelectorParseError> {
+        Self::try_open_argument(reader)?;
+
+        let mut children = Vec::new();
+        let mut descendants = Vec::new();
+        loop {
+            reader.next_while_list(b" \t\n\r\x0c");
+            let list = match reader.peek() {
+                Some(b'>') => {
+                    reader.skip();
+                    reader.next_while_list(b" \t\n\r\x0c");
+                    &mut children
+                }
+                Some(b'+' | b'~') => {
+                    return Err(SelectorParseError::new(
+                        "`:has()` only supports descendant and child (`>`) relative selectors",
+                        reader.get_position(),
+                    ));
+                }
+                _ => &mut descendants,
+            };
+            if matches!(reader.peek(), Some(b')' | b',')) {
+                return Err(SelectorParseError::new(
+                    "pseudo-class argument is empty",
+                    reader.get_position(),
+                ));
+            }
+
+            let start = reader.get_position();
+            let predicate = Self::try_parse(reader, namespaces)?;
+            // Descendants are checked as they open.
+            if predicate.is_end_relative() {
+                return Err(SelectorParseError::new(
+                    "end-relative pseudo-classes are not supported in `:has()`",
+                    start,
+                ));
+            }
+            if predicate.is_relational() {
+                return Err(SelectorParseError::new("`:has()` can't be nested", start));
+            }
+            list.push(predicate);
+
+            reader.next_while_list(b" \t\n\r\x0c");
+            match reader.peek() {
+                Some(b',') => reader.skip(),
+                Some(b')') => {
+                    reader.skip();
+                    return Ok(PseudoClass::Has {
+                        children: children.into(),
+                        descendants: descendants.into(),
+                    });
+                }
+                None => {
+                    return Err(SelectorParseError::new(
+                        "pseudo-class is missing a closing ')'",
+                        reader.get_position(),
+                    ));
+                }
+                Some(_) => {
+                    return Err(SelectorParseError::new(
+                        "pseudo-class argument must be a compound selector",
+                        reader.get_position(),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Parses the `(a, .b, [c])` argument of a pseudo-class.
+    fn try_parse_compound_list(
+        reader: &mut Reader<'a>,
+        namespaces: &Namespaces<'a>,
+    ) -> Result<Vec<ElementPredicate<'a>>, SelectorParseError> {
+        Self::try_open_argument(reader)?;
+        Self::try_parse_compound_items(reader, namespaces)
+    }
+
+    fn try_parse_compound_items(
+        reader: &mut Reader<'a>,
+        namespaces: &Namespaces<'a>,
+    ) -> Result<Vec<ElementPredicate<'a>>, SelectorParseError> {
+        let mut predicates = Vec::new();
+        loop {
+            reader.next_while_list(b" \t\n\r\x0c");
+            if matches!(reader.peek(), Some(b')' | b',')) {
+                return Err(SelectorParseError::new(
+                    "pseudo-class argument is empty",
+                    reader.get_position(),
+                ));
+            }
+            predicates.push(Self::try_parse(reader, namespaces)?);
+
+            reader.next_while_list(b" \t\n\r\x0c");
+            match reader.peek() {
+                Some(b',') => reader.skip(),
+                Some(b')') => {
+                    reader.skip();
+                    return Ok(predicates);
+                }
+                None => {
+                    return Err(SelectorParseError::new(
+                        "pseudo-class is missing a closing ')'",
+                        reader.get_position(),
+                    ));
+                }
+                Some(_) => {
+                    return Err(SelectorParseError::new(
+                        "pseudo-class argument must be a compound selector",
+                        reader.get_position(),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Whether matching depends on the siblings that follow the element
+    /// (`:last-child`, `:nth-last-of-type()`, ...), which are only known
+    /// once its parent closes.
+    pub fn is_end_relative(&self) -> bool {
+        self.pseudo_classes
+            .as_slice()
+            .iter()
+            .any(PseudoClass::is_end_relative)
+    }
+
+    /// Whether matching depends on the element's descendants (`:has()`),
+    /// which are only known once it closes.
+    pub fn is_relational(&self) -> bool {
+        self.pseudo_classes
+            .as_slice()
+            .iter()
+            .any(PseudoClass::is_relational)
+    }
+
+    /// Whether matching numbers the element among its siblings
+    /// (`:first-child`, `:nth-of-type()`, ...), here or in a nested list.
+    pub fn numbers_siblings(&self) -> bool {
+        self.pseudo_classes
+            .as_slice()
+            .iter()
+            .any(PseudoClass::numbers_siblings)
+    }
+
+    /// Whether matching numbers the element among the siblings of its type
+    /// (`:nth-of-type()`, `:last-of-type`, ...), here or in a nested list.
+    pub fn is_of_type(&self) -> bool {
+        self.pseudo_classes
+            .as_slice()
+            .iter()
+            .any(PseudoClass::is_of_type)
+    }
+
+    /// Calls `f` with every list of a `:has()` in the predicate, along with
+    /// whether it only looks at children.
+    pub fn for_each_relative_selector<'s>(
+        &'s self,
+        f: &mut impl FnMut(&'s [ElementPredicate<'a>], bool),
+    ) {
+        for pseudo_class in self.pseudo_classes.as_slice() {
+            match pseudo_class {
+                PseudoClass::Not(predicates) | PseudoClass::Is(predicates) => {
+                    for predicate in predicates.as_slice() {
+                        predicate.for_each_relative_selector(f);
+                    }
+                }
+                PseudoClass::Has {
+                    children,
+                    descendants,
+                } => {
+                    for (list, children_only) in [(children, true), (descendants, false)] {
+                        if !list.as_slice().is_empty() {
+                            f(list.as_slice(), children_only);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Calls `f` with the `S` of every `:nth-child(an+b of S)` and
+    /// `:nth-last-child(an+b of S)` in the predicate, nested ones first.
+    pub fn for_each_nth_selector<'s>(&'s self, f: &mut impl FnMut(&'s [ElementPredicate<'a>])) {
+        for pseudo_class in self.pseudo_classes.as_slice() {
+            match pseudo_class {
+                PseudoClass::Not(predicates) | PseudoClass::Is(predicates) => {
+                    for predicate in predicates.as_slice() {
+                        predicate.for_each_nth_selector(f);
+                    }
+                }
+                PseudoClass::NthChild { of: Some(of), .. }
+                | PseudoClass::NthLastChild { of: Some(of), .. } => {
+                    for predicate in of.as_slice() {
+                        predicate.for_each_nth_selector(f);
+                    }
+                    f(of.as_slice());
+                }
+                PseudoClass::Has {
+                    children,
+                    descendants,
+                } => {
+                    for predicate in children.as_slice().iter().chain(descendants.as_slice()) {
+                        predicate.for_each_nth_selector(f);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Parses the `ns|name`, `*|*`, `|name` or `*` type selector at the start
+    /// of a compound, and returns whether there was one.
+    fn try_parse_type_selector(
+        &mut self,
+        reader: &mut Reader<'a>,
+        namespaces: &Namespaces<'a>,
+    ) -> Result<bool, SelectorParseError> {
+        let start = reader.get_position();
+        // `None` for `*`.
+        let prefix = match reader.peek() {
+            Some(b'*') => {
+                reader.skip();
+                None
+            }
+            Some(b'|') => Some(Cow::Borrowed("")),
+            Some(token) if token == b'\\' || is_identifier_byte(token) => {
+                Some(next_identifier(reader)?)
+            }
+            _ => return Ok(false),
+        };
+
+        // `|=` only appears inside attribute selectors.
+        if reader.peek() != Some(b'|') {
+            self.name = prefix;
+            self.namespace = namespaces.default_namespace();
+            return Ok(true);
+        }
+        reader.skip();
+
+        self.namespace = match prefix.as_deref() {
+            None => None,
+            Some("") => Some(""),
+            Some(prefix) => Some(namespaces.resolve(prefix).ok_or(SelectorParseError::new(
+                "undeclared namespace prefix",
+                start,
+            ))?),
+        };
+        self.name = match reader.peek() {
+            Some(b'*') => {
+                reader.skip();
+                None
+            }
+            Some(token) if token == b'\\' || is_identifier_byte(token) => {
+                Some(next_identifier(reader)?)
+            }
+            _ => {
+                return Err(SelectorParseError::new(
+                    "missing element name after namespace",
+                    reader.get_position(),
+                ));
+            }
+        };
+        Ok(true)
+    }
+
+    /// Parses a compound selector, resolving namespace prefixes against
+    /// [`Namespaces::default`].
     pub fn try_from(reader: &mut Reader<'a>) -> Result<Self, SelectorParseError> {
+        Self::try_parse(reader, &Namespaces::default())
+    }
+
+    pub fn try_parse(
+        reader: &mut Reader<'a>,
+        namespaces: &Namespaces<'a>,
+    ) -> Result<Self, SelectorParseError> {
         let mut element = Self {
             name: None,
+            namespace: None,
             id: None,
             classes: ClassSelections::default(),
             attributes: AttributeSelections::default(),
+            pseudo_classes: PseudoClassSelections::default(),
         };
 
+        let has_type = element.try_parse_type_selector(reader, namespaces)?;
         let mut previous: Option<SelectionKeyWords> = None;
 
-        while let Some(word) = SelectionKeyWords::next(reader) {
-            match (previous, &word) {
-                (Option::None, SelectionKeyWords::String(name)) => {
-                    if !is_valid_selector_name(name) {
-                        return Err(SelectorParseError::new(
-                            "illegal selector token",
-                            reader.get_position().saturating_sub(name.len()),
-                        ));
-                    }
-                    if element.name.is_some() {
-                        return Err(SelectorParseError::new(
-                            "selector has multiple element names",
-                            reader.get_position().saturating_sub(name.len()),
-                        ));
-                    }
-                    element.name = Some(*name);
+        loop {
+            let token_start = reader.get_position();
+            let Some(word) = SelectionKeyWords::next(reader)? else {
+                break;
+            };
+
+            match (&previous, &word) {
+                (Some(SelectionKeyWords::ID), SelectionKeyWords::Invalid) => {
+                    return Err(SelectorParseError::new("missing id string", token_start));
+                }
+                (Some(SelectionKeyWords::Class), SelectionKeyWords::Invalid) => {
+                    return Err(SelectorParseError::new("missing class string", token_start));
+                }
+                (_, SelectionKeyWords::Invalid) => {
+                    return Err(SelectorParseError::new(
+                        "illegal selector token",
+                        token_start,
+                    ));
+                }
+                // The type selector has already taken a leading name.
+                (Option::None, SelectionKeyWords::String(_)) => {
+                    return Err(SelectorParseError::new(
+                        "selector has multiple element names",
+                        token_start,
+                    ));
                 }
                 (Some(SelectionKeyWords::ID), SelectionKeyWords::String(id_name)) => {
-                    if !is_valid_selector_name(id_name) {
-                        return Err(SelectorParseError::new(
-                            "missing id string",
-                            reader.get_position().saturating_sub(id_name.len()),
-                        ));
-                    }
                     if element.id.is_none() {
-                        element.id = Some(*id_name);
+                        element.id = Some(id_name.clone());
                     }
                 }
                 (Some(SelectionKeyWords::Class), SelectionKeyWords::String(class_name)) => {
-                    if !is_valid_selector_name(class_name) {
-                        return Err(SelectorParseError::new(
-                            "missing class string",
-                            reader.get_position().saturating_sub(class_name.len()),
-                        ));
-                    }
-                    element.push_class(class_name);
+                    element.push_class(class_name.clone());
                 }
                 (_, SelectionKeyWords::OpenAttribute) => element.try_parse_attribute(reader)?,
+                (_, SelectionKeyWords::PseudoClass) => {
+                    element.try_parse_pseudo_class(reader, namespaces)?
+                }
 
                 (Some(SelectionKeyWords::ID), _) => {
                     return Err(SelectorParseError::new(
@@ -442,10 +1119,15 @@ impl<'a> ElementPredicate<'a> {
                 "missing class string",
                 reader.get_position(),
             )),
-            _ if element.name.is_none()
+            None if !has_type && reader.peek() == Some(b')') => Err(SelectorParseError::new(
+                "illegal selector token",
+                reader.get_position(),
+            )),
+            _ if !has_type
                 && element.id.is_none()
                 && element.classes.as_slice().is_empty()
-                && element.attributes.as_slice().is_empty() =>
+                && element.attributes.as_slice().is_empty()
+                && element.pseudo_classes.as_slice().is_empty() =>
             {
                 Err(SelectorParseError::new(
                     "missing selector element",
@@ -463,26 +1145,20 @@ impl<'a> From<&mut Reader<'a>> for ElementPredicate<'a> {
     }
 }
 
-fn is_valid_selector_name(value: &str) -> bool {
-    !value.is_empty()
-        && value
-            .bytes()
-            .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_'))
-}
-
 fn is_valid_attribute_name(value: &str) -> bool {
     let mut bytes = value.bytes();
     match bytes.next() {
-        Some(first) if first.is_ascii_alphabetic() || first == b'_' => (),
+        Some(first) if first.is_ascii_alphabetic() || first == b'_' || !first.is_ascii() => (),
         _ => return false,
     }
 
-    bytes.all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_'))
+    bytes.all(is_identifier_byte)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{HTML_NAMESPACE, SVG_NAMESPACE};
 
     #[test]
     fn test_basic_element_selection() {
@@ -492,10 +1168,12 @@ mod tests {
         assert_eq!(
             element,
             ElementPredicate {
-                name: Some("element"),
-                id: Some("id"),
-                classes: ClassSelections::from_static(&["class"]),
+                name: Some(Cow::Borrowed("element")),
+                namespace: None,
+                id: Some(Cow::Borrowed("id")),
+                classes: ClassSelections::from_static(&[Cow::Borrowed("class")]),
                 attributes: AttributeSelections::from_static(&[]),
+                pseudo_classes: PseudoClassSelections::from_static(&[]),
             }
         );
     }
@@ -509,14 +1187,17 @@ mod tests {
         assert_eq!(
             element,
             ElementPredicate {
-                name: Some("element"),
-                id: Some("id"),
-                classes: ClassSelections::from_static(&["class"]),
+                name: Some(Cow::Borrowed("element")),
+                namespace: None,
+                id: Some(Cow::Borrowed("id")),
+                classes: ClassSelections::from_static(&[Cow::Borrowed("class")]),
                 attributes: AttributeSelections::from(vec![AttributeSelection {
-                    name: "selected",
-                    value: Some("true"),
-                    kind: AttributeSelectionKind::Exact
+                    name: Cow::Borrowed("selected"),
+                    value: Some(Cow::Borrowed("true")),
+                    kind: AttributeSelectionKind::Exact,
+                    case: CaseSensitivity::Sensitive,
                 }]),
+                pseudo_classes: PseudoClassSelections::from_static(&[]),
             }
         );
     }
@@ -530,21 +1211,25 @@ mod tests {
         assert_eq!(
             element,
             ElementPredicate {
-                name: Some("element"),
-                id: Some("id"),
-                classes: ClassSelections::from_static(&["class"]),
+                name: Some(Cow::Borrowed("element")),
+                namespace: None,
+                id: Some(Cow::Borrowed("id")),
+                classes: ClassSelections::from_static(&[Cow::Borrowed("class")]),
                 attributes: AttributeSelections::from(vec![
                     AttributeSelection {
-                        name: "href",
-                        value: Some("_blank"),
-                        kind: AttributeSelectionKind::WhitespaceSeparated
+                        name: Cow::Borrowed("href"),
+                        value: Some(Cow::Borrowed("_blank")),
+                        kind: AttributeSelectionKind::WhitespaceSeparated,
+                        case: CaseSensitivity::Sensitive,
                     },
                     AttributeSelection {
-                        name: "selected",
-                        value: Some("true"),
-                        kind: AttributeSelectionKind::Exact
+                        name: Cow::Borrowed("selected"),
+                        value: Some(Cow::Borrowed("true")),
+                        kind: AttributeSelectionKind::Exact,
+                        case: CaseSensitivity::Sensitive,
                     }
                 ]),
+                pseudo_classes: PseudoClassSelections::from_static(&[]),
             }
         );
     }
@@ -557,14 +1242,17 @@ mod tests {
         assert_eq!(
             element,
             ElementPredicate {
-                name: Some("element"),
-                id: Some("id"),
-                classes: ClassSelections::from_static(&["class"]),
+                name: Some(Cow::Borrowed("element")),
+                namespace: None,
+                id: Some(Cow::Borrowed("id")),
+                classes: ClassSelections::from_static(&[Cow::Borrowed("class")]),
                 attributes: AttributeSelections::from(vec![AttributeSelection {
-                    name: "selected",
-                    value: Some("true"),
-                    kind: AttributeSelectionKind::Exact
+                    name: Cow::Borrowed("selected"),
+                    value: Some(Cow::Borrowed("true")),
+                    kind: AttributeSelectionKind::Exact,
+                    case: CaseSensitivity::Sensitive,
                 }]),
+                pseudo_classes: PseudoClassSelections::from_static(&[]),
             }
         );
     }
@@ -577,11 +1265,346 @@ mod tests {
         assert_eq!(
             element,
             ElementPredicate {
-                name: Some("a"),
+                name: Some(Cow::Borrowed("a")),
+                namespace: None,
                 id: None,
-                classes: ClassSelections::from_static(&["blue", "exit"]),
+                classes: ClassSelections::from_static(&[
+                    Cow::Borrowed("blue"),
+                    Cow::Borrowed("exit")
+                ]),
                 attributes: AttributeSelections::from_static(&[]),
+                pseudo_classes: PseudoClassSelections::from_static(&[]),
             }
         );
     }
+
+    #[test]
+    fn test_not_pseudo_class_takes_a_compound_list() {
+        let mut reader = Reader::new("a.link:not(.ad, [rel=nofollow])");
+        let element = ElementPredicate::from(&mut reader);
+
+        assert_eq!(
+            element,
+            ElementPredicate {
+                name: Some(Cow::Borrowed("a")),
+                namespace: None,
+                id: None,
+                classes: ClassSelections::from_static(&[Cow::Borrowed("link")]),
+                attributes: AttributeSelections::from_static(&[]),
+                pseudo_classes: PseudoClassSelections::from(vec![PseudoClass::Not(
+                    PredicateSelections::from(vec![
+                        ElementPredicate {
+                            name: None,
+                            namespace: None,
+                            id: None,
+                            classes: ClassSelections::from_static(&[Cow::Borrowed("ad")]),
+                            attributes: AttributeSelections::from_static(&[]),
+                            pseudo_classes: PseudoClassSelections::from_static(&[]),
+                        },
+                        ElementPredicate {
+                            name: None,
+                            namespace: None,
+                            id: None,
+                            classes: ClassSelections::from_static(&[]),
+                            attributes: AttributeSelections::from(vec![AttributeSelection {
+                                name: Cow::Borrowed("rel"),
+                                value: Some(Cow::Borrowed("nofollow")),
+                                kind: AttributeSelectionKind::Exact,
+                                case: CaseSensitivity::Sensitive,
+                            }]),
+                            pseudo_classes: PseudoClassSelections::from_static(&[]),
+                        },
+                    ])
+                )]),
+            }
+        );
+    }
+
+    #[test]
+    fn test_is_and_where_parse_to_the_same_disjunction() {
+        let is = ElementPredicate::from(&mut Reader::new(":is(h1, h2.title)"));
+        let where_ = ElementPredicate::from(&mut Reader::new(":where( h1 ,h2.title )"));
+
+        assert_eq!(is, where_);
+        assert_eq!(
+            is.pseudo_classes.as_slice(),
+            &[PseudoClass::Is(PredicateSelections::from(vec![
+                ElementPredicate::from(&mut Reader::new("h1")),
+                ElementPredicate::from(&mut Reader::new("h2.title")),
+            ]))]
+        );
+    }
+
+    #[test]
+    fn test_structural_pseudo_classes() {
+        let pseudo_classes = |selector: &'static str| {
+            ElementPredicate::from(&mut Reader::new(selector))
+                .pseudo_classes
+                .as_slice()
+                .to_vec()
+        };
+
+        assert_eq!(
+            pseudo_classes("li:first-child:last-of-type"),
+            vec![
+                PseudoClass::NthChild {
+                    nth: Nth::new(0, 1),
+                    of: None
+                },
+                PseudoClass::NthLastOfType(Nth::new(0, 1)),
+            ]
+        );
+        assert_eq!(
+            pseudo_classes("td:nth-child( 2n+1 ):nth-last-of-type(-n + 3)"),
+            vec![
+                PseudoClass::NthChild {
+                    nth: Nth::new(2, 1),
+                    of: None
+                },
+                PseudoClass::NthLastOfType(Nth::new(-1, 3)),
+            ]
+        );
+        assert_eq!(
+            pseudo_classes(":nth-last-child(odd of .a, p)"),
+            vec![PseudoClass::NthLastChild {
+                nth: Nth::new(2, 1),
+                of: Some(PredicateSelections::from(vec![
+                    ElementPredicate::from(&mut Reader::new(".a")),
+                    ElementPredicate::from(&mut Reader::new("p")),
+                ])),
+            }]
+        );
+
+        assert!(ElementPredicate::from(&mut Reader::new("li:last-child")).is_end_relative());
+        assert!(
+            ElementPredicate::from(&mut Reader::new("li:not(:nth-last-child(2))"))
+                .is_end_relative()
+        );
+        assert!(
+            !ElementPredicate::from(&mut Reader::new("li:nth-child(2 of .a)")).is_end_relative()
+        );
+
+        assert!(ElementPredicate::from(&mut Reader::new("li:first-child")).numbers_siblings());
+        assert!(
+            ElementPredicate::from(&mut Reader::new("ul:has(> li:nth-of-type(2))"))
+                .numbers_siblings()
+        );
+        assert!(!ElementPredicate::from(&mut Reader::new("li:not(.a)")).numbers_siblings());
+    }
+
+    #[test]
+    fn test_for_each_nth_selector_visits_nested_selectors_first() {
+        let element = ElementPredicate::from(&mut Reader::new(
+            ":nth-child(1 of :nth-child(2 of .a)):not(:nth-last-child(3 of p))",
+        ));
+
+        let mut selectors = Vec::new();
+        element.for_each_nth_selector(&mut |selector| selectors.push(selector.to_vec()));
+
+        assert_eq!(
+            selectors,
+            vec![
+                vec![ElementPredicate::from(&mut Reader::new(".a"))],
+                vec![ElementPredicate::from(&mut Reader::new(
+                    ":nth-child(2 of .a)"
+                ))],
+                vec![ElementPredicate::from(&mut Reader::new("p"))],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_has_splits_child_and_descendant_lists() {
+        let element = ElementPredicate::from(&mut Reader::new("li:has(> a.download, img, >b)"));
+
+        assert_eq!(
+            element.pseudo_classes.as_slice(),
+            &[PseudoClass::Has {
+                children: PredicateSelections::from(vec![
+                    ElementPredicate::from(&mut Reader::new("a.download")),
+                    ElementPredicate::from(&mut Reader::new("b")),
+                ]),
+                descendants: PredicateSelections::from(vec![ElementPredicate::from(
+                    &mut Reader::new("img")
+                )]),
+            }]
+        );
+        assert!(element.is_relational());
+        assert!(!element.is_end_relative());
+        assert!(ElementPredicate::from(&mut Reader::new("li:not(:has(a))")).is_relational());
+
+        let mut lists = Vec::new();
+        element.for_each_relative_selector(&mut |selector, children_only| {
+            lists.push((selector.len(), children_only))
+        });
+        assert_eq!(lists, vec![(2, true), (1, false)]);
+    }
+
+    #[test]
+    fn test_universal_and_namespaced_type_selectors() {
+        let parse = |selector: &'static str, namespaces: &Namespaces<'static>| {
+            let element =
+                ElementPredicate::try_parse(&mut Reader::new(selector), namespaces).unwrap();
+            (element.namespace, element.name)
+        };
+        let namespaces = Namespaces::default();
+
+        assert_eq!(parse("*", &namespaces), (None, None));
+        assert_eq!(parse("*|*", &namespaces), (None, None));
+        assert_eq!(parse("*|a", &namespaces), (None, Some("a".into())));
+        assert_eq!(parse("|a", &namespaces), (Some(""), Some("a".into())));
+        assert_eq!(
+            parse("svg|rect", &namespaces),
+            (Some(SVG_NAMESPACE), Some("rect".into()))
+        );
+        assert_eq!(parse("svg|*", &namespaces), (Some(SVG_NAMESPACE), None));
+
+        let namespaces = Namespaces::empty().with_default(HTML_NAMESPACE);
+        assert_eq!(
+            parse("a", &namespaces),
+            (Some(HTML_NAMESPACE), Some("a".into()))
+        );
+        assert_eq!(parse("*", &namespaces), (Some(HTML_NAMESPACE), None));
+        assert_eq!(parse("*|a", &namespaces), (None, Some("a".into())));
+
+        let element = ElementPredicate::try_from(&mut Reader::new("*[data-id]")).unwrap();
+        assert_eq!(element.name, None);
+        assert_eq!(element.attributes.as_slice()[0].name, "data-id");
+
+        let cases = [
+            ("x|a", "undeclared namespace prefix", 0),
+            ("svg|", "missing element name after namespace", 4),
+            ("svg|.a", "missing element name after namespace", 4),
+            ("a*", "illegal selector token", 1),
+        ];
+        for (selector, message, position) in cases {
+            let error = ElementPredicate::try_from(&mut Reader::new(selector)).unwrap_err();
+            assert_eq!(error.message(), message, "{selector}");
+            assert_eq!(error.position(), position, "{selector}");
+        }
+    }
+
+    #[test]
+    fn test_attribute_case_modifiers() {
+        let case_of = |selector: &'static str| {
+            let element = ElementPredicate::try_from(&mut Reader::new(selector)).unwrap();
+            element.attributes.as_slice()[0].case
+        };
+
+        assert_eq!(case_of("[type=submit]"), CaseSensitivity::Sensitive);
+        assert_eq!(case_of("[type=submit i]"), CaseSensitivity::Insensitive);
+        assert_eq!(case_of("[type=\"a b\" I]"), CaseSensitivity::Insensitive);
+        assert_eq!(case_of("[lang|='en' s]"), CaseSensitivity::Sensitive);
+
+        let cases = [
+            (
+                "[type=submit x]",
+                "attribute selector has an unknown modifier",
+                13,
+            ),
+            (
+                "[type=submit i s]",
+                "attribute selector has multiple modifiers",
+                15,
+            ),
+        ];
+        for (selector, message, position) in cases {
+            let error = ElementPredicate::try_from(&mut Reader::new(selector)).unwrap_err();
+            assert_eq!(error.message(), message, "{selector}");
+            assert_eq!(error.position(), position, "{selector}");
+        }
+    }
+
+    #[test]
+    fn test_escaped_and_non_ascii_identifiers() {
+        let selector = r"d\69v#\31 23.md\:flex.café[data\:x='\\']";
+        let element = ElementPredicate::try_from(&mut Reader::new(selector)).unwrap();
+
+        assert_eq!(element.name.as_deref(), Some("div"));
+        assert_eq!(element.id.as_deref(), Some("123"));
+        assert_eq!(
+            element.classes,
+            ClassSelections::from_static(&[Cow::Borrowed("md:flex"), Cow::Borrowed("café")])
+        );
+        assert!(matches!(element.classes.as_slice()[1], Cow::Borrowed(_)));
+        let attribute = &element.attributes.as_slice()[0];
+        assert_eq!(attribute.name, "data:x");
+        assert_eq!(attribute.value.as_deref(), Some("\\"));
+
+        let cases = [
+            ("a.\\", "selector ends inside an escape", 3),
+            ("a#!", "missing id string", 2),
+            ("a.!", "missing class string", 2),
+            (
+                "a[x='y]",
+                "attribute selector has an unclosed quoted value",
+                7,
+            ),
+        ];
+        for (selector, message, position) in cases {
+            let error = ElementPredicate::try_from(&mut Reader::new(selector)).unwrap_err();
+            assert_eq!(error.message(), message, "{selector}");
+            assert_eq!(error.position(), position, "{selector}");
+        }
+    }
+
+    #[test]
+    fn test_pseudo_class_errors() {
+        let cases = [
+            ("a:hover", "unsupported pseudo-class", 2),
+            ("a:", "missing pseudo-class name", 2),
+            ("a:not", "pseudo-class is missing its argument", 5),
+            ("a:not()", "pseudo-class argument is empty", 6),
+            ("a:not(.x", "pseudo-class is missing a closing ')'", 8),
+            (
+                "a:not(a > b)",
+                "pseudo-class argument must be a compound selector",
+                8,
+            ),
+            ("a:nth-child", "pseudo-class is missing its argument", 11),
+            ("a:nth-child()", "invalid an+b argument", 12),
+            (
+                "a:nth-child(2n",
+                "pseudo-class is missing a closing ')'",
+                14,
+            ),
+            ("a:nth-child(2 x)", "invalid an+b argument", 14),
+            ("a:nth-of-type(2 of .a)", "invalid an+b argument", 16),
+            (
+                "a:nth-child(2 of :last-child)",
+                "end-relative pseudo-classes are not supported in `of` selectors",
+                17,
+            ),
+            (
+                "a:nth-child(2 of :has(b))",
+                "`:has()` is not supported in `of` selectors",
+                17,
+            ),
+            ("a:has()", "pseudo-class argument is empty", 6),
+            ("a:has(>)", "pseudo-class argument is empty", 7),
+            (
+                "a:has(+ b)",
+                "`:has()` only supports descendant and child (`>`) relative selectors",
+                6,
+            ),
+            (
+                "a:has(> b > c)",
+                "pseudo-class argument must be a compound selector",
+                10,
+            ),
+            ("a:has(:has(b))", "`:has()` can't be nested", 6),
+            (
+                "a:has(b:last-child)",
+                "end-relative pseudo-classes are not supported in `:has()`",
+                6,
+            ),
+        ];
+
+        for (selector, message, position) in cases {
+            let mut reader = Reader::new(selector);
+            let error = ElementPredicate::try_from(&mut reader).unwrap_err();
+            assert_eq!(error.message(), message, "{selector}");
+            assert_eq!(error.position(), position, "{selector}");
+        }
+    }
 }
@@ -1,21 +1,29 @@
-use super::builder::{Attribute, AttributeSelection, ElementPredicate, IElement};
-use super::string_search::AttributeSelectionKind;
+use super::builder::{
+    Attribute, AttributeSelection, ElementPredicate, IElement, PredicateSelections, PseudoClass,
+    SiblingFilter,
+};
+use super::nth::Nth;
+use super::string_search::{AttributeSelectionKind, CaseSensitivity};
 
 impl<'a> AttributeSelection<'a> {
-    pub fn matches_attribute(&self, other: &Attribute<'_>) -> bool {
-        if self.name != other.key {
+    /// Whether `other` matches, comparing attribute names with `name_case`.
+    pub fn matches_attribute(&self, other: &Attribute<'_>, name_case: CaseSensitivity) -> bool {
+        if !name_case.eq(self.name.as_bytes(), other.key.as_bytes()) {
             return false;
         }
 
-        if self.value.is_none() || self.kind == AttributeSelectionKind::Presence {
+        let Some(value) = self.value.as_deref() else {
+            return true;
+        };
+        if self.kind == AttributeSelectionKind::Presence {
             return true;
         }
 
-        if other.value.is_none() {
+        let Some(other_value) = other.value.as_deref() else {
             return false;
-        }
+        };
 
-        self.kind.find(self.value.unwrap(), other.value.unwrap())
+        self.kind.find(value, other_value, self.case)
     }
 }
 
@@ -56,40 +64,152 @@ impl<'a> ElementPredicate<'a> {
         }
     }
 
+    /// Whether `other` can match.
+    ///
+    /// A match that depends on the siblings after `other` (`:last-child`)
+    /// counts until [`Self::try_match_element`] can decide it.
     pub fn matches_element<'b, E: IElement<'b>>(&self, other: &E) -> bool {
-        if let Some(name) = self.name
-            && name != other.name()
+        self.try_match_element(other) != Some(false)
+    }
+
+    /// Like [`Self::matches_element`], but `None` while the answer depends
+    /// on siblings that [`IElement::sibling_count`] doesn't know yet.
+    pub fn try_match_element<'b, E: IElement<'b>>(&self, other: &E) -> Option<bool> {
+        let name_case = other.name_case();
+        if let Some(name) = self.name.as_deref()
+            && !name_case.eq(name.as_bytes(), other.name().as_bytes())
         {
-            return false;
+            return Some(false);
         }
 
-        if self.id.is_some() && self.id != other.id() {
-            return false;
+        if let Some(namespace) = self.namespace
+            && namespace != other.namespace()
+        {
+            return Some(false);
+        }
+
+        if self.id.is_some() && self.id.as_deref() != other.id() {
+            return Some(false);
         }
 
         if !self.classes.as_slice().is_empty() {
             let Some(element_classes) = other.class() else {
-                return false;
+                return Some(false);
             };
 
             if !self.matches_classes(element_classes) {
-                return false;
+                return Some(false);
             }
         }
 
-        self.attributes.as_slice().iter().all(|selector_attribute| {
-            other
-                .attributes()
-                .iter()
-                .any(|xhtml_attribute| selector_attribute.matches_attribute(xhtml_attribute))
-        })
+        let attributes_match = self.attributes.as_slice().iter().all(|selector_attribute| {
+            other.attributes().iter().any(|xhtml_attribute| {
+                selector_attribute.matches_attribute(xhtml_attribute, name_case)
+            })
+        });
+        if !attributes_match {
+            return Some(false);
+        }
+
+        let mut decided = true;
+        for pseudo_class in self.pseudo_classes.as_slice() {
+            match pseudo_class.try_match_element(other) {
+                Some(false) => return Some(false),
+                Some(true) => {}
+                None => decided = false,
+            }
+        }
+
+        decided.then_some(true)
+    }
+}
+
+impl<'a> PseudoClass<'a> {
+    fn try_match_any<'b, E: IElement<'b>>(
+        predicates: &PredicateSelections<'a>,
+        other: &E,
+    ) -> Option<bool> {
+        let mut matched = Some(false);
+        for predicate in predicates.as_slice() {
+            match predicate.try_match_element(other) {
+                Some(true) => return Some(true),
+                Some(false) => {}
+                None => matched = None,
+            }
+        }
+        matched
+    }
+
+    fn filter<'s>(of: &'s Option<PredicateSelections<'a>>) -> SiblingFilter<'s, 'a> {
+        match of {
+            Some(of) => SiblingFilter::Matching(of.as_slice()),
+            None => SiblingFilter::All,
+        }
+    }
+
+    fn try_match_from_end<'b, E: IElement<'b>>(
+        nth: &Nth,
+        filter: SiblingFilter<'_, '_>,
+        other: &E,
+    ) -> Option<bool> {
+        let Some(index) = other.sibling_index(filter) else {
+            return Some(false);
+        };
+        let count = other.sibling_count(filter)?;
+
+        Some(index <= count && nth.matches(count - index + 1))
+    }
+
+    pub fn try_match_element<'b, E: IElement<'b>>(&self, other: &E) -> Option<bool> {
+        match self {
+            Self::Not(predicates) => Self::try_match_any(predicates, other).map(|matched| !matched),
+            Self::Is(predicates) => Self::try_match_any(predicates, other),
+            Self::NthChild { nth, of } => Some(
+                other
+                    .sibling_index(Self::filter(of))
+                    .is_some_and(|index| nth.matches(index)),
+            ),
+            Self::NthOfType(nth) => Some(
+                other
+                    .sibling_index(SiblingFilter::SameType)
+                    .is_some_and(|index| nth.matches(index)),
+            ),
+            Self::NthLastChild { nth, of } => {
+                Self::try_match_from_end(nth, Self::filter(of), other)
+            }
+            Self::NthLastOfType(nth) => {
+                Self::try_match_from_end(nth, SiblingFilter::SameType, other)
+            }
+            Self::Has {
+                children,
+                descendants,
+            } => {
+                let mut matched = Some(false);
+                for list in [children, descendants] {
+                    if list.as_slice().is_empty() {
+                        continue;
+                    }
+                    match other.has_relative_match(list.as_slice()) {
+                        Some(true) => return Some(true),
+                        Some(false) => {}
+                        None => matched = None,
+                    }
+                }
+                matched
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::borrow::Cow;
+
     use super::*;
-    use crate::{AttributeSelections, ClassSelections};
+    use crate::{
+        AttributeSelections, CaseSensitivity, ClassSelections, HTML_NAMESPACE,
+        PseudoClassSelections, SVG_NAMESPACE,
+    };
 
     #[derive(Debug)]
     struct FakeElement<'a> {
@@ -117,18 +237,75 @@ mod tests {
         }
     }
 
+    // An element at `index` among `count` siblings, `count` being unknown
+    // until its parent closes.
+    struct SiblingElement {
+        name: &'static str,
+        index: u32,
+        type_index: u32,
+        count: Option<(u32, u32)>,
+    }
+
+    impl IElement<'static> for SiblingElement {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn id(&self) -> Option<&'static str> {
+            None
+        }
+
+        fn class(&self) -> Option<&'static str> {
+            None
+        }
+
+        fn attributes(&self) -> &[Attribute<'static>] {
+            &[]
+        }
+
+        fn sibling_index(&self, filter: SiblingFilter<'_, '_>) -> Option<u32> {
+            match filter {
+                SiblingFilter::All => Some(self.index),
+                SiblingFilter::SameType => Some(self.type_index),
+                SiblingFilter::Matching(_) => None,
+            }
+        }
+
+        fn sibling_count(&self, filter: SiblingFilter<'_, '_>) -> Option<u32> {
+            let (count, type_count) = self.count?;
+            match filter {
+                SiblingFilter::All => Some(count),
+                SiblingFilter::SameType => Some(type_count),
+                SiblingFilter::Matching(_) => None,
+            }
+        }
+    }
+
     #[test]
     fn test_attribute_selection_comparison() {
         assert!(
             AttributeSelection {
-                name: "hello",
-                value: Some("World"),
+                name: Cow::Borrowed("hello"),
+                value: Some(Cow::Borrowed("World")),
                 kind: AttributeSelectionKind::Exact,
+                case: CaseSensitivity::Sensitive,
             }
-            .matches_attribute(&Attribute {
-                key: "hello",
-                value: Some("World")
-            })
+            .matches_attribute(
+                &Attribute::new("hello", Some("World")),
+                CaseSensitivity::Insensitive
+            )
+        );
+        assert!(
+            AttributeSelection {
+                name: Cow::Borrowed("hello"),
+                value: Some(Cow::Borrowed("world")),
+                kind: AttributeSelectionKind::Exact,
+                case: CaseSensitivity::Insensitive,
+            }
+            .matches_attribute(
+                &Attribute::new("hello", Some("World")),
+                CaseSensitivity::Insensitive
+            )
         );
     }
 
@@ -136,67 +313,128 @@ mod tests {
     fn test_element_selection_comparison() {
         assert!(
             ElementPredicate {
-                name: Some("hello"),
-                id: Some("id"),
-                classes: ClassSelections::from_static(&["world"]),
+                name: Some(Cow::Borrowed("hello")),
+                namespace: None,
+                id: Some(Cow::Borrowed("id")),
+                classes: ClassSelections::from_static(&[Cow::Borrowed("world")]),
                 attributes: AttributeSelections::from(vec![AttributeSelection {
-                    name: "selected",
-                    value: Some("true"),
-                    kind: AttributeSelectionKind::Exact
-                }])
+                    name: Cow::Borrowed("selected"),
+                    value: Some(Cow::Borrowed("true")),
+                    kind: AttributeSelectionKind::Exact,
+                    case: CaseSensitivity::Sensitive,
+                }]),
+                pseudo_classes: PseudoClassSelections::from_static(&[])
             }
             .matches_element(&FakeElement {
                 name: "hello",
                 id: Some("id"),
                 class: Some("hello world"),
                 attributes: &[
-                    Attribute {
-                        key: "key1",
-                        value: Some("value1")
-                    },
-                    Attribute {
-                        key: "key2",
-                        value: Some("value2")
-                    },
-                    Attribute {
-                        key: "selected",
-                        value: Some("true")
-                    },
+                    Attribute::new("key1", Some("value1")),
+                    Attribute::new("key2", Some("value2")),
+                    Attribute::new("selected", Some("true")),
                 ]
             })
         );
     }
 
+    #[test]
+    fn test_names_compare_with_the_element_name_case() {
+        struct XmlElement<'a>(FakeElement<'a>);
+
+        impl<'a> IElement<'a> for XmlElement<'a> {
+            fn name(&self) -> &'a str {
+                self.0.name
+            }
+
+            fn id(&self) -> Option<&'a str> {
+                None
+            }
+
+            fn class(&self) -> Option<&'a str> {
+                None
+            }
+
+            fn attributes(&self) -> &[Attribute<'a>] {
+                self.0.attributes
+            }
+
+            fn name_case(&self) -> CaseSensitivity {
+                CaseSensitivity::Sensitive
+            }
+        }
+
+        let predicate = ElementPredicate {
+            name: Some(Cow::Borrowed("div")),
+            namespace: None,
+            id: None,
+            classes: ClassSelections::from_static(&[]),
+            attributes: AttributeSelections::from(vec![AttributeSelection {
+                name: Cow::Borrowed("data-x"),
+                value: None,
+                kind: AttributeSelectionKind::Presence,
+                case: CaseSensitivity::Sensitive,
+            }]),
+            pseudo_classes: PseudoClassSelections::from_static(&[]),
+        };
+        let element = FakeElement {
+            name: "DIV",
+            id: None,
+            class: None,
+            attributes: &[Attribute::new("DATA-X", None)],
+        };
+
+        assert!(predicate.matches_element(&element));
+        assert!(!predicate.matches_element(&XmlElement(element)));
+    }
+
+    #[test]
+    fn test_namespace_must_match_the_element() {
+        let predicate = |namespace| ElementPredicate {
+            name: Some(Cow::Borrowed("a")),
+            namespace,
+            id: None,
+            classes: ClassSelections::from_static(&[]),
+            attributes: AttributeSelections::from_static(&[]),
+            pseudo_classes: PseudoClassSelections::from_static(&[]),
+        };
+        let element = FakeElement {
+            name: "a",
+            id: None,
+            class: None,
+            attributes: &[],
+        };
+
+        assert!(predicate(None).matches_element(&element));
+        assert!(predicate(Some(HTML_NAMESPACE)).matches_element(&element));
+        assert!(!predicate(Some(SVG_NAMESPACE)).matches_element(&element));
+        assert!(!predicate(Some("")).matches_element(&element));
+    }
+
     #[test]
     fn test_realistic_search() {
         assert!(
             ElementPredicate {
-                name: Some("a"),
+                name: Some(Cow::Borrowed("a")),
+                namespace: None,
                 id: None,
-                classes: ClassSelections::from_static(&["underline-green"]),
+                classes: ClassSelections::from_static(&[Cow::Borrowed("underline-green")]),
                 attributes: AttributeSelections::from(vec![AttributeSelection {
-                    name: "href",
+                    name: Cow::Borrowed("href"),
                     value: None,
                     kind: AttributeSelectionKind::Presence,
-                }])
+                    case: CaseSensitivity::Sensitive,
+                }]),
+                pseudo_classes: PseudoClassSelections::from_static(&[])
             }
             .matches_element(&FakeElement {
                 name: "a",
                 id: Some("search-link"),
                 class: Some("text-white underline-green p-4"),
                 attributes: &[
-                    Attribute {
-                        key: "key1",
-                        value: Some("value1")
-                    },
-                    Attribute {
-                        key: "href",
-                        value: Some("/search")
-                    },
-                    Attribute {
-                        key: "selected",
-                        value: Some("true")
-                    },
+                    Attribute::new("key1", Some("value1")),
+                    Attribute::new("href", Some("/search")),
+                    Attribute::new("selected", Some("true")),
                 ]
             })
         );
@@ -206,10 +444,15 @@ mod tests {
     fn test_multiple_class_selection_comparison() {
         assert!(
             ElementPredicate {
-                name: Some("a"),
+                name: Some(Cow::Borrowed("a")),
+                namespace: None,
                 id: None,
-                classes: ClassSelections::from_static(&["blue", "exit"]),
-                attributes: AttributeSelections::from_static(&[])
+                classes: ClassSelections::from_static(&[
+                    Cow::Borrowed("blue"),
+                    Cow::Borrowed("exit")
+                ]),
+                attributes: AttributeSelections::from_static(&[]),
+                pseudo_classes: PseudoClassSelections::from_static(&[])
             }
             .matches_element(&FakeElement {
                 name: "a",
@@ -224,10 +467,15 @@ mod tests {
     fn test_multiple_class_selection_comparison_is_order_independent() {
         assert!(
             ElementPredicate {
-                name: Some("a"),
+                name: Some(Cow::Borrowed("a")),
+                namespace: None,
                 id: None,
-                classes: ClassSelections::from_static(&["exit", "blue"]),
-                attributes: AttributeSelections::from_static(&[])
+                classes: ClassSelections::from_static(&[
+                    Cow::Borrowed("exit"),
+                    Cow::Borrowed("blue")
+                ]),
+                attributes: AttributeSelections::from_static(&[]),
+                pseudo_classes: PseudoClassSelections::from_static(&[])
             }
             .matches_element(&FakeElement {
                 name: "a",
@@ -242,10 +490,16 @@ mod tests {
     fn test_multiple_class_selection_comparison_requires_all_classes() {
         assert!(
             !ElementPredicate {
-                name: Some("a"),
+                name: Some(Cow::Borrowed("a")),
+                namespace: None,
                 id: None,
-                classes: ClassSelections::from_static(&["blue", "exit", "missing"]),
-                attributes: AttributeSelections::from_static(&[])
+                classes: ClassSelections::from_static(&[
+                    Cow::Borrowed("blue"),
+                    Cow::Borrowed("exit"),
+                    Cow::Borrowed("missing")
+                ]),
+                attributes: AttributeSelections::from_static(&[]),
+                pseudo_classes: PseudoClassSelections::from_static(&[])
             }
             .matches_element(&FakeElement {
                 name: "a",
@@ -259,16 +513,20 @@ mod tests {
     #[test]
     fn test_class_matching_is_order_independent_for_selector_and_element() {
         let selector_one = ElementPredicate {
-            name: Some("a"),
+            name: Some(Cow::Borrowed("a")),
+            namespace: None,
             id: None,
-            classes: ClassSelections::from_static(&["blue", "exit"]),
+            classes: ClassSelections::from_static(&[Cow::Borrowed("blue"), Cow::Borrowed("exit")]),
             attributes: AttributeSelections::from_static(&[]),
+            pseudo_classes: PseudoClassSelections::from_static(&[]),
         };
         let selector_two = ElementPredicate {
-            name: Some("a"),
+            name: Some(Cow::Borrowed("a")),
+            namespace: None,
             id: None,
-            classes: ClassSelections::from_static(&["exit", "blue"]),
+            classes: ClassSelections::from_static(&[Cow::Borrowed("exit"), Cow::Borrowed("blue")]),
             attributes: AttributeSelections::from_static(&[]),
+            pseudo_classes: PseudoClassSelections::from_static(&[]),
         };
 
         let element_one = FakeElement {
@@ -289,4 +547,123 @@ mod tests {
         assert!(selector_two.matches_element(&element_one));
         assert!(selector_two.matches_element(&element_two));
     }
+
+    #[test]
+    fn test_not_pseudo_class_rejects_matching_elements() {
+        let selector =
+            ElementPredicate::from(&mut crate::Reader::new("a:not(.ad, [rel=nofollow])"));
+
+        assert!(selector.matches_element(&FakeElement {
+            name: "a",
+            id: None,
+            class: Some("link"),
+            attributes: &[],
+        }));
+        assert!(!selector.matches_element(&FakeElement {
+            name: "a",
+            id: None,
+            class: Some("link ad"),
+            attributes: &[],
+        }));
+        assert!(!selector.matches_element(&FakeElement {
+            name: "a",
+            id: None,
+            class: None,
+            attributes: &[Attribute::new("rel", Some("nofollow"))],
+        }));
+    }
+
+    #[test]
+    fn test_structural_pseudo_classes_read_the_sibling_index() {
+        let selector = |source| ElementPredicate::from(&mut crate::Reader::new(source));
+        let second_p = SiblingElement {
+            name: "p",
+            index: 3,
+            type_index: 2,
+            count: None,
+        };
+
+        assert!(selector("p:nth-child(odd)").matches_element(&second_p));
+        assert!(selector("p:nth-of-type(2)").matches_element(&second_p));
+        assert!(!selector("p:first-child").matches_element(&second_p));
+        assert!(!selector("p:first-of-type").matches_element(&second_p));
+        assert_eq!(
+            selector("p:nth-child(-n+2)").try_match_element(&second_p),
+            Some(false)
+        );
+
+        // Untracked positions never match.
+        assert!(!selector(":first-child").matches_element(&FakeElement {
+            name: "p",
+            id: None,
+            class: None,
+            attributes: &[],
+        }));
+    }
+
+    #[test]
+    fn test_end_relative_pseudo_classes_wait_for_the_sibling_count() {
+        let selector = |source| ElementPredicate::from(&mut crate::Reader::new(source));
+        let mut second_p = SiblingElement {
+            name: "p",
+            index: 3,
+            type_index: 2,
+            count: None,
+        };
+
+        assert_eq!(selector("p:last-child").try_match_element(&second_p), None);
+        assert!(selector("p:last-child").matches_element(&second_p));
+        assert_eq!(
+            selector("p:not(:last-child)").try_match_element(&second_p),
+            None
+        );
+        assert_eq!(
+            selector("p:is(:last-child, :first-of-type)").try_match_element(&second_p),
+            None
+        );
+        assert_eq!(
+            selector("p:is(:last-child, :nth-of-type(2))").try_match_element(&second_p),
+            Some(true)
+        );
+        assert_eq!(
+            selector("div:last-child").try_match_element(&second_p),
+            Some(false)
+        );
+
+        second_p.count = Some((4, 2));
+        assert_eq!(
+            selector("p:last-child").try_match_element(&second_p),
+            Some(false)
+        );
+        assert_eq!(
+            selector("p:last-of-type").try_match_element(&second_p),
+            Some(true)
+        );
+        assert_eq!(
+            selector("p:nth-last-child(2)").try_match_element(&second_p),
+            Some(true)
+        );
+        assert_eq!(
+            selector("p:not(:last-child)").try_match_element(&second_p),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn test_has_waits_for_the_element_to_close() {
+        let selector = ElementPredicate::from(&mut crate::Reader::new("div:has(img)"));
+        let div = FakeElement {
+            name: "div",
+            id: None,
+            class: None,
+            attributes: &[],
+        };
+
+        assert_eq!(selector.try_match_element(&div), None);
+        assert!(selector.matches_element(&div));
+        assert_eq!(
+            selector.try_match_element(&FakeElement { name: "p", ..div }),
+            Some(false)
+        );
+    }
 }
@@ -0,0 +1,184 @@
+use std::borrow::Cow;
+
+use crate::Reader;
+use crate::query::compiler::SelectorParseError;
+
+pub(crate) fn is_identifier_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_') || !byte.is_ascii()
+}
+
+/// Reads an identifier (`foo`, `café`, `md\:flex`, `\31 23`), decoding its
+/// escapes. Unescaped identifiers stay borrowed from the selector.
+pub(crate) fn next_identifier<'a>(
+    reader: &mut Reader<'a>,
+) -> Result<Cow<'a, str>, SelectorParseError> {
+    next_decoded(reader, is_identifier_byte, false)
+}
+
+/// Reads up to one of `terminators`, decoding escapes on the way.
+pub(crate) fn next_until_unescaped<'a>(
+    reader: &mut Reader<'a>,
+    terminators: &[u8],
+) -> Result<Cow<'a, str>, SelectorParseError> {
+    next_decoded(reader, |byte| !terminators.contains(&byte), false)
+}
+
+/// Reads the rest of a string opened by `quote`, and its closing quote.
+pub(crate) fn next_quoted<'a>(
+    reader: &mut Reader<'a>,
+    quote: u8,
+) -> Result<Cow<'a, str>, SelectorParseError> {
+    let content = next_decoded(reader, |byte| byte != quote, true)?;
+    if reader.peek() != Some(quote) {
+        return Err(SelectorParseError::new(
+            "attribute selector has an unclosed quoted value",
+            reader.get_position(),
+        ));
+    }
+    reader.skip();
+    Ok(content)
+}
+
+fn next_decoded<'a>(
+    reader: &mut Reader<'a>,
+    accept: impl Fn(u8) -> bool,
+    in_string: bool,
+) -> Result<Cow<'a, str>, SelectorParseError> {
+    let start = reader.get_position();
+    let mut decoded: Option<String> = None;
+    let mut run_start = start;
+    loop {
+        match reader.peek() {
+            Some(b'\\') => {
+                let decoded = decoded.get_or_insert_with(String::new);
+                decoded.push_str(reader.slice(run_start..reader.get_position()));
+                reader.skip();
+                if let Some(character) = next_escape(reader, in_string)? {
+                    decoded.push(character);
+                }
+                run_start = reader.get_position();
+            }
+            Some(byte) if accept(byte) => reader.skip(),
+            _ => break,
+        }
+    }
+
+    let rest = reader.slice(run_start..reader.get_position());
+    Ok(match decoded {
+        None => Cow::Borrowed(rest),
+        Some(mut decoded) => {
+            decoded.push_str(rest);
+            Cow::Owned(decoded)
+        }
+    })
+}
+
+/// Decodes the escape after a `\`: up to six hex digits and one optional
+/// whitespace, or any other character as itself. Inside strings an escaped
+/// newline is a line continuation and decodes to nothing.
+fn next_escape(
+    reader: &mut Reader<'_>,
+    in_string: bool,
+) -> Result<Option<char>, SelectorParseError> {
+    let start = reader.get_position();
+    match reader.peek() {
+        None => Err(SelectorParseError::new(
+            "selector ends inside an escape",
+            start,
+        )),
+        Some(b'\n' | b'\r' | b'\x0c') if in_string => {
+            if reader.next() == Some(b'\r') && reader.peek() == Some(b'\n') {
+                reader.skip();
+            }
+            Ok(None)
+        }
+        Some(b'\n' | b'\r' | b'\x0c') => Err(SelectorParseError::new(
+            "newline can't be escaped in an identifier",
+            start,
+        )),
+        Some(byte) if byte.is_ascii_hexdigit() => {
+            while reader.get_position() - start < 6
+                && reader.peek().is_some_and(|byte| byte.is_ascii_hexdigit())
+            {
+                reader.skip();
+            }
+            let code_point =
+                u32::from_str_radix(reader.slice(start..reader.get_position()), 16).unwrap_or(0);
+            match reader.peek() {
+                Some(b'\r') => {
+                    reader.skip();
+                    if reader.peek() == Some(b'\n') {
+                        reader.skip();
+                    }
+                }
+                Some(b' ' | b'\t' | b'\n' | b'\x0c') => reader.skip(),
+                _ => {}
+            }
+
+            Ok(Some(match char::from_u32(code_point) {
+                Some('\0') | None => char::REPLACEMENT_CHARACTER,
+                Some(character) => character,
+            }))
+        }
+        Some(byte) => {
+            reader.skip();
+            // Take the continuation bytes of a multi-byte character too.
+            if !byte.is_ascii() {
+                while reader
+                    .peek()
+                    .is_some_and(|byte| byte & 0b1100_0000 == 0b1000_0000)
+                {
+                    reader.skip();
+                }
+            }
+            Ok(reader.slice(start..reader.get_position()).chars().next())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identifier(selector: &str) -> (Cow<'_, str>, usize) {
+        let mut reader = Reader::new(selector);
+        let identifier = next_identifier(&mut reader).unwrap();
+        (identifier, reader.get_position())
+    }
+
+    #[test]
+    fn test_identifier_escapes() {
+        assert!(matches!(identifier("foo.bar").0, Cow::Borrowed("foo")));
+        assert!(matches!(identifier("café:hover").0, Cow::Borrowed("café")));
+        assert_eq!(identifier("md\\:flex.x"), (Cow::Borrowed("md:flex"), 8));
+        assert_eq!(identifier("\\31 23"), (Cow::Borrowed("123"), 6));
+        assert_eq!(identifier("\\31 \\32 "), (Cow::Borrowed("12"), 8));
+        assert_eq!(identifier("caf\\e9"), (Cow::Borrowed("café"), 6));
+        assert_eq!(identifier("caf\\\u{e9}"), (Cow::Borrowed("café"), 6));
+        assert_eq!(identifier("\\0"), (Cow::Borrowed("\u{fffd}"), 2));
+        assert_eq!(identifier("\\110000"), (Cow::Borrowed("\u{fffd}"), 7));
+    }
+
+    #[test]
+    fn test_escape_errors() {
+        let error = next_identifier(&mut Reader::new("a\\")).unwrap_err();
+        assert_eq!(error.message(), "selector ends inside an escape");
+        assert_eq!(error.position(), 2);
+
+        let error = next_identifier(&mut Reader::new("a\\\nb")).unwrap_err();
+        assert_eq!(error.message(), "newline can't be escaped in an identifier");
+    }
+
+    #[test]
+    fn test_quoted_strings() {
+        let mut reader = Reader::new("a\\\"b\\\nc\"]");
+        assert_eq!(next_quoted(&mut reader, b'"').unwrap(), "a\"bc");
+        assert_eq!(reader.peek(), Some(b']'));
+
+        let error = next_quoted(&mut Reader::new("abc"), b'"').unwrap_err();
+        assert_eq!(
+            error.message(),
+            "attribute selector has an unclosed quoted value"
+        );
+    }
+}
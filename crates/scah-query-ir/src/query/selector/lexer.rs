@@ -1,69 +1,26 @@
 use super::builder::ElementPredicate;
+use super::namespace::Namespaces;
 use crate::Reader;
 use crate::query::compiler::SelectorParseError;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Combinator {
     // u4: Last Element Depth (size of stack)
-    Child,       // `>`
-    Descendant,  // ` `
-    NextSibling, // `+`
-
-    // BUG: By definition of this Combinator it's a SelectAll query
+    Child,             // `>`
+    Descendant,        // ` `
+    NextSibling,       // `+`
     SubsequentSibling, // `~`
-
-    // I'm pretty sure this does not apply to the scope of the project.
-    Namespace, // `|`
 }
 
 impl Combinator {
-    fn next<'a>(reader: &mut Reader<'a>) -> Option<Self> {
-        if let Some(token) = reader.peek()
-            && !matches!(token, b'>' | b' ' | b'+' | b'~' | b'|')
-        {
-            return None;
-        }
-
-        match reader.next()? {
-            b'>' => Some(Self::Child),
-            b' ' => Some(Self::Descendant),
-            b'+' => Some(Self::NextSibling),
-            b'~' => Some(Self::SubsequentSibling),
-            b'|' => Some(Self::Namespace),
-            _ => panic!("Not possible root"),
-        }
-    }
-}
-
-impl<'a> Combinator {
-    pub fn try_from(reader: &mut Reader<'a>) -> Option<Self> {
-        let mut combinator: Option<Self> = None;
-        while let Some(next_combinator) = Combinator::next(reader) {
-            match combinator {
-                Option::None => combinator = Some(next_combinator),
-                Some(c) if c == Self::Descendant && next_combinator != Self::Descendant => {
-                    combinator = Some(next_combinator);
-                }
-                _ => (),
-            }
-        }
-
-        combinator
-    }
-
     pub(crate) fn evaluate(&self, last_depth: u16, current_depth: u16) -> bool {
         match self {
             Combinator::Child => last_depth + 1 == current_depth,
             Combinator::Descendant => last_depth == 0 || current_depth != last_depth,
 
-            // BUG: I need to know if it's the element right after
-            // TODO: After first Fail it goes back
-            Combinator::NextSibling => last_depth == current_depth,
-
-            // BUG: I need to know if it's found a match before, so I know if it's ON/OFF
-            Combinator::SubsequentSibling => true,
-
-            Combinator::Namespace => panic!("Why are you using Namespace Selector ???"),
+            // Only checks that both elements share a parent; the engine checks
+            // the order of the siblings.
+            Combinator::NextSibling | Combinator::SubsequentSibling => last_depth == current_depth,
         }
     }
 }
@@ -74,19 +31,28 @@ impl Lexer {
     pub fn next<'query>(
         reader: &mut Reader<'query>,
     ) -> Option<(Combinator, ElementPredicate<'query>)> {
-        Self::try_next(reader, false).unwrap()
+        Self::try_next(reader, false, &Namespaces::default()).unwrap()
     }
 
     pub fn try_next<'query>(
         reader: &mut Reader<'query>,
         seen_selector: bool,
+        namespaces: &Namespaces<'query>,
     ) -> Result<Option<(Combinator, ElementPredicate<'query>)>, SelectorParseError> {
         if reader.eof() {
             return Ok(None);
         }
 
-        let combinator = Self::parse_combinator(reader, seen_selector)?;
-        let element = ElementPredicate::try_from(reader)?;
+        // The selector ends at the `,` of a selector list.
+        let start = reader.get_position();
+        reader.next_while_list(b" \t\n\r\x0c");
+        if reader.peek() == Some(b',') {
+            return Ok(None);
+        }
+
+        let saw_whitespace = reader.get_position() != start;
+        let combinator = Self::parse_combinator(reader, seen_selector, saw_whitespace)?;
+        let element = ElementPredicate::try_parse(reader, namespaces)?;
 
         Ok(Some((combinator, element)))
     }
@@ -94,73 +60,89 @@ impl Lexer {
     fn parse_combinator<'query>(
         reader: &mut Reader<'query>,
         seen_selector: bool,
+        saw_whitespace: bool,
     ) -> Result<Combinator, SelectorParseError> {
-        let mut saw_whitespace = false;
-        while let Some(token) = reader.peek() {
-            if !token.is_ascii_whitespace() {
-                break;
-            }
-            saw_whitespace = true;
-            reader.skip();
-        }
-
         match reader.peek() {
             None => Err(SelectorParseError::new(
                 "missing selector after combinator",
                 reader.get_position(),
             )),
             Some(b'>') => {
-                reader.skip();
-                while let Some(token) = reader.peek() {
-                    if !token.is_ascii_whitespace() {
-                        break;
-                    }
-                    reader.skip();
-                }
+                Self::skip_combinator(reader);
                 Ok(Combinator::Child)
             }
-            Some(b'+') => Err(SelectorParseError::new(
-                "unsupported combinator '+'",
-                reader.get_position(),
-            )),
-            Some(b'~') => Err(SelectorParseError::new(
-                "unsupported combinator '~'",
-                reader.get_position(),
-            )),
-            Some(b'|') => Err(SelectorParseError::new(
+            Some(b'+') => {
+                Self::skip_combinator(reader);
+                Ok(Combinator::NextSibling)
+            }
+            Some(b'~') => {
+                Self::skip_combinator(reader);
+                Ok(Combinator::SubsequentSibling)
+            }
+            // `|name` after whitespace is a type selector without a namespace.
+            Some(b'|') if !saw_whitespace && seen_selector => Err(SelectorParseError::new(
                 "unsupported combinator '|'",
                 reader.get_position(),
             )),
-            Some(_) if saw_whitespace || !seen_selector => Ok(Combinator::Descendant),
             Some(_) => Ok(Combinator::Descendant),
         }
     }
+
+    /// Consumes the `,` between two selectors of a selector list, once
+    /// [`Lexer::try_next`] has run out of the current selector.
+    pub fn next_alternative(reader: &mut Reader<'_>) -> bool {
+        if reader.peek() != Some(b',') {
+            return false;
+        }
+
+        reader.skip();
+        true
+    }
+
+    fn skip_combinator(reader: &mut Reader<'_>) {
+        reader.skip();
+        while let Some(token) = reader.peek() {
+            if !token.is_ascii_whitespace() {
+                break;
+            }
+            reader.skip();
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::borrow::Cow;
+
     use super::*;
-    use crate::{AttributeSelections, ClassSelections};
+    use crate::{AttributeSelections, ClassSelections, PseudoClassSelections};
 
     #[test]
     fn test_whitespace_only_returns_none() {
         let mut reader = Reader::new("   \n\t  ");
-        assert_eq!(Lexer::try_next(&mut reader, false).unwrap(), None);
+        assert_eq!(
+            Lexer::try_next(&mut reader, false, &Namespaces::default()).unwrap(),
+            None
+        );
     }
 
     #[test]
     fn test_leading_whitespace_uses_descendant_combinator() {
         let mut reader = Reader::new("   article#main.hero");
-        let (combinator, element) = Lexer::try_next(&mut reader, false).unwrap().unwrap();
+        let (combinator, element) = Lexer::try_next(&mut reader, false, &Namespaces::default())
+            .unwrap()
+            .unwrap();
 
         assert_eq!(combinator, Combinator::Descendant);
         assert_eq!(
             element,
             ElementPredicate {
-                name: Some("article"),
-                id: Some("main"),
-                classes: ClassSelections::from_static(&["hero"]),
+                name: Some(Cow::Borrowed("article")),
+                namespace: None,
+                id: Some(Cow::Borrowed("main")),
+                classes: ClassSelections::from_static(&[Cow::Borrowed("hero")]),
                 attributes: AttributeSelections::from_static(&[]),
+                pseudo_classes: PseudoClassSelections::from_static(&[]),
             }
         );
     }
@@ -176,10 +158,12 @@ mod tests {
         assert_eq!(
             first_element,
             ElementPredicate {
-                name: Some("element"),
-                id: Some("id"),
-                classes: ClassSelections::from_static(&["class"]),
-                attributes: AttributeSelections::from_static(&[])
+                name: Some(Cow::Borrowed("element")),
+                namespace: None,
+                id: Some(Cow::Borrowed("id")),
+                classes: ClassSelections::from_static(&[Cow::Borrowed("class")]),
+                attributes: AttributeSelections::from_static(&[]),
+                pseudo_classes: PseudoClassSelections::from_static(&[])
             }
         );
 
@@ -188,35 +172,60 @@ mod tests {
         assert_eq!(
             second_element,
             ElementPredicate {
-                name: Some("other"),
-                id: Some("other_id"),
-                classes: ClassSelections::from_static(&["other_class"]),
+                name: Some(Cow::Borrowed("other")),
+                namespace: None,
+                id: Some(Cow::Borrowed("other_id")),
+                classes: ClassSelections::from_static(&[Cow::Borrowed("other_class")]),
                 attributes: AttributeSelections::from_static(&[]),
+                pseudo_classes: PseudoClassSelections::from_static(&[]),
             }
         );
     }
 
     #[test]
-    fn test_unsupported_combinator_leading_selector() {
+    fn test_subsequent_sibling_combinator_leading_selector() {
         let mut reader = Reader::new("~ element#id.class > other#other_id.other_class");
-        let error = Lexer::try_next(&mut reader, false).unwrap_err();
+        let (first_combinator, first_element) =
+            Lexer::try_next(&mut reader, false, &Namespaces::default())
+                .unwrap()
+                .unwrap();
+        let (second_combinator, _) = Lexer::try_next(&mut reader, true, &Namespaces::default())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(first_combinator, Combinator::SubsequentSibling);
+        assert_eq!(first_element.name.as_deref(), Some("element"));
+        assert_eq!(second_combinator, Combinator::Child);
+    }
 
-        assert_eq!(error.message(), "unsupported combinator '~'");
+    #[test]
+    fn test_subsequent_sibling_combinator_without_whitespace() {
+        let mut reader = Reader::new("h2~ul");
+        let (first_combinator, _) = Lexer::next(&mut reader).unwrap();
+        let (second_combinator, second_element) = Lexer::next(&mut reader).unwrap();
+
+        assert_eq!(first_combinator, Combinator::Descendant);
+        assert_eq!(second_combinator, Combinator::SubsequentSibling);
+        assert_eq!(second_element.name.as_deref(), Some("ul"));
     }
 
     #[test]
     fn test_child_combinator_after_seen_selector() {
         let mut reader = Reader::new("> span.highlight");
-        let (combinator, element) = Lexer::try_next(&mut reader, true).unwrap().unwrap();
+        let (combinator, element) = Lexer::try_next(&mut reader, true, &Namespaces::default())
+            .unwrap()
+            .unwrap();
 
         assert_eq!(combinator, Combinator::Child);
         assert_eq!(
             element,
             ElementPredicate {
-                name: Some("span"),
+                name: Some(Cow::Borrowed("span")),
+                namespace: None,
                 id: None,
-                classes: ClassSelections::from_static(&["highlight"]),
+                classes: ClassSelections::from_static(&[Cow::Borrowed("highlight")]),
                 attributes: AttributeSelections::from_static(&[]),
+                pseudo_classes: PseudoClassSelections::from_static(&[]),
             }
         );
     }
@@ -224,23 +233,60 @@ mod tests {
     #[test]
     fn test_missing_selector_after_child_combinator() {
         let mut reader = Reader::new(">   ");
-        let error = Lexer::try_next(&mut reader, true).unwrap_err();
+        let error = Lexer::try_next(&mut reader, true, &Namespaces::default()).unwrap_err();
 
         assert_eq!(error.message(), "missing selector element");
     }
 
     #[test]
-    fn test_unsupported_adjacent_sibling_combinator_after_selector() {
+    fn test_next_sibling_combinator_after_selector() {
         let mut reader = Reader::new("+ a");
-        let error = Lexer::try_next(&mut reader, true).unwrap_err();
+        let (combinator, element) = Lexer::try_next(&mut reader, true, &Namespaces::default())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(combinator, Combinator::NextSibling);
+        assert_eq!(element.name.as_deref(), Some("a"));
+    }
+
+    #[test]
+    fn test_next_sibling_combinator_without_whitespace() {
+        let mut reader = Reader::new("h1+p.lead");
+        let (first_combinator, first_element) = Lexer::next(&mut reader).unwrap();
+        let (second_combinator, second_element) = Lexer::next(&mut reader).unwrap();
+
+        assert_eq!(first_combinator, Combinator::Descendant);
+        assert_eq!(first_element.name.as_deref(), Some("h1"));
+        assert_eq!(second_combinator, Combinator::NextSibling);
+        assert_eq!(
+            second_element,
+            ElementPredicate {
+                name: Some(Cow::Borrowed("p")),
+                namespace: None,
+                id: None,
+                classes: ClassSelections::from_static(&[Cow::Borrowed("lead")]),
+                attributes: AttributeSelections::from_static(&[]),
+                pseudo_classes: PseudoClassSelections::from_static(&[]),
+            }
+        );
+        assert_eq!(
+            Lexer::try_next(&mut reader, true, &Namespaces::default()).unwrap(),
+            None
+        );
+    }
 
-        assert_eq!(error.message(), "unsupported combinator '+'");
+    #[test]
+    fn test_missing_selector_after_next_sibling_combinator() {
+        let mut reader = Reader::new("+  ");
+        let error = Lexer::try_next(&mut reader, true, &Namespaces::default()).unwrap_err();
+
+        assert_eq!(error.message(), "missing selector element");
     }
 
     #[test]
     fn test_unsupported_namespace_combinator_after_selector() {
         let mut reader = Reader::new("| a");
-        let error = Lexer::try_next(&mut reader, true).unwrap_err();
+        let error = Lexer::try_next(&mut reader, true, &Namespaces::default()).unwrap_err();
 
         assert_eq!(error.message(), "unsupported combinator '|'");
     }
@@ -248,7 +294,7 @@ mod tests {
     #[test]
     fn test_illegal_character_bang() {
         let mut reader = Reader::new("!");
-        let error = Lexer::try_next(&mut reader, false).unwrap_err();
+        let error = Lexer::try_next(&mut reader, false, &Namespaces::default()).unwrap_err();
 
         assert_eq!(error.message(), "illegal selector token");
     }
@@ -256,7 +302,7 @@ mod tests {
     #[test]
     fn test_illegal_character_at() {
         let mut reader = Reader::new("@");
-        let error = Lexer::try_next(&mut reader, false).unwrap_err();
+        let error = Lexer::try_next(&mut reader, false, &Namespaces::default()).unwrap_err();
 
         assert_eq!(error.message(), "illegal selector token");
     }
@@ -264,7 +310,7 @@ mod tests {
     #[test]
     fn test_illegal_character_dollar() {
         let mut reader = Reader::new("$");
-        let error = Lexer::try_next(&mut reader, false).unwrap_err();
+        let error = Lexer::try_next(&mut reader, false, &Namespaces::default()).unwrap_err();
 
         assert_eq!(error.message(), "illegal selector token");
     }
@@ -272,7 +318,7 @@ mod tests {
     #[test]
     fn test_illegal_character_percent() {
         let mut reader = Reader::new("%");
-        let error = Lexer::try_next(&mut reader, false).unwrap_err();
+        let error = Lexer::try_next(&mut reader, false, &Namespaces::default()).unwrap_err();
 
         assert_eq!(error.message(), "illegal selector token");
     }
@@ -280,7 +326,7 @@ mod tests {
     #[test]
     fn test_illegal_character_caret() {
         let mut reader = Reader::new("^");
-        let error = Lexer::try_next(&mut reader, false).unwrap_err();
+        let error = Lexer::try_next(&mut reader, false, &Namespaces::default()).unwrap_err();
 
         assert_eq!(error.message(), "illegal selector token");
     }
@@ -288,7 +334,7 @@ mod tests {
     #[test]
     fn test_illegal_character_ampersand() {
         let mut reader = Reader::new("&");
-        let error = Lexer::try_next(&mut reader, false).unwrap_err();
+        let error = Lexer::try_next(&mut reader, false, &Namespaces::default()).unwrap_err();
 
         assert_eq!(error.message(), "illegal selector token");
     }
@@ -296,7 +342,7 @@ mod tests {
     #[test]
     fn test_illegal_character_open_paren() {
         let mut reader = Reader::new("(");
-        let error = Lexer::try_next(&mut reader, false).unwrap_err();
+        let error = Lexer::try_next(&mut reader, false, &Namespaces::default()).unwrap_err();
 
         assert_eq!(error.message(), "illegal selector token");
     }
@@ -304,7 +350,7 @@ mod tests {
     #[test]
     fn test_illegal_character_close_paren() {
         let mut reader = Reader::new(")");
-        let error = Lexer::try_next(&mut reader, false).unwrap_err();
+        let error = Lexer::try_next(&mut reader, false, &Namespaces::default()).unwrap_err();
 
         assert_eq!(error.message(), "illegal selector token");
     }
@@ -312,7 +358,7 @@ mod tests {
     #[test]
     fn test_illegal_character_question_mark() {
         let mut reader = Reader::new("?");
-        let error = Lexer::try_next(&mut reader, false).unwrap_err();
+        let error = Lexer::try_next(&mut reader, false, &Namespaces::default()).unwrap_err();
 
         assert_eq!(error.message(), "illegal selector token");
     }
@@ -320,7 +366,7 @@ mod tests {
     #[test]
     fn test_illegal_character_backtick() {
         let mut reader = Reader::new("`");
-        let error = Lexer::try_next(&mut reader, false).unwrap_err();
+        let error = Lexer::try_next(&mut reader, false, &Namespaces::default()).unwrap_err();
 
         assert_eq!(error.message(), "illegal selector token");
     }
@@ -328,24 +374,51 @@ mod tests {
     #[test]
     fn test_illegal_character_less_than() {
         let mut reader = Reader::new("<");
-        let error = Lexer::try_next(&mut reader, false).unwrap_err();
+        let error = Lexer::try_next(&mut reader, false, &Namespaces::default()).unwrap_err();
 
         assert_eq!(error.message(), "illegal selector token");
     }
 
     #[test]
-    fn test_illegal_character_colon() {
+    fn test_lone_colon_is_missing_pseudo_class_name() {
         let mut reader = Reader::new(":");
-        let error = Lexer::try_next(&mut reader, false).unwrap_err();
+        let error = Lexer::try_next(&mut reader, false, &Namespaces::default()).unwrap_err();
 
-        assert_eq!(error.message(), "illegal selector token");
+        assert_eq!(error.message(), "missing pseudo-class name");
     }
 
     #[test]
     fn test_illegal_character_semicolon() {
         let mut reader = Reader::new(";");
-        let error = Lexer::try_next(&mut reader, false).unwrap_err();
+        let error = Lexer::try_next(&mut reader, false, &Namespaces::default()).unwrap_err();
 
         assert_eq!(error.message(), "illegal selector token");
     }
+
+    #[test]
+    fn test_bar_after_whitespace_starts_a_type_without_namespace() {
+        let namespaces = Namespaces::default();
+        let mut reader = Reader::new("div |b");
+        Lexer::try_next(&mut reader, false, &namespaces).unwrap();
+        let (combinator, element) = Lexer::try_next(&mut reader, true, &namespaces)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(combinator, Combinator::Descendant);
+        assert_eq!(element.name, Some(Cow::Borrowed("b")));
+        assert_eq!(element.namespace, Some(""));
+    }
+
+    #[test]
+    fn test_bar_without_whitespace_is_a_namespace_prefix() {
+        let namespaces = Namespaces::default();
+        let mut reader = Reader::new("div|b");
+        let error = Lexer::try_next(&mut reader, false, &namespaces).unwrap_err();
+        assert_eq!(error.message(), "undeclared namespace prefix");
+
+        let mut reader = Reader::new("div.a|b");
+        Lexer::try_next(&mut reader, false, &namespaces).unwrap();
+        let error = Lexer::try_next(&mut reader, true, &namespaces).unwrap_err();
+        assert_eq!(error.message(), "unsupported combinator '|'");
+    }
 }
@@ -1,11 +1,17 @@
 mod builder;
 mod eq;
+mod ident;
 mod lexer;
+mod namespace;
+mod nth;
 mod string_search;
 
 pub use builder::{
-    Attribute, AttributeSelection, AttributeSelections, ClassSelections, ElementPredicate, IElement,
+    Attribute, AttributeSelection, AttributeSelections, ClassSelections, ElementPredicate,
+    IElement, PredicateSelections, PseudoClass, PseudoClassSelections, SiblingFilter,
 };
 pub use lexer::Combinator;
 pub(super) use lexer::Lexer;
-pub use string_search::AttributeSelectionKind;
+pub use namespace::{HTML_NAMESPACE, MATHML_NAMESPACE, Namespaces, SVG_NAMESPACE};
+pub use nth::Nth;
+pub use string_search::{AttributeSelectionKind, CaseSensitivity};
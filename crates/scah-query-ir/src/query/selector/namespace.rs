@@ -0,0 +1,85 @@
+pub const HTML_NAMESPACE: &str = "http://www.w3.org/1999/xhtml";
+pub const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+pub const MATHML_NAMESPACE: &str = "http://www.w3.org/1998/Math/MathML";
+
+/// The prefix map that `ns|name` type selectors resolve against, like a
+/// stylesheet's `@namespace` rules.
+///
+/// The default map declares `html`, `svg` and `math`, and no default
+/// namespace, so an unprefixed type selector matches elements of any
+/// namespace.
+///
+/// # Example
+///
+/// ```rust
+/// use scah_query_ir::{Namespaces, Query, SVG_NAMESPACE, Save};
+///
+/// let namespaces = Namespaces::default().with_prefix("s", SVG_NAMESPACE);
+/// let query = Query::with_namespaces(namespaces)
+///     .all("s|svg > s|rect", Save::none())?
+///     .build();
+/// # Ok::<(), scah_query_ir::SelectorParseError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Namespaces<'query> {
+    default: Option<&'query str>,
+    prefixes: Vec<(&'query str, &'query str)>,
+}
+
+impl<'query> Namespaces<'query> {
+    /// A map without any prefix or default namespace.
+    pub fn empty() -> Self {
+        Self {
+            default: None,
+            prefixes: Vec::new(),
+        }
+    }
+
+    /// Declares `prefix` for `uri`, replacing an earlier declaration.
+    pub fn with_prefix(mut self, prefix: &'query str, uri: &'query str) -> Self {
+        self.prefixes.retain(|(declared, _)| *declared != prefix);
+        self.prefixes.push((prefix, uri));
+        self
+    }
+
+    /// Restricts type selectors without a prefix to `uri`.
+    pub fn with_default(mut self, uri: &'query str) -> Self {
+        self.default = Some(uri);
+        self
+    }
+
+    pub fn resolve(&self, prefix: &str) -> Option<&'query str> {
+        self.prefixes
+            .iter()
+            .find(|(declared, _)| *declared == prefix)
+            .map(|(_, uri)| *uri)
+    }
+
+    pub fn default_namespace(&self) -> Option<&'query str> {
+        self.default
+    }
+}
+
+impl Default for Namespaces<'_> {
+    fn default() -> Self {
+        Self::empty()
+            .with_prefix("html", HTML_NAMESPACE)
+            .with_prefix("svg", SVG_NAMESPACE)
+            .with_prefix("math", MATHML_NAMESPACE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefixes_can_be_redeclared() {
+        let namespaces = Namespaces::default().with_prefix("svg", "urn:x");
+
+        assert_eq!(namespaces.resolve("svg"), Some("urn:x"));
+        assert_eq!(namespaces.resolve("math"), Some(MATHML_NAMESPACE));
+        assert_eq!(namespaces.resolve("x"), None);
+        assert_eq!(Namespaces::empty().resolve("html"), None);
+    }
+}
@@ -0,0 +1,121 @@
+use crate::Reader;
+use crate::query::compiler::SelectorParseError;
+
+/// The `an+b` argument of `:nth-child()` and friends.
+///
+/// An element at 1-based `index` matches when `index = a*n + b` for some
+/// `n >= 0`, so `:first-child` is `0n+1` and `odd` is `2n+1`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Nth {
+    pub a: i32,
+    pub b: i32,
+}
+
+impl Nth {
+    pub const fn new(a: i32, b: i32) -> Self {
+        Self { a, b }
+    }
+
+    pub fn matches(&self, index: u32) -> bool {
+        let offset = i64::from(index) - i64::from(self.b);
+        let a = i64::from(self.a);
+        if a == 0 {
+            return offset == 0;
+        }
+
+        offset % a == 0 && offset / a >= 0
+    }
+
+    fn try_parse_integer(reader: &mut Reader<'_>) -> Option<i32> {
+        let start = reader.get_position();
+        while reader.peek().is_some_and(|token| token.is_ascii_digit()) {
+            reader.skip();
+        }
+        reader.slice(start..reader.get_position()).parse().ok()
+    }
+
+    /// Parses `odd`, `even`, `5`, `-n+3`, `2n + 1`, ... up to the closing `)`
+    /// or a trailing `of`.
+    pub(crate) fn try_from(reader: &mut Reader<'_>) -> Result<Self, SelectorParseError> {
+        const INVALID: &str = "invalid an+b argument";
+
+        for (keyword, nth) in [("odd", Self::new(2, 1)), ("even", Self::new(2, 0))] {
+            if reader.match_ignore_case(keyword) {
+                keyword.bytes().for_each(|_| reader.skip());
+                return Ok(nth);
+            }
+        }
+
+        let start = reader.get_position();
+        let sign = match reader.peek() {
+            Some(b'-') => {
+                reader.skip();
+                -1
+            }
+            Some(b'+') => {
+                reader.skip();
+                1
+            }
+            _ => 1,
+        };
+        let number = Self::try_parse_integer(reader);
+
+        if !matches!(reader.peek(), Some(b'n' | b'N')) {
+            return number
+                .map(|b| Self::new(0, sign * b))
+                .ok_or(SelectorParseError::new(INVALID, start));
+        }
+        reader.skip();
+        let a = sign * number.unwrap_or(1);
+
+        reader.next_while_list(b" \t\n\r\x0c");
+        let sign = match reader.peek() {
+            Some(b'-') => -1,
+            Some(b'+') => 1,
+            _ => return Ok(Self::new(a, 0)),
+        };
+        reader.skip();
+        reader.next_while_list(b" \t\n\r\x0c");
+        let position = reader.get_position();
+        let b =
+            Self::try_parse_integer(reader).ok_or(SelectorParseError::new(INVALID, position))?;
+
+        Ok(Self::new(a, sign * b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(argument: &str) -> Result<Nth, SelectorParseError> {
+        Nth::try_from(&mut Reader::new(argument))
+    }
+
+    #[test]
+    fn test_parse_an_plus_b() {
+        assert_eq!(parse("odd"), Ok(Nth::new(2, 1)));
+        assert_eq!(parse("EVEN"), Ok(Nth::new(2, 0)));
+        assert_eq!(parse("3"), Ok(Nth::new(0, 3)));
+        assert_eq!(parse("+3"), Ok(Nth::new(0, 3)));
+        assert_eq!(parse("n"), Ok(Nth::new(1, 0)));
+        assert_eq!(parse("-n+3"), Ok(Nth::new(-1, 3)));
+        assert_eq!(parse("2n + 1"), Ok(Nth::new(2, 1)));
+        assert_eq!(parse("3n-2"), Ok(Nth::new(3, -2)));
+        assert!(parse("").is_err());
+        assert!(parse("x").is_err());
+        assert!(parse("2n+").is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let matching = |nth: Nth| (1..=8).filter(|&i| nth.matches(i)).collect::<Vec<_>>();
+
+        assert_eq!(matching(Nth::new(0, 3)), vec![3]);
+        assert_eq!(matching(Nth::new(2, 1)), vec![1, 3, 5, 7]);
+        assert_eq!(matching(Nth::new(2, 0)), vec![2, 4, 6, 8]);
+        assert_eq!(matching(Nth::new(-1, 3)), vec![1, 2, 3]);
+        assert_eq!(matching(Nth::new(3, -2)), vec![1, 4, 7]);
+        assert_eq!(matching(Nth::new(0, 0)), Vec::<u32>::new());
+    }
+}
@@ -9,14 +9,37 @@ pub enum AttributeSelectionKind {
     Substring,           // [attribute*=value]
 }
 
+/// The `i` / `s` modifier of an attribute selector, e.g. `[type="submit" i]`.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum CaseSensitivity {
+    #[default]
+    Sensitive, // [attribute=value s]
+    Insensitive, // [attribute=value i]
+}
+
+impl CaseSensitivity {
+    pub(crate) fn eq(self, query: &[u8], source: &[u8]) -> bool {
+        match self {
+            Self::Sensitive => query == source,
+            Self::Insensitive => query.eq_ignore_ascii_case(source),
+        }
+    }
+}
+
 impl AttributeSelectionKind {
-    pub fn find(&self, query: &str, source: &str) -> bool {
+    pub fn find(&self, query: &str, source: &str, case: CaseSensitivity) -> bool {
+        let query = query.as_bytes();
+        let source = source.as_bytes();
+        let mut words = source
+            .split(u8::is_ascii_whitespace)
+            .filter(|word| !word.is_empty());
+
         match self {
-            Self::Exact => query == source,
+            Self::Exact => case.eq(query, source),
             Self::Presence => true,
-            Self::WhitespaceSeparated => source.split_whitespace().any(|word| word == query),
-            Self::HyphenSeparated => source.split_whitespace().any(|word| {
-                if word == query {
+            Self::WhitespaceSeparated => words.any(|word| case.eq(query, word)),
+            Self::HyphenSeparated => words.any(|word| {
+                if case.eq(query, word) {
                     return true;
                 }
 
@@ -24,8 +47,8 @@ impl AttributeSelectionKind {
                     return false;
                 }
 
-                let is_prefix = query == &word[0..query.len()];
-                let is_dash_next = "-" == &word[query.len()..query.len() + 1];
+                let is_prefix = case.eq(query, &word[0..query.len()]);
+                let is_dash_next = word[query.len()] == b'-';
 
                 is_prefix && is_dash_next
             }),
@@ -33,16 +56,21 @@ impl AttributeSelectionKind {
                 if query.len() > source.len() {
                     return false;
                 }
-                query == &source[0..query.len()]
+                case.eq(query, &source[0..query.len()])
             }
             Self::Suffix => {
                 if query.len() > source.len() {
                     return false;
                 }
-                query == &source[(source.len() - query.len())..]
+                case.eq(query, &source[(source.len() - query.len())..])
             }
 
-            Self::Substring => source.contains(query),
+            Self::Substring => {
+                query.is_empty()
+                    || source
+                        .windows(query.len())
+                        .any(|window| case.eq(query, window))
+            }
         }
     }
 }
@@ -54,48 +82,84 @@ mod tests {
     #[test]
     fn test_presence() {
         let kind = AttributeSelectionKind::Presence;
-        assert!(kind.find("", "Hello"));
+        assert!(kind.find("", "Hello", CaseSensitivity::Sensitive));
     }
 
     #[test]
     fn test_exact() {
         let kind = AttributeSelectionKind::Exact;
-        assert!(kind.find("Hello", "Hello"));
+        assert!(kind.find("Hello", "Hello", CaseSensitivity::Sensitive));
     }
 
     #[test]
     fn test_whitespace() {
         let kind = AttributeSelectionKind::WhitespaceSeparated;
-        assert!(kind.find("world", "hello world in test"));
+        assert!(kind.find("world", "hello world in test", CaseSensitivity::Sensitive));
     }
 
     #[test]
     fn test_with_hypen_separated() {
         let kind = AttributeSelectionKind::HyphenSeparated;
-        assert!(kind.find("en", "hello en-world"));
+        assert!(kind.find("en", "hello en-world", CaseSensitivity::Sensitive));
     }
 
     #[test]
     fn test_without_hypen_separated() {
         let kind = AttributeSelectionKind::HyphenSeparated;
-        assert!(kind.find("en", "hello en world"));
+        assert!(kind.find("en", "hello en world", CaseSensitivity::Sensitive));
     }
 
     #[test]
     fn test_prefix() {
         let kind = AttributeSelectionKind::Prefix;
-        assert!(kind.find("hello wor", "hello world in test"));
+        assert!(kind.find(
+            "hello wor",
+            "hello world in test",
+            CaseSensitivity::Sensitive
+        ));
     }
 
     #[test]
     fn test_suffix() {
         let kind = AttributeSelectionKind::Suffix;
-        assert!(kind.find("ld in test", "hello world in test"));
+        assert!(kind.find(
+            "ld in test",
+            "hello world in test",
+            CaseSensitivity::Sensitive
+        ));
     }
 
     #[test]
     fn test_substring() {
         let kind = AttributeSelectionKind::Substring;
-        assert!(kind.find("world", "helloworldintest"));
+        assert!(kind.find("world", "helloworldintest", CaseSensitivity::Sensitive));
+    }
+
+    #[test]
+    fn test_case_insensitive_kinds() {
+        let find = |kind: AttributeSelectionKind, query: &str, source: &str| {
+            kind.find(query, source, CaseSensitivity::Insensitive)
+        };
+
+        assert!(find(AttributeSelectionKind::Exact, "submit", "Submit"));
+        assert!(find(
+            AttributeSelectionKind::WhitespaceSeparated,
+            "b",
+            "a B c"
+        ));
+        assert!(find(AttributeSelectionKind::HyphenSeparated, "en", "EN-us"));
+        assert!(find(AttributeSelectionKind::Prefix, "HTTPS:", "https://x"));
+        assert!(find(AttributeSelectionKind::Suffix, ".PDF", "a.pdf"));
+        assert!(find(
+            AttributeSelectionKind::Substring,
+            "World",
+            "helloWORLD"
+        ));
+        assert!(!find(AttributeSelectionKind::Exact, "submit", "submits"));
+        assert!(!AttributeSelectionKind::Exact.find(
+            "submit",
+            "Submit",
+            CaseSensitivity::Sensitive
+        ));
     }
 }
@@ -13,11 +13,10 @@ impl<'a> Reader<'a> {
         }
     }
 
-    pub fn from_bytes(input: &'a [u8]) -> Self {
-        Self {
-            source: input,
-            position: 0,
-        }
+    /// Reads `input` if it is UTF-8. Documents in other encodings have to
+    /// be decoded first.
+    pub fn from_bytes(input: &'a [u8]) -> Result<Self, std::str::Utf8Error> {
+        std::str::from_utf8(input).map(Self::new)
     }
 
     #[inline]
@@ -27,16 +26,27 @@ impl<'a> Reader<'a> {
 
     #[inline]
     pub fn slice(&self, range: Range<usize>) -> &'a str {
-        // SAFETY: The source was originally a &str, and structural characters are ASCII.
+        // SAFETY: The source is a &str (`from_bytes` checks it), and structural characters are ASCII.
         // Should be careful about slicing in the middle of a UTF-8 character.
         unsafe { std::str::from_utf8_unchecked(&self.source[range]) }
     }
 
+    /// The whole input, whatever has been read of it.
+    #[inline]
+    pub fn source(&self) -> &'a str {
+        self.slice(0..self.source.len())
+    }
+
     #[inline]
     pub fn peek(&self) -> Option<u8> {
         self.source.get(self.position).copied()
     }
 
+    #[inline]
+    pub fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.source.get(self.position + offset).copied()
+    }
+
     #[inline]
     pub fn next_while_list(&mut self, characters: &[u8]) {
         let len = self.source.len();
@@ -155,4 +165,10 @@ mod tests {
 
         assert_eq!(reader.slice(0..5), "Hello");
     }
+
+    #[test]
+    fn from_bytes_rejects_invalid_utf8() {
+        assert!(Reader::from_bytes(b"caf\xC3\xA9").is_ok());
+        assert!(Reader::from_bytes(b"caf\xE9").is_err());
+    }
 }
@@ -122,6 +122,22 @@ pub enum TraceEvent<'html, 'query> {
         selection: QuerySectionId,
         state: TransitionId,
     },
+    SiblingCursorCreated {
+        runner_index: usize,
+        anchor_depth: u16,
+        anchor_index: u32,
+        parent: ElementId,
+        selection: QuerySectionId,
+        state: TransitionId,
+    },
+    SiblingCursorPruned {
+        runner_index: usize,
+        cursor_index: usize,
+        anchor_depth: u16,
+        close_depth: u16,
+        selection: QuerySectionId,
+        state: TransitionId,
+    },
     ElementSaved {
         runner_index: usize,
         selector: &'query str,
@@ -137,6 +153,11 @@ pub enum TraceEvent<'html, 'query> {
         has_inner_html: bool,
         has_text_content: bool,
     },
+    DeferredMatchResolved {
+        element_id: ElementId,
+        tag: &'html str,
+        confirmed: bool,
+    },
     EarlyExit {
         runner_index: usize,
         selector: &'query str,
@@ -148,6 +169,7 @@ pub enum TraceEvent<'html, 'query> {
 pub enum CursorTraceKind {
     Main,
     Scoped { index: usize },
+    Sibling { index: usize },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -155,6 +177,7 @@ pub enum ScopedCursorReason {
     DescendantFork,
     BranchSibling,
     ChildSelection,
+    SelectorListAlternative,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -304,6 +327,39 @@ impl<'html, 'query> TraceEvent<'html, 'query> {
                 )
                 .unwrap();
             }
+            Self::SiblingCursorCreated {
+                runner_index,
+                anchor_depth,
+                anchor_index,
+                parent,
+                selection,
+                state,
+            } => {
+                write!(
+                    output,
+                    "\"event\":\"SiblingCursorCreated\",\"runner_index\":{runner_index},\"anchor_depth\":{anchor_depth},\"anchor_index\":{anchor_index},\"parent\":{},\"selection\":{},\"state\":{}",
+                    parent.index(),
+                    selection.index(),
+                    state.index()
+                )
+                .unwrap();
+            }
+            Self::SiblingCursorPruned {
+                runner_index,
+                cursor_index,
+                anchor_depth,
+                close_depth,
+                selection,
+                state,
+            } => {
+                write!(
+                    output,
+                    "\"event\":\"SiblingCursorPruned\",\"runner_index\":{runner_index},\"cursor_index\":{cursor_index},\"anchor_depth\":{anchor_depth},\"close_depth\":{close_depth},\"selection\":{},\"state\":{}",
+                    selection.index(),
+                    state.index()
+                )
+                .unwrap();
+            }
             Self::ElementSaved {
                 runner_index,
                 selector,
@@ -337,6 +393,19 @@ impl<'html, 'query> TraceEvent<'html, 'query> {
                 )
                 .unwrap();
             }
+            Self::DeferredMatchResolved {
+                element_id,
+                tag,
+                confirmed,
+            } => {
+                write!(
+                    output,
+                    "\"event\":\"DeferredMatchResolved\",\"element_id\":{},\"tag\":{},\"confirmed\":{confirmed}",
+                    element_id.index(),
+                    JsonString(tag)
+                )
+                .unwrap();
+            }
             Self::EarlyExit {
                 runner_index,
                 selector,
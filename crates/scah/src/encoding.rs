@@ -0,0 +1,381 @@
+use std::borrow::Cow;
+
+use encoding_rs::{Encoding, UTF_8, UTF_16BE, UTF_16LE, WINDOWS_1252, X_USER_DEFINED};
+
+use crate::{QuerySpec, Store};
+
+// How far into the document a `<meta charset>` is looked for.
+const PRESCAN_LEN: usize = 1024;
+const WHITESPACE: [u8; 5] = [b'\t', b'\n', b'\x0c', b'\r', b' '];
+
+/// A document decoded to UTF-8 by [`decode_html`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedHtml<'a> {
+    /// The document, borrowed from the bytes when they were UTF-8 already.
+    pub html: Cow<'a, str>,
+    /// The encoding it was decoded from.
+    pub encoding: &'static Encoding,
+}
+
+impl DecodedHtml<'_> {
+    /// Like [`parse`](crate::parse), with the encoding recorded in
+    /// [`Store::encoding`].
+    pub fn parse<'a: 'query, 'html: 'query, 'query: 'html, Q>(
+        &'html self,
+        queries: &'a [Q],
+    ) -> Store<'html, 'query>
+    where
+        Q: QuerySpec<'query>,
+    {
+        let mut store = crate::parse(&self.html, queries);
+        store.encoding = self.encoding.name();
+        store
+    }
+}
+
+/// Finds the encoding of an HTML document with the WHATWG encoding sniffing
+/// algorithm: its byte order mark comes first, then `transport_label` (the
+/// `charset` of the HTTP `Content-Type` it was served with, if known), then
+/// a `<meta charset>` or `<meta http-equiv="Content-Type">` within its first
+/// 1024 bytes. Without any of them, it is UTF-8 if it is valid UTF-8 and
+/// windows-1252 otherwise.
+///
+/// # Example
+///
+/// ```rust
+/// use scah::sniff_encoding;
+///
+/// let html = b"<html><head><meta charset='shift_jis'><title>\x93\xfa\x96\x7b</title>";
+/// assert_eq!(sniff_encoding(html, None).name(), "Shift_JIS");
+/// assert_eq!(sniff_encoding(html, Some("euc-jp")).name(), "EUC-JP");
+/// ```
+pub fn sniff_encoding(bytes: &[u8], transport_label: Option<&str>) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+    if let Some(encoding) = transport_label.and_then(|label| Encoding::for_label(label.as_bytes()))
+    {
+        return encoding;
+    }
+    if let Some(encoding) = prescan(&bytes[..bytes.len().min(PRESCAN_LEN)]) {
+        return encoding;
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        UTF_8
+    } else {
+        WINDOWS_1252
+    }
+}
+
+/// Decodes an HTML document to UTF-8, from the encoding
+/// [`sniff_encoding`] finds. Malformed sequences become `U+FFFD`.
+///
+/// # Example
+///
+/// ```rust
+/// use scah::{Query, Save, decode_html};
+///
+/// let bytes = b"<meta charset=windows-1252><p>caf\xe9</p>";
+/// let queries = &[Query::all("p", Save::all())
+///     .expect("valid selector")
+///     .build()];
+/// let document = decode_html(bytes, None);
+/// let store = document.parse(queries);
+///
+/// assert_eq!(store.encoding, "windows-1252");
+/// let p = store.get("p").unwrap().next().unwrap();
+/// assert_eq!(p.text_content(&store), Some("café"));
+/// ```
+pub fn decode_html<'a>(bytes: &'a [u8], transport_label: Option<&str>) -> DecodedHtml<'a> {
+    let (html, encoding, _) = sniff_encoding(bytes, transport_label).decode(bytes);
+    DecodedHtml { html, encoding }
+}
+
+/// The WHATWG "prescan a byte stream to determine its encoding" algorithm.
+fn prescan(bytes: &[u8]) -> Option<&'static Encoding> {
+    let mut position = 0;
+    while position < bytes.len() {
+        let rest = &bytes[position..];
+        if rest.starts_with(b"<!--") {
+            // `<!-->` is a whole comment.
+            position += 2 + find(&rest[2..], b"-->")? + 3;
+            continue;
+        }
+        if starts_with_ignore_case(rest, b"<meta")
+            && rest
+                .get(5)
+                .is_some_and(|&byte| WHITESPACE.contains(&byte) || byte == b'/')
+        {
+            position += 6;
+            if let Some(encoding) = meta_encoding(bytes, &mut position)? {
+                return Some(encoding);
+            }
+            continue;
+        }
+        let is_letter = |index: usize| rest.get(index).is_some_and(u8::is_ascii_alphabetic);
+        if rest.starts_with(b"<") && (is_letter(1) || (rest.get(1) == Some(&b'/') && is_letter(2)))
+        {
+            position += rest
+                .iter()
+                .position(|byte| WHITESPACE.contains(byte) || *byte == b'>')?;
+            while attribute(bytes, &mut position)?.is_some() {}
+        } else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            position += find(rest, b">")?;
+        }
+        position += 1;
+    }
+    None
+}
+
+/// The encoding a `<meta>` declares, reading its attributes from `position`.
+/// `None` at the end of the bytes, `Some(None)` if it declares none.
+fn meta_encoding(bytes: &[u8], position: &mut usize) -> Option<Option<&'static Encoding>> {
+    let mut names = Vec::new();
+    let mut got_pragma = false;
+    let mut need_pragma = None;
+    let mut charset = None;
+
+    while let Some((name, value)) = attribute(bytes, position)? {
+        if names.contains(&name) {
+            continue;
+        }
+        match name.as_slice() {
+            b"http-equiv" => got_pragma |= value == b"content-type",
+            b"content" if charset.is_none() => {
+                if let Some(encoding) = charset_from_content(&value) {
+                    charset = Some(encoding);
+                    need_pragma = Some(true);
+                }
+            }
+            b"charset" => {
+                charset = Encoding::for_label(&value);
+                need_pragma = Some(false);
+            }
+            _ => {}
+        }
+        names.push(name);
+    }
+
+    if need_pragma.is_none() || (need_pragma == Some(true) && !got_pragma) {
+        return Some(None);
+    }
+    Some(charset.map(|encoding| {
+        if encoding == UTF_16BE || encoding == UTF_16LE {
+            UTF_8
+        } else if encoding == X_USER_DEFINED {
+            WINDOWS_1252
+        } else {
+            encoding
+        }
+    }))
+}
+
+/// The WHATWG "get an attribute" algorithm: the next attribute from
+/// `position`, with its name and value lowercased. `None` at the end of the
+/// bytes, `Some(None)` at the end of the tag.
+#[allow(clippy::type_complexity)]
+fn attribute(bytes: &[u8], position: &mut usize) -> Option<Option<(Vec<u8>, Vec<u8>)>> {
+    let byte_at = |position: usize| bytes.get(position).copied();
+    while WHITESPACE.contains(&byte_at(*position)?) || byte_at(*position)? == b'/' {
+        *position += 1;
+    }
+    if byte_at(*position)? == b'>' {
+        return Some(None);
+    }
+
+    let mut name = Vec::new();
+    let mut value = Vec::new();
+    loop {
+        match byte_at(*position)? {
+            b'=' if !name.is_empty() => {
+                *position += 1;
+                break;
+            }
+            byte if WHITESPACE.contains(&byte) => {
+                while WHITESPACE.contains(&byte_at(*position)?) {
+                    *position += 1;
+                }
+                if byte_at(*position)? != b'=' {
+                    return Some(Some((name, value)));
+                }
+                *position += 1;
+                break;
+            }
+            b'/' | b'>' => return Some(Some((name, value))),
+            byte => name.push(byte.to_ascii_lowercase()),
+        }
+        *position += 1;
+    }
+
+    while WHITESPACE.contains(&byte_at(*position)?) {
+        *position += 1;
+    }
+    match byte_at(*position)? {
+        quote @ (b'"' | b'\'') => loop {
+            *position += 1;
+            match byte_at(*position)? {
+                byte if byte == quote => {
+                    *position += 1;
+                    return Some(Some((name, value)));
+                }
+                byte => value.push(byte.to_ascii_lowercase()),
+            }
+        },
+        b'>' => return Some(Some((name, value))),
+        _ => {}
+    }
+    loop {
+        match byte_at(*position)? {
+            byte if WHITESPACE.contains(&byte) || byte == b'>' => {
+                return Some(Some((name, value)));
+            }
+            byte => value.push(byte.to_ascii_lowercase()),
+        }
+        *position += 1;
+    }
+}
+
+/// The WHATWG "extract a character encoding from a meta element" algorithm,
+/// for the `content` of `<meta http-equiv="Content-Type">`.
+fn charset_from_content(content: &[u8]) -> Option<&'static Encoding> {
+    let mut position = 0;
+    loop {
+        position += find_ignore_case(&content[position..], b"charset")? + b"charset".len();
+        let mut rest = &content[position..];
+        rest = trim_start(rest);
+        let Some(value) = rest.strip_prefix(b"=") else {
+            continue;
+        };
+        let value = trim_start(value);
+        return match value.first()? {
+            quote @ (b'"' | b'\'') => {
+                let end = value[1..].iter().position(|byte| byte == quote)?;
+                Encoding::for_label(&value[1..end + 1])
+            }
+            _ => {
+                let end = value
+                    .iter()
+                    .position(|byte| WHITESPACE.contains(byte) || *byte == b';')
+                    .unwrap_or(value.len());
+                Encoding::for_label(&value[..end])
+            }
+        };
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn find_ignore_case(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle))
+}
+
+fn starts_with_ignore_case(bytes: &[u8], prefix: &[u8]) -> bool {
+    bytes
+        .get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+fn trim_start(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|byte| !WHITESPACE.contains(byte))
+        .unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sniff(bytes: &[u8]) -> &'static str {
+        sniff_encoding(bytes, None).name()
+    }
+
+    #[test]
+    fn test_byte_order_mark_comes_first() {
+        assert_eq!(sniff(b"\xEF\xBB\xBF<meta charset=latin1>"), "UTF-8");
+        assert_eq!(sniff(b"\xFF\xFE<\0p\0>\0"), "UTF-16LE");
+        assert_eq!(sniff(b"\xFE\xFF\0<\0p\0>"), "UTF-16BE");
+        assert_eq!(
+            sniff_encoding(b"\xFF\xFE<\0", Some("shift_jis")).name(),
+            "UTF-16LE"
+        );
+    }
+
+    #[test]
+    fn test_transport_label_comes_before_meta() {
+        let html = b"<meta charset=euc-kr>";
+        assert_eq!(
+            sniff_encoding(html, Some(" Shift_JIS ")).name(),
+            "Shift_JIS"
+        );
+        assert_eq!(sniff_encoding(html, Some("unknown")).name(), "EUC-KR");
+    }
+
+    #[test]
+    fn test_meta_charset() {
+        assert_eq!(sniff(b"<meta charset=\"ISO-8859-2\">"), "ISO-8859-2");
+        assert_eq!(sniff(b"<META CHARSET='gbk'/>"), "GBK");
+        assert_eq!(sniff(b"<meta/charset=euc-jp>"), "EUC-JP");
+        assert_eq!(sniff(b"<meta name=x charset = koi8-r >"), "KOI8-R");
+        // Only the first of an attribute counts.
+        assert_eq!(sniff(b"<meta charset=big5 charset=gbk>"), "Big5");
+        // UTF-16 can't be declared in the document it encodes.
+        assert_eq!(sniff(b"<meta charset=utf-16le>\xE9"), "UTF-8");
+        assert_eq!(sniff(b"<meta charset=x-user-defined>"), "windows-1252");
+    }
+
+    #[test]
+    fn test_meta_http_equiv() {
+        let html = b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=shift_jis\">";
+        assert_eq!(sniff(html), "Shift_JIS");
+        let html = b"<meta content='text/html;charset = \"euc-kr\"' http-equiv=content-type>";
+        assert_eq!(sniff(html), "EUC-KR");
+        // `content` needs the pragma.
+        assert_eq!(sniff(b"<meta content=\"charset=gbk\">"), "UTF-8");
+        assert_eq!(
+            sniff(b"<meta content=\"text/html\" http-equiv=content-type>"),
+            "UTF-8"
+        );
+    }
+
+    #[test]
+    fn test_prescan_skips_other_markup() {
+        assert_eq!(
+            sniff(b"<!-- <meta charset=gbk> --><meta charset=big5>"),
+            "Big5"
+        );
+        assert_eq!(sniff(b"<!--><meta charset=big5>"), "Big5");
+        assert_eq!(
+            sniff(b"<title x='<meta charset=gbk>'><meta charset=big5>"),
+            "Big5"
+        );
+        assert_eq!(
+            sniff(b"<?xml encoding='gbk'?><!doctype html></p><meta charset=big5>"),
+            "Big5"
+        );
+        assert_eq!(sniff(b"<metadata charset=gbk>"), "UTF-8");
+    }
+
+    #[test]
+    fn test_prescan_stops_after_1024_bytes() {
+        let mut html = vec![b' '; 1000];
+        html.extend_from_slice(b"<meta charset=gbk>");
+        assert_eq!(sniff(&html), "GBK");
+        // A tag cut short by the limit doesn't count.
+        html.splice(..0, [b' '; 10]);
+        assert_eq!(sniff(&html), "UTF-8");
+    }
+
+    #[test]
+    fn test_fallback() {
+        assert_eq!(sniff("<p>café</p>".as_bytes()), "UTF-8");
+        assert_eq!(sniff(b"<p>caf\xE9</p>"), "windows-1252");
+    }
+}
@@ -1,13 +1,15 @@
 use std::fmt::Debug;
 
-use crate::{Position, QuerySectionId, QuerySpec, TransitionId, XHtmlElement};
+use crate::{Combinator, Position, QuerySectionId, QuerySpec, TransitionId, XHtmlElement};
 use smallvec::SmallVec;
 
 use crate::store::ElementId;
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Clone, Debug)]
 pub struct Cursor {
     pub(super) parent: ElementId,
+    // Parents replaced on the way into child sections, restored on the way out.
+    pub(super) parents: SmallVec<[ElementId; 4]>,
     pub(super) position: Position,
     pub(super) match_stack: SmallVec<[super::DepthSize; 10]>,
     pub(super) end: bool, // This is a flag to say is a save point and this might be the end
@@ -40,10 +42,21 @@ pub trait CursorOps<'query, 'html> {
     fn add_depth(&mut self, depth: super::DepthSize);
 }
 
+/// Whether the section at `position` can match `element`: the contents of a
+/// `<template>` are only seen by the sections that opt in.
+fn sees<'query, Q: QuerySpec<'query>>(
+    tree: &Q,
+    position: &Position,
+    element: &XHtmlElement,
+) -> bool {
+    !element.template_content || tree.get_selection(position.selection).save.template_content
+}
+
 impl Cursor {
     pub fn new() -> Self {
         Self {
             parent: ElementId::default(),
+            parents: SmallVec::new(),
             position: Position {
                 selection: QuerySectionId(0),
                 state: TransitionId(0),
@@ -63,7 +76,7 @@ impl<'query, 'html> CursorOps<'query, 'html> for Cursor {
     ) -> bool {
         let fsm = tree.get_transition(self.position.state);
         let last_depth = *self.match_stack.last().unwrap_or(&0);
-        fsm.next(element, depth, last_depth)
+        sees(tree, &self.position, element) && fsm.next(element, depth, last_depth)
     }
 
     fn back<Q: QuerySpec<'query>>(&self, tree: &Q, depth: super::DepthSize, element: &str) -> bool {
@@ -75,7 +88,11 @@ impl<'query, 'html> CursorOps<'query, 'html> for Cursor {
     fn step_backward<Q: QuerySpec<'query>>(&mut self, tree: &Q) {
         self.match_stack.pop();
 
+        let selection = self.position.selection;
         self.position.back(tree);
+        if self.position.selection != selection {
+            self.parent = self.parents.pop().unwrap_or_default();
+        }
     }
 
     fn get_position(&self) -> &Position {
@@ -94,7 +111,9 @@ impl<'query, 'html> CursorOps<'query, 'html> for Cursor {
         self.parent
     }
 
+    // Only called when entering child sections, which `step_backward` leaves.
     fn set_parent(&mut self, value: ElementId) {
+        self.parents.push(self.parent);
         self.parent = value;
     }
 
@@ -132,7 +151,7 @@ impl<'query, 'html> CursorOps<'query, 'html> for ScopedCursor {
         element: &XHtmlElement,
     ) -> bool {
         let fsm = tree.get_transition(self.position.state);
-        fsm.next(element, depth, self.scope_depth)
+        sees(tree, &self.position, element) && fsm.next(element, depth, self.scope_depth)
     }
 
     fn back<Q: QuerySpec<'query>>(&self, tree: &Q, depth: super::DepthSize, element: &str) -> bool {
@@ -165,6 +184,62 @@ impl<'query, 'html> CursorOps<'query, 'html> for ScopedCursor {
     fn set_end(&mut self, _: bool) {}
 }
 
+/// A thread waiting on a sibling combinator (`+` or `~`).
+///
+/// It is anchored on the element matched by the previous transition and
+/// only looks at the elements that follow it under the same parent, so
+/// it outlives the anchor's close tag and dies with the anchor's parent.
+/// A `~` thread stays on for every later sibling, a `+` thread only sees
+/// the next one.
+#[derive(PartialEq, Clone, Debug)]
+pub struct SiblingCursor {
+    pub anchor_depth: super::DepthSize,
+    pub anchor_index: u32,
+    pub parent: ElementId,
+    pub position: Position,
+}
+
+impl SiblingCursor {
+    pub fn new(
+        anchor_depth: super::DepthSize,
+        anchor_index: u32,
+        parent: ElementId,
+        position: Position,
+    ) -> Self {
+        Self {
+            anchor_depth,
+            anchor_index,
+            parent,
+            position,
+        }
+    }
+
+    /// `+` only ever looks at the first element after its anchor.
+    pub fn is_one_shot<'query, Q: QuerySpec<'query>>(&self, tree: &Q) -> bool {
+        tree.get_transition(self.position.state).guard == Combinator::NextSibling
+    }
+
+    pub fn next<'query, Q: QuerySpec<'query>>(
+        &self,
+        tree: &Q,
+        depth: super::DepthSize,
+        sibling_index: u32,
+        element: &XHtmlElement,
+    ) -> bool {
+        let in_order = if self.is_one_shot(tree) {
+            sibling_index == self.anchor_index + 1
+        } else {
+            sibling_index > self.anchor_index
+        };
+
+        in_order
+            && sees(tree, &self.position, element)
+            && tree
+                .get_transition(self.position.state)
+                .next(element, depth, self.anchor_depth)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{Cursor, CursorOps};
@@ -184,6 +259,7 @@ mod tests {
                 id: None,
                 class: None,
                 attributes: &[],
+                ..Default::default()
             },
         );
 
@@ -202,6 +278,7 @@ mod tests {
                 id: None,
                 class: None,
                 attributes: &[],
+                ..Default::default()
             },
         );
 
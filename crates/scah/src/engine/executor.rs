@@ -1,10 +1,10 @@
 use super::cursor::CursorOps;
-use super::cursor::{Cursor, ScopedCursor};
+use super::cursor::{Cursor, ScopedCursor, SiblingCursor};
 use super::multiplexer::{DocumentPosition, SaveHit};
 #[cfg(any(debug_assertions, test))]
 use crate::debug::{CursorTraceKind, ScopedCursorReason, TraceEvent, TransitionRejectReason};
-use crate::store::Store;
-use crate::{QuerySpec, SelectionKind, XHtmlElement};
+use crate::store::{ElementId, Nullable, Store};
+use crate::{QuerySectionId, QuerySpec, SelectionKind, XHtmlElement};
 
 /*
  * A Selection works runs the fsm's using 2 types of tasks:
@@ -14,6 +14,7 @@ use crate::{QuerySpec, SelectionKind, XHtmlElement};
  */
 
 type ScopedCursorVec = Vec<ScopedCursor>;
+type SiblingCursorVec = Vec<SiblingCursor>;
 
 /// The `QueryExecutor` is an NFA execution engine optimized for streaming StAX events.
 ///
@@ -31,89 +32,362 @@ type ScopedCursorVec = Vec<ScopedCursor>;
 /// 3. **Pruning**: `ScopedCursor`s have a `scope_depth`. When the StAX parser emits
 ///    a close tag that drops the document depth below the cursor's scope, that NFA
 ///    thread is killed.
+/// 4. **Sibling Anchors**: A transition guarded by a sibling combinator (`+`, `~`)
+///    can only match after the previous element has closed, so the engine parks a
+///    `SiblingCursor` on that element instead. It is killed when the anchor's
+///    parent closes, which turns a `~` selector off for that parent.
 pub struct QueryExecutor<'a, Q> {
     pub(crate) query: &'a Q,
     pub(crate) fsm: Cursor,
     pub(crate) scoped_fsms: ScopedCursorVec,
+    pub(crate) sibling_fsms: SiblingCursorVec,
+    // Depth of the element saved by the early exit section, once there is one.
+    pub(crate) exit_depth: Option<super::DepthSize>,
+    // Saves made for the element being opened, as (section, parent, saved element).
+    // Selector lists (`h1, h2`) and nested descendant matches can reach one
+    // section through several threads.
+    pub(crate) opened_saves: Vec<(QuerySectionId, ElementId, ElementId)>,
 }
 
-impl<'a, 'html, 'query: 'html, Q> QueryExecutor<'a, Q>
+// Not derived, which would require `Q: Clone`.
+impl<Q> Clone for QueryExecutor<'_, Q> {
+    fn clone(&self) -> Self {
+        Self {
+            query: self.query,
+            fsm: self.fsm.clone(),
+            scoped_fsms: self.scoped_fsms.clone(),
+            sibling_fsms: self.sibling_fsms.clone(),
+            exit_depth: self.exit_depth,
+            opened_saves: self.opened_saves.clone(),
+        }
+    }
+}
+
+impl<'a, 'html, 'query: 'html + 'a, Q> QueryExecutor<'a, Q>
 where
     Q: QuerySpec<'query>,
 {
     pub fn new(query: &'a Q) -> Self {
+        let fsm = Cursor::new();
+        // The other selectors of a root selector list are never pruned.
+        let scoped_fsms = query
+            .alternatives(fsm.position.selection)
+            .map(|position| ScopedCursor::new(0, fsm.parent, position))
+            .collect();
+
         Self {
             query,
-            fsm: Cursor::new(),
-            scoped_fsms: Vec::new(),
+            fsm,
+            scoped_fsms,
+            sibling_fsms: Vec::new(),
+            exit_depth: None,
+            opened_saves: Vec::new(),
         }
     }
 
+    /// Whether no thread is inside a saved element, so the store can be
+    /// emptied under the runner. The saves of the last opened element are
+    /// only looked at while it opens.
+    pub(crate) fn is_settled(&self) -> bool {
+        self.fsm.parent.is_null()
+            && self.fsm.parents.iter().all(Nullable::is_null)
+            && self.scoped_fsms.iter().all(|fsm| fsm.parent.is_null())
+            && self.sibling_fsms.iter().all(|fsm| fsm.parent.is_null())
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn next_position(
         #[cfg_attr(not(any(debug_assertions, test)), allow(unused_variables))] runner_index: usize,
         tree: &Q,
         list: &mut ScopedCursorVec,
-        depth: super::DepthSize,
+        siblings: &mut SiblingCursorVec,
+        document_position: &DocumentPosition,
         fsm: &mut impl CursorOps<'query, 'html>,
         #[cfg_attr(not(any(debug_assertions, test)), allow(unused_variables))] store: &mut Store<
             'html,
             'query,
         >,
     ) {
+        let depth = document_position.element_depth;
         // 1) child, then 2) sibling, then 2) leaf of tree
         fsm.add_depth(depth);
         if let Some(next_transition) = fsm.get_position().next_transition(tree) {
             fsm.set_state(next_transition);
             fsm.set_end(false);
+            Self::anchor_sibling(
+                runner_index,
+                tree,
+                siblings,
+                document_position,
+                fsm.get_parent(),
+                *fsm.get_position(),
+                store,
+            );
         } else if let Some(child) = fsm.get_position().next_child(tree) {
             fsm.set_position(child);
             fsm.set_end(false);
 
             let mut has_sibling = fsm.get_position().next_sibling(tree);
             while let Some(sibling) = has_sibling {
-                list.push(ScopedCursor::new(
-                    depth,
+                Self::spawn_alternatives(
+                    runner_index,
+                    tree,
+                    list,
+                    siblings,
+                    document_position,
                     fsm.get_parent(),
                     *fsm.get_position(),
-                ));
-                #[cfg(any(debug_assertions, test))]
-                {
-                    let created = list.last().unwrap();
-                    crate::scah_trace!(
-                        store,
-                        TraceEvent::ScopedCursorCreated {
-                            runner_index,
-                            depth,
-                            scope_depth: created.scope_depth,
-                            parent: created.parent,
-                            selection: created.position.selection,
-                            state: created.position.state,
-                            reason: ScopedCursorReason::BranchSibling,
-                        }
-                    );
+                    store,
+                );
+                if !Self::anchor_sibling(
+                    runner_index,
+                    tree,
+                    siblings,
+                    document_position,
+                    fsm.get_parent(),
+                    *fsm.get_position(),
+                    store,
+                ) && Self::push_scoped(
+                    tree,
+                    list,
+                    ScopedCursor::new(depth, fsm.get_parent(), *fsm.get_position()),
+                ) {
+                    #[cfg(any(debug_assertions, test))]
+                    {
+                        let created = list.last().unwrap();
+                        crate::scah_trace!(
+                            store,
+                            TraceEvent::ScopedCursorCreated {
+                                runner_index,
+                                depth,
+                                scope_depth: created.scope_depth,
+                                parent: created.parent,
+                                selection: created.position.selection,
+                                state: created.position.state,
+                                reason: ScopedCursorReason::BranchSibling,
+                            }
+                        );
+                    }
                 }
 
                 fsm.set_position(sibling);
                 has_sibling = sibling.next_sibling(tree);
             }
+            Self::spawn_alternatives(
+                runner_index,
+                tree,
+                list,
+                siblings,
+                document_position,
+                fsm.get_parent(),
+                *fsm.get_position(),
+                store,
+            );
+            Self::anchor_sibling(
+                runner_index,
+                tree,
+                siblings,
+                document_position,
+                fsm.get_parent(),
+                *fsm.get_position(),
+                store,
+            );
         } else {
             fsm.set_end(true);
         }
     }
 
-    pub fn save_element(
+    /// Adds `cursor` unless a thread in `list` already matches everything it
+    /// would: the same state under the same parent, scoped to the same
+    /// element or, behind a descendant combinator, to one of its ancestors.
+    /// Nested matches would otherwise fork a thread per ancestor, each
+    /// finding the same elements again.
+    fn push_scoped(tree: &Q, list: &mut ScopedCursorVec, cursor: ScopedCursor) -> bool {
+        let is_descendant = tree.is_descendant(cursor.position.state);
+        let covered = list.iter().any(|other| {
+            other.position == cursor.position
+                && other.parent == cursor.parent
+                && (other.scope_depth == cursor.scope_depth
+                    || is_descendant && other.scope_depth <= cursor.scope_depth)
+        });
+        if !covered {
+            list.push(cursor);
+        }
+        !covered
+    }
+
+    /// Forks a thread for every other selector of the selector list of the
+    /// section entered at `position`.
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_alternatives(
+        #[cfg_attr(not(any(debug_assertions, test)), allow(unused_variables))] runner_index: usize,
+        tree: &Q,
+        list: &mut ScopedCursorVec,
+        siblings: &mut SiblingCursorVec,
+        document_position: &DocumentPosition,
+        parent: ElementId,
+        position: crate::Position,
+        #[cfg_attr(not(any(debug_assertions, test)), allow(unused_variables))] store: &mut Store<
+            'html,
+            'query,
+        >,
+    ) {
+        for alternative in tree.alternatives(position.selection) {
+            if Self::anchor_sibling(
+                runner_index,
+                tree,
+                siblings,
+                document_position,
+                parent,
+                alternative,
+                store,
+            ) {
+                continue;
+            }
+
+            if !Self::push_scoped(
+                tree,
+                list,
+                ScopedCursor::new(document_position.element_depth, parent, alternative),
+            ) {
+                continue;
+            }
+            crate::scah_trace!(
+                store,
+                TraceEvent::ScopedCursorCreated {
+                    runner_index,
+                    depth: document_position.element_depth,
+                    scope_depth: document_position.element_depth,
+                    parent,
+                    selection: alternative.selection,
+                    state: alternative.state,
+                    reason: ScopedCursorReason::SelectorListAlternative,
+                }
+            );
+        }
+    }
+
+    /// Self-closing elements never reach `next_position`, since no close tag
+    /// will step the cursor back; they can still anchor a sibling combinator.
+    fn anchor_self_closing(
+        runner_index: usize,
+        tree: &Q,
+        siblings: &mut SiblingCursorVec,
+        document_position: &DocumentPosition,
+        fsm: &impl CursorOps<'query, 'html>,
+        store: &mut Store<'html, 'query>,
+    ) {
+        let position = fsm.get_position();
+        if let Some(state) = position.next_transition(tree) {
+            Self::anchor_sibling(
+                runner_index,
+                tree,
+                siblings,
+                document_position,
+                fsm.get_parent(),
+                crate::Position {
+                    selection: position.selection,
+                    state,
+                },
+                store,
+            );
+        }
+    }
+
+    /// Spawns a [`SiblingCursor`] anchored on the element that was just matched
+    /// when `position` is guarded by a sibling combinator.
+    fn anchor_sibling(
         #[cfg_attr(not(any(debug_assertions, test)), allow(unused_variables))] runner_index: usize,
         tree: &Q,
+        siblings: &mut SiblingCursorVec,
+        document_position: &DocumentPosition,
+        parent: ElementId,
+        position: crate::Position,
+        #[cfg_attr(not(any(debug_assertions, test)), allow(unused_variables))] store: &mut Store<
+            'html,
+            'query,
+        >,
+    ) -> bool {
+        if !tree.is_sibling(position.state) {
+            return false;
+        }
+
+        // A `~` thread already parked under this parent covers every later
+        // sibling, so a second anchor would only produce duplicate matches.
+        let is_on = siblings.iter().any(|sibling| {
+            sibling.anchor_depth == document_position.element_depth
+                && sibling.parent == parent
+                && sibling.position == position
+                && !sibling.is_one_shot(tree)
+        });
+        if is_on {
+            return true;
+        }
+
+        siblings.push(SiblingCursor::new(
+            document_position.element_depth,
+            document_position.sibling_index,
+            parent,
+            position,
+        ));
+        crate::scah_trace!(
+            store,
+            TraceEvent::SiblingCursorCreated {
+                runner_index,
+                anchor_depth: document_position.element_depth,
+                anchor_index: document_position.sibling_index,
+                parent,
+                selection: position.selection,
+                state: position.state,
+            }
+        );
+        true
+    }
+
+    pub fn save_element(
+        #[cfg_attr(not(any(debug_assertions, test)), allow(unused_variables))] runner_index: usize,
+        tree: &'a Q,
         store: &mut Store<'html, 'query>,
-        element: XHtmlElement<'html>,
+        opened_saves: &mut Vec<(QuerySectionId, ElementId, ElementId)>,
+        element: &XHtmlElement<'html>,
         fsm: &mut impl CursorOps<'query, 'html>,
-    ) -> SaveHit {
+    ) -> Option<SaveHit<'a>> {
         // I can't check for this anymore, since the save is not instant and the fsm position is moved afterwards
         //debug_assert!(fsm.is_save_point(tree));
 
-        let section = tree.get_selection(fsm.get_position().selection);
+        let selection = fsm.get_position().selection;
+        let section = tree.get_selection(selection);
+        // Child sections will look for their elements inside this one.
+        let is_parent = !element.is_self_closing() && fsm.get_position().next_child(tree).is_some();
+
+        // `:has()` waits for the element to close, `:last-child` and
+        // friends for its parent.
+        let predicate = &tree.get_transition(fsm.get_position().state).predicate;
+        let deferred = ((predicate.is_relational() || predicate.is_end_relative())
+            && predicate.try_match_element(element).is_none())
+        .then_some(predicate);
+
+        // Another thread already saved this element, through a selector of
+        // the same list or an ancestor matching the same step.
+        if let Some(&(_, _, saved)) = opened_saves.iter().find(|(saved_selection, parent, _)| {
+            *saved_selection == selection && *parent == fsm.get_parent()
+        }) {
+            if is_parent {
+                fsm.set_parent(saved);
+            }
+            // Still reported, as this selector may be the one that confirms it.
+            return Some(SaveHit {
+                element_id: saved,
+                save_inner_html: section.save.inner_html,
+                save_outer_html: section.save.outer_html,
+                save_text_content: section.save.text_content,
+                link: None,
+                deferred,
+            });
+        }
 
-        let element_pointer = store.push(fsm.get_parent(), section, element);
+        let (element_pointer, link) = store.push_linked(fsm.get_parent(), section, element);
+        opened_saves.push((selection, fsm.get_parent(), element_pointer));
         crate::scah_trace!(
             store,
             TraceEvent::ElementSaved {
@@ -126,15 +400,18 @@ where
                 save_text_content: section.save.text_content,
             }
         );
-        if !tree.is_last_save_point(fsm.get_position()) {
+        if is_parent {
             fsm.set_parent(element_pointer);
         }
 
-        SaveHit {
+        Some(SaveHit {
             element_id: element_pointer,
             save_inner_html: section.save.inner_html,
+            save_outer_html: section.save.outer_html,
             save_text_content: section.save.text_content,
-        }
+            link: Some(link),
+            deferred,
+        })
     }
 
     #[cfg(any(debug_assertions, test))]
@@ -160,8 +437,12 @@ where
         element: &XHtmlElement<'html>,
         document_position: &DocumentPosition,
         store: &mut Store<'html, 'query>,
-        save_hits: &mut Vec<SaveHit>,
+        save_hits: &mut Vec<SaveHit<'a>>,
     ) {
+        // Sibling cursors anchored on this very element must not see it.
+        let sibling_len = self.sibling_fsms.len();
+        self.opened_saves.clear();
+
         for i in 0..self.scoped_fsms.len() {
             if !self.scoped_fsms[i].next(self.query, document_position.element_depth, element) {
                 #[cfg(any(debug_assertions, test))]
@@ -206,58 +487,87 @@ where
                 }
             );
 
-            if self
-                .query
-                .is_descendant(self.scoped_fsms[i].get_position().state)
-            {
-                // This should only be done if the task is not done (meaning it will move forward)
-                self.scoped_fsms.push(ScopedCursor::new(
-                    document_position.element_depth,
-                    self.scoped_fsms[i].parent,
-                    self.scoped_fsms[i].position,
-                ));
-                #[cfg(any(debug_assertions, test))]
-                {
-                    let created = self.scoped_fsms.last().unwrap();
-                    crate::scah_trace!(
-                        store,
-                        TraceEvent::ScopedCursorCreated {
-                            runner_index,
-                            depth: document_position.element_depth,
-                            scope_depth: created.scope_depth,
-                            parent: created.parent,
-                            selection: created.position.selection,
-                            state: created.position.state,
-                            reason: ScopedCursorReason::DescendantFork,
-                        }
-                    );
-                }
-            }
-
-            let mut new_scoped_fsm = self.scoped_fsms[i].clone();
+            let new_scoped_fsm = self.scoped_fsms[i].clone();
+            self.advance_scoped(
+                runner_index,
+                new_scoped_fsm,
+                element,
+                document_position,
+                store,
+                save_hits,
+            );
+        }
 
-            if self.query.is_save_point(&new_scoped_fsm.position) {
-                save_hits.push(Self::save_element(
-                    runner_index,
-                    self.query,
-                    store,
-                    element.clone(),
-                    &mut new_scoped_fsm,
-                ));
+        for i in (0..sibling_len).rev() {
+            if self.sibling_fsms[i].anchor_depth != document_position.element_depth {
+                continue;
             }
 
-            if !element.is_self_closing() {
-                Self::next_position(
-                    runner_index,
-                    self.query,
-                    &mut self.scoped_fsms,
-                    document_position.element_depth,
-                    &mut new_scoped_fsm,
+            let sibling_fsm = if self.sibling_fsms[i].is_one_shot(self.query) {
+                self.sibling_fsms.swap_remove(i)
+            } else {
+                self.sibling_fsms[i].clone()
+            };
+            if !sibling_fsm.next(
+                self.query,
+                document_position.element_depth,
+                document_position.sibling_index,
+                element,
+            ) {
+                crate::scah_trace!(
                     store,
+                    TraceEvent::TransitionRejected {
+                        runner_index,
+                        cursor: CursorTraceKind::Sibling { index: i },
+                        selector: self
+                            .query
+                            .get_selection(sibling_fsm.position.selection)
+                            .source,
+                        element: element.name,
+                        depth: document_position.element_depth,
+                        selection: sibling_fsm.position.selection,
+                        state: sibling_fsm.position.state,
+                        reason: Self::transition_reject_reason(
+                            self.query,
+                            &sibling_fsm.position,
+                            document_position.element_depth,
+                            sibling_fsm.anchor_depth,
+                            element,
+                        ),
+                    }
                 );
+                continue;
             }
 
-            self.scoped_fsms.push(new_scoped_fsm);
+            crate::scah_trace!(
+                store,
+                TraceEvent::TransitionMatched {
+                    runner_index,
+                    cursor: CursorTraceKind::Sibling { index: i },
+                    selector: self
+                        .query
+                        .get_selection(sibling_fsm.position.selection)
+                        .source,
+                    element: element.name,
+                    depth: document_position.element_depth,
+                    selection: sibling_fsm.position.selection,
+                    state: sibling_fsm.position.state,
+                }
+            );
+
+            // From here on the thread continues inside the matched element.
+            self.advance_scoped(
+                runner_index,
+                ScopedCursor::new(
+                    document_position.element_depth,
+                    sibling_fsm.parent,
+                    sibling_fsm.position,
+                ),
+                element,
+                document_position,
+                store,
+                save_hits,
+            );
         }
 
         // STEP 2: check tasks
@@ -284,12 +594,14 @@ where
                 .get_section_selection_kind(fsm.position.selection);
             let is_all = matches!(section_kind, SelectionKind::All);
 
-            if is_descendant_combinator && (!last_save_point || is_all) {
-                self.scoped_fsms.push(ScopedCursor::new(
-                    document_position.element_depth,
-                    fsm.parent,
-                    fsm.position,
-                ));
+            if is_descendant_combinator
+                && (!last_save_point || is_all)
+                && Self::push_scoped(
+                    self.query,
+                    &mut self.scoped_fsms,
+                    ScopedCursor::new(document_position.element_depth, fsm.parent, fsm.position),
+                )
+            {
                 #[cfg(any(debug_assertions, test))]
                 {
                     let created = self.scoped_fsms.last().unwrap();
@@ -309,11 +621,19 @@ where
             }
 
             if self.query.is_save_point(&fsm.position) {
-                save_hits.push(Self::save_element(
+                if Some(fsm.position.selection) == self.query.exit_at_section_end() {
+                    if Self::exit_section_saved(self.exit_depth, document_position.element_depth) {
+                        return;
+                    }
+                    self.exit_depth
+                        .get_or_insert(document_position.element_depth);
+                }
+                save_hits.extend(Self::save_element(
                     runner_index,
                     self.query,
                     store,
-                    element.clone(),
+                    &mut self.opened_saves,
+                    element,
                     fsm,
                 ));
             }
@@ -323,7 +643,17 @@ where
                     runner_index,
                     self.query,
                     &mut self.scoped_fsms,
-                    document_position.element_depth,
+                    &mut self.sibling_fsms,
+                    document_position,
+                    fsm,
+                    store,
+                );
+            } else {
+                Self::anchor_self_closing(
+                    runner_index,
+                    self.query,
+                    &mut self.sibling_fsms,
+                    document_position,
                     fsm,
                     store,
                 );
@@ -355,14 +685,82 @@ where
         }
     }
 
-    pub fn early_exit(&self) -> bool {
-        if let Some(early_exit_section) = self.query.exit_at_section_end() {
-            return early_exit_section == self.fsm.position.selection;
+    /// Saves and moves forward a scoped thread that just matched `element`.
+    fn advance_scoped(
+        &mut self,
+        runner_index: usize,
+        mut scoped_fsm: ScopedCursor,
+        element: &XHtmlElement<'html>,
+        document_position: &DocumentPosition,
+        store: &mut Store<'html, 'query>,
+        save_hits: &mut Vec<SaveHit<'a>>,
+    ) {
+        if self.query.is_save_point(&scoped_fsm.position) {
+            if Some(scoped_fsm.position.selection) == self.query.exit_at_section_end() {
+                if Self::exit_section_saved(self.exit_depth, document_position.element_depth) {
+                    return;
+                }
+                self.exit_depth
+                    .get_or_insert(document_position.element_depth);
+            }
+            save_hits.extend(Self::save_element(
+                runner_index,
+                self.query,
+                store,
+                &mut self.opened_saves,
+                element,
+                &mut scoped_fsm,
+            ));
+        }
+
+        if element.is_self_closing() {
+            Self::anchor_self_closing(
+                runner_index,
+                self.query,
+                &mut self.sibling_fsms,
+                document_position,
+                &scoped_fsm,
+                store,
+            );
+            if !self.query.is_sibling(scoped_fsm.position.state) {
+                Self::push_scoped(self.query, &mut self.scoped_fsms, scoped_fsm);
+            }
+            return;
         }
 
-        false
+        // The next transition is relative to the matched element, and the
+        // thread must not outlive it.
+        scoped_fsm.scope_depth = document_position.element_depth;
+        Self::next_position(
+            runner_index,
+            self.query,
+            &mut self.scoped_fsms,
+            &mut self.sibling_fsms,
+            document_position,
+            &mut scoped_fsm,
+            store,
+        );
+
+        // A thread waiting on a sibling combinator is carried by a `SiblingCursor`.
+        if !self.query.is_sibling(scoped_fsm.position.state) {
+            Self::push_scoped(self.query, &mut self.scoped_fsms, scoped_fsm);
+        }
     }
 
+    /// Whether the early exit section already saved an element other than the
+    /// one opened at `depth`; another selector of its list can still match
+    /// inside that element.
+    fn exit_section_saved(exit_depth: Option<super::DepthSize>, depth: super::DepthSize) -> bool {
+        exit_depth.is_some_and(|exit_depth| exit_depth < depth)
+    }
+
+    /// Whether the runner is done: the element saved by the early exit
+    /// section (see [`QuerySpec::exit_at_section_end`]) has been closed.
+    pub fn early_exit(&self, close_depth: super::DepthSize) -> bool {
+        self.exit_depth.is_some_and(|depth| close_depth <= depth)
+    }
+
+    /// Handles a close tag; returns `true` once the runner can stop.
     pub fn back(
         &mut self,
         #[cfg_attr(not(any(debug_assertions, test)), allow(unused_variables))] runner_index: usize,
@@ -376,8 +774,8 @@ where
                 continue;
             }
 
+            #[cfg_attr(not(any(debug_assertions, test)), allow(unused_variables))]
             let scoped_fsm = self.scoped_fsms.swap_remove(index);
-            self.fsm.parent = scoped_fsm.parent;
             crate::scah_trace!(
                 store,
                 TraceEvent::ScopedCursorPruned {
@@ -391,29 +789,50 @@ where
             );
         }
 
+        // A sibling cursor lives as long as its anchor's parent.
+        for index in (0..self.sibling_fsms.len()).rev() {
+            if self.sibling_fsms[index].anchor_depth <= document_position.element_depth {
+                continue;
+            }
+
+            #[cfg_attr(not(any(debug_assertions, test)), allow(unused_variables))]
+            let sibling_fsm = self.sibling_fsms.swap_remove(index);
+            crate::scah_trace!(
+                store,
+                TraceEvent::SiblingCursorPruned {
+                    runner_index,
+                    cursor_index: index,
+                    anchor_depth: sibling_fsm.anchor_depth,
+                    close_depth: document_position.element_depth,
+                    selection: sibling_fsm.position.selection,
+                    state: sibling_fsm.position.state,
+                }
+            );
+        }
+
         let fsm = &mut self.fsm;
         if fsm.back(self.query, document_position.element_depth, element) {
             if fsm.end {
                 fsm.end = false;
                 fsm.match_stack.pop();
-                #[cfg(any(debug_assertions, test))]
-                if let Some(section) = self.query.exit_at_section_end() {
-                    crate::scah_trace!(
-                        store,
-                        TraceEvent::EarlyExit {
-                            runner_index,
-                            selector: self.query.get_selection(section).source,
-                            section,
-                        }
-                    );
-                }
-                return true;
+            } else {
+                fsm.step_backward(self.query);
             }
-            fsm.step_backward(self.query);
-            return true;
         }
 
-        false
+        let early_exit = self.early_exit(document_position.element_depth);
+        #[cfg(any(debug_assertions, test))]
+        if early_exit && let Some(section) = self.query.exit_at_section_end() {
+            crate::scah_trace!(
+                store,
+                TraceEvent::EarlyExit {
+                    runner_index,
+                    selector: self.query.get_selection(section).source,
+                    section,
+                }
+            );
+        }
+        early_exit
     }
 }
 #[cfg(test)]
@@ -443,11 +862,13 @@ mod tests {
                 id: None,
                 class: None,
                 attributes: &[],
+                ..Default::default()
             },
             &DocumentPosition {
                 reader_position: 0,
                 text_content_position: 0,
                 element_depth: 0,
+                sibling_index: 1,
             },
             &mut store,
             &mut Vec::new(),
@@ -459,6 +880,7 @@ mod tests {
             selection.fsm,
             Cursor {
                 parent: NULL_PARENT,
+                parents: smallvec![],
                 position: Position {
                     selection: QuerySectionId(0),
                     state: TransitionId(1),
@@ -487,11 +909,13 @@ mod tests {
                 id: None,
                 class: None,
                 attributes: &[],
+                ..Default::default()
             },
             &DocumentPosition {
                 reader_position: 0,
                 text_content_position: 0,
                 element_depth: 1,
+                sibling_index: 1,
             },
             &mut store,
             &mut Vec::new(),
@@ -545,11 +969,13 @@ mod tests {
                 id: None,
                 class: None,
                 attributes: &[],
+                ..Default::default()
             },
             &DocumentPosition {
                 reader_position: 0,
                 text_content_position: 0,
                 element_depth: 0,
+                sibling_index: 1,
             },
             &mut store,
             &mut Vec::new(),
@@ -561,6 +987,7 @@ mod tests {
             selection.fsm,
             Cursor {
                 parent: NULL_PARENT,
+                parents: smallvec![],
                 position: Position {
                     selection: QuerySectionId(0),
                     state: TransitionId(1),
@@ -590,11 +1017,13 @@ mod tests {
                 id: None,
                 class: Some("class"),
                 attributes: &[],
+                ..Default::default()
             },
             &DocumentPosition {
                 reader_position: 0,
                 text_content_position: 0,
                 element_depth: 1,
+                sibling_index: 1,
             },
             &mut store,
             &mut Vec::new(),
@@ -611,6 +1040,7 @@ mod tests {
             selection.fsm,
             Cursor {
                 parent: ElementId(0),
+                parents: smallvec![NULL_PARENT],
                 position: Position {
                     selection: QuerySectionId(2),
                     state: TransitionId(3),
@@ -699,6 +1129,7 @@ mod tests {
                 reader_position: 0,
                 text_content_position: 0,
                 element_depth: 2,
+                sibling_index: 1,
             },
             &mut store,
         );
@@ -718,7 +1149,8 @@ mod tests {
             .collect::<Vec<_>>();
         retained_parents.sort_unstable();
         assert_eq!(retained_parents, vec![10, 30, 50]);
-        assert_eq!(selection.fsm.parent, ElementId(20));
+        // Pruned threads don't hand their parent over to the main cursor.
+        assert_eq!(selection.fsm.parent, NULL_PARENT);
     }
 
     #[test]
@@ -737,11 +1169,13 @@ mod tests {
                 id: None,
                 class: None,
                 attributes: &[],
+                ..Default::default()
             },
             &DocumentPosition {
                 reader_position: 0,
                 text_content_position: 0,
                 element_depth: 0,
+                sibling_index: 1,
             },
             &mut store,
             &mut Vec::new(),
@@ -756,6 +1190,7 @@ mod tests {
             selection.fsm,
             Cursor {
                 parent: NULL_PARENT,
+                parents: smallvec![],
                 position: Position {
                     selection: QuerySectionId(0),
                     state: TransitionId(0),
@@ -773,6 +1208,7 @@ mod tests {
                 reader_position: 0,
                 text_content_position: 0,
                 element_depth: 0,
+                sibling_index: 1,
             },
             &mut store,
         );
@@ -783,6 +1219,7 @@ mod tests {
             selection.fsm,
             Cursor {
                 parent: NULL_PARENT,
+                parents: smallvec![],
                 position: Position {
                     selection: QuerySectionId(0),
                     state: TransitionId(0),
@@ -1,20 +1,29 @@
 use super::executor::QueryExecutor;
 use crate::XHtmlElement;
-use crate::store::ElementId;
 use crate::store::Store;
-use crate::{QuerySpec, Reader};
+use crate::store::{ElementId, ElementLink};
+use crate::{Combinator, ElementPredicate, QuerySpec, Reader};
 
+#[derive(Clone)]
 pub(crate) struct DocumentPosition {
     pub reader_position: usize,
     pub text_content_position: usize,
     pub element_depth: crate::engine::DepthSize,
+    // 1-based position of the opened element among its element siblings.
+    pub sibling_index: u32,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
-pub(crate) struct SaveHit {
+pub(crate) struct SaveHit<'a> {
     pub element_id: ElementId,
     pub save_inner_html: bool,
+    pub save_outer_html: bool,
     pub save_text_content: bool,
+    // `None` when another selector of the list already saved the element.
+    pub link: Option<ElementLink>,
+    // The predicate that matched, when it can only be decided once the
+    // element's parent closes.
+    pub deferred: Option<&'a ElementPredicate<'a>>,
 }
 
 //type Runner<'query, Q> = SmallVec<[QueryExecutor<'query, Q>; 1]>;
@@ -22,6 +31,27 @@ type Runner<'query, Q> = Vec<QueryExecutor<'query, Q>>;
 
 pub struct QueryMultiplexer<'query, Q> {
     runners: Runner<'query, Q>,
+    // Whether any selector numbers elements among their siblings or has a
+    // sibling combinator, which the parser only counts siblings for.
+    siblings: bool,
+    // Whether any selector numbers elements among their siblings of the same
+    // type, which the parser only counts then.
+    of_type: bool,
+    // Whether any selector has a `:has()`, whose lists the parser only
+    // matches against the descendants of open elements then.
+    relational: bool,
+}
+
+// Not derived, which would require `Q: Clone`.
+impl<Q> Clone for QueryMultiplexer<'_, Q> {
+    fn clone(&self) -> Self {
+        Self {
+            runners: self.runners.clone(),
+            siblings: self.siblings,
+            of_type: self.of_type,
+            relational: self.relational,
+        }
+    }
 }
 
 impl<'html, 'query: 'html, Q> QueryMultiplexer<'query, Q>
@@ -29,7 +59,26 @@ where
     Q: QuerySpec<'query>,
 {
     pub fn new(queries: &'query [Q]) -> Self {
+        let any_predicate = |f: fn(&ElementPredicate<'query>) -> bool| {
+            queries.iter().any(|query| {
+                query
+                    .states()
+                    .iter()
+                    .any(|transition| f(&transition.predicate))
+            })
+        };
+        let any_sibling_combinator = queries.iter().any(|query| {
+            query.states().iter().any(|transition| {
+                matches!(
+                    transition.guard,
+                    Combinator::NextSibling | Combinator::SubsequentSibling
+                )
+            })
+        });
         Self {
+            siblings: any_sibling_combinator || any_predicate(ElementPredicate::numbers_siblings),
+            of_type: any_predicate(ElementPredicate::is_of_type),
+            relational: any_predicate(ElementPredicate::is_relational),
             #[allow(clippy::redundant_closure)]
             runners: queries
                 .iter()
@@ -43,7 +92,7 @@ where
         xhtml_element: &XHtmlElement<'html>,
         position: &DocumentPosition,
         store: &mut Store<'html, 'query>,
-    ) -> Vec<SaveHit> {
+    ) -> Vec<SaveHit<'query>> {
         let len = store.elements.len();
         let mut save_hits = Vec::new();
         for (runner_index, session) in self.runners.iter_mut().enumerate() {
@@ -57,6 +106,44 @@ where
         save_hits
     }
 
+    /// The `S` of every `:nth-child(an+b of S)` in the queries, nested
+    /// selectors before the ones containing them.
+    pub(crate) fn nth_selectors(&self) -> Vec<&'query [ElementPredicate<'query>]> {
+        let mut selectors = Vec::new();
+        for runner in &self.runners {
+            let query: &'query Q = runner.query;
+            for transition in query.states() {
+                transition.predicate.for_each_nth_selector(&mut |selector| {
+                    selectors.push(selector);
+                });
+            }
+        }
+        selectors
+    }
+
+    /// Whether a selector numbers elements among their siblings, by a
+    /// pseudo-class or a sibling combinator.
+    pub(crate) fn counts_siblings(&self) -> bool {
+        self.siblings
+    }
+
+    /// Whether a selector reads `:nth-of-type()` and the like, which need
+    /// the siblings of each type counted.
+    pub(crate) fn is_of_type(&self) -> bool {
+        self.of_type
+    }
+
+    /// Whether a selector has a `:has()`, whose lists are matched against
+    /// the descendants of its candidates.
+    pub(crate) fn is_relational(&self) -> bool {
+        self.relational
+    }
+
+    /// Whether no runner refers to an element of the store.
+    pub(crate) fn is_settled(&self) -> bool {
+        self.runners.iter().all(QueryExecutor::is_settled)
+    }
+
     pub(crate) fn back(
         &mut self,
         xhtml_element: &'html str,
@@ -66,11 +153,7 @@ where
     ) -> bool {
         let mut remove_indices = vec![];
         for (index, session) in self.runners.iter_mut().enumerate() {
-            let early_exit_previous = session.early_exit();
-            let back = session.back(index, xhtml_element, position, store);
-            let early_exit_current = session.early_exit();
-
-            if back && (early_exit_previous || early_exit_current) {
+            if session.back(index, xhtml_element, position, store) {
                 remove_indices.push(index);
             }
         }
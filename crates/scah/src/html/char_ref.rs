@@ -0,0 +1,246 @@
+use std::borrow::Cow;
+
+use super::entities::{ENTITIES, LONGEST_NAME};
+
+/// Decodes the character references (`&lt;`, `&#60;`, `&#x3C;`) of a text
+/// node, as a browser does.
+///
+/// Text without a reference is returned as is, without copying.
+///
+/// # Example
+///
+/// ```rust
+/// use scah::decode_text;
+///
+/// assert_eq!(decode_text("Post &lt;0&gt; &copy 2024"), "Post <0> © 2024");
+/// assert_eq!(decode_text("a &unknown; b"), "a &unknown; b");
+/// ```
+pub fn decode_text(text: &str) -> Cow<'_, str> {
+    decode(text, false)
+}
+
+/// Like [`decode_text`], but for an attribute value: a reference without its
+/// `;` is kept when a letter, a digit or `=` follows it, so query strings
+/// such as `?a=1&copy=2` survive.
+///
+/// # Example
+///
+/// ```rust
+/// use scah::decode_attribute_value;
+///
+/// assert_eq!(decode_attribute_value("?a=1&amp;b=2"), "?a=1&b=2");
+/// assert_eq!(decode_attribute_value("?a=1&copy=2"), "?a=1&copy=2");
+/// ```
+pub fn decode_attribute_value(value: &str) -> Cow<'_, str> {
+    decode(value, true)
+}
+
+enum Reference {
+    Named(&'static str),
+    Numeric(char),
+}
+
+/// Like [`decode_text`], appending the decoded text to `out` rather than
+/// copying it out first.
+pub(crate) fn decode_text_into(text: &str, out: &mut Vec<u8>) {
+    let copied = decode_with(text, false, |part| out.extend_from_slice(part.as_bytes()));
+    out.extend_from_slice(&text.as_bytes()[copied..]);
+}
+
+fn decode(raw: &str, in_attribute: bool) -> Cow<'_, str> {
+    let mut decoded = String::new();
+    let copied = decode_with(raw, in_attribute, |part| {
+        if decoded.is_empty() {
+            decoded.reserve(raw.len());
+        }
+        decoded.push_str(part);
+    });
+
+    if copied == 0 {
+        return Cow::Borrowed(raw);
+    }
+    decoded.push_str(&raw[copied..]);
+    Cow::Owned(decoded)
+}
+
+/// Hands `raw` to `push` piece by piece, its references decoded, up to the
+/// end of the last one. Returns where that is, 0 when `raw` has none.
+fn decode_with(raw: &str, in_attribute: bool, mut push: impl FnMut(&str)) -> usize {
+    // `raw[..copied]` has been pushed.
+    let mut copied = 0;
+    let mut search = 0;
+    while let Some(offset) = raw[search..].find('&') {
+        let start = search + offset + 1;
+        let Some((length, reference)) = next_reference(&raw[start..], in_attribute) else {
+            search = start;
+            continue;
+        };
+
+        push(&raw[copied..start - 1]);
+        match reference {
+            Reference::Named(characters) => push(characters),
+            Reference::Numeric(character) => push(character.encode_utf8(&mut [0; 4])),
+        }
+        copied = start + length;
+        search = copied;
+    }
+    copied
+}
+
+/// Reads the reference after a `&`, returning its length and what it stands
+/// for, or `None` when the `&` is just text.
+fn next_reference(rest: &str, in_attribute: bool) -> Option<(usize, Reference)> {
+    if rest.starts_with('#') {
+        return next_numeric_reference(rest);
+    }
+
+    let name_length = rest
+        .bytes()
+        .take_while(u8::is_ascii_alphanumeric)
+        .count()
+        .min(LONGEST_NAME);
+    if name_length == 0 {
+        return None;
+    }
+    if rest[name_length..].starts_with(';')
+        && let Some(characters) = lookup(&rest[..=name_length])
+    {
+        return Some((name_length + 1, Reference::Named(characters)));
+    }
+
+    // The longest legacy name (`&amp`, `&not`, ...) that the run starts with.
+    let (length, characters) = (1..=name_length)
+        .rev()
+        .find_map(|length| lookup(&rest[..length]).map(|characters| (length, characters)))?;
+    if in_attribute
+        && rest
+            .as_bytes()
+            .get(length)
+            .is_some_and(|next| next.is_ascii_alphanumeric() || *next == b'=')
+    {
+        return None;
+    }
+    Some((length, Reference::Named(characters)))
+}
+
+fn next_numeric_reference(rest: &str) -> Option<(usize, Reference)> {
+    let (radix, digits_start) = match rest.as_bytes().get(1) {
+        Some(b'x' | b'X') => (16, 2),
+        _ => (10, 1),
+    };
+    let digits = rest[digits_start..]
+        .bytes()
+        .take_while(|byte| char::from(*byte).is_digit(radix))
+        .count();
+    if digits == 0 {
+        return None;
+    }
+
+    let code_point = rest[digits_start..digits_start + digits]
+        .chars()
+        .filter_map(|digit| digit.to_digit(radix))
+        .fold(0_u32, |value, digit| {
+            value.saturating_mul(radix).saturating_add(digit)
+        });
+    let mut length = digits_start + digits;
+    if rest[length..].starts_with(';') {
+        length += 1;
+    }
+
+    let character = match code_point {
+        0 => char::REPLACEMENT_CHARACTER,
+        0x80..=0x9F => windows_1252(code_point),
+        _ => char::from_u32(code_point).unwrap_or(char::REPLACEMENT_CHARACTER),
+    };
+    Some((length, Reference::Numeric(character)))
+}
+
+/// Numeric references to C1 controls mean the Windows-1252 character with
+/// that byte instead.
+fn windows_1252(code_point: u32) -> char {
+    match code_point {
+        0x80 => '\u{20AC}',
+        0x82 => '\u{201A}',
+        0x83 => '\u{0192}',
+        0x84 => '\u{201E}',
+        0x85 => '\u{2026}',
+        0x86 => '\u{2020}',
+        0x87 => '\u{2021}',
+        0x88 => '\u{02C6}',
+        0x89 => '\u{2030}',
+        0x8A => '\u{0160}',
+        0x8B => '\u{2039}',
+        0x8C => '\u{0152}',
+        0x8E => '\u{017D}',
+        0x91 => '\u{2018}',
+        0x92 => '\u{2019}',
+        0x93 => '\u{201C}',
+        0x94 => '\u{201D}',
+        0x95 => '\u{2022}',
+        0x96 => '\u{2013}',
+        0x97 => '\u{2014}',
+        0x98 => '\u{02DC}',
+        0x99 => '\u{2122}',
+        0x9A => '\u{0161}',
+        0x9B => '\u{203A}',
+        0x9C => '\u{0153}',
+        0x9E => '\u{017E}',
+        0x9F => '\u{0178}',
+        _ => char::from_u32(code_point).unwrap_or(char::REPLACEMENT_CHARACTER),
+    }
+}
+
+fn lookup(name: &str) -> Option<&'static str> {
+    ENTITIES
+        .binary_search_by(|(entity, _)| entity.as_bytes().cmp(name.as_bytes()))
+        .ok()
+        .map(|index| ENTITIES[index].1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unescaped_text_is_borrowed() {
+        assert!(matches!(decode_text("a & b"), Cow::Borrowed("a & b")));
+        assert!(matches!(decode_text("&;&#;&#x;"), Cow::Borrowed(_)));
+        assert!(matches!(decode_text("&amp;"), Cow::Owned(_)));
+    }
+
+    #[test]
+    fn test_named_references() {
+        assert_eq!(decode_text("&lt;p&gt; &amp;&AMP;"), "<p> &&");
+        assert_eq!(
+            decode_text("&nbsp;&NotEqualTilde;"),
+            "\u{a0}\u{2242}\u{338}"
+        );
+        assert_eq!(decode_text("&CounterClockwiseContourIntegral;"), "\u{2233}");
+        // Legacy names match without `;`, and the longest one wins.
+        assert_eq!(decode_text("&copy2024 &notit; &notin;"), "©2024 ¬it; ∉");
+        assert_eq!(decode_text("&ampx &bogus; &"), "&x &bogus; &");
+    }
+
+    #[test]
+    fn test_numeric_references() {
+        assert_eq!(decode_text("&#60;&#x3c;&#X3C&#0060;"), "<<<<");
+        assert_eq!(decode_text("&#128512;&#x1F600;"), "😀😀");
+        assert_eq!(
+            decode_text("&#0;&#xD800;&#x110000;&#99999999999;"),
+            "\u{fffd}".repeat(4)
+        );
+        assert_eq!(decode_text("&#x80;&#150;&#x81;"), "€–\u{81}");
+        assert_eq!(decode_text("&#xg &#a"), "&#xg &#a");
+    }
+
+    #[test]
+    fn test_attribute_values_keep_ambiguous_ampersands() {
+        assert_eq!(decode_attribute_value("?a=1&amp;b=2"), "?a=1&b=2");
+        assert_eq!(
+            decode_attribute_value("?a=1&copy=2&not1"),
+            "?a=1&copy=2&not1"
+        );
+        assert_eq!(decode_attribute_value("&copy 2024 &lt;"), "© 2024 <");
+        assert_eq!(decode_attribute_value("&#169x"), "©x");
+    }
+}
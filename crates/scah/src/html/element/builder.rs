@@ -1,18 +1,26 @@
-use super::tokenizer::ElementAttributeToken;
+use std::ops::Range;
+
+use super::foreign;
+use super::tokenizer::{ElementAttributeToken, next_tag_name};
 use crate::Reader;
-use scah_query_ir::{Attribute, IElement};
+use crate::html::char_ref::decode_attribute_value;
+use crate::html::open_elements::{SiblingPosition, lowercase, selector_key};
+use scah_query_ir::{
+    Attribute, CaseSensitivity, ElementPredicate, HTML_NAMESPACE, IElement, SiblingFilter,
+};
 
 /// A key-value pair representing an HTML element attribute.
 ///
-/// Both `key` and `value` are zero-copy `&str` references into the
-/// original HTML source.
+/// `key` and `raw_value` are zero-copy `&str` references into the
+/// original HTML source; `value` has its character references decoded, and
+/// only allocates when there were any.
 ///
 /// # Example
 ///
 /// ```rust
 /// use scah::{Query, Save, parse};
 ///
-/// let html = r#"<a href="https://example.com" target="_blank">Link</a>"#;
+/// let html = r#"<a href="/?a=1&amp;b=2" target="_blank">Link</a>"#;
 /// let queries = &[Query::all("a", Save::all())
 ///     .expect("valid selector")
 ///     .build()];
@@ -21,9 +29,10 @@ use scah_query_ir::{Attribute, IElement};
 /// let a = store.get("a").unwrap().next().unwrap();
 /// let attrs = a.attributes(&store).unwrap();
 /// assert_eq!(attrs[0].key, "href");
-/// assert_eq!(attrs[0].value, Some("https://example.com"));
+/// assert_eq!(attrs[0].value.as_deref(), Some("/?a=1&b=2"));
+/// assert_eq!(attrs[0].raw_value, Some("/?a=1&amp;b=2"));
 /// assert_eq!(attrs[1].key, "target");
-/// assert_eq!(attrs[1].value, Some("_blank"));
+/// assert_eq!(attrs[1].value.as_deref(), Some("_blank"));
 /// ```
 /// An HTML element as parsed from the token stream.
 ///
@@ -40,6 +49,22 @@ pub struct XHtmlElement<'html> {
     pub class: Option<&'html str>,
     /// Slice of additional attributes (excludes `id` and `class`).
     pub attributes: &'html [Attribute<'html>],
+    // `None` for HTML elements.
+    pub(crate) namespace: Option<&'static str>,
+    // Whether the tag ended with `/>`.
+    pub(crate) self_closing: bool,
+    // Byte range of the start tag in the source.
+    pub(crate) start_tag: Range<usize>,
+    // Whether the element is part of a `<template>`'s contents, which only
+    // the selectors saved with `Save::template_content` see.
+    pub(crate) template_content: bool,
+    // Whether the document is read as XML: names compare exactly, and `/>`
+    // closes any element.
+    pub(crate) xml: bool,
+    pub(crate) siblings: SiblingPosition,
+    // Keys of the `:has()` lists matched inside the element, once it has
+    // closed.
+    pub(crate) relations: Option<Vec<usize>>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -53,21 +78,43 @@ impl<'html> XHtmlElement<'html> {
         &mut self,
         attribute: Attribute<'html>,
         attribute_tape: &mut Vec<Attribute<'html>>,
+        start_len: usize,
     ) {
-        if self.name.is_empty() && attribute.value.is_none() {
-            self.name = attribute.key;
-        } else if self.class.is_none() && attribute.key == "class" && attribute.value.is_some() {
-            self.class = attribute.value;
-        } else if self.id.is_none() && attribute.key == "id" && attribute.value.is_some() {
-            self.id = attribute.value;
+        // A repeated attribute is dropped, the first one wins.
+        if attribute_tape[start_len..]
+            .iter()
+            .any(|existing| self.name_eq(existing.key, attribute.key))
+        {
+            return;
+        }
+        if self.name_eq(attribute.key, "class") && attribute.value.is_some() {
+            if self.class.is_none() {
+                self.class = attribute.raw_value;
+            }
+        } else if self.name_eq(attribute.key, "id") && attribute.value.is_some() {
+            if self.id.is_none() {
+                self.id = attribute.raw_value;
+            }
         } else {
             attribute_tape.push(attribute);
         }
     }
 
+    fn name_eq(&self, name: &str, other: &str) -> bool {
+        if self.xml {
+            name == other
+        } else {
+            name.eq_ignore_ascii_case(other)
+        }
+    }
+
     pub fn is_self_closing(&self) -> bool {
-        if matches!(
-            self.name,
+        if self.xml || self.namespace.is_some() {
+            return self.self_closing;
+        }
+        // HTML elements ignore the `/` of a `/>`, only void ones are empty.
+        matches!(
+            &*lowercase(self.name),
             "area"
                 | "base"
                 | "br"
@@ -82,14 +129,12 @@ impl<'html> XHtmlElement<'html> {
                 | "source"
                 | "track"
                 | "wbr"
-        ) {
-            return true;
-        }
-        if let Some(last_attribute) = self.attributes.last() {
-            return last_attribute.key == "\\";
-        }
+        )
+    }
 
-        false
+    /// Whether it is an HTML `<template>`, whose children are its contents.
+    pub fn is_template(&self) -> bool {
+        !self.xml && self.namespace.is_none() && self.name.eq_ignore_ascii_case("template")
     }
 
     pub fn clear(&mut self) {
@@ -97,6 +142,12 @@ impl<'html> XHtmlElement<'html> {
         self.id = None;
         self.class = None;
         self.attributes = &[];
+        self.namespace = None;
+        self.self_closing = false;
+        self.start_tag = 0..0;
+        self.template_content = false;
+        self.siblings = SiblingPosition::default();
+        self.relations = None;
     }
 
     /*
@@ -115,57 +166,60 @@ impl<'html> XHtmlElement<'html> {
     }
 
     pub fn from(&mut self, reader: &mut Reader<'html>, attribute_tape: &mut Vec<Attribute<'html>>) {
-        let mut assign = false;
-        let mut key = None;
         let start_len = attribute_tape.len();
 
+        self.name = next_tag_name(reader);
         while let Some(token) = ElementAttributeToken::next(reader) {
             match token {
-                ElementAttributeToken::String(string_value) => match key {
-                    None => {
-                        debug_assert!(!assign);
-                        key = Some(string_value);
-                    }
-                    Some(k) => {
-                        if assign {
-                            self.add_to_element(
-                                Attribute {
-                                    key: k,
-                                    value: Some(string_value),
-                                },
-                                attribute_tape,
-                            );
-                            key = None;
-                        } else {
-                            self.add_to_element(
-                                Attribute {
-                                    key: k,
-                                    value: None,
-                                },
-                                attribute_tape,
-                            );
-                            key = Some(string_value)
-                        }
-                        assign = false;
-                    }
-                },
-
-                ElementAttributeToken::Equal => {
-                    assign = true;
-                }
+                ElementAttributeToken::Attribute(key, value) => self.add_to_element(
+                    Attribute {
+                        key,
+                        value: value.map(decode_attribute_value),
+                        raw_value: value,
+                    },
+                    attribute_tape,
+                    start_len,
+                ),
+                ElementAttributeToken::SelfClosing => self.self_closing = true,
             }
         }
 
-        if let Some(attribute) = key {
-            self.add_to_element(
-                Attribute {
-                    key: attribute,
-                    value: None,
-                },
-                attribute_tape,
-            );
+        self.set_attributes(attribute_tape, start_len);
+    }
+
+    /// Adds attributes read beforehand to the tape, as [`Self::from`] would.
+    pub(crate) fn copy_attributes(
+        &mut self,
+        attributes: &[Attribute<'html>],
+        attribute_tape: &mut Vec<Attribute<'html>>,
+    ) {
+        let start_len = attribute_tape.len();
+        attribute_tape.extend_from_slice(attributes);
+        self.set_attributes(attribute_tape, start_len);
+    }
+
+    /// Spells the names of an SVG or MathML element and of its attributes,
+    /// the last ones on the tape, as browsers do: `<lineargradient viewbox>`
+    /// becomes `<linearGradient viewBox>` and `<SVG>` becomes `<svg>`. Other
+    /// names, outside the WHATWG adjustment tables, are kept as written.
+    pub(crate) fn adjust_foreign_names(&mut self, attribute_tape: &mut [Attribute<'html>]) {
+        let Some(namespace) = self.namespace else {
+            return;
+        };
+        if let Some(name) = foreign::tag_name(namespace, self.name) {
+            self.name = name;
+        }
+
+        let start_len = attribute_tape.len() - self.attributes.len();
+        for attribute in &mut attribute_tape[start_len..] {
+            if let Some(key) = foreign::attribute_name(namespace, attribute.key) {
+                attribute.key = key;
+            }
         }
+        self.set_attributes(attribute_tape, start_len);
+    }
 
+    fn set_attributes(&mut self, attribute_tape: &[Attribute<'html>], start_len: usize) {
         // Since we are
         //  1) assigning after adding the Attributes
         //  and 2) either transforming it into a Range in Store or removing them
@@ -195,39 +249,73 @@ impl<'html> IElement<'html> for XHtmlElement<'html> {
     fn attributes(&self) -> &[Attribute<'html>] {
         self.attributes
     }
-}
 
-// TODO: Parse the closing tag for the XHtmlTag
-impl<'a> XHtmlTag<'a> {
-    pub fn from(reader: &mut Reader<'a>) -> Option<Self> {
-        reader.next_while_list(&[b' ', b'\n', b'\r', b'\t', b'<']);
-        if let Some(character) = reader.peek() {
-            if character == b'/' {
-                let start = reader.get_position() + 1;
-                reader.next_until(b'>');
-
-                let end = reader.get_position();
-                reader.skip();
+    fn namespace(&self) -> &str {
+        self.namespace.unwrap_or(HTML_NAMESPACE)
+    }
 
-                // BUG: Handle start and end not conforming to the rules of slices.
+    fn name_case(&self) -> CaseSensitivity {
+        if self.xml || self.namespace.is_some() {
+            CaseSensitivity::Sensitive
+        } else {
+            CaseSensitivity::Insensitive
+        }
+    }
 
-                // BUG: The Formating of the string breaks this code
+    fn sibling_index(&self, filter: SiblingFilter<'_, '_>) -> Option<u32> {
+        let index = match filter {
+            SiblingFilter::All => self.siblings.index,
+            SiblingFilter::SameType => self.siblings.type_index,
+            SiblingFilter::Matching(selector) => {
+                find_count(&self.siblings.matching, selector_key(selector))
+            }
+        };
+        (index != 0).then_some(index)
+    }
 
-                return Some(Self::Close(reader.slice(start..end).trim()));
-            } else if character == b'!' {
-                // This is a comment
-                reader.next_until(b'>');
-                reader.skip();
-                return None;
+    fn sibling_count(&self, filter: SiblingFilter<'_, '_>) -> Option<u32> {
+        let totals = self.siblings.totals.as_ref()?;
+        Some(match filter {
+            SiblingFilter::All => totals.count,
+            SiblingFilter::SameType => totals.type_count,
+            SiblingFilter::Matching(selector) => {
+                find_count(&totals.matching, selector_key(selector))
             }
+        })
+    }
+
+    fn has_relative_match(&self, selector: &[ElementPredicate<'_>]) -> Option<bool> {
+        let found = self.relations.as_ref()?;
+        Some(found.contains(&selector_key(selector)))
+    }
+}
+
+fn find_count(counts: &[(usize, u32)], key: usize) -> u32 {
+    counts
+        .iter()
+        .find(|(selector, _)| *selector == key)
+        .map_or(0, |(_, count)| *count)
+}
+
+impl<'a> XHtmlTag<'a> {
+    pub fn from(reader: &mut Reader<'a>) -> Self {
+        reader.next_while_list(&[b' ', b'\n', b'\r', b'\t', b'<']);
+        if reader.peek() == Some(b'/') {
+            reader.skip();
+            reader.next_while_list(&[b' ', b'\n', b'\r', b'\t']);
+            let name = next_tag_name(reader);
+            // An end tag's attributes are read, then ignored.
+            while ElementAttributeToken::next(reader).is_some() {}
+            return Self::Close(name);
         }
-        Some(Self::Open)
+        Self::Open
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::html::markup::Markup;
 
     #[test]
     fn test_key_no_quote_and_value_with_quote() {
@@ -237,13 +325,7 @@ mod tests {
         element.from(&mut reader, &mut attributes);
         assert_eq!(element.name, "p");
 
-        assert_eq!(
-            element.attributes[0],
-            Attribute {
-                key: "key",
-                value: Some("value")
-            }
-        );
+        assert_eq!(element.attributes[0], Attribute::new("key", Some("value")));
     }
 
     #[test]
@@ -257,13 +339,7 @@ mod tests {
 
         assert_eq!(element.attributes.len(), 1);
 
-        assert_eq!(
-            element.attributes[0],
-            Attribute {
-                key: "key",
-                value: Some("value")
-            }
-        );
+        assert_eq!(element.attributes[0], Attribute::new("key", Some("value")));
     }
 
     #[test]
@@ -275,12 +351,10 @@ mod tests {
 
         assert_eq!(element.name, "p");
 
+        // Quotes are part of an attribute name.
         assert_eq!(
             element.attributes[0],
-            Attribute {
-                key: "key",
-                value: Some("value")
-            }
+            Attribute::new("\"key\"", Some("value"))
         );
     }
 
@@ -293,34 +367,16 @@ mod tests {
 
         assert_eq!(element.name, "p");
 
-        assert_eq!(
-            element.attributes[0],
-            Attribute {
-                key: "key",
-                value: Some("value")
-            }
-        );
+        assert_eq!(element.attributes[0], Attribute::new("key", Some("value")));
         assert_eq!(
             element.attributes[1],
-            Attribute {
-                key: "key1",
-                value: Some("value1")
-            }
+            Attribute::new("\"key1\"", Some("value1"))
         );
         assert_eq!(
             element.attributes[2],
-            Attribute {
-                key: "key2",
-                value: Some("value2")
-            }
-        );
-        assert_eq!(
-            element.attributes[3],
-            Attribute {
-                key: "keey",
-                value: None
-            }
+            Attribute::new("\"key2\"", Some("value2"))
         );
+        assert_eq!(element.attributes[3], Attribute::new("keey", None));
     }
 
     #[test]
@@ -332,13 +388,7 @@ mod tests {
 
         assert_eq!(element.name, "p");
 
-        assert_eq!(
-            element.attributes[0],
-            Attribute {
-                key: "key",
-                value: None
-            }
-        );
+        assert_eq!(element.attributes[0], Attribute::new("\"key\"", None));
     }
 
     #[test]
@@ -350,17 +400,10 @@ mod tests {
 
         assert_eq!(element.name, "p");
 
-        assert_eq!(
-            element.attributes[0],
-            Attribute {
-                key: "key",
-                value: None
-            }
-        );
+        assert_eq!(element.attributes[0], Attribute::new("key", None));
     }
 
     #[test]
-    #[ignore = "Known issue: Escapes are not handled"]
     fn test_key_no_quote_and_escaped_space_value() {
         let mut reader = Reader::new("p key = hello\\ world");
         let mut element = XHtmlElement::default();
@@ -369,12 +412,13 @@ mod tests {
 
         assert_eq!(element.name, "p");
 
+        // HTML has no escapes: whitespace ends an unquoted value.
         assert_eq!(
-            element.attributes[0],
-            Attribute {
-                key: "key",
-                value: Some("hello\\ world")
-            }
+            element.attributes,
+            &[
+                Attribute::new("key", Some("hello\\")),
+                Attribute::new("world", None)
+            ]
         );
     }
 
@@ -387,12 +431,15 @@ mod tests {
 
         assert_eq!(element.name, "p");
 
+        // Quotes don't group an attribute name.
         assert_eq!(
-            element.attributes[0],
-            Attribute {
-                key: "long key with spaces",
-                value: Some("value")
-            }
+            element.attributes,
+            &[
+                Attribute::new("\"long", None),
+                Attribute::new("key", None),
+                Attribute::new("with", None),
+                Attribute::new("spaces\"", Some("value"))
+            ]
         );
     }
 
@@ -406,16 +453,17 @@ mod tests {
         assert_eq!(element.name, "p");
 
         assert_eq!(
-            element.attributes[0],
-            Attribute {
-                key: "long key's with spaces",
-                value: Some("value")
-            }
+            element.attributes,
+            &[
+                Attribute::new("\"long", None),
+                Attribute::new("key's", None),
+                Attribute::new("with", None),
+                Attribute::new("spaces\"", Some("value"))
+            ]
         );
     }
 
     #[test]
-    #[ignore = "Known issue: Escapes are not handled"]
     fn test_long_key_with_spaces_and_real_same_quote_inside() {
         let mut reader = Reader::new(r#"p "long key\"s with spaces"="value""#);
         let mut element = XHtmlElement::default();
@@ -425,32 +473,38 @@ mod tests {
         assert_eq!(element.name, "p");
 
         assert_eq!(
-            element.attributes[0],
-            Attribute {
-                key: r#"long key\"s with spaces"#,
-                value: Some("value")
-            }
+            element.attributes,
+            &[
+                Attribute::new("\"long", None),
+                Attribute::new(r#"key\"s"#, None),
+                Attribute::new("with", None),
+                Attribute::new("spaces\"", Some("value"))
+            ]
         );
     }
 
     #[test]
-    #[ignore = "Known issue: Escapes are not handled"]
     fn test_long_key_and_value_with_spaces_and_real_same_quote_inside() {
-        let mut reader = Reader::new(
-            r#"p "long key\"s with spaces"="value\"s of an other person \\\\\\ \\\\\ \ \  \"""#,
-        );
+        let mut reader = Reader::new(r#"p key="value\"s of an other person \\ \""#);
         let mut element = XHtmlElement::default();
         let mut attributes = vec![];
         element.from(&mut reader, &mut attributes);
 
         assert_eq!(element.name, "p");
 
+        // A backslash doesn't escape the closing quote.
         assert_eq!(
-            element.attributes[0],
-            Attribute {
-                key: r#"long key\"s with spaces"#,
-                value: Some(r#"value\"s of an other person \\\\\\ \\\\\ \ \  \""#)
-            }
+            element.attributes,
+            &[
+                Attribute::new("key", Some("value\\")),
+                Attribute::new("s", None),
+                Attribute::new("of", None),
+                Attribute::new("an", None),
+                Attribute::new("other", None),
+                Attribute::new("person", None),
+                Attribute::new(r#"\\"#, None),
+                Attribute::new(r#"\""#, None)
+            ]
         );
     }
 
@@ -467,28 +521,19 @@ mod tests {
 
         assert_eq!(
             element.attributes[0],
-            Attribute {
-                key: "target",
-                value: Some("_blank")
-            }
+            Attribute::new("target", Some("_blank"))
         );
 
         assert_eq!(
             element.attributes[1],
-            Attribute {
-                key: "href",
-                value: Some("/my_cv.pdf")
-            }
+            Attribute::new("href", Some("/my_cv.pdf"))
         );
 
         assert_eq!(element.class, Some("px-7 py-3"));
 
         assert_eq!(
             element.attributes[2],
-            Attribute {
-                key: "hello-world",
-                value: Some("hello-world")
-            }
+            Attribute::new("hello-world", Some("hello-world"))
         );
     }
 
@@ -503,7 +548,7 @@ mod tests {
         let mut attributes = vec![];
         element.from(&mut reader, &mut attributes);
 
-        assert_eq!(tag, Some(XHtmlTag::Open));
+        assert_eq!(tag, XHtmlTag::Open);
 
         assert_eq!(
             element,
@@ -512,19 +557,20 @@ mod tests {
                 id: None,
                 class: None,
                 attributes: &[
-                    Attribute {
-                        key: "href",
-                        value: Some(
+                    Attribute::new(
+                        "href",
+                        Some(
                             "https://developer.mozilla.org/en-US/docs/Web/HTML/Attributes/crossorigin"
                         )
-                    },
-                    Attribute {
-                        key: "title",
-                        value: Some(
+                    ),
+                    Attribute::new(
+                        "title",
+                        Some(
                             "The crossorigin attribute, valid on the <audio>, <img>, <link>, <script>, and <video> elements, provides support for CORS, defining how the element handles cross-origin requests, thereby enabling the configuration of the CORS requests for the element's fetched data. Depending on the element, the attribute can be a CORS settings attribute."
                         )
-                    }
+                    )
                 ],
+                ..Default::default()
             }
         );
     }
@@ -537,7 +583,7 @@ mod tests {
         let mut attributes = vec![];
         element.from(&mut reader, &mut attributes);
 
-        assert_eq!(tag, Some(XHtmlTag::Open));
+        assert_eq!(tag, XHtmlTag::Open);
 
         assert_eq!(
             element,
@@ -545,10 +591,8 @@ mod tests {
                 name: "p",
                 id: None,
                 class: None,
-                attributes: &[Attribute {
-                    key: "key",
-                    value: Some("value")
-                }],
+                attributes: &[Attribute::new("key", Some("value"))],
+                ..Default::default()
             }
         );
     }
@@ -558,7 +602,7 @@ mod tests {
         let mut reader = Reader::new("/p>");
         let tag = XHtmlTag::from(&mut reader);
 
-        assert_eq!(tag, Some(XHtmlTag::Close("p")));
+        assert_eq!(tag, XHtmlTag::Close("p"));
     }
 
     #[test]
@@ -566,15 +610,16 @@ mod tests {
         let mut reader = Reader::new("  /   p   >");
         let tag = XHtmlTag::from(&mut reader);
 
-        assert_eq!(tag, Some(XHtmlTag::Close("p")));
+        assert_eq!(tag, XHtmlTag::Close("p"));
     }
 
     #[test]
     fn test_parsing_comment() {
         let mut reader = Reader::new("<!-- These 3 links will be selected by the selector -->");
-        let tag = XHtmlTag::from(&mut reader);
+        let markup = Markup::from(&mut reader, false);
 
-        assert!(tag.is_none())
+        assert_eq!(markup, Some(Markup::Comment));
+        assert!(reader.peek().is_none());
     }
 
     #[test]
@@ -584,8 +629,10 @@ mod tests {
             <!-- These 3 links will be selected by the selector -->
         "#,
         );
-        let tag = XHtmlTag::from(&mut reader);
+        reader.next_until(b'<');
+        let markup = Markup::from(&mut reader, false);
 
-        assert!(tag.is_none())
+        assert_eq!(markup, Some(Markup::Comment));
+        assert!(reader.eof());
     }
 }
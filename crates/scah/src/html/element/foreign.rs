@@ -0,0 +1,162 @@
+//! The spelling browsers give SVG and MathML names, whatever their case in
+//! the source, from the WHATWG foreign content adjustments.
+
+use scah_query_ir::SVG_NAMESPACE;
+
+const SVG_TAG_NAMES: [&str; 37] = [
+    "altGlyph",
+    "altGlyphDef",
+    "altGlyphItem",
+    "animateColor",
+    "animateMotion",
+    "animateTransform",
+    "clipPath",
+    "feBlend",
+    "feColorMatrix",
+    "feComponentTransfer",
+    "feComposite",
+    "feConvolveMatrix",
+    "feDiffuseLighting",
+    "feDisplacementMap",
+    "feDistantLight",
+    "feDropShadow",
+    "feFlood",
+    "feFuncA",
+    "feFuncB",
+    "feFuncG",
+    "feFuncR",
+    "feGaussianBlur",
+    "feImage",
+    "feMerge",
+    "feMergeNode",
+    "feMorphology",
+    "feOffset",
+    "fePointLight",
+    "feSpecularLighting",
+    "feSpotLight",
+    "feTile",
+    "feTurbulence",
+    "foreignObject",
+    "glyphRef",
+    "linearGradient",
+    "radialGradient",
+    "textPath",
+];
+
+const SVG_ATTRIBUTE_NAMES: [&str; 58] = [
+    "attributeName",
+    "attributeType",
+    "baseFrequency",
+    "baseProfile",
+    "calcMode",
+    "clipPathUnits",
+    "diffuseConstant",
+    "edgeMode",
+    "filterUnits",
+    "glyphRef",
+    "gradientTransform",
+    "gradientUnits",
+    "kernelMatrix",
+    "kernelUnitLength",
+    "keyPoints",
+    "keySplines",
+    "keyTimes",
+    "lengthAdjust",
+    "limitingConeAngle",
+    "markerHeight",
+    "markerUnits",
+    "markerWidth",
+    "maskContentUnits",
+    "maskUnits",
+    "numOctaves",
+    "pathLength",
+    "patternContentUnits",
+    "patternTransform",
+    "patternUnits",
+    "pointsAtX",
+    "pointsAtY",
+    "pointsAtZ",
+    "preserveAlpha",
+    "preserveAspectRatio",
+    "primitiveUnits",
+    "refX",
+    "refY",
+    "repeatCount",
+    "repeatDur",
+    "requiredExtensions",
+    "requiredFeatures",
+    "specularConstant",
+    "specularExponent",
+    "spreadMethod",
+    "startOffset",
+    "stdDeviation",
+    "stitchTiles",
+    "surfaceScale",
+    "systemLanguage",
+    "tableValues",
+    "targetX",
+    "targetY",
+    "textLength",
+    "viewBox",
+    "viewTarget",
+    "xChannelSelector",
+    "yChannelSelector",
+    "zoomAndPan",
+];
+
+const MATHML_ATTRIBUTE_NAMES: [&str; 1] = ["definitionURL"];
+
+fn adjusted(names: &[&'static str], name: &str) -> Option<&'static str> {
+    names
+        .iter()
+        .find(|adjusted| adjusted.eq_ignore_ascii_case(name))
+        .copied()
+}
+
+/// The spelling of an element name in `namespace`: camel-cased in SVG
+/// (`linearGradient`), lowercase for the `<svg>` and `<math>` roots.
+pub(super) fn tag_name(namespace: &str, name: &str) -> Option<&'static str> {
+    if namespace == SVG_NAMESPACE {
+        adjusted(&SVG_TAG_NAMES, name).or_else(|| adjusted(&["svg"], name))
+    } else {
+        adjusted(&["math"], name)
+    }
+}
+
+/// The camel-cased spelling of an attribute name in `namespace` (`viewBox`,
+/// `definitionURL`).
+pub(super) fn attribute_name(namespace: &str, name: &str) -> Option<&'static str> {
+    if namespace == SVG_NAMESPACE {
+        adjusted(&SVG_ATTRIBUTE_NAMES, name)
+    } else {
+        adjusted(&MATHML_ATTRIBUTE_NAMES, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scah_query_ir::MATHML_NAMESPACE;
+
+    #[test]
+    fn test_adjusted_names() {
+        assert_eq!(
+            tag_name(SVG_NAMESPACE, "lineargradient"),
+            Some("linearGradient")
+        );
+        assert_eq!(
+            tag_name(SVG_NAMESPACE, "FOREIGNOBJECT"),
+            Some("foreignObject")
+        );
+        assert_eq!(tag_name(SVG_NAMESPACE, "SVG"), Some("svg"));
+        assert_eq!(tag_name(SVG_NAMESPACE, "path"), None);
+        assert_eq!(tag_name(MATHML_NAMESPACE, "Math"), Some("math"));
+        assert_eq!(attribute_name(SVG_NAMESPACE, "viewbox"), Some("viewBox"));
+        assert_eq!(attribute_name(SVG_NAMESPACE, "d"), None);
+        assert_eq!(
+            attribute_name(MATHML_NAMESPACE, "DefinitionUrl"),
+            Some("definitionURL")
+        );
+        assert_eq!(attribute_name(MATHML_NAMESPACE, "viewbox"), None);
+    }
+}
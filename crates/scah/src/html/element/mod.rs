@@ -1,2 +1,3 @@
 pub mod builder;
+mod foreign;
 mod tokenizer;
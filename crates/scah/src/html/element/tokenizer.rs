@@ -1,47 +1,93 @@
 use crate::Reader;
 
+/// The contents of an open tag after its name, read as in the WHATWG
+/// before/after attribute name and attribute value states.
 #[derive(Debug, PartialEq)]
 pub enum ElementAttributeToken<'a> {
-    String(&'a str),
-    Equal,
+    /// An attribute name, and its value as written when it has one.
+    Attribute(&'a str, Option<&'a str>),
+    /// The `/` of a closing `/>`.
+    SelfClosing,
 }
 
+const WHITESPACE: [u8; 5] = [b' ', b'\t', b'\n', b'\r', b'\x0c'];
 const DOUBLEQUOTE: u8 = b'"';
 const SINGLEQUOTE: u8 = b'\'';
 const EQUAL: u8 = b'=';
+const SOLIDUS: u8 = b'/';
 const END_OF_ELEMENT: u8 = b'>';
 
+/// Reads the name of an open tag, which ends at whitespace, `/` or `>`.
+pub fn next_tag_name<'a>(reader: &mut Reader<'a>) -> &'a str {
+    let start = reader.get_position();
+    reader.next_until_list(&[b' ', b'\t', b'\n', b'\r', b'\x0c', SOLIDUS, END_OF_ELEMENT]);
+    reader.slice(start..reader.get_position())
+}
+
 impl<'a> ElementAttributeToken<'a> {
+    /// Reads the next attribute, or `None` once the tag's `>` (or the end of
+    /// the document) is consumed.
     pub fn next(reader: &mut Reader<'a>) -> Option<Self> {
-        reader.next_while(b' ');
+        // Before attribute name: a `/` that doesn't close the tag is ignored.
+        loop {
+            reader.next_while_list(&WHITESPACE);
+            match reader.peek()? {
+                END_OF_ELEMENT => {
+                    reader.skip();
+                    return None;
+                }
+                SOLIDUS => {
+                    reader.skip();
+                    if reader.peek() == Some(END_OF_ELEMENT) {
+                        return Some(Self::SelfClosing);
+                    }
+                }
+                _ => break,
+            }
+        }
 
-        let start_pos = reader.get_position();
+        // Attribute name: quotes belong to it, and so does a leading `=`.
+        let name_start = reader.get_position();
+        reader.skip();
+        reader.next_until_list(&[
+            b' ',
+            b'\t',
+            b'\n',
+            b'\r',
+            b'\x0c',
+            SOLIDUS,
+            END_OF_ELEMENT,
+            EQUAL,
+        ]);
+        let name = reader.slice(name_start..reader.get_position());
+
+        // After attribute name
+        reader.next_while_list(&WHITESPACE);
+        if reader.peek() != Some(EQUAL) {
+            return Some(Self::Attribute(name, None));
+        }
+        reader.skip();
 
-        match reader.next()? {
-            DOUBLEQUOTE => {
-                let star_position = reader.get_position();
-                reader.next_until(DOUBLEQUOTE);
-                let content_inside_quotes = reader.slice(star_position..reader.get_position());
+        // Before attribute value
+        reader.next_while_list(&WHITESPACE);
+        let value = match reader.peek() {
+            Some(quote @ (DOUBLEQUOTE | SINGLEQUOTE)) => {
                 reader.skip();
-
-                Some(Self::String(content_inside_quotes))
-            }
-            SINGLEQUOTE => {
-                let star_position = reader.get_position();
-                reader.next_until(SINGLEQUOTE);
-                let content_inside_quotes = reader.slice(star_position..reader.get_position());
+                let value_start = reader.get_position();
+                reader.next_until(quote);
+                let value = reader.slice(value_start..reader.get_position());
                 reader.skip();
-
-                Some(Self::String(content_inside_quotes))
+                value
             }
-            EQUAL => Some(Self::Equal),
-            END_OF_ELEMENT => None,
-            _ => {
-                // Find end of word
-                reader.next_until_list(&[b' ', DOUBLEQUOTE, SINGLEQUOTE, EQUAL, END_OF_ELEMENT]);
-                Some(Self::String(reader.slice(start_pos..reader.get_position())))
+            // A missing value is an empty one.
+            Some(END_OF_ELEMENT) | None => "",
+            Some(_) => {
+                let value_start = reader.get_position();
+                reader.next_until_list(&[b' ', b'\t', b'\n', b'\r', b'\x0c', END_OF_ELEMENT]);
+                reader.slice(value_start..reader.get_position())
             }
-        }
+        };
+        Some(Self::Attribute(name, Some(value)))
     }
 }
 
@@ -49,6 +95,11 @@ impl<'a> ElementAttributeToken<'a> {
 mod tests {
     use super::*;
 
+    fn tokens(tag: &str) -> Vec<ElementAttributeToken<'_>> {
+        let mut reader = Reader::new(tag);
+        std::iter::from_fn(|| ElementAttributeToken::next(&mut reader)).collect()
+    }
+
     #[test]
     fn basic_attribute_iterator() {
         let string: String = String::from("key=\"value\"");
@@ -58,25 +109,96 @@ mod tests {
         let mut next_iter = ElementAttributeToken::next(&mut reader);
         assert!(next_iter.is_some());
 
-        let mut next_value = next_iter.unwrap();
-
-        assert_eq!(next_value, ElementAttributeToken::String("key"));
+        let next_value = next_iter.unwrap();
+        assert_eq!(
+            next_value,
+            ElementAttributeToken::Attribute("key", Some("value"))
+        );
 
         next_iter = ElementAttributeToken::next(&mut reader);
-        assert!(next_iter.is_some());
+        assert!(next_iter.is_none());
+    }
 
-        next_value = next_iter.unwrap();
-        assert_eq!(next_value, ElementAttributeToken::Equal);
+    #[test]
+    fn test_tag_name_ends_at_whitespace_and_solidus() {
+        assert_eq!(next_tag_name(&mut Reader::new("div\tclass=x>")), "div");
+        assert_eq!(next_tag_name(&mut Reader::new("br/>")), "br");
+        assert_eq!(next_tag_name(&mut Reader::new("my-el>")), "my-el");
+    }
 
-        next_iter = ElementAttributeToken::next(&mut reader);
-        assert!(next_iter.is_some());
+    #[test]
+    fn test_whitespace_between_attributes() {
+        assert_eq!(
+            tokens("a\n\thref = 'x'\r\n\x0cdisabled\n>"),
+            vec![
+                ElementAttributeToken::Attribute("a", None),
+                ElementAttributeToken::Attribute("href", Some("x")),
+                ElementAttributeToken::Attribute("disabled", None),
+            ]
+        );
+    }
 
-        next_value = next_iter.unwrap();
-        assert_eq!(next_value, ElementAttributeToken::String("value"));
+    #[test]
+    fn test_quotes_inside_names_and_values() {
+        assert_eq!(
+            tokens(r#"key="value's" k'ey="value" key2="v"alue">"#),
+            vec![
+                ElementAttributeToken::Attribute("key", Some("value's")),
+                ElementAttributeToken::Attribute("k'ey", Some("value")),
+                ElementAttributeToken::Attribute("key2", Some("v")),
+                ElementAttributeToken::Attribute("alue\"", None),
+            ]
+        );
+        assert_eq!(
+            tokens("a=b\"c d=e'f<g>"),
+            vec![
+                ElementAttributeToken::Attribute("a", Some("b\"c")),
+                ElementAttributeToken::Attribute("d", Some("e'f<g")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_solidus() {
+        assert_eq!(
+            tokens("a/b c=/d/ />"),
+            vec![
+                ElementAttributeToken::Attribute("a", None),
+                ElementAttributeToken::Attribute("b", None),
+                ElementAttributeToken::Attribute("c", Some("/d/")),
+                ElementAttributeToken::SelfClosing,
+            ]
+        );
+        assert_eq!(
+            tokens("a='x'/>"),
+            vec![
+                ElementAttributeToken::Attribute("a", Some("x")),
+                ElementAttributeToken::SelfClosing,
+            ]
+        );
+        // `/>` right after an unquoted value is part of it.
+        assert_eq!(
+            tokens("a=x/>"),
+            vec![ElementAttributeToken::Attribute("a", Some("x/"))]
+        );
     }
 
-    // TOKENIZER / FSM attribute robustness tests
-    // TODO: key="value's" <-- `'` should be part of the string
-    // TODO: k'ey="value" <-- `'` should be part of the string
-    // TODO: key="v"alue" <-- parsed as `key="v"` and `alue"` which is equal to true.
+    #[test]
+    fn test_missing_and_odd_values() {
+        assert_eq!(
+            tokens("a= >"),
+            vec![ElementAttributeToken::Attribute("a", Some(""))]
+        );
+        assert_eq!(
+            tokens("=a b==c>"),
+            vec![
+                ElementAttributeToken::Attribute("=a", None),
+                ElementAttributeToken::Attribute("b", Some("=c")),
+            ]
+        );
+        assert_eq!(
+            tokens("a=\"unclosed >"),
+            vec![ElementAttributeToken::Attribute("a", Some("unclosed >"))]
+        );
+    }
 }
@@ -0,0 +1,262 @@
+use std::ops::Range;
+
+use crate::Reader;
+
+/// The `<!DOCTYPE>` of a document, as written in the source.
+///
+/// # Example
+///
+/// ```rust
+/// use scah::{Query, Save, parse};
+///
+/// let html = r#"<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN"
+///     "http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd"><html></html>"#;
+/// let queries = &[Query::all("html", Save::none())
+///     .expect("valid selector")
+///     .build()];
+/// let store = parse(html, queries);
+///
+/// let doctype = store.doctype.unwrap();
+/// assert_eq!(doctype.name, Some("html"));
+/// assert_eq!(doctype.public_id, Some("-//W3C//DTD XHTML 1.0 Strict//EN"));
+/// assert_eq!(
+///     doctype.system_id,
+///     Some("http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd")
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Doctype<'html> {
+    /// The root element name (`"html"`), if any.
+    pub name: Option<&'html str>,
+    /// The quoted identifier after `PUBLIC`, if any.
+    pub public_id: Option<&'html str>,
+    /// The quoted identifier after `SYSTEM`, or after the public one.
+    pub system_id: Option<&'html str>,
+}
+
+/// Markup that isn't an element tag.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Markup<'html> {
+    /// A comment, or markup the tokenizer reads as one (`<?xml ...?>`,
+    /// `<![if IE]>`, `</ x>`).
+    Comment,
+    /// The text of a `<![CDATA[...]]>` section.
+    CData(Range<usize>),
+    Doctype(Doctype<'html>),
+}
+
+const WHITESPACE: [u8; 5] = [b' ', b'\t', b'\n', b'\r', b'\x0c'];
+
+impl<'html> Markup<'html> {
+    /// Reads the markup at a `<`, or leaves the reader untouched when it
+    /// starts a tag. CDATA sections are only recognized in foreign content,
+    /// elsewhere they are bogus comments.
+    pub fn from(reader: &mut Reader<'html>, allow_cdata: bool) -> Option<Self> {
+        match (reader.peek_at(1), reader.peek_at(2)) {
+            (Some(b'!'), _) => {
+                reader.skip();
+                reader.skip();
+            }
+            (Some(b'?'), _) => {
+                reader.skip();
+            }
+            (Some(b'/'), Some(next)) if !next.is_ascii_alphabetic() && next != b'>' => {
+                reader.skip();
+                reader.skip();
+            }
+            _ => return None,
+        }
+        if reader.match_ignore_case("--") {
+            reader.skip();
+            reader.skip();
+            next_comment(reader);
+            return Some(Self::Comment);
+        }
+        if reader.match_ignore_case("DOCTYPE") {
+            return Some(Self::Doctype(next_doctype(reader)));
+        }
+        if allow_cdata && reader.match_ignore_case("[CDATA[") {
+            return Some(Self::CData(next_cdata(reader)));
+        }
+        reader.next_until(b'>');
+        reader.skip();
+        Some(Self::Comment)
+    }
+}
+
+/// Reads a comment's text and its `-->`. `<!-->` and `<!--->` are empty
+/// comments, `--!>` also ends one, and an unclosed comment runs to the end
+/// of the document.
+fn next_comment(reader: &mut Reader<'_>) {
+    if reader.peek() == Some(b'>') {
+        reader.skip();
+        return;
+    }
+    if reader.match_ignore_case("->") {
+        reader.skip();
+        reader.skip();
+        return;
+    }
+    loop {
+        reader.next_until(b'-');
+        if reader.peek().is_none() {
+            return;
+        }
+        for end in ["-->", "--!>"] {
+            if reader.match_ignore_case(end) {
+                (0..end.len()).for_each(|_| reader.skip());
+                return;
+            }
+        }
+        reader.skip();
+    }
+}
+
+fn next_cdata(reader: &mut Reader<'_>) -> Range<usize> {
+    (0.."[CDATA[".len()).for_each(|_| reader.skip());
+    let start = reader.get_position();
+    loop {
+        reader.next_until(b']');
+        if reader.peek().is_none() {
+            return start..reader.get_position();
+        }
+        if reader.match_ignore_case("]]>") {
+            let end = reader.get_position();
+            (0.."]]>".len()).for_each(|_| reader.skip());
+            return start..end;
+        }
+        reader.skip();
+    }
+}
+
+/// Reads the rest of a `<!DOCTYPE`, up to and including its `>`.
+fn next_doctype<'html>(reader: &mut Reader<'html>) -> Doctype<'html> {
+    (0.."DOCTYPE".len()).for_each(|_| reader.skip());
+    let mut doctype = Doctype::default();
+
+    reader.next_while_list(&WHITESPACE);
+    let start = reader.get_position();
+    reader.next_until_list(&[b' ', b'\t', b'\n', b'\r', b'\x0c', b'>']);
+    if reader.get_position() > start {
+        doctype.name = Some(reader.slice(start..reader.get_position()));
+    }
+
+    reader.next_while_list(&WHITESPACE);
+    if reader.match_ignore_case("PUBLIC") {
+        (0.."PUBLIC".len()).for_each(|_| reader.skip());
+        doctype.public_id = next_identifier(reader);
+        if doctype.public_id.is_some() {
+            doctype.system_id = next_identifier(reader);
+        }
+    } else if reader.match_ignore_case("SYSTEM") {
+        (0.."SYSTEM".len()).for_each(|_| reader.skip());
+        doctype.system_id = next_identifier(reader);
+    }
+
+    reader.next_until(b'>');
+    reader.skip();
+    doctype
+}
+
+/// Reads a quoted DOCTYPE identifier, which a `>` also ends.
+fn next_identifier<'html>(reader: &mut Reader<'html>) -> Option<&'html str> {
+    reader.next_while_list(&WHITESPACE);
+    let quote = reader
+        .peek()
+        .filter(|quote| matches!(quote, b'"' | b'\''))?;
+    reader.skip();
+    let start = reader.get_position();
+    reader.next_until_list(&[quote, b'>']);
+    let identifier = reader.slice(start..reader.get_position());
+    if reader.peek() == Some(quote) {
+        reader.skip();
+    }
+    Some(identifier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markup(html: &str, allow_cdata: bool) -> (Option<Markup<'_>>, &str) {
+        let mut reader = Reader::new(html);
+        let markup = Markup::from(&mut reader, allow_cdata);
+        (markup, reader.slice(reader.get_position()..html.len()))
+    }
+
+    #[test]
+    fn test_comments() {
+        assert_eq!(
+            markup("<!-- a > b -->x", false),
+            (Some(Markup::Comment), "x")
+        );
+        assert_eq!(
+            markup("<!-- a -- b --!>x", false),
+            (Some(Markup::Comment), "x")
+        );
+        assert_eq!(markup("<!-->x", false), (Some(Markup::Comment), "x"));
+        assert_eq!(markup("<!--->x", false), (Some(Markup::Comment), "x"));
+        assert_eq!(markup("<!-- open <p>", false), (Some(Markup::Comment), ""));
+        assert_eq!(
+            markup("<!--[if IE]><p>old</p><![endif]-->x", false),
+            (Some(Markup::Comment), "x")
+        );
+    }
+
+    #[test]
+    fn test_bogus_comments() {
+        assert_eq!(
+            markup("<?xml version='1.0'?>x", false),
+            (Some(Markup::Comment), "x")
+        );
+        assert_eq!(markup("<![if !IE]>x", false), (Some(Markup::Comment), "x"));
+        assert_eq!(markup("</ p>x", false), (Some(Markup::Comment), "x"));
+        assert_eq!(
+            markup("<![CDATA[a>b]]>", false),
+            (Some(Markup::Comment), "b]]>")
+        );
+        assert_eq!(markup("<p>", false), (None, "<p>"));
+        assert_eq!(markup("</p>", false), (None, "</p>"));
+    }
+
+    #[test]
+    fn test_cdata() {
+        let html = "<![CDATA[a > b]]>x";
+        assert_eq!(markup(html, true), (Some(Markup::CData(9..14)), "x"));
+        assert_eq!(&html[9..14], "a > b");
+        assert_eq!(markup("<![CDATA[a", true), (Some(Markup::CData(9..10)), ""));
+    }
+
+    #[test]
+    fn test_doctypes() {
+        let doctype = |html| match markup(html, false) {
+            (Some(Markup::Doctype(doctype)), "x") => doctype,
+            other => panic!("{other:?}"),
+        };
+
+        assert_eq!(
+            doctype("<!DOCTYPE html>x"),
+            Doctype {
+                name: Some("html"),
+                ..Doctype::default()
+            }
+        );
+        assert_eq!(
+            doctype("<!doctype HTML system 'about:legacy-compat'>x"),
+            Doctype {
+                name: Some("HTML"),
+                public_id: None,
+                system_id: Some("about:legacy-compat"),
+            }
+        );
+        assert_eq!(
+            doctype("<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01//EN\" 'a>x"),
+            Doctype {
+                name: Some("html"),
+                public_id: Some("-//W3C//DTD HTML 4.01//EN"),
+                system_id: Some("a"),
+            }
+        );
+        assert_eq!(doctype("<!DOCTYPE>x"), Doctype::default());
+    }
+}
@@ -1,3 +1,8 @@
+pub mod char_ref;
 pub mod element;
+mod entities;
+pub mod markup;
 mod open_elements;
 pub mod parser;
+pub mod streaming;
+mod tree_builder;
@@ -1,22 +1,249 @@
+use std::borrow::Cow;
+
+use smallvec::SmallVec;
+
 use crate::engine::DepthSize;
-use crate::store::ElementId;
+use crate::store::{ElementId, ElementLink};
+use crate::{Attribute, ElementPredicate, MATHML_NAMESPACE, SVG_NAMESPACE};
 
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) struct SavedElement {
     pub element_id: ElementId,
     pub inner_html_start: Option<usize>,
+    pub outer_html: bool,
     pub text_content_start: Option<usize>,
 }
 
+/// Where an element sits among its element siblings, as read by the
+/// structural pseudo-classes. All indices are 1-based.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct SiblingPosition {
+    pub index: u32,
+    pub type_index: u32,
+    // Index among the siblings matching each `of S` selector the element
+    // matches, keyed by `selector_key`.
+    pub matching: Vec<(usize, u32)>,
+    // Only known once the parent has closed.
+    pub totals: Option<SiblingTotals>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct SiblingTotals {
+    pub count: u32,
+    pub type_count: u32,
+    pub matching: Vec<(usize, u32)>,
+}
+
+/// Identifies an `of S` selector list by the address of its predicates, which
+/// live as long as the query.
+pub(crate) fn selector_key(selector: &[ElementPredicate<'_>]) -> usize {
+    selector.as_ptr() as usize
+}
+
+/// Element children opened so far under one parent, used to number siblings.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct ChildCounters<'html> {
+    count: u32,
+    // Only allocated once a query numbers siblings by type or by an `of S`
+    // selector, as every open element carries its counters.
+    typed: Option<Box<TypedCounters<'html>>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct TypedCounters<'html> {
+    types: Vec<(&'html str, u32)>,
+    // Indexed like the parser's `of S` selectors.
+    matching: Vec<u32>,
+}
+
+impl<'html> ChildCounters<'html> {
+    fn next_position(&mut self, name: &'html str) -> SiblingPosition {
+        let index = self.next_index();
+        let types = &mut self.typed.get_or_insert_default().types;
+        let type_index = match types
+            .iter_mut()
+            .find(|(tag, _)| tag.eq_ignore_ascii_case(name))
+        {
+            Some((_, count)) => {
+                *count = count.saturating_add(1);
+                *count
+            }
+            None => {
+                types.push((name, 1));
+                1
+            }
+        };
+
+        SiblingPosition {
+            index,
+            type_index,
+            ..Default::default()
+        }
+    }
+
+    fn next_index(&mut self) -> u32 {
+        self.count = self.count.saturating_add(1);
+        self.count
+    }
+
+    fn count_matching(&mut self, slot: usize) -> u32 {
+        let matching = &mut self.typed.get_or_insert_default().matching;
+        if matching.len() <= slot {
+            matching.resize(slot + 1, 0);
+        }
+        matching[slot] = matching[slot].saturating_add(1);
+        matching[slot]
+    }
+
+    fn snapshot(&self) -> ChildCountersSnapshot {
+        let Some(typed) = &self.typed else {
+            return ChildCountersSnapshot {
+                count: self.count,
+                ..Default::default()
+            };
+        };
+        ChildCountersSnapshot {
+            count: self.count,
+            types: typed
+                .types
+                .iter()
+                .map(|(name, count)| (name.to_string(), *count))
+                .collect(),
+            matching: typed.matching.clone(),
+        }
+    }
+
+    fn restore(snapshot: &'html ChildCountersSnapshot) -> Self {
+        let typed = (!snapshot.types.is_empty() || !snapshot.matching.is_empty()).then(|| {
+            Box::new(TypedCounters {
+                types: snapshot
+                    .types
+                    .iter()
+                    .map(|(name, count)| (name.as_str(), *count))
+                    .collect(),
+                matching: snapshot.matching.clone(),
+            })
+        });
+        Self {
+            count: snapshot.count,
+            typed,
+        }
+    }
+
+    pub fn totals(&self, name: &str, selectors: &[&[ElementPredicate<'_>]]) -> SiblingTotals {
+        let typed = self.typed.as_deref();
+        SiblingTotals {
+            count: self.count,
+            type_count: typed
+                .and_then(|typed| {
+                    typed
+                        .types
+                        .iter()
+                        .find(|(tag, _)| tag.eq_ignore_ascii_case(name))
+                })
+                .map_or(0, |(_, count)| *count),
+            matching: selectors
+                .iter()
+                .zip(typed.map_or(&[][..], |typed| &typed.matching))
+                .map(|(selector, count)| (selector_key(selector), *count))
+                .collect(),
+        }
+    }
+}
+
+/// The names and sibling counts of an [`OpenElementStack`], copied out of the
+/// source so parsing can resume from them once the source is gone. Only
+/// taken while the stack [`is_settled`](OpenElementStack::is_settled), as
+/// saved elements and deferred matches can't be carried over.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct OpenElementsSnapshot {
+    entries: Vec<OpenElementSnapshot>,
+    root_children: ChildCountersSnapshot,
+    xml: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct OpenElementSnapshot {
+    name: String,
+    namespace: Option<&'static str>,
+    template_content: bool,
+    children: ChildCountersSnapshot,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct ChildCountersSnapshot {
+    count: u32,
+    types: Vec<(String, u32)>,
+    matching: Vec<u32>,
+}
+
+/// A saved element whose selector depends on what follows its open tag: its
+/// descendants (`:has()`) or its later siblings (`:last-child`). It is
+/// confirmed or unlinked from the store once the element, or its parent,
+/// closes.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DeferredMatch<'html> {
+    pub element_id: ElementId,
+    pub link: ElementLink,
+    pub namespace: Option<&'static str>,
+    pub siblings: SiblingPosition,
+    // Keys of the `:has()` lists matched inside the element, once it closed.
+    pub relations: Option<Vec<usize>>,
+    // The element is kept when any of them matches.
+    pub predicates: Vec<&'html ElementPredicate<'html>>,
+}
+
+/// A `:has()` list an open element is waiting for.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Relation<'html> {
+    pub selector: &'html [ElementPredicate<'html>],
+    pub children_only: bool,
+    pub found: bool,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) struct OpenElement<'html> {
     pub name: &'html str,
+    // `None` for HTML elements.
+    pub namespace: Option<&'static str>,
+    // Whether its children are part of a `<template>`'s contents.
+    pub template_content: bool,
+    // The scopes whose searches stop at it, one bit per `ScopeKind`, worked
+    // out once rather than on every search going past it.
+    barriers: u8,
     pub saved: Vec<SavedElement>,
+    pub children: ChildCounters<'html>,
+    // Boxed, as only `:has()` and `:last-child` selectors leave anything
+    // here and the element is moved around as it closes.
+    pub waiting: Option<Box<Waiting<'html>>>,
 }
 
+/// The deferred matches an open element holds until it closes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Waiting<'html> {
+    // Matches of children that wait for this element to close.
+    pub deferred: Vec<DeferredMatch<'html>>,
+    // Matches of this element that wait for it to close.
+    pub pending: Vec<DeferredMatch<'html>>,
+    pub relations: Vec<Relation<'html>>,
+}
+
+impl<'html> OpenElement<'html> {
+    pub fn waiting_mut(&mut self) -> &mut Waiting<'html> {
+        self.waiting.get_or_insert_default()
+    }
+}
+
+/// The elements an end tag closes, most often just one, innermost first.
+pub(crate) type Popped<'html> = SmallVec<[OpenElement<'html>; 1]>;
+
 #[derive(Debug, PartialEq)]
 pub(crate) struct OpenElementStack<'html> {
     entries: Vec<OpenElement<'html>>,
+    // XML has no implied end tags nor scopes, and its names compare exactly.
+    xml: bool,
+    root_children: ChildCounters<'html>,
+    root_deferred: Vec<DeferredMatch<'html>>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -28,83 +255,327 @@ enum ScopeKind {
     Select,
 }
 
+impl ScopeKind {
+    const fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
 impl<'html> Default for OpenElementStack<'html> {
     fn default() -> Self {
         const ASSUMED_MAX_DEPTH: usize = 16;
         Self {
             entries: Vec::with_capacity(ASSUMED_MAX_DEPTH),
+            xml: false,
+            root_children: ChildCounters::default(),
+            root_deferred: Vec::new(),
         }
     }
 }
 
 impl<'html> OpenElementStack<'html> {
+    pub fn for_xml() -> Self {
+        Self {
+            xml: true,
+            ..Self::default()
+        }
+    }
+
     pub fn depth(&self) -> DepthSize {
         self.entries.len().try_into().unwrap_or(DepthSize::MAX)
     }
 
-    pub fn push(&mut self, name: &'html str) {
+    /// Whether the current node is an SVG or MathML element.
+    pub fn in_foreign_content(&self) -> bool {
+        self.entries
+            .last()
+            .is_some_and(|entry| entry.namespace.is_some())
+    }
+
+    /// Whether an element opened under the current node is part of a
+    /// `<template>`'s contents.
+    pub fn in_template_content(&self) -> bool {
+        self.entries
+            .last()
+            .is_some_and(|entry| entry.template_content)
+    }
+
+    pub fn push(&mut self, name: &'html str, namespace: Option<&'static str>) {
+        let template_content = self.in_template_content()
+            || !self.xml && namespace.is_none() && name.eq_ignore_ascii_case("template");
         self.entries.push(OpenElement {
             name,
+            namespace,
+            template_content,
+            barriers: scope_barriers(name),
             saved: Vec::new(),
+            children: ChildCounters::default(),
+            waiting: None,
         });
     }
 
+    /// Namespace of an element opened under the current node: `<svg>` and
+    /// `<math>` start foreign subtrees, which lead back to HTML inside their
+    /// integration points (`<foreignObject>`, `<mtext>`, ...).
+    pub fn namespace_of(&self, name: &str) -> Option<&'static str> {
+        if name.eq_ignore_ascii_case("svg") {
+            return Some(SVG_NAMESPACE);
+        }
+        if name.eq_ignore_ascii_case("math") {
+            return Some(MATHML_NAMESPACE);
+        }
+
+        let parent = self.entries.last()?;
+        let namespace = parent.namespace?;
+        if is_integration_point(parent) {
+            return None;
+        }
+        Some(namespace)
+    }
+
+    /// Pops the SVG and MathML elements an HTML start tag breaks out of:
+    /// `<p>`, `<div>`, `<font color>` and the like close them up to the
+    /// nearest HTML element or integration point.
+    pub fn break_out_of_foreign(
+        &mut self,
+        name: &str,
+        attributes: &[Attribute<'_>],
+    ) -> Popped<'html> {
+        let mut popped = Popped::new();
+        if self.xml || !self.in_foreign_content() {
+            return popped;
+        }
+        let breaks_out = is_one_of(name, &FOREIGN_BREAKOUTS)
+            || name.eq_ignore_ascii_case("font")
+                && attributes
+                    .iter()
+                    .any(|attribute| is_one_of(attribute.key, &["color", "face", "size"]));
+        if !breaks_out {
+            return popped;
+        }
+
+        while let Some(entry) = self.entries.last() {
+            if entry.namespace.is_none() || is_integration_point(entry) {
+                break;
+            }
+            popped.extend(self.entries.pop());
+        }
+        popped
+    }
+
+    fn current_children(&mut self) -> &mut ChildCounters<'html> {
+        match self.entries.last_mut() {
+            Some(parent) => &mut parent.children,
+            None => &mut self.root_children,
+        }
+    }
+
+    /// Registers a new element under the current node and returns its
+    /// position among its element siblings.
+    ///
+    /// Must be called after the implied closes of [`Self::prepare_for_open`]
+    /// have been popped, so the element is counted under its real parent.
+    pub fn next_sibling_position(&mut self, name: &'html str) -> SiblingPosition {
+        self.current_children().next_position(name)
+    }
+
+    /// Like [`Self::next_sibling_position`], without counting the siblings
+    /// of the element's type: its `type_index` is left at 0.
+    pub fn next_sibling_index(&mut self) -> SiblingPosition {
+        SiblingPosition {
+            index: self.current_children().next_index(),
+            ..Default::default()
+        }
+    }
+
+    /// Counts the element just registered as matching the `of S` selector at
+    /// `slot`, and returns its index among the matching siblings.
+    pub fn count_matching(&mut self, slot: usize) -> u32 {
+        self.current_children().count_matching(slot)
+    }
+
+    /// Holds a deferred match of the element just registered: on the element
+    /// itself when it was pushed onto the stack (`opened`), so its `:has()`
+    /// lists are watched until it closes, and on its parent otherwise.
+    pub fn attach_deferred(&mut self, deferred: DeferredMatch<'html>, opened: bool) {
+        if !opened {
+            self.attach_to_current(deferred);
+            return;
+        }
+
+        let Some(open_element) = self.entries.last_mut() else {
+            return;
+        };
+        let waiting = open_element.waiting_mut();
+        for predicate in &deferred.predicates {
+            predicate.for_each_relative_selector(&mut |selector, children_only| {
+                waiting.relations.push(Relation {
+                    selector,
+                    children_only,
+                    found: false,
+                });
+            });
+        }
+        waiting.pending.push(deferred);
+    }
+
+    /// Hands a deferred match to the current node, to be resolved once all
+    /// of its children are known.
+    pub fn attach_to_current(&mut self, deferred: DeferredMatch<'html>) {
+        match self.entries.last_mut() {
+            Some(parent) => parent.waiting_mut().deferred.push(deferred),
+            None => self.root_deferred.push(deferred),
+        }
+    }
+
+    /// Marks the `:has()` lists of the open elements that the element about
+    /// to be pushed satisfies, according to `matches`.
+    pub fn match_relations(&mut self, matches: impl Fn(&[ElementPredicate<'html>]) -> bool) {
+        let parent = self.entries.len().wrapping_sub(1);
+        for (index, open_element) in self.entries.iter_mut().enumerate() {
+            let Some(waiting) = &mut open_element.waiting else {
+                continue;
+            };
+            for relation in &mut waiting.relations {
+                if !relation.found
+                    && (!relation.children_only || index == parent)
+                    && matches(relation.selector)
+                {
+                    relation.found = true;
+                }
+            }
+        }
+    }
+
+    /// Whether no open element has a saved element to finalize or a match
+    /// waiting on it, so none of them depends on the source read so far
+    /// beyond its name.
+    pub fn is_settled(&self) -> bool {
+        self.root_deferred.is_empty()
+            && self.entries.iter().all(|entry| {
+                entry.saved.is_empty()
+                    && entry.waiting.as_deref().is_none_or(|waiting| {
+                        waiting.deferred.is_empty()
+                            && waiting.pending.is_empty()
+                            && waiting.relations.is_empty()
+                    })
+            })
+    }
+
+    pub fn snapshot(&self) -> OpenElementsSnapshot {
+        debug_assert!(self.is_settled());
+        OpenElementsSnapshot {
+            entries: self
+                .entries
+                .iter()
+                .map(|entry| OpenElementSnapshot {
+                    name: entry.name.to_string(),
+                    namespace: entry.namespace,
+                    template_content: entry.template_content,
+                    children: entry.children.snapshot(),
+                })
+                .collect(),
+            root_children: self.root_children.snapshot(),
+            xml: self.xml,
+        }
+    }
+
+    pub fn restore(snapshot: &'html OpenElementsSnapshot) -> Self {
+        Self {
+            entries: snapshot
+                .entries
+                .iter()
+                .map(|entry| OpenElement {
+                    name: &entry.name,
+                    namespace: entry.namespace,
+                    template_content: entry.template_content,
+                    barriers: scope_barriers(&entry.name),
+                    saved: Vec::new(),
+                    children: ChildCounters::restore(&entry.children),
+                    waiting: None,
+                })
+                .collect(),
+            xml: snapshot.xml,
+            root_children: ChildCounters::restore(&snapshot.root_children),
+            root_deferred: Vec::new(),
+        }
+    }
+
+    /// The top-level siblings and their deferred matches, which can only be
+    /// resolved once the document ends.
+    pub fn take_root(&mut self) -> (ChildCounters<'html>, Vec<DeferredMatch<'html>>) {
+        (
+            std::mem::take(&mut self.root_children),
+            std::mem::take(&mut self.root_deferred),
+        )
+    }
+
     pub fn attach_saved(
         &mut self,
         element_id: ElementId,
         inner_html_start: Option<usize>,
+        outer_html: bool,
         text_content_start: Option<usize>,
     ) {
         if let Some(open_element) = self.entries.last_mut() {
             open_element.saved.push(SavedElement {
                 element_id,
                 inner_html_start,
+                outer_html,
                 text_content_start,
             });
         }
     }
 
-    pub fn prepare_for_open(&mut self, name: &str) -> Vec<OpenElement<'html>> {
-        let mut popped = Vec::new();
+    /// Pops the elements a start tag closes before it opens: the foreign
+    /// ones it breaks out of, then the `<p>`, `<li>`, ... it implies the end
+    /// of. They are popped into one list, as they are popped in turn.
+    pub fn prepare_for_open(&mut self, name: &str, attributes: &[Attribute<'_>]) -> Popped<'html> {
+        if self.xml {
+            return Popped::new();
+        }
+        let mut popped = self.break_out_of_foreign(name, attributes);
 
-        if closes_open_p(name) {
-            popped.extend(self.pop_matching_in_scope(&["p"], ScopeKind::Default));
+        let name = lowercase(name);
+        if closes_open_p(&name) {
+            self.pop_matching_in_scope(&["p"], ScopeKind::Default, &mut popped);
         }
 
-        match name {
-            "button" => popped.extend(self.pop_matching_in_scope(&["button"], ScopeKind::Button)),
-            "li" => popped.extend(self.pop_matching_in_scope(&["li"], ScopeKind::ListItem)),
+        match &*name {
+            "button" => self.pop_matching_in_scope(&["button"], ScopeKind::Button, &mut popped),
+            "li" => self.pop_matching_in_scope(&["li"], ScopeKind::ListItem, &mut popped),
             "dt" | "dd" => {
-                popped.extend(self.pop_matching_in_scope(&["dt", "dd"], ScopeKind::ListItem))
+                self.pop_matching_in_scope(&["dt", "dd"], ScopeKind::ListItem, &mut popped)
             }
-            "option" => popped.extend(self.pop_matching_in_scope(&["option"], ScopeKind::Select)),
+            "option" => self.pop_matching_in_scope(&["option"], ScopeKind::Select, &mut popped),
             "optgroup" => {
-                popped.extend(self.pop_matching_in_scope(&["option"], ScopeKind::Select));
-                popped.extend(self.pop_matching_in_scope(&["optgroup"], ScopeKind::Select));
-            }
-            "tr" => popped.extend(self.pop_matching_in_scope(&["tr"], ScopeKind::Table)),
-            "td" | "th" => {
-                popped.extend(self.pop_matching_in_scope(&["td", "th"], ScopeKind::Table))
+                self.pop_matching_in_scope(&["option"], ScopeKind::Select, &mut popped);
+                self.pop_matching_in_scope(&["optgroup"], ScopeKind::Select, &mut popped);
             }
+            "tr" => self.pop_matching_in_scope(&["tr"], ScopeKind::Table, &mut popped),
+            "td" | "th" => self.pop_matching_in_scope(&["td", "th"], ScopeKind::Table, &mut popped),
             _ => {}
         }
 
         popped
     }
 
-    pub fn close_by_end_tag(&mut self, name: &str) -> Vec<OpenElement<'html>> {
+    pub fn close_by_end_tag(&mut self, name: &str) -> Popped<'html> {
         let scope = close_scope(name);
+        let mut popped = Popped::new();
         if let Some(index) = self.find_matching_index(name, scope) {
-            let mut popped = Vec::with_capacity(self.entries.len() - index);
             while self.entries.len() > index {
                 if let Some(open) = self.entries.pop() {
                     popped.push(open);
                 }
             }
-            popped
-        } else {
-            Vec::new()
         }
+        popped
+    }
+
+    /// Pops the current node alone, as the spec tree closes it.
+    pub fn pop_current(&mut self) -> Popped<'html> {
+        self.entries.pop().into_iter().collect()
     }
 
     pub fn close_all_at_eof(&mut self) -> Vec<OpenElement<'html>> {
@@ -115,17 +586,12 @@ impl<'html> OpenElementStack<'html> {
         &mut self,
         names: &[&str],
         scope: ScopeKind,
-    ) -> Vec<OpenElement<'html>> {
+        popped: &mut Popped<'html>,
+    ) {
         if let Some(index) = self.find_first_of(names, scope) {
-            let mut popped = Vec::with_capacity(self.entries.len() - index);
             while self.entries.len() > index {
-                if let Some(open) = self.entries.pop() {
-                    popped.push(open);
-                }
+                popped.extend(self.entries.pop());
             }
-            popped
-        } else {
-            Vec::new()
         }
     }
 
@@ -137,7 +603,7 @@ impl<'html> OpenElementStack<'html> {
             {
                 return Some(index);
             }
-            if is_scope_barrier(entry.name, scope) {
+            if entry.barriers & scope.bit() != 0 {
                 return None;
             }
         }
@@ -146,10 +612,16 @@ impl<'html> OpenElementStack<'html> {
 
     fn find_matching_index(&self, name: &str, scope: ScopeKind) -> Option<usize> {
         for (index, entry) in self.entries.iter().enumerate().rev() {
+            if self.xml {
+                if entry.name == name {
+                    return Some(index);
+                }
+                continue;
+            }
             if entry.name.eq_ignore_ascii_case(name) {
                 return Some(index);
             }
-            if is_scope_barrier(entry.name, scope) {
+            if entry.barriers & scope.bit() != 0 {
                 return None;
             }
         }
@@ -157,8 +629,84 @@ impl<'html> OpenElementStack<'html> {
     }
 }
 
+/// Start tags that leave SVG and MathML for the HTML element they name.
+pub(super) const FOREIGN_BREAKOUTS: [&str; 44] = [
+    "b",
+    "big",
+    "blockquote",
+    "body",
+    "br",
+    "center",
+    "code",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "embed",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "hr",
+    "i",
+    "img",
+    "li",
+    "listing",
+    "menu",
+    "meta",
+    "nobr",
+    "ol",
+    "p",
+    "pre",
+    "ruby",
+    "s",
+    "small",
+    "span",
+    "strong",
+    "strike",
+    "sub",
+    "sup",
+    "table",
+    "tt",
+    "u",
+    "ul",
+    "var",
+];
+
+fn is_integration_point(entry: &OpenElement<'_>) -> bool {
+    let integration_points: &[&str] = match entry.namespace {
+        Some(namespace) if namespace == SVG_NAMESPACE => &["foreignobject", "desc", "title"],
+        Some(_) => &["mi", "mo", "mn", "ms", "mtext", "annotation-xml"],
+        None => return false,
+    };
+    is_one_of(entry.name, integration_points)
+}
+
+/// `name` in ASCII lowercase, only copied when it isn't already, so the
+/// tree rules can match it against the names they look for.
+pub(super) fn lowercase(name: &str) -> Cow<'_, str> {
+    if name.bytes().any(|byte| byte.is_ascii_uppercase()) {
+        Cow::Owned(name.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// Whether `name` is one of `names`, ignoring ASCII case. `names` must be
+/// lowercase, so a name written in lowercase, as most are, compares exactly.
+pub(super) fn is_one_of(name: &str, names: &[&str]) -> bool {
+    if !name.bytes().any(|byte| byte.is_ascii_uppercase()) {
+        return names.contains(&name);
+    }
+    names.iter().any(|other| name.eq_ignore_ascii_case(other))
+}
+
 fn close_scope(name: &str) -> ScopeKind {
-    match name {
+    match &*lowercase(name) {
         "li" | "dt" | "dd" => ScopeKind::ListItem,
         "button" => ScopeKind::Button,
         "tr" | "td" | "th" | "thead" | "tbody" | "tfoot" | "caption" | "colgroup" => {
@@ -200,40 +748,36 @@ fn closes_open_p(name: &str) -> bool {
     )
 }
 
-fn is_scope_barrier(name: &str, scope: ScopeKind) -> bool {
-    if matches!(name, "html" | "template") {
-        return true;
-    }
-
-    match scope {
-        ScopeKind::Default => matches!(
-            name,
-            "applet" | "marquee" | "object" | "table" | "td" | "th"
-        ),
-        ScopeKind::ListItem => matches!(
-            name,
-            "applet" | "marquee" | "object" | "table" | "td" | "th" | "ol" | "ul"
-        ),
-        ScopeKind::Button => {
-            matches!(
-                name,
-                "applet" | "marquee" | "object" | "table" | "td" | "th" | "button"
-            )
-        }
-        ScopeKind::Table => matches!(name, "html" | "table" | "template"),
-        ScopeKind::Select => !matches!(name, "option" | "optgroup"),
+/// The scopes whose searches stop at an element named `name`, one bit per
+/// [`ScopeKind`].
+fn scope_barriers(name: &str) -> u8 {
+    const DEFAULT: u8 = ScopeKind::Default.bit();
+    const LIST_ITEM: u8 = ScopeKind::ListItem.bit();
+    const BUTTON: u8 = ScopeKind::Button.bit();
+    const TABLE: u8 = ScopeKind::Table.bit();
+    const SELECT: u8 = ScopeKind::Select.bit();
+
+    match &*lowercase(name) {
+        "html" | "template" | "table" => DEFAULT | LIST_ITEM | BUTTON | TABLE | SELECT,
+        "applet" | "marquee" | "object" | "td" | "th" => DEFAULT | LIST_ITEM | BUTTON | SELECT,
+        "ol" | "ul" => LIST_ITEM | SELECT,
+        "button" => BUTTON | SELECT,
+        // Only the select scope looks past them.
+        "option" | "optgroup" => 0,
+        _ => SELECT,
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::OpenElementStack;
+    use super::{OpenElementStack, Relation};
+    use crate::{MATHML_NAMESPACE, SVG_NAMESPACE};
 
     #[test]
     fn test_misnested_close_bubbles_to_match() {
         let mut stack = OpenElementStack::default();
-        stack.push("div");
-        stack.push("span");
+        stack.push("div", None);
+        stack.push("span", None);
 
         let popped = stack.close_by_end_tag("div");
         assert_eq!(popped.len(), 2);
@@ -244,7 +788,7 @@ mod tests {
     #[test]
     fn test_stray_close_is_ignored() {
         let mut stack = OpenElementStack::default();
-        stack.push("div");
+        stack.push("div", None);
 
         let popped = stack.close_by_end_tag("span");
         assert!(popped.is_empty());
@@ -254,10 +798,10 @@ mod tests {
     #[test]
     fn test_opening_li_closes_previous_li() {
         let mut stack = OpenElementStack::default();
-        stack.push("ul");
-        stack.push("li");
+        stack.push("ul", None);
+        stack.push("li", None);
 
-        let popped = stack.prepare_for_open("li");
+        let popped = stack.prepare_for_open("li", &[]);
         assert_eq!(popped.len(), 1);
         assert_eq!(popped[0].name, "li");
     }
@@ -265,10 +809,10 @@ mod tests {
     #[test]
     fn test_opening_option_closes_previous_option() {
         let mut stack = OpenElementStack::default();
-        stack.push("select");
-        stack.push("option");
+        stack.push("select", None);
+        stack.push("option", None);
 
-        let popped = stack.prepare_for_open("option");
+        let popped = stack.prepare_for_open("option", &[]);
         assert_eq!(popped.len(), 1);
         assert_eq!(popped[0].name, "option");
     }
@@ -276,11 +820,11 @@ mod tests {
     #[test]
     fn test_opening_optgroup_closes_option_then_optgroup() {
         let mut stack = OpenElementStack::default();
-        stack.push("select");
-        stack.push("optgroup");
-        stack.push("option");
+        stack.push("select", None);
+        stack.push("optgroup", None);
+        stack.push("option", None);
 
-        let popped = stack.prepare_for_open("optgroup");
+        let popped = stack.prepare_for_open("optgroup", &[]);
         assert_eq!(popped.len(), 2);
         assert_eq!(popped[0].name, "option");
         assert_eq!(popped[1].name, "optgroup");
@@ -289,11 +833,11 @@ mod tests {
     #[test]
     fn test_opening_td_closes_previous_cell() {
         let mut stack = OpenElementStack::default();
-        stack.push("table");
-        stack.push("tr");
-        stack.push("td");
+        stack.push("table", None);
+        stack.push("tr", None);
+        stack.push("td", None);
 
-        let popped = stack.prepare_for_open("td");
+        let popped = stack.prepare_for_open("td", &[]);
         assert_eq!(popped.len(), 1);
         assert_eq!(popped[0].name, "td");
     }
@@ -301,10 +845,10 @@ mod tests {
     #[test]
     fn test_opening_button_closes_previous_button() {
         let mut stack = OpenElementStack::default();
-        stack.push("div");
-        stack.push("button");
+        stack.push("div", None);
+        stack.push("button", None);
 
-        let popped = stack.prepare_for_open("button");
+        let popped = stack.prepare_for_open("button", &[]);
         assert_eq!(popped.len(), 1);
         assert_eq!(popped[0].name, "button");
         assert_eq!(stack.depth(), 1);
@@ -313,11 +857,99 @@ mod tests {
     #[test]
     fn test_select_scope_ignores_non_select_end_tags() {
         let mut stack = OpenElementStack::default();
-        stack.push("select");
-        stack.push("option");
+        stack.push("select", None);
+        stack.push("option", None);
 
         let popped = stack.close_by_end_tag("div");
         assert!(popped.is_empty());
         assert_eq!(stack.depth(), 2);
     }
+
+    #[test]
+    fn test_sibling_index_counts_after_implied_close() {
+        let mut stack = OpenElementStack::default();
+        assert_eq!(stack.next_sibling_position("dl").index, 1);
+        stack.push("dl", None);
+        assert_eq!(stack.next_sibling_position("dt").index, 1);
+        stack.push("dt", None);
+
+        let popped = stack.prepare_for_open("dd", &[]);
+        assert_eq!(popped.len(), 1);
+        let position = stack.next_sibling_position("dd");
+        assert_eq!((position.index, position.type_index), (2, 1));
+        stack.push("dd", None);
+        assert_eq!(stack.next_sibling_position("b").index, 1);
+    }
+
+    #[test]
+    fn test_sibling_position_counts_types() {
+        let mut stack = OpenElementStack::default();
+        stack.push("div", None);
+
+        let indices = ["p", "span", "P", "p"].map(|name| {
+            let position = stack.next_sibling_position(name);
+            (position.index, position.type_index)
+        });
+        assert_eq!(indices, [(1, 1), (2, 1), (3, 2), (4, 3)]);
+
+        let totals = stack.entries[0].children.totals("p", &[]);
+        assert_eq!((totals.count, totals.type_count), (4, 3));
+    }
+
+    #[test]
+    fn test_child_relations_only_match_under_their_element() {
+        let mut stack = OpenElementStack::default();
+        stack.push("ul", None);
+        stack.entries[0].waiting_mut().relations = vec![
+            Relation {
+                selector: &[],
+                children_only: true,
+                found: false,
+            },
+            Relation {
+                selector: &[],
+                children_only: false,
+                found: false,
+            },
+        ];
+        stack.push("li", None);
+
+        stack.match_relations(|_| true);
+        let found = stack.entries[0]
+            .waiting_mut()
+            .relations
+            .iter()
+            .map(|relation| relation.found);
+        assert_eq!(found.collect::<Vec<_>>(), vec![false, true]);
+    }
+
+    #[test]
+    fn test_foreign_subtrees_have_their_own_namespace() {
+        let mut stack = OpenElementStack::default();
+        assert_eq!(stack.namespace_of("div"), None);
+
+        stack.push("svg", stack.namespace_of("svg"));
+        assert_eq!(stack.namespace_of("rect"), Some(SVG_NAMESPACE));
+        stack.push("foreignObject", stack.namespace_of("foreignObject"));
+        assert_eq!(stack.namespace_of("p"), None);
+        stack.push("math", stack.namespace_of("math"));
+        assert_eq!(stack.namespace_of("mi"), Some(MATHML_NAMESPACE));
+    }
+
+    #[test]
+    fn test_html_start_tags_break_out_of_foreign_content() {
+        let mut stack = OpenElementStack::default();
+        stack.push("div", None);
+        stack.push("svg", stack.namespace_of("svg"));
+        stack.push("g", stack.namespace_of("g"));
+
+        assert!(stack.break_out_of_foreign("rect", &[]).is_empty());
+        assert!(stack.break_out_of_foreign("font", &[]).is_empty());
+        let popped = stack.break_out_of_foreign("p", &[]);
+        assert_eq!(
+            popped.iter().map(|open| open.name).collect::<Vec<_>>(),
+            ["g", "svg"]
+        );
+        assert!(!stack.in_foreign_content());
+    }
 }
@@ -1,13 +1,20 @@
+use std::ops::Deref;
+
 use super::element::builder::XHtmlTag;
-use super::open_elements::{OpenElement, OpenElementStack};
+use super::markup::Markup;
+use super::open_elements::{
+    ChildCounters, DeferredMatch, OpenElement, OpenElementStack, OpenElementsSnapshot,
+    SiblingPosition, SiblingTotals, Waiting, lowercase, selector_key,
+};
+use super::tree_builder::{DOCUMENT, NodeData, Tree, TreeBuilder, TreeElement};
 use crate::QuerySpec;
 use crate::Reader;
-use crate::XHtmlElement;
 use crate::debug::ImpliedCloseReason;
 #[cfg(any(debug_assertions, test))]
 use crate::debug::TraceEvent;
 use crate::engine::multiplexer::{DocumentPosition, QueryMultiplexer};
-use crate::store::Store;
+use crate::store::{ElementId, Store};
+use crate::{ElementPredicate, XHtmlElement};
 
 pub struct XHtmlParser<'html, 'query, Q> {
     position: DocumentPosition,
@@ -15,8 +22,82 @@ pub struct XHtmlParser<'html, 'query, Q> {
     store: Store<'html, 'query>,
     element: crate::XHtmlElement<'html>,
     open_elements: OpenElementStack<'html>,
-    in_script: bool,
+    // The `S` of every `:nth-child(an+b of S)`, whose matching siblings are
+    // counted as elements open.
+    nth_selectors: Vec<&'query [ElementPredicate<'query>]>,
+    text_state: TextState,
     eof_drained: bool,
+    // Whether the document is built as a browser would before its events
+    // are replayed, see [`Self::spec_tree`].
+    spec_tree: bool,
+    // Whether every selector sees the contents of `<template>` elements, see
+    // [`Self::match_template_contents`].
+    match_template_contents: bool,
+    // Where the text content of each open `<template>`'s contents starts,
+    // and the first element saved inside it.
+    template_text: Vec<(usize, ElementId)>,
+}
+
+/// What a parser needs to go on reading a document from a position, without
+/// the source before it, see [`XHtmlParser::snapshot`].
+pub(crate) struct ParserSnapshot<'query, Q> {
+    position: DocumentPosition,
+    selectors: QueryMultiplexer<'query, Q>,
+    nth_selectors: Vec<&'query [ElementPredicate<'query>]>,
+    open_elements: OpenElementsSnapshot,
+    text_state: TextState,
+    in_text: bool,
+    xml: bool,
+    match_template_contents: bool,
+}
+
+/// How the tokenizer reads the text after an open tag, following the WHATWG
+/// tokenizer states.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum TextState {
+    Data,
+    /// Text up to the element's end tag, kept as written (`<script>`,
+    /// `<style>`, ...).
+    RawText(&'static str),
+    /// Text up to the element's end tag, with its character references
+    /// decoded (`<title>`, `<textarea>`).
+    RcData(&'static str),
+    /// Text up to the end of the document (`<plaintext>`).
+    PlainText,
+}
+
+impl TextState {
+    pub(super) fn after_open_tag(name: &str) -> Self {
+        match &*lowercase(name) {
+            "script" => Self::RawText("script"),
+            "style" => Self::RawText("style"),
+            "xmp" => Self::RawText("xmp"),
+            "iframe" => Self::RawText("iframe"),
+            "noembed" => Self::RawText("noembed"),
+            "noframes" => Self::RawText("noframes"),
+            "title" => Self::RcData("title"),
+            "textarea" => Self::RcData("textarea"),
+            "plaintext" => Self::PlainText,
+            _ => Self::Data,
+        }
+    }
+
+    /// The element's text ends at `</name` followed by whitespace, `/` or `>`.
+    pub(super) fn is_at_end_tag(&self, reader: &Reader<'_>) -> bool {
+        let (Self::RawText(name) | Self::RcData(name)) = *self else {
+            return false;
+        };
+        reader.match_ignore_case("</")
+            && name.bytes().enumerate().all(|(index, byte)| {
+                reader
+                    .peek_at(index + 2)
+                    .is_some_and(|next| next.eq_ignore_ascii_case(&byte))
+            })
+            && matches!(
+                reader.peek_at(name.len() + 2),
+                Some(b'\t' | b'\n' | b'\x0c' | b'\r' | b' ' | b'/' | b'>')
+            )
+    }
 }
 
 impl<'html, 'query: 'html, Q> XHtmlParser<'html, 'query, Q>
@@ -27,14 +108,19 @@ where
         Self {
             position: DocumentPosition {
                 element_depth: 0,
+                sibling_index: 0,
                 reader_position: 0, // for inner_html
                 text_content_position: usize::MAX,
             },
+            nth_selectors: selectors.nth_selectors(),
             selectors,
             element: XHtmlElement::default(),
             open_elements: OpenElementStack::default(),
-            in_script: false,
+            text_state: TextState::Data,
             eof_drained: false,
+            spec_tree: false,
+            match_template_contents: false,
+            template_text: Vec::new(),
             store: Store::default(),
         }
     }
@@ -43,72 +129,117 @@ where
         Self {
             position: DocumentPosition {
                 element_depth: 0,
+                sibling_index: 0,
                 reader_position: 0, // for inner_html
                 text_content_position: usize::MAX,
             },
+            nth_selectors: selectors.nth_selectors(),
             selectors,
             element: XHtmlElement::default(),
             open_elements: OpenElementStack::default(),
-            in_script: false,
+            text_state: TextState::Data,
             eof_drained: false,
+            spec_tree: false,
+            match_template_contents: false,
+            template_text: Vec::new(),
             store: Store::with_capacity(capacity),
         }
     }
 
+    /// Reads the document as XML: tag and attribute names compare exactly,
+    /// `/>` closes any element, and no element is void, raw text or closed
+    /// implicitly.
+    pub fn xml(mut self) -> Self {
+        self.open_elements = OpenElementStack::for_xml();
+        self.element.xml = true;
+        self
+    }
+
+    /// Builds the document with the WHATWG tree construction rules before
+    /// running the selectors on it, so elements open and close where a
+    /// browser's would: `<tbody>` is implied, misnested formatting elements
+    /// are reopened, and content misplaced in a table is moved before it.
+    ///
+    /// The whole document is read by the first call to [`Self::next`], and
+    /// its tree is kept in memory until the selectors have run.
+    pub fn spec_tree(mut self) -> Self {
+        self.spec_tree = true;
+        self
+    }
+
+    /// Lets every selector match inside `<template>` elements. Their
+    /// contents aren't rendered, so by default only the selectors saved with
+    /// [`Save::with_template_content`](crate::Save::with_template_content)
+    /// see them, and they don't count for `:has()`.
+    pub fn match_template_contents(mut self) -> Self {
+        self.match_template_contents = true;
+        self
+    }
+
     pub fn next(&mut self, reader: &mut Reader<'html>) -> bool {
-        if self.in_script {
+        self.store.source = reader.source();
+        if self.spec_tree {
+            if !self.eof_drained {
+                self.replay_spec_tree(reader);
+            }
+            return false;
+        }
+
+        if self.text_state != TextState::Data {
             loop {
                 reader.next_until(b'<');
+                let at_end_tag = self.text_state.is_at_end_tag(reader);
+                if (at_end_tag || reader.peek().is_none())
+                    && self.store.text_content.text_start.is_some()
+                {
+                    let end = reader.get_position();
+                    let pushed = match self.text_state {
+                        TextState::RcData(_) => self.store.text_content.push(reader, end),
+                        _ => self.store.text_content.push_raw(reader, end),
+                    };
+                    if let Some(position) = pushed {
+                        self.position.text_content_position = position;
+                    }
+                }
+
                 if reader.peek().is_none() {
                     self.drain_open_elements(reader);
                     return false;
                 }
-
-                if reader.match_ignore_case("</script>") {
-                    if self.store.text_content.text_start.is_some()
-                        && let Some(position) =
-                            self.store.text_content.push(reader, reader.get_position())
-                    {
-                        self.position.text_content_position = position;
-                    }
-                    self.in_script = false;
+                if at_end_tag {
+                    self.text_state = TextState::Data;
                     break;
-                } else {
-                    reader.skip();
                 }
+                reader.skip();
             }
         }
 
-        // move until it finds the first `<`
-        reader.next_until(b'<');
-
-        if reader.peek().is_none() {
-            self.drain_open_elements(reader);
-            return false;
-        }
+        let tag = loop {
+            // move until it finds the first `<`
+            reader.next_until(b'<');
 
-        let tag = {
-            let mut tag: Option<XHtmlTag> = None;
+            if reader.peek().is_none() {
+                self.drain_open_elements(reader);
+                return false;
+            }
 
-            while tag.is_none() {
-                self.position.reader_position = reader.get_position();
-                tag = XHtmlTag::from(reader);
-                if let Some(XHtmlTag::Open) = tag {
-                    self.element.from(reader, &mut self.store.attributes);
-                } else if tag.is_none()
-                    && self.store.text_content.text_start.is_some()
-                    && let Some(position) = self
-                        .store
-                        .text_content
-                        .push(reader, self.position.reader_position)
+            self.position.reader_position = reader.get_position();
+            let allow_cdata = self.element.xml || self.open_elements.in_foreign_content();
+            match Markup::from(reader, allow_cdata) {
+                Some(markup) => self.read_markup(markup, reader),
+                // A `<` that doesn't start a tag is text.
+                None if !reader
+                    .peek_at(1)
+                    .is_some_and(|next| next.is_ascii_alphabetic() || next == b'/') =>
                 {
-                    self.position.text_content_position = position;
-                    self.store.text_content.set_start(reader.get_position());
+                    reader.skip()
                 }
+                None => break XHtmlTag::from(reader),
             }
-
-            tag.unwrap()
         };
+        if tag == XHtmlTag::Open {
+            self.element.from(reader, &mut self.store.attributes);
+        }
         let tag_start_position = self.position.reader_position;
 
         if self.store.text_content.text_start.is_some()
@@ -128,56 +259,26 @@ where
 
         match tag {
             XHtmlTag::Open => {
-                if self.element.name.eq_ignore_ascii_case("script") {
-                    self.in_script = true;
-                }
-
                 self.position.reader_position = tag_start_position;
-                let implied_closes = self.open_elements.prepare_for_open(self.element.name);
+                let mut implied_closes = self
+                    .open_elements
+                    .prepare_for_open(self.element.name, self.element.attributes);
                 self.pop_open_elements(
-                    implied_closes,
+                    &mut implied_closes,
                     reader,
                     Some(ImpliedCloseReason::OpenTagRule),
                     None,
+                    None,
                 );
                 self.position.reader_position = reader.get_position();
-
-                let is_self_closing = self.element.is_self_closing();
-                if is_self_closing {
-                    self.position.element_depth = self.open_elements.depth().saturating_add(1);
-                } else {
-                    self.open_elements.push(self.element.name);
-                    self.position.element_depth = self.open_elements.depth();
-                }
-
-                crate::scah_trace!(
-                    self.store,
-                    TraceEvent::OpenTag {
-                        tag: self.element.name,
-                        depth: self.position.element_depth,
-                        reader_position: self.position.reader_position,
-                        self_closing: is_self_closing,
-                    }
-                );
-
-                let save_hits = self
-                    .selectors
-                    .next(&self.element, &self.position, &mut self.store);
-                if !is_self_closing {
-                    for save_hit in save_hits {
-                        self.open_elements.attach_saved(
-                            save_hit.element_id,
-                            save_hit
-                                .save_inner_html
-                                .then_some(self.position.reader_position),
-                            save_hit
-                                .save_text_content
-                                .then_some(self.position.text_content_position),
-                        );
-                    }
+                self.element.start_tag = tag_start_position..self.position.reader_position;
+                self.element.namespace = self.open_elements.namespace_of(self.element.name);
+                self.element
+                    .adjust_foreign_names(&mut self.store.attributes);
+                if self.element.namespace.is_none() && !self.element.xml {
+                    self.text_state = TextState::after_open_tag(self.element.name);
                 }
-
-                self.element.clear();
+                self.open_element(self.element.is_self_closing());
             }
             XHtmlTag::Close(closing_tag) => {
                 crate::scah_trace!(
@@ -189,23 +290,103 @@ where
                     }
                 );
 
-                let closing_elements = self.open_elements.close_by_end_tag(closing_tag);
+                let mut closing_elements = self.open_elements.close_by_end_tag(closing_tag);
                 early_exit = self.pop_open_elements(
-                    closing_elements,
+                    &mut closing_elements,
                     reader,
                     Some(ImpliedCloseReason::MismatchedEndTag),
                     Some(closing_tag),
+                    Some(reader.get_position()),
                 ) || early_exit;
             }
         }
 
+        if !early_exit && reader.eof() {
+            self.drain_open_elements(reader);
+        }
         !early_exit && !reader.eof()
     }
 
-    pub fn matches(self) -> Store<'html, 'query> {
+    pub fn matches(mut self) -> Store<'html, 'query> {
+        self.store.compact();
         self.store
     }
 
+    /// The state to go on reading from `position` with a source that starts
+    /// there, see [`Self::resume`]. `None` unless `position` is between two
+    /// tokens and no saved element or pending match needs the source before
+    /// it.
+    pub(crate) fn snapshot(&self, position: usize) -> Option<ParserSnapshot<'query, Q>> {
+        let text_start = self.store.text_content.text_start;
+        if self.spec_tree
+            || self.eof_drained
+            || text_start.is_some_and(|start| start != position)
+            || !self.open_elements.is_settled()
+            || !self.selectors.is_settled()
+        {
+            return None;
+        }
+
+        Some(ParserSnapshot {
+            position: DocumentPosition {
+                reader_position: 0,
+                text_content_position: usize::MAX,
+                ..self.position.clone()
+            },
+            selectors: self.selectors.clone(),
+            nth_selectors: self.nth_selectors.clone(),
+            open_elements: self.open_elements.snapshot(),
+            text_state: self.text_state,
+            in_text: text_start.is_some(),
+            xml: self.element.xml,
+            match_template_contents: self.match_template_contents,
+        })
+    }
+
+    /// A parser for the rest of a document, from where `snapshot` was taken.
+    /// Its reader starts at that position, and so do the ranges it records.
+    pub(crate) fn resume(snapshot: &'html ParserSnapshot<'query, Q>) -> Self {
+        let mut store = Store::default();
+        if snapshot.in_text {
+            store.text_content.set_start(0);
+        }
+        Self {
+            position: snapshot.position.clone(),
+            selectors: snapshot.selectors.clone(),
+            store,
+            element: XHtmlElement {
+                xml: snapshot.xml,
+                ..XHtmlElement::default()
+            },
+            open_elements: OpenElementStack::restore(&snapshot.open_elements),
+            nth_selectors: snapshot.nth_selectors.clone(),
+            text_state: snapshot.text_state,
+            eof_drained: false,
+            spec_tree: false,
+            match_template_contents: snapshot.match_template_contents,
+            template_text: Vec::new(),
+        }
+    }
+
+    /// Takes the elements stored so far, leaving the parser an empty store.
+    /// Only between two [`Self::snapshot`]-able tokens, when none of them is
+    /// still open, or once the document has ended.
+    pub(crate) fn take_matches(&mut self) -> Store<'html, 'query> {
+        let mut store = std::mem::take(&mut self.store);
+        self.store.source = store.source;
+        self.store.text_content.text_start = store.text_content.text_start.take();
+        self.position.text_content_position = usize::MAX;
+        // They are on the tape that was taken.
+        self.element.attributes = &[];
+        store
+    }
+
+    /// Whether the end of the source was read as the end of the document,
+    /// closing every open element.
+    pub(crate) fn drained(&self) -> bool {
+        self.eof_drained
+    }
+
     pub fn trace_parse_started(
         &mut self,
         #[cfg_attr(not(any(debug_assertions, test)), allow(unused_variables))] html_len: usize,
@@ -220,9 +401,8 @@ where
         );
     }
 
-    pub fn finish(
-        #[cfg_attr(not(any(debug_assertions, test)), allow(unused_mut))] mut self,
-    ) -> Store<'html, 'query> {
+    pub fn finish(mut self) -> Store<'html, 'query> {
+        self.store.compact();
         crate::scah_trace!(
             self.store,
             TraceEvent::ParseFinished {
@@ -235,13 +415,383 @@ where
         self.store
     }
 
+    /// Steps over a comment or DOCTYPE, keeping the text around it, and adds
+    /// the text of a CDATA section as written.
+    fn read_markup(&mut self, markup: Markup<'html>, reader: &Reader<'html>) {
+        let in_text = self.store.text_content.text_start.is_some();
+        if in_text
+            && let Some(position) = self
+                .store
+                .text_content
+                .push(reader, self.position.reader_position)
+        {
+            self.position.text_content_position = position;
+        }
+
+        match markup {
+            Markup::Comment => {}
+            Markup::CData(text) => {
+                if in_text {
+                    self.store.text_content.set_start(text.start);
+                    if let Some(position) = self.store.text_content.push_raw(reader, text.end) {
+                        self.position.text_content_position = position;
+                    }
+                }
+            }
+            Markup::Doctype(doctype) => {
+                if self.store.doctype.is_none() && self.open_elements.depth() == 0 {
+                    self.store.doctype = Some(doctype);
+                }
+            }
+        }
+
+        if in_text {
+            self.store.text_content.set_start(reader.get_position());
+        }
+    }
+
+    /// Registers the element just read as open, or as opened and closed
+    /// at once, and runs the selectors on it.
+    fn open_element(&mut self, is_self_closing: bool) {
+        self.count_sibling();
+
+        self.element.template_content =
+            !self.match_template_contents && self.open_elements.in_template_content();
+        if !self.element.template_content && self.selectors.is_relational() {
+            self.open_elements.match_relations(|selector| {
+                selector
+                    .iter()
+                    .any(|predicate| predicate.try_match_element(&self.element) == Some(true))
+            });
+        }
+        if is_self_closing {
+            // Nothing can be found inside it.
+            self.element.relations = Some(Vec::new());
+            self.position.element_depth = self.open_elements.depth().saturating_add(1);
+        } else {
+            self.open_elements
+                .push(self.element.name, self.element.namespace);
+            self.position.element_depth = self.open_elements.depth();
+            if self.open_elements.in_template_content() && self.element.is_template() {
+                self.template_text.push((
+                    self.store.text_content.len(),
+                    ElementId::from(self.store.elements.len()),
+                ));
+            }
+        }
+
+        crate::scah_trace!(
+            self.store,
+            TraceEvent::OpenTag {
+                tag: self.element.name,
+                depth: self.position.element_depth,
+                reader_position: self.position.reader_position,
+                self_closing: is_self_closing,
+            }
+        );
+
+        let save_hits = self
+            .selectors
+            .next(&self.element, &self.position, &mut self.store);
+        for (index, save_hit) in save_hits.iter().enumerate() {
+            // Selector lists can report the same element more than once.
+            if save_hits[..index]
+                .iter()
+                .any(|hit| hit.element_id == save_hit.element_id)
+            {
+                continue;
+            }
+            if is_self_closing {
+                // Nothing follows the start tag, so the element ends with it.
+                if save_hit.save_outer_html {
+                    self.store.elements[save_hit.element_id].outer_html =
+                        Some(&self.store.source[self.element.start_tag.clone()]);
+                }
+            } else {
+                if save_hit.save_text_content {
+                    self.store.text_content.readers += 1;
+                }
+                self.open_elements.attach_saved(
+                    save_hit.element_id,
+                    save_hit
+                        .save_inner_html
+                        .then_some(self.position.reader_position),
+                    save_hit.save_outer_html,
+                    save_hit
+                        .save_text_content
+                        .then_some(self.position.text_content_position),
+                );
+            }
+            if let Some(link) = save_hit.link
+                && let Some(predicates) = save_hits
+                    .iter()
+                    .filter(|hit| hit.element_id == save_hit.element_id)
+                    .map(|hit| hit.deferred)
+                    .collect::<Option<Vec<_>>>()
+            {
+                self.open_elements.attach_deferred(
+                    DeferredMatch {
+                        element_id: save_hit.element_id,
+                        link,
+                        namespace: self.element.namespace,
+                        siblings: self.element.siblings.clone(),
+                        relations: self.element.relations.clone(),
+                        predicates,
+                    },
+                    !is_self_closing,
+                );
+            }
+        }
+
+        self.element.clear();
+    }
+
+    /// Builds the document's tree, then opens and closes its elements in
+    /// order, as [`Self::next`] would for a well-formed document.
+    fn replay_spec_tree(&mut self, reader: &mut Reader<'html>) {
+        let tree = TreeBuilder::default().build(reader);
+        if self.store.doctype.is_none() {
+            self.store.doctype = tree.doctype;
+        }
+
+        // Each open node and the index of its next child.
+        let mut open = vec![(DOCUMENT, 0)];
+        while let Some((node, next_child)) = open.last_mut() {
+            let node = *node;
+            let Some(&child) = tree.nodes[node].children.get(*next_child) else {
+                open.pop();
+                if let NodeData::Element(element) = &tree.nodes[node].data
+                    && self.close_tree_element(element, reader)
+                {
+                    return;
+                }
+                continue;
+            };
+            *next_child += 1;
+
+            match &tree.nodes[child].data {
+                NodeData::Text { range, raw } => {
+                    self.store.text_content.set_start(range.start);
+                    let pushed = if *raw {
+                        self.store.text_content.push_raw(reader, range.end)
+                    } else {
+                        self.store.text_content.push(reader, range.end)
+                    };
+                    if let Some(position) = pushed {
+                        self.position.text_content_position = position;
+                    }
+                }
+                NodeData::Element(element) => {
+                    let has_children = !tree.nodes[child].children.is_empty();
+                    if self.open_tree_element(&tree, element, has_children) {
+                        open.push((child, 0));
+                    }
+                }
+                NodeData::Document => unreachable!("the document is the root"),
+            }
+        }
+        self.drain_open_elements(reader);
+    }
+
+    /// Opens an element of the spec tree. Returns whether it stays open
+    /// until its children are read.
+    fn open_tree_element(
+        &mut self,
+        tree: &Tree<'html>,
+        element: &TreeElement<'html>,
+        has_children: bool,
+    ) -> bool {
+        self.element.name = element.name;
+        self.element.id = element.id;
+        self.element.class = element.class;
+        self.element.namespace = element.namespace;
+        self.element.self_closing = element.self_closing;
+        self.element.start_tag = element.start_tag.clone();
+        self.element.copy_attributes(
+            &tree.attributes[element.attributes.clone()],
+            &mut self.store.attributes,
+        );
+        self.element
+            .adjust_foreign_names(&mut self.store.attributes);
+        self.position.reader_position = element.content_start;
+
+        let is_self_closing = !has_children && self.element.is_self_closing();
+        self.open_element(is_self_closing);
+        !is_self_closing
+    }
+
+    /// Closes the current element, which the spec tree closed at
+    /// `element.end`.
+    fn close_tree_element(&mut self, element: &TreeElement<'html>, reader: &Reader<'html>) -> bool {
+        self.position.reader_position = element.end;
+        crate::scah_trace!(
+            self.store,
+            TraceEvent::CloseTag {
+                tag: element.name,
+                depth: self.position.element_depth,
+                reader_position: self.position.reader_position,
+            }
+        );
+        let mut closing_elements = self.open_elements.pop_current();
+        self.pop_open_elements(
+            &mut closing_elements,
+            reader,
+            None,
+            None,
+            Some(element.outer_end),
+        )
+    }
+
+    /// Numbers the element being opened among its siblings, when a selector
+    /// looks at them.
+    fn count_sibling(&mut self) {
+        if !self.selectors.counts_siblings() {
+            return;
+        }
+        self.element.siblings = if self.selectors.is_of_type() {
+            self.open_elements.next_sibling_position(self.element.name)
+        } else {
+            self.open_elements.next_sibling_index()
+        };
+        self.position.sibling_index = self.element.siblings.index;
+
+        // Nested selectors come first, so their counts are already known.
+        for (slot, selector) in self.nth_selectors.iter().enumerate() {
+            if selector
+                .iter()
+                .any(|predicate| predicate.try_match_element(&self.element) == Some(true))
+            {
+                let index = self.open_elements.count_matching(slot);
+                self.element
+                    .siblings
+                    .matching
+                    .push((selector_key(selector), index));
+            }
+        }
+    }
+
+    /// Confirms or drops a deferred match, or gives it back while it still
+    /// can't be decided.
+    fn resolve_deferred(
+        &mut self,
+        deferred: DeferredMatch<'html>,
+        totals: Option<SiblingTotals>,
+    ) -> Option<DeferredMatch<'html>> {
+        let stored = &self.store.elements[deferred.element_id];
+        let element = XHtmlElement {
+            name: stored.name,
+            id: stored.id,
+            class: stored.class,
+            attributes: stored.attributes.as_ref().map_or(&[], |range| {
+                &self.store.attributes.deref()[(range.start as usize)..(range.end as usize)]
+            }),
+            namespace: deferred.namespace,
+            self_closing: false,
+            template_content: false,
+            start_tag: stored.start_tag_range.clone(),
+            xml: self.element.xml,
+            siblings: SiblingPosition {
+                totals,
+                ..deferred.siblings.clone()
+            },
+            relations: deferred.relations.clone(),
+        };
+
+        let mut confirmed = Some(false);
+        for predicate in &deferred.predicates {
+            match predicate.try_match_element(&element) {
+                Some(true) => {
+                    confirmed = Some(true);
+                    break;
+                }
+                Some(false) => {}
+                None => confirmed = None,
+            }
+        }
+        let Some(confirmed) = confirmed else {
+            return Some(deferred);
+        };
+
+        crate::scah_trace!(
+            self.store,
+            TraceEvent::DeferredMatchResolved {
+                element_id: deferred.element_id,
+                tag: self.store.elements[deferred.element_id].name,
+                confirmed,
+            }
+        );
+        if !confirmed {
+            self.store.unlink(deferred.element_id, deferred.link);
+        }
+        None
+    }
+
+    /// Resolves the matches that waited for all of a parent's children to be
+    /// known, latest first so their store links stay valid.
+    fn resolve_children(
+        &mut self,
+        children: &ChildCounters<'html>,
+        deferred: Vec<DeferredMatch<'html>>,
+    ) {
+        for deferred in deferred.into_iter().rev() {
+            let name = self.store.elements[deferred.element_id].name;
+            let totals = children.totals(name, &self.nth_selectors);
+            let undecided = self.resolve_deferred(deferred, Some(totals));
+            debug_assert!(undecided.is_none());
+        }
+    }
+
+    /// Resolves the deferred matches a closed element held, and hands the
+    /// ones still undecided to its `parent`.
+    fn resolve_waiting(
+        &mut self,
+        children: &ChildCounters<'html>,
+        waiting: Waiting<'html>,
+        parent: Option<&mut OpenElement<'html>>,
+    ) {
+        self.resolve_children(children, waiting.deferred);
+
+        // Its descendants are all known now, its later siblings aren't.
+        let relations = waiting
+            .relations
+            .iter()
+            .filter(|relation| relation.found)
+            .map(|relation| selector_key(relation.selector))
+            .collect::<Vec<_>>();
+        let mut undecided = Vec::new();
+        for mut deferred in waiting.pending.into_iter().rev() {
+            deferred.relations = Some(relations.clone());
+            undecided.extend(self.resolve_deferred(deferred, None));
+        }
+        let undecided = undecided.into_iter().rev();
+        match parent {
+            Some(parent) => parent.waiting_mut().deferred.extend(undecided),
+            None => undecided.for_each(|deferred| self.open_elements.attach_to_current(deferred)),
+        }
+    }
+
     fn pop_open_element(
         &mut self,
-        open_element: OpenElement<'html>,
+        open_element: &mut OpenElement<'html>,
+        parent: Option<&mut OpenElement<'html>>,
         close_depth: crate::engine::DepthSize,
+        outer_end: usize,
         reader: &Reader<'html>,
     ) -> bool {
-        self.finalize_open_element(&open_element, reader);
+        if open_element.template_content
+            && open_element.namespace.is_none()
+            && open_element.name.eq_ignore_ascii_case("template")
+            && let Some((start, first)) = self.template_text.pop()
+        {
+            // Its contents aren't part of its ancestors' text.
+            self.store.detach_text(start, first);
+            self.reset_text_content_position();
+        }
+        self.finalize_open_element(open_element, outer_end, reader);
+        if let Some(waiting) = open_element.waiting.take() {
+            self.resolve_waiting(&open_element.children, *waiting, parent);
+        }
+
         self.position.element_depth = close_depth;
         self.selectors
             .back(open_element.name, &self.position, reader, &mut self.store)
@@ -249,16 +799,22 @@ where
 
     fn pop_open_elements(
         &mut self,
-        open_elements: Vec<OpenElement<'html>>,
+        open_elements: &mut [OpenElement<'html>],
         reader: &Reader<'html>,
         implied_close_reason: Option<ImpliedCloseReason>,
         expected_tag: Option<&'html str>,
+        // Where the last of `open_elements` ends, after the end tag closing
+        // it. The others end where they were closed.
+        last_end: Option<usize>,
     ) -> bool {
         let base_depth = self.open_elements.depth();
         let total = open_elements.len();
         let mut early_exit = false;
 
-        for (index, open_element) in open_elements.into_iter().enumerate() {
+        for index in 0..total {
+            // Each element is popped before its parent, which may be next.
+            let (open_element, rest) = open_elements[index..].split_at_mut(1);
+            let open_element = &mut open_element[0];
             let close_depth =
                 base_depth.saturating_add((total - index) as crate::engine::DepthSize);
             if implied_close_reason.is_some_and(|_| {
@@ -274,26 +830,42 @@ where
                     }
                 );
             }
-            early_exit = self.pop_open_element(open_element, close_depth, reader) || early_exit;
+            let outer_end = last_end
+                .filter(|_| index + 1 == total)
+                .unwrap_or(self.position.reader_position);
+            let parent = rest.first_mut();
+            early_exit =
+                self.pop_open_element(open_element, parent, close_depth, outer_end, reader)
+                    || early_exit;
         }
 
         early_exit
     }
 
-    fn finalize_open_element(&mut self, open_element: &OpenElement<'html>, reader: &Reader<'html>) {
+    fn finalize_open_element(
+        &mut self,
+        open_element: &OpenElement<'html>,
+        outer_end: usize,
+        reader: &Reader<'html>,
+    ) {
         for saved in &open_element.saved {
+            let element = &mut self.store.elements[saved.element_id];
+            element.outer_range.end = outer_end;
+            if saved.outer_html {
+                element.outer_html = Some(reader.slice(element.outer_range.clone()));
+            }
             let inner_html = saved
                 .inner_html_start
                 .map(|start_idx| reader.slice(start_idx..self.position.reader_position));
 
             let text_content = saved.text_content_start.and_then(|start_idx| {
+                self.store.text_content.readers -= 1;
+                if self.store.text_content.is_empty() {
+                    return None;
+                }
                 let end = self.store.text_content.get_position();
                 if start_idx == usize::MAX {
-                    if self.store.text_content.is_empty() {
-                        None
-                    } else {
-                        Some(0..end)
-                    }
+                    Some(0..end)
                 } else if start_idx == end {
                     None
                 } else {
@@ -304,6 +876,19 @@ where
             self.store
                 .set_content(saved.element_id, inner_html, text_content);
         }
+        if self.store.text_content.readers == 0 && !self.store.text_content.detached.is_empty() {
+            self.store.reattach_text();
+            self.reset_text_content_position();
+        }
+    }
+
+    /// Points the next element saving its text content at the end of the
+    /// text read so far, after it was moved around.
+    fn reset_text_content_position(&mut self) {
+        self.position.text_content_position = match self.store.text_content.len() {
+            0 => usize::MAX,
+            len => len - 1,
+        };
     }
 
     fn drain_open_elements(&mut self, reader: &Reader<'html>) {
@@ -317,8 +902,16 @@ where
             self.position.text_content_position = position;
         }
         self.position.reader_position = reader.get_position();
-        let remaining = self.open_elements.close_all_at_eof();
-        self.pop_open_elements(remaining, reader, Some(ImpliedCloseReason::EofDrain), None);
+        let mut remaining = self.open_elements.close_all_at_eof();
+        self.pop_open_elements(
+            &mut remaining,
+            reader,
+            Some(ImpliedCloseReason::EofDrain),
+            None,
+            None,
+        );
+        let (children, deferred) = self.open_elements.take_root();
+        self.resolve_children(&children, deferred);
         self.eof_drained = true;
     }
 }
@@ -381,7 +974,7 @@ mod tests {
     fn test_text_content() {
         let mut reader = Reader::new(BASIC_HTML);
 
-        let queries = &[Query::all("p.indent > .bold", Save::none())
+        let queries = &[Query::all("html", Save::only_text_content())
             .unwrap()
             .build()];
         let manager = QueryMultiplexer::new(queries);
@@ -516,7 +1109,7 @@ mod tests {
         assert_eq!(s1_div_a.len(), 1);
         assert_eq!(s1_div_a[0].text_content(&store), Some("World"));
         assert_eq!(
-            s1_div_a[0].attributes(&store).unwrap()[0].value,
+            s1_div_a[0].attributes(&store).unwrap()[0].value.as_deref(),
             Some("https://world.com")
         );
 
@@ -526,7 +1119,9 @@ mod tests {
         assert_eq!(s1_direct_a.len(), 1);
         assert_eq!(s1_direct_a[0].text_content(&store), Some("Hello"));
         assert_eq!(
-            s1_direct_a[0].attributes(&store).unwrap()[0].value,
+            s1_direct_a[0].attributes(&store).unwrap()[0]
+                .value
+                .as_deref(),
             Some("https://hello.com")
         );
 
@@ -625,13 +1220,16 @@ mod tests {
         assert_eq!(inputs[0].name, "input");
         assert_eq!(inputs[0].id, Some("name"));
         assert_eq!(inputs[0].attributes(&store).unwrap()[0].key, "type");
-        assert_eq!(inputs[0].attributes(&store).unwrap()[0].value, Some("text"));
+        assert_eq!(
+            inputs[0].attributes(&store).unwrap()[0].value.as_deref(),
+            Some("text")
+        );
 
         assert_eq!(inputs[1].name, "input");
         assert_eq!(inputs[1].id, Some("mail"));
         assert_eq!(inputs[1].attributes(&store).unwrap()[0].key, "type");
         assert_eq!(
-            inputs[1].attributes(&store).unwrap()[0].value,
+            inputs[1].attributes(&store).unwrap()[0].value.as_deref(),
             Some("email")
         );
     }
@@ -713,9 +1311,12 @@ mod tests {
         let anchor = store.get("div.article a").unwrap().next().unwrap();
 
         assert_eq!(anchor.name, "a");
-        assert_eq!(anchor.attributes(&store).unwrap()[0].value, Some("/post/0"));
+        assert_eq!(
+            anchor.attributes(&store).unwrap()[0].value.as_deref(),
+            Some("/post/0")
+        );
         assert_eq!(anchor.inner_html, Some("<b>Post</b> &lt;0&gt;"));
-        assert_eq!(anchor.text_content(&store), Some("Post &lt;0&gt;"));
+        assert_eq!(anchor.text_content(&store), Some("Post <0>"));
     }
 
     const PYTHON_TEST_HTML: &str = r#"
@@ -754,14 +1355,8 @@ mod tests {
         assert_eq!(
             store.attributes.deref().clone(),
             vec![
-                Attribute {
-                    key: "hello",
-                    value: Some("world")
-                },
-                Attribute {
-                    key: "href",
-                    value: Some("https://www.example.com")
-                },
+                Attribute::new("hello", Some("world")),
+                Attribute::new("href", Some("https://www.example.com")),
             ]
         );
 
@@ -774,10 +1369,7 @@ mod tests {
         assert_eq!(span.id, Some("world"));
         assert_eq!(
             span.attributes(&store).unwrap(),
-            &[Attribute {
-                key: "hello",
-                value: Some("world")
-            },]
+            &[Attribute::new("hello", Some("world")),]
         );
         assert_eq!(
             span.inner_html,
@@ -798,10 +1390,7 @@ mod tests {
         assert_eq!(a.id, None);
         assert_eq!(
             a.attributes(&store).unwrap(),
-            &[Attribute {
-                key: "href",
-                value: Some("https://www.example.com")
-            },]
+            &[Attribute::new("href", Some("https://www.example.com")),]
         );
         assert_eq!(a.inner_html, Some("World"));
         assert!(a.text_content(&store).is_some());
@@ -824,7 +1413,7 @@ mod tests {
         }
 
         let html = generate_html(100);
-        let mut reader = Reader::from_bytes(html.as_bytes());
+        let mut reader = Reader::from_bytes(html.as_bytes()).unwrap();
 
         let query = Query::first("a", Save::all()).unwrap().build();
         assert_eq!(query.exit_at_section_end, Some(crate::QuerySectionId(0)));
@@ -842,14 +1431,11 @@ mod tests {
 
         assert_eq!(
             store.attributes.deref().clone(),
-            vec![Attribute {
-                key: "href",
-                value: Some("/post/0"),
-            }]
+            vec![Attribute::new("href", Some("/post/0"))]
         );
 
         assert_eq!(element.inner_html, Some("<b>Post</b> &lt;0&gt;"));
-        assert_eq!(element.text_content(&store), Some("Post &lt;0&gt;"));
+        assert_eq!(element.text_content(&store), Some("Post <0>"));
     }
 
     #[test]
@@ -1132,16 +1718,7 @@ mod tests {
 
         assert_eq!(
             store.attributes.deref().clone(),
-            vec![
-                Attribute {
-                    key: "src",
-                    value: Some("https://example.com/p1.png")
-                },
-                Attribute {
-                    key: "/",
-                    value: None
-                }
-            ]
+            vec![Attribute::new("src", Some("https://example.com/p1.png")),]
         );
 
         let products_sections: Vec<&Element> = store.get("#products").unwrap().collect();
@@ -1230,22 +1807,8 @@ mod tests {
         assert_eq!(
             store.attributes.deref().clone(),
             vec![
-                Attribute {
-                    key: "src",
-                    value: Some("https://example.com/p1.png")
-                },
-                Attribute {
-                    key: "/",
-                    value: None
-                },
-                Attribute {
-                    key: "src",
-                    value: Some("https://example.com/p2.png")
-                },
-                Attribute {
-                    key: "/",
-                    value: None
-                },
+                Attribute::new("src", Some("https://example.com/p1.png")),
+                Attribute::new("src", Some("https://example.com/p2.png")),
             ]
         );
 
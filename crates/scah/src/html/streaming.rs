@@ -0,0 +1,240 @@
+use super::parser::{ParserSnapshot, XHtmlParser};
+use crate::store::{OwnedStore, SourceWindow};
+use crate::{QueryMultiplexer, QuerySpec, Reader, SourceLocation};
+
+// How much of the document is read between two snapshots, at most, while
+// nothing is open that needs what was read before.
+const SNAPSHOT_INTERVAL: usize = 4 * 1024;
+// The least input gathered before the parser runs on it.
+const MIN_RUN_LEN: usize = 8 * 1024;
+
+/// Parses a document fed to it in chunks, such as the bodies of network
+/// responses, without holding on to all of it.
+///
+/// The chunks are read as UTF-8, with the invalid sequences replaced by
+/// `U+FFFD` as [`String::from_utf8_lossy`] does, and may split the
+/// document anywhere, even in the middle of a character. The parser only
+/// keeps the document from the last point where no saved element was open:
+/// matches are copied out into an [`OwnedStore`] as soon as they are
+/// complete, so the memory used stays bounded by the largest saved element
+/// rather than the document. An element saved with the whole `<html>` keeps
+/// all of it.
+///
+/// The elements' byte ranges are offsets into the whole decoded document.
+///
+/// # Example
+///
+/// ```rust
+/// use scah::{Query, Save, StreamingParser};
+///
+/// let queries = &[Query::all("li", Save::all())
+///     .expect("valid selector")
+///     .build()];
+/// let mut parser = StreamingParser::new(queries);
+/// for chunk in ["<ul><li>O", "ne</li><l", "i>Two</li></ul>"] {
+///     parser.feed(chunk.as_bytes());
+/// }
+/// let store = parser.finish();
+///
+/// let items: Vec<_> = store
+///     .get("li")
+///     .unwrap()
+///     .map(|li| li.text_content(&store).unwrap())
+///     .collect();
+/// assert_eq!(items, ["One", "Two"]);
+/// ```
+pub struct StreamingParser<'query, Q> {
+    // The state to read `window` with.
+    snapshot: ParserSnapshot<'query, Q>,
+    // The document from the last snapshot on.
+    window: String,
+    // Where `window` starts in the document.
+    base: usize,
+    origin: SourceLocation,
+    // The start of a character split by the end of the last chunk.
+    incomplete: Vec<u8>,
+    // The length of `window` the parser runs again at.
+    run_at: usize,
+    store: OwnedStore,
+    // Whether every query is done with the document.
+    done: bool,
+}
+
+impl<'query, Q> StreamingParser<'query, Q>
+where
+    Q: QuerySpec<'query>,
+{
+    pub fn new(queries: &'query [Q]) -> Self {
+        Self::from_parser(XHtmlParser::new(QueryMultiplexer::new(queries)))
+    }
+
+    fn from_parser(parser: XHtmlParser<'_, 'query, Q>) -> Self {
+        Self {
+            snapshot: parser
+                .snapshot(0)
+                .expect("a new parser is between two tokens"),
+            window: String::new(),
+            base: 0,
+            origin: SourceLocation { line: 1, column: 1 },
+            incomplete: Vec::new(),
+            run_at: MIN_RUN_LEN,
+            store: OwnedStore::default(),
+            done: false,
+        }
+    }
+
+    /// Reads the document as XML, see [`XHtmlParser::xml`].
+    pub fn xml(self) -> Self {
+        let parser = XHtmlParser::resume(&self.snapshot).xml();
+        Self::from_parser(parser)
+    }
+
+    /// Lets every selector match inside `<template>` elements, see
+    /// [`XHtmlParser::match_template_contents`].
+    pub fn match_template_contents(self) -> Self {
+        let parser = XHtmlParser::resume(&self.snapshot).match_template_contents();
+        Self::from_parser(parser)
+    }
+
+    /// Whether every query is done with the document, such as a
+    /// [`Query::first`](crate::Query::first) that found its element, so the
+    /// rest of it doesn't need to be fed.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Reads the next chunk of the document.
+    pub fn feed(&mut self, chunk: &[u8]) {
+        if self.done {
+            return;
+        }
+
+        let buffered;
+        let mut bytes = if self.incomplete.is_empty() {
+            chunk
+        } else {
+            self.incomplete.extend_from_slice(chunk);
+            buffered = std::mem::take(&mut self.incomplete);
+            &buffered[..]
+        };
+        loop {
+            match std::str::from_utf8(bytes) {
+                Ok(text) => {
+                    self.window.push_str(text);
+                    break;
+                }
+                Err(error) => {
+                    let (valid, rest) = bytes.split_at(error.valid_up_to());
+                    // SAFETY: `from_utf8` checked the bytes up to the error.
+                    self.window
+                        .push_str(unsafe { std::str::from_utf8_unchecked(valid) });
+                    match error.error_len() {
+                        Some(len) => {
+                            self.window.push(char::REPLACEMENT_CHARACTER);
+                            bytes = &rest[len..];
+                        }
+                        None => {
+                            self.incomplete = rest.to_vec();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        if self.window.len() >= self.run_at {
+            self.run(false);
+        }
+    }
+
+    /// Reads the end of the document, and returns the elements matched in
+    /// it.
+    pub fn finish(mut self) -> OwnedStore {
+        if !self.done {
+            if !self.incomplete.is_empty() {
+                self.window.push(char::REPLACEMENT_CHARACTER);
+            }
+            self.run(true);
+        }
+        self.store
+    }
+
+    /// Parses `window` from the snapshot, copying out the elements matched
+    /// up to the last point a snapshot could be taken at. Unless `is_final`,
+    /// what follows it is read again with the next chunks, as its last token
+    /// may not be whole.
+    fn run(&mut self, is_final: bool) {
+        let window = SourceWindow::new(&self.window, self.base, self.origin);
+        let mut parser = XHtmlParser::resume(&self.snapshot);
+        let mut reader = Reader::new(&self.window);
+        let mut next_snapshot = None;
+        let mut snapshot_position = 0;
+
+        loop {
+            let more = parser.next(&mut reader);
+            let position = reader.get_position();
+            if !is_final && (position >= self.window.len() || parser.drained()) {
+                break;
+            }
+            if !more {
+                self.store.extend_from(&parser.finish(), &window);
+                self.done = true;
+                break;
+            }
+            if position - snapshot_position >= SNAPSHOT_INTERVAL
+                && let Some(snapshot) = parser.snapshot(position)
+            {
+                self.store.extend_from(&parser.take_matches(), &window);
+                next_snapshot = Some(snapshot);
+                snapshot_position = position;
+            }
+        }
+
+        if self.done {
+            self.window = String::new();
+            return;
+        }
+        if let Some(snapshot) = next_snapshot {
+            self.origin = window.location(snapshot_position);
+            self.snapshot = snapshot;
+            self.base += snapshot_position;
+            self.window.drain(..snapshot_position);
+        }
+        self.run_at = MIN_RUN_LEN.max(2 * self.window.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Query, Save};
+
+    #[test]
+    fn test_window_stays_bounded() {
+        let queries = &[
+            Query::all("a", Save::all()).unwrap().build(),
+            Query::all("li + li", Save::all()).unwrap().build(),
+        ];
+        let mut parser = StreamingParser::new(queries);
+        let item = "<ul><li><a href='/post'>Post</a><li>Next</ul>\n";
+        let mut longest = 0;
+        for _ in 0..20_000 {
+            parser.feed(item.as_bytes());
+            longest = longest.max(parser.window.len());
+        }
+        let store = parser.finish();
+
+        assert!(longest < 4 * MIN_RUN_LEN, "window grew to {longest}");
+        assert_eq!(store.get("a").unwrap().count(), 20_000);
+        assert_eq!(store.get("li + li").unwrap().count(), 20_000);
+        let last = store.get("a").unwrap().last().unwrap();
+        assert_eq!(
+            last.location,
+            SourceLocation {
+                line: 20_000,
+                column: 9
+            }
+        );
+        assert_eq!(last.outer_range.start, 19_999 * item.len() + 8);
+    }
+}
@@ -0,0 +1,2459 @@
+use std::ops::Range;
+
+use super::element::builder::{XHtmlElement, XHtmlTag};
+use super::markup::{Doctype, Markup};
+use super::open_elements::{FOREIGN_BREAKOUTS, is_one_of};
+use super::parser::TextState;
+use crate::{Attribute, MATHML_NAMESPACE, Reader, SVG_NAMESPACE};
+
+pub(crate) type NodeId = usize;
+
+/// The document node, parent of the `<html>` element.
+pub(crate) const DOCUMENT: NodeId = 0;
+
+/// A document built with the WHATWG tree construction rules, as a browser
+/// would before any script runs.
+///
+/// Elements keep the source positions they were opened and closed at, so
+/// their inner HTML can still be sliced out of the source. Comments are not
+/// kept.
+#[derive(Debug)]
+pub(crate) struct Tree<'html> {
+    pub nodes: Vec<Node<'html>>,
+    pub attributes: Vec<Attribute<'html>>,
+    pub doctype: Option<Doctype<'html>>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Node<'html> {
+    pub data: NodeData<'html>,
+    pub children: Vec<NodeId>,
+    parent: Option<NodeId>,
+}
+
+#[derive(Debug)]
+pub(crate) enum NodeData<'html> {
+    Document,
+    Element(TreeElement<'html>),
+    /// Text as written, decoded when it is read unless `raw`.
+    Text {
+        range: Range<usize>,
+        raw: bool,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct TreeElement<'html> {
+    pub name: &'html str,
+    // `None` for HTML elements.
+    pub namespace: Option<&'static str>,
+    pub id: Option<&'html str>,
+    pub class: Option<&'html str>,
+    // Range into `Tree::attributes`.
+    pub attributes: Range<usize>,
+    pub self_closing: bool,
+    // Empty where the element was implied.
+    pub start_tag: Range<usize>,
+    // Where the element's content starts: after its start tag, or where it
+    // was implied.
+    pub content_start: usize,
+    // Where it was popped off the stack of open elements.
+    pub end: usize,
+    // `end`, or after the end tag that popped it.
+    pub outer_end: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Tag<'html> {
+    name: &'html str,
+    id: Option<&'html str>,
+    class: Option<&'html str>,
+    attributes: Range<usize>,
+    self_closing: bool,
+    start: usize,
+    end: usize,
+}
+
+impl<'html> Tag<'html> {
+    /// A start tag the tree construction rules insert on their own
+    /// (`<html>`, `<tbody>`, ...).
+    fn implied(name: &'static str, position: usize) -> Self {
+        Self {
+            name,
+            id: None,
+            class: None,
+            attributes: 0..0,
+            self_closing: false,
+            start: position,
+            end: position,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Text {
+    range: Range<usize>,
+    raw: bool,
+    leading_whitespace: usize,
+}
+
+impl Text {
+    fn is_whitespace(&self) -> bool {
+        self.leading_whitespace == self.range.len()
+    }
+
+    /// Splits off the leading whitespace, for the insertion modes that only
+    /// keep whitespace.
+    fn split_whitespace(self) -> (Self, Self) {
+        let middle = self.range.start + self.leading_whitespace;
+        (
+            Self {
+                range: self.range.start..middle,
+                ..self
+            },
+            Self {
+                range: middle..self.range.end,
+                raw: self.raw,
+                leading_whitespace: 0,
+            },
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Token<'html> {
+    StartTag(Tag<'html>),
+    EndTag(&'html str),
+    Text(Text),
+    Doctype(Doctype<'html>),
+    Comment,
+    Eof,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InsertionMode {
+    Initial,
+    BeforeHtml,
+    BeforeHead,
+    InHead,
+    InHeadNoscript,
+    AfterHead,
+    InBody,
+    Text,
+    InTable,
+    InTableText,
+    InCaption,
+    InColumnGroup,
+    InTableBody,
+    InRow,
+    InCell,
+    InSelect,
+    InSelectInTable,
+    InTemplate,
+    AfterBody,
+    InFrameset,
+    AfterFrameset,
+    AfterAfterBody,
+    AfterAfterFrameset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    Default,
+    ListItem,
+    Button,
+    Table,
+    Select,
+}
+
+const WHITESPACE: [u8; 5] = [b' ', b'\t', b'\n', b'\r', b'\x0c'];
+
+const IMPLIED_END_TAGS: [&str; 10] = [
+    "dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc",
+];
+
+const HEADINGS: [&str; 6] = ["h1", "h2", "h3", "h4", "h5", "h6"];
+
+const FORMATTING: [&str; 14] = [
+    "a", "b", "big", "code", "em", "font", "i", "nobr", "s", "small", "strike", "strong", "tt", "u",
+];
+
+const SPECIAL: [&str; 83] = [
+    "address",
+    "applet",
+    "area",
+    "article",
+    "aside",
+    "base",
+    "basefont",
+    "bgsound",
+    "blockquote",
+    "body",
+    "br",
+    "button",
+    "caption",
+    "center",
+    "col",
+    "colgroup",
+    "dd",
+    "details",
+    "dir",
+    "div",
+    "dl",
+    "dt",
+    "embed",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "frame",
+    "frameset",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hgroup",
+    "hr",
+    "html",
+    "iframe",
+    "img",
+    "input",
+    "keygen",
+    "li",
+    "link",
+    "listing",
+    "main",
+    "marquee",
+    "menu",
+    "meta",
+    "nav",
+    "noembed",
+    "noframes",
+    "noscript",
+    "object",
+    "ol",
+    "p",
+    "param",
+    "plaintext",
+    "pre",
+    "script",
+    "search",
+    "section",
+    "select",
+    "source",
+    "style",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "template",
+    "textarea",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "track",
+    "ul",
+    "wbr",
+    "xmp",
+];
+
+const MATHML_TEXT_INTEGRATION_POINTS: [&str; 5] = ["mi", "mo", "mn", "ms", "mtext"];
+
+const SVG_HTML_INTEGRATION_POINTS: [&str; 3] = ["foreignobject", "desc", "title"];
+
+/// Builds a [`Tree`] out of a whole document, following the WHATWG tree
+/// construction stage with scripting disabled.
+pub(crate) struct TreeBuilder<'html> {
+    tree: Tree<'html>,
+    mode: InsertionMode,
+    original_mode: InsertionMode,
+    template_modes: Vec<InsertionMode>,
+    open: Vec<NodeId>,
+    // `None` is a marker.
+    formatting: Vec<Option<NodeId>>,
+    head: Option<NodeId>,
+    form: Option<NodeId>,
+    frameset_ok: bool,
+    foster_parenting: bool,
+    quirks: bool,
+    ignore_line_feed: bool,
+    pending_table_text: Vec<Text>,
+    text_state: TextState,
+    // Start of the token being processed.
+    position: usize,
+    // The end tag being processed, and where it ends.
+    end_tag: Option<(&'html str, usize)>,
+}
+
+impl<'html> Default for TreeBuilder<'html> {
+    fn default() -> Self {
+        Self {
+            tree: Tree {
+                nodes: vec![Node {
+                    data: NodeData::Document,
+                    children: Vec::new(),
+                    parent: None,
+                }],
+                attributes: Vec::new(),
+                doctype: None,
+            },
+            mode: InsertionMode::Initial,
+            original_mode: InsertionMode::Initial,
+            template_modes: Vec::new(),
+            open: Vec::new(),
+            formatting: Vec::new(),
+            head: None,
+            form: None,
+            frameset_ok: true,
+            foster_parenting: false,
+            quirks: false,
+            ignore_line_feed: false,
+            pending_table_text: Vec::new(),
+            text_state: TextState::Data,
+            position: 0,
+            end_tag: None,
+        }
+    }
+}
+
+impl<'html> TreeBuilder<'html> {
+    pub fn build(mut self, reader: &mut Reader<'html>) -> Tree<'html> {
+        loop {
+            let token = self.next_token(reader);
+            self.end_tag = match token {
+                Token::EndTag(name) => Some((name, reader.get_position())),
+                _ => None,
+            };
+            let eof = matches!(token, Token::Eof);
+            self.dispatch(token);
+            if eof {
+                break;
+            }
+        }
+
+        self.position = reader.get_position();
+        while self.pop().is_some() {}
+        self.tree
+    }
+
+    fn next_token(&mut self, reader: &mut Reader<'html>) -> Token<'html> {
+        let ignore_line_feed = std::mem::take(&mut self.ignore_line_feed);
+        let mut start = reader.get_position();
+        if ignore_line_feed && reader.peek() == Some(b'\n') {
+            reader.skip();
+            start += 1;
+        }
+        self.position = start;
+
+        let raw = match self.text_state {
+            TextState::Data => false,
+            state => {
+                loop {
+                    reader.next_until(b'<');
+                    if reader.peek().is_none() || state.is_at_end_tag(reader) {
+                        break;
+                    }
+                    reader.skip();
+                }
+                self.text_state = TextState::Data;
+                !matches!(state, TextState::RcData(_))
+            }
+        };
+        if !raw {
+            // A `<` that doesn't start a tag nor markup is text.
+            loop {
+                reader.next_until(b'<');
+                if reader.peek().is_none()
+                    || reader.peek_at(1).is_some_and(|next| {
+                        matches!(next, b'!' | b'?' | b'/') || next.is_ascii_alphabetic()
+                    })
+                {
+                    break;
+                }
+                reader.skip();
+            }
+        }
+        if reader.get_position() > start {
+            return Token::Text(Self::text(reader, start..reader.get_position(), raw));
+        }
+        if reader.peek().is_none() {
+            return Token::Eof;
+        }
+
+        let allow_cdata = self
+            .adjusted_current_element()
+            .is_some_and(|element| element.namespace.is_some());
+        if let Some(markup) = Markup::from(reader, allow_cdata) {
+            return match markup {
+                Markup::Comment => Token::Comment,
+                Markup::CData(range) => Token::Text(Self::text(reader, range, true)),
+                Markup::Doctype(doctype) => Token::Doctype(doctype),
+            };
+        }
+        let token = match XHtmlTag::from(reader) {
+            // `</>` is dropped like a comment.
+            XHtmlTag::Close("") => Token::Comment,
+            XHtmlTag::Close(name) => Token::EndTag(name),
+            XHtmlTag::Open => {
+                let start_len = self.tree.attributes.len();
+                let mut element = XHtmlElement::default();
+                element.from(reader, &mut self.tree.attributes);
+                Token::StartTag(Tag {
+                    name: element.name,
+                    id: element.id,
+                    class: element.class,
+                    attributes: start_len..self.tree.attributes.len(),
+                    self_closing: element.self_closing,
+                    start: self.position,
+                    end: reader.get_position(),
+                })
+            }
+        };
+        // A tag the document ends in is dropped.
+        if reader.peek().is_none() && !reader.slice(start..reader.get_position()).ends_with('>') {
+            return Token::Eof;
+        }
+        token
+    }
+
+    fn text(reader: &Reader<'html>, range: Range<usize>, raw: bool) -> Text {
+        let leading_whitespace = reader
+            .slice(range.clone())
+            .bytes()
+            .take_while(|byte| WHITESPACE.contains(byte))
+            .count();
+        Text {
+            range,
+            raw,
+            leading_whitespace,
+        }
+    }
+
+    /// The tree construction dispatcher: tokens inside SVG and MathML follow
+    /// the foreign content rules, except at integration points.
+    fn dispatch(&mut self, token: Token<'html>) {
+        let Some(current) = self.open.last().copied() else {
+            return self.process(self.mode, token);
+        };
+        let element = self.element(current);
+        let foreign = match element.namespace {
+            None => false,
+            Some(namespace) => match &token {
+                Token::Eof => false,
+                Token::StartTag(tag) => {
+                    if namespace == MATHML_NAMESPACE {
+                        let text_integration =
+                            is_one_of(element.name, &MATHML_TEXT_INTEGRATION_POINTS)
+                                && !is_one_of(tag.name, &["mglyph", "malignmark"]);
+                        let svg_in_annotation = element.name.eq_ignore_ascii_case("annotation-xml")
+                            && tag.name.eq_ignore_ascii_case("svg");
+                        !(text_integration
+                            || svg_in_annotation
+                            || self.is_html_integration_point(current))
+                    } else {
+                        !self.is_html_integration_point(current)
+                    }
+                }
+                Token::Text(_) => {
+                    !(self.is_html_integration_point(current)
+                        || namespace == MATHML_NAMESPACE
+                            && is_one_of(element.name, &MATHML_TEXT_INTEGRATION_POINTS))
+                }
+                _ => true,
+            },
+        };
+
+        if foreign {
+            self.process_foreign(token);
+        } else {
+            self.process(self.mode, token);
+        }
+    }
+
+    fn process(&mut self, mode: InsertionMode, token: Token<'html>) {
+        // Modes that only keep whitespace see the rest of a text separately.
+        if let Token::Text(text) = &token
+            && text.leading_whitespace > 0
+            && !text.is_whitespace()
+            && matches!(
+                mode,
+                InsertionMode::Initial
+                    | InsertionMode::BeforeHtml
+                    | InsertionMode::BeforeHead
+                    | InsertionMode::InHead
+                    | InsertionMode::InHeadNoscript
+                    | InsertionMode::AfterHead
+                    | InsertionMode::InColumnGroup
+                    | InsertionMode::InFrameset
+                    | InsertionMode::AfterFrameset
+                    | InsertionMode::AfterBody
+                    | InsertionMode::AfterAfterBody
+                    | InsertionMode::AfterAfterFrameset
+            )
+        {
+            let Token::Text(text) = token else {
+                unreachable!()
+            };
+            let (whitespace, rest) = text.split_whitespace();
+            self.process(mode, Token::Text(whitespace));
+            return self.process(self.mode, Token::Text(rest));
+        }
+
+        match mode {
+            InsertionMode::Initial => self.initial(token),
+            InsertionMode::BeforeHtml => self.before_html(token),
+            InsertionMode::BeforeHead => self.before_head(token),
+            InsertionMode::InHead => self.in_head(token),
+            InsertionMode::InHeadNoscript => self.in_head_noscript(token),
+            InsertionMode::AfterHead => self.after_head(token),
+            InsertionMode::InBody => self.in_body(token),
+            InsertionMode::Text => self.in_text(token),
+            InsertionMode::InTable => self.in_table(token),
+            InsertionMode::InTableText => self.in_table_text(token),
+            InsertionMode::InCaption => self.in_caption(token),
+            InsertionMode::InColumnGroup => self.in_column_group(token),
+            InsertionMode::InTableBody => self.in_table_body(token),
+            InsertionMode::InRow => self.in_row(token),
+            InsertionMode::InCell => self.in_cell(token),
+            InsertionMode::InSelect => self.in_select(token),
+            InsertionMode::InSelectInTable => self.in_select_in_table(token),
+            InsertionMode::InTemplate => self.in_template(token),
+            InsertionMode::AfterBody => self.after_body(token),
+            InsertionMode::InFrameset => self.in_frameset(token),
+            InsertionMode::AfterFrameset => self.after_frameset(token),
+            InsertionMode::AfterAfterBody => self.after_after_body(token),
+            InsertionMode::AfterAfterFrameset => self.after_after_frameset(token),
+        }
+    }
+
+    fn reprocess(&mut self, token: Token<'html>) {
+        self.process(self.mode, token);
+    }
+
+    fn initial(&mut self, token: Token<'html>) {
+        match token {
+            Token::Text(text) if text.is_whitespace() => {}
+            Token::Comment => {}
+            Token::Doctype(doctype) => {
+                self.quirks = is_quirky(&doctype);
+                self.tree.doctype = Some(doctype);
+                self.mode = InsertionMode::BeforeHtml;
+            }
+            token => {
+                self.quirks = true;
+                self.mode = InsertionMode::BeforeHtml;
+                self.reprocess(token);
+            }
+        }
+    }
+
+    fn before_html(&mut self, token: Token<'html>) {
+        match token {
+            Token::Doctype(_) | Token::Comment => {}
+            Token::Text(text) if text.is_whitespace() => {}
+            Token::StartTag(tag) if is(&tag, &["html"]) => {
+                self.insert_html(&tag);
+                self.mode = InsertionMode::BeforeHead;
+            }
+            Token::EndTag(name) if !is_one_of(name, &["head", "body", "html", "br"]) => {}
+            token => {
+                self.insert_html(&Tag::implied("html", self.position));
+                self.mode = InsertionMode::BeforeHead;
+                self.reprocess(token);
+            }
+        }
+    }
+
+    fn before_head(&mut self, token: Token<'html>) {
+        match token {
+            Token::Text(text) if text.is_whitespace() => {}
+            Token::Doctype(_) | Token::Comment => {}
+            Token::StartTag(tag) if is(&tag, &["html"]) => self.in_body(Token::StartTag(tag)),
+            Token::StartTag(tag) if is(&tag, &["head"]) => {
+                self.head = Some(self.insert_html(&tag));
+                self.mode = InsertionMode::InHead;
+            }
+            Token::EndTag(name) if !is_one_of(name, &["head", "body", "html", "br"]) => {}
+            token => {
+                self.head = Some(self.insert_html(&Tag::implied("head", self.position)));
+                self.mode = InsertionMode::InHead;
+                self.reprocess(token);
+            }
+        }
+    }
+
+    fn in_head(&mut self, token: Token<'html>) {
+        match token {
+            Token::Text(text) if text.is_whitespace() => self.insert_text(text),
+            Token::Doctype(_) | Token::Comment => {}
+            Token::StartTag(tag) if is(&tag, &["html"]) => self.in_body(Token::StartTag(tag)),
+            Token::StartTag(tag) if is(&tag, &["base", "basefont", "bgsound", "link", "meta"]) => {
+                self.insert_html(&tag);
+                self.pop();
+            }
+            Token::StartTag(tag) if is(&tag, &["title", "noframes", "style", "script"]) => {
+                self.insert_text_element(&tag)
+            }
+            Token::StartTag(tag) if is(&tag, &["noscript"]) => {
+                self.insert_html(&tag);
+                self.mode = InsertionMode::InHeadNoscript;
+            }
+            Token::StartTag(tag) if is(&tag, &["template"]) => {
+                self.insert_html(&tag);
+                self.formatting.push(None);
+                self.frameset_ok = false;
+                self.mode = InsertionMode::InTemplate;
+                self.template_modes.push(InsertionMode::InTemplate);
+            }
+            Token::EndTag(name) if is_one_of(name, &["head"]) => {
+                self.pop();
+                self.mode = InsertionMode::AfterHead;
+            }
+            Token::EndTag(name) if is_one_of(name, &["template"]) => {
+                if !self.open.iter().any(|&id| self.is_html(id, &["template"])) {
+                    return;
+                }
+                self.generate_all_implied_end_tags();
+                self.pop_until(&["template"]);
+                self.clear_formatting_to_last_marker();
+                self.template_modes.pop();
+                self.reset_insertion_mode();
+            }
+            Token::StartTag(tag) if is(&tag, &["head"]) => {}
+            Token::EndTag(name) if !is_one_of(name, &["body", "html", "br"]) => {}
+            token => {
+                self.pop();
+                self.mode = InsertionMode::AfterHead;
+                self.reprocess(token);
+            }
+        }
+    }
+
+    fn in_head_noscript(&mut self, token: Token<'html>) {
+        match token {
+            Token::Doctype(_) => {}
+            Token::StartTag(tag) if is(&tag, &["html"]) => self.in_body(Token::StartTag(tag)),
+            Token::EndTag(name) if is_one_of(name, &["noscript"]) => {
+                self.pop();
+                self.mode = InsertionMode::InHead;
+            }
+            Token::Text(text) if text.is_whitespace() => self.in_head(Token::Text(text)),
+            Token::Comment => {}
+            Token::StartTag(tag)
+                if is(
+                    &tag,
+                    &["basefont", "bgsound", "link", "meta", "noframes", "style"],
+                ) =>
+            {
+                self.in_head(Token::StartTag(tag))
+            }
+            Token::StartTag(tag) if is(&tag, &["head", "noscript"]) => {}
+            Token::EndTag(name) if !is_one_of(name, &["br"]) => {}
+            token => {
+                self.pop();
+                self.mode = InsertionMode::InHead;
+                self.reprocess(token);
+            }
+        }
+    }
+
+    fn after_head(&mut self, token: Token<'html>) {
+        match token {
+            Token::Text(text) if text.is_whitespace() => self.insert_text(text),
+            Token::Doctype(_) | Token::Comment => {}
+            Token::StartTag(tag) if is(&tag, &["html"]) => self.in_body(Token::StartTag(tag)),
+            Token::StartTag(tag) if is(&tag, &["body"]) => {
+                self.insert_html(&tag);
+                self.frameset_ok = false;
+                self.mode = InsertionMode::InBody;
+            }
+            Token::StartTag(tag) if is(&tag, &["frameset"]) => {
+                self.insert_html(&tag);
+                self.mode = InsertionMode::InFrameset;
+            }
+            Token::StartTag(tag)
+                if is(
+                    &tag,
+                    &[
+                        "base", "basefont", "bgsound", "link", "meta", "noframes", "script",
+                        "style", "template", "title",
+                    ],
+                ) =>
+            {
+                let Some(head) = self.head else {
+                    return;
+                };
+                self.open.push(head);
+                self.in_head(Token::StartTag(tag));
+                if let Some(index) = self.open.iter().position(|&id| id == head) {
+                    self.open.remove(index);
+                }
+            }
+            Token::EndTag(name) if is_one_of(name, &["template"]) => {
+                self.in_head(Token::EndTag(name))
+            }
+            Token::StartTag(tag) if is(&tag, &["head"]) => {}
+            Token::EndTag(name) if !is_one_of(name, &["body", "html", "br"]) => {}
+            token => {
+                self.insert_html(&Tag::implied("body", self.position));
+                self.mode = InsertionMode::InBody;
+                self.reprocess(token);
+            }
+        }
+    }
+
+    fn in_body(&mut self, token: Token<'html>) {
+        match token {
+            Token::Text(text) => {
+                self.reconstruct_formatting();
+                if !text.is_whitespace() {
+                    self.frameset_ok = false;
+                }
+                self.insert_text(text);
+            }
+            Token::Comment | Token::Doctype(_) => {}
+            Token::Eof => {
+                if !self.template_modes.is_empty() {
+                    self.in_template(Token::Eof);
+                }
+            }
+            Token::StartTag(tag) => self.in_body_start_tag(tag),
+            Token::EndTag(name) => self.in_body_end_tag(name),
+        }
+    }
+
+    fn in_body_start_tag(&mut self, tag: Tag<'html>) {
+        if is(&tag, &["html"]) {
+            // Its attributes would be added to the `<html>` element, which
+            // the tree doesn't keep track of.
+        } else if is(
+            &tag,
+            &[
+                "base", "basefont", "bgsound", "link", "meta", "noframes", "script", "style",
+                "template", "title",
+            ],
+        ) {
+            self.in_head(Token::StartTag(tag));
+        } else if is(&tag, &["body"]) {
+            if self.open.len() > 1
+                && self.is_html(self.open[1], &["body"])
+                && !self.has_open(&["template"])
+            {
+                self.frameset_ok = false;
+            }
+        } else if is(&tag, &["frameset"]) {
+            if self.open.len() < 2 || !self.is_html(self.open[1], &["body"]) || !self.frameset_ok {
+                return;
+            }
+            self.detach(self.open[1]);
+            while self.open.len() > 1 {
+                self.pop();
+            }
+            self.insert_html(&tag);
+            self.mode = InsertionMode::InFrameset;
+        } else if is(
+            &tag,
+            &[
+                "address",
+                "article",
+                "aside",
+                "blockquote",
+                "center",
+                "details",
+                "dialog",
+                "dir",
+                "div",
+                "dl",
+                "fieldset",
+                "figcaption",
+                "figure",
+                "footer",
+                "header",
+                "hgroup",
+                "main",
+                "menu",
+                "nav",
+                "ol",
+                "p",
+                "search",
+                "section",
+                "summary",
+                "ul",
+            ],
+        ) {
+            self.close_p_in_button_scope();
+            self.insert_html(&tag);
+        } else if is(&tag, &HEADINGS) {
+            self.close_p_in_button_scope();
+            if self.current_is(&HEADINGS) {
+                self.pop();
+            }
+            self.insert_html(&tag);
+        } else if is(&tag, &["pre", "listing"]) {
+            self.close_p_in_button_scope();
+            self.insert_html(&tag);
+            self.ignore_line_feed = true;
+            self.frameset_ok = false;
+        } else if is(&tag, &["form"]) {
+            let in_template = self.has_open(&["template"]);
+            if self.form.is_some() && !in_template {
+                return;
+            }
+            self.close_p_in_button_scope();
+            let form = self.insert_html(&tag);
+            if !in_template {
+                self.form = Some(form);
+            }
+        } else if is(&tag, &["li"]) || is(&tag, &["dd", "dt"]) {
+            self.frameset_ok = false;
+            let names: &[&str] = if is(&tag, &["li"]) {
+                &["li"]
+            } else {
+                &["dd", "dt"]
+            };
+            for index in (0..self.open.len()).rev() {
+                let node = self.open[index];
+                if self.is_html(node, names) {
+                    let name = self.element(node).name;
+                    self.generate_implied_end_tags(name);
+                    self.pop_until_node(node);
+                    break;
+                }
+                if self.is_special(node) && !self.is_html(node, &["address", "div", "p"]) {
+                    break;
+                }
+            }
+            self.close_p_in_button_scope();
+            self.insert_html(&tag);
+        } else if is(&tag, &["plaintext"]) {
+            self.close_p_in_button_scope();
+            self.insert_html(&tag);
+            self.text_state = TextState::PlainText;
+        } else if is(&tag, &["button"]) {
+            if self.in_scope(&["button"], Scope::Default) {
+                self.generate_implied_end_tags("");
+                self.pop_until(&["button"]);
+            }
+            self.reconstruct_formatting();
+            self.insert_html(&tag);
+            self.frameset_ok = false;
+        } else if is(&tag, &["a"]) {
+            let open_a = self.formatting[self.last_marker()..]
+                .iter()
+                .flatten()
+                .copied()
+                .find(|&id| self.is_html(id, &["a"]));
+            if let Some(open_a) = open_a {
+                self.adoption_agency("a");
+                self.remove_formatting(open_a);
+                if let Some(index) = self.open.iter().position(|&id| id == open_a) {
+                    self.open.remove(index);
+                    self.close(open_a);
+                }
+            }
+            self.reconstruct_formatting();
+            let element = self.insert_html(&tag);
+            self.push_formatting(element);
+        } else if is(&tag, &["nobr"]) {
+            self.reconstruct_formatting();
+            if self.in_scope(&["nobr"], Scope::Default) {
+                self.adoption_agency("nobr");
+                self.reconstruct_formatting();
+            }
+            let element = self.insert_html(&tag);
+            self.push_formatting(element);
+        } else if is(&tag, &FORMATTING) {
+            self.reconstruct_formatting();
+            let element = self.insert_html(&tag);
+            self.push_formatting(element);
+        } else if is(&tag, &["applet", "marquee", "object"]) {
+            self.reconstruct_formatting();
+            self.insert_html(&tag);
+            self.formatting.push(None);
+            self.frameset_ok = false;
+        } else if is(&tag, &["table"]) {
+            if !self.quirks {
+                self.close_p_in_button_scope();
+            }
+            self.insert_html(&tag);
+            self.frameset_ok = false;
+            self.mode = InsertionMode::InTable;
+        } else if is(&tag, &["area", "br", "embed", "img", "keygen", "wbr"]) {
+            self.reconstruct_formatting();
+            self.insert_html(&tag);
+            self.pop();
+            self.frameset_ok = false;
+        } else if is(&tag, &["input"]) {
+            self.reconstruct_formatting();
+            self.insert_html(&tag);
+            self.pop();
+            if !self.is_hidden_input(&tag) {
+                self.frameset_ok = false;
+            }
+        } else if is(&tag, &["param", "source", "track"]) {
+            self.insert_html(&tag);
+            self.pop();
+        } else if is(&tag, &["hr"]) {
+            self.close_p_in_button_scope();
+            self.insert_html(&tag);
+            self.pop();
+            self.frameset_ok = false;
+        } else if is(&tag, &["image"]) {
+            self.in_body_start_tag(Tag { name: "img", ..tag });
+        } else if is(&tag, &["textarea"]) {
+            self.insert_text_element(&tag);
+            self.ignore_line_feed = true;
+            self.frameset_ok = false;
+        } else if is(&tag, &["xmp"]) {
+            self.close_p_in_button_scope();
+            self.reconstruct_formatting();
+            self.frameset_ok = false;
+            self.insert_text_element(&tag);
+        } else if is(&tag, &["iframe"]) {
+            self.frameset_ok = false;
+            self.insert_text_element(&tag);
+        } else if is(&tag, &["noembed"]) {
+            self.insert_text_element(&tag);
+        } else if is(&tag, &["select"]) {
+            self.reconstruct_formatting();
+            self.insert_html(&tag);
+            self.frameset_ok = false;
+            self.mode = match self.mode {
+                InsertionMode::InTable
+                | InsertionMode::InCaption
+                | InsertionMode::InTableBody
+                | InsertionMode::InRow
+                | InsertionMode::InCell => InsertionMode::InSelectInTable,
+                _ => InsertionMode::InSelect,
+            };
+        } else if is(&tag, &["optgroup", "option"]) {
+            if self.current_is(&["option"]) {
+                self.pop();
+            }
+            self.reconstruct_formatting();
+            self.insert_html(&tag);
+        } else if is(&tag, &["rb", "rtc"]) {
+            if self.in_scope(&["ruby"], Scope::Default) {
+                self.generate_implied_end_tags("");
+            }
+            self.insert_html(&tag);
+        } else if is(&tag, &["rp", "rt"]) {
+            if self.in_scope(&["ruby"], Scope::Default) {
+                self.generate_implied_end_tags("rtc");
+            }
+            self.insert_html(&tag);
+        } else if is(&tag, &["math"]) || is(&tag, &["svg"]) {
+            self.reconstruct_formatting();
+            let namespace = if is(&tag, &["math"]) {
+                MATHML_NAMESPACE
+            } else {
+                SVG_NAMESPACE
+            };
+            self.insert_element(&tag, Some(namespace));
+            if tag.self_closing {
+                self.pop();
+            }
+        } else if is(
+            &tag,
+            &[
+                "caption", "col", "colgroup", "frame", "head", "tbody", "td", "tfoot", "th",
+                "thead", "tr",
+            ],
+        ) {
+        } else {
+            self.reconstruct_formatting();
+            self.insert_html(&tag);
+        }
+    }
+
+    fn in_body_end_tag(&mut self, name: &'html str) {
+        if is_one_of(name, &["template"]) {
+            self.in_head(Token::EndTag(name));
+        } else if is_one_of(name, &["body", "html"]) {
+            if !self.in_scope(&["body"], Scope::Default) {
+                return;
+            }
+            self.mode = InsertionMode::AfterBody;
+            if is_one_of(name, &["html"]) {
+                self.reprocess(Token::EndTag(name));
+            }
+        } else if is_one_of(
+            name,
+            &[
+                "address",
+                "article",
+                "aside",
+                "blockquote",
+                "button",
+                "center",
+                "details",
+                "dialog",
+                "dir",
+                "div",
+                "dl",
+                "fieldset",
+                "figcaption",
+                "figure",
+                "footer",
+                "header",
+                "hgroup",
+                "listing",
+                "main",
+                "menu",
+                "nav",
+                "ol",
+                "pre",
+                "search",
+                "section",
+                "summary",
+                "ul",
+            ],
+        ) {
+            if !self.in_scope(&[name], Scope::Default) {
+                return;
+            }
+            self.generate_implied_end_tags("");
+            self.pop_until(&[name]);
+        } else if is_one_of(name, &["form"]) {
+            if self.has_open(&["template"]) {
+                if !self.in_scope(&["form"], Scope::Default) {
+                    return;
+                }
+                self.generate_implied_end_tags("");
+                self.pop_until(&["form"]);
+                return;
+            }
+            let Some(form) = self.form.take() else {
+                return;
+            };
+            if !self.node_in_scope(form, Scope::Default) {
+                return;
+            }
+            self.generate_implied_end_tags("");
+            if let Some(index) = self.open.iter().position(|&id| id == form) {
+                self.open.remove(index);
+                self.close(form);
+            }
+        } else if is_one_of(name, &["p"]) {
+            if !self.in_scope(&["p"], Scope::Button) {
+                self.insert_html(&Tag::implied("p", self.position));
+            }
+            self.close_p();
+        } else if is_one_of(name, &["li"]) {
+            if !self.in_scope(&["li"], Scope::ListItem) {
+                return;
+            }
+            self.generate_implied_end_tags("li");
+            self.pop_until(&["li"]);
+        } else if is_one_of(name, &["dd", "dt"]) {
+            if !self.in_scope(&[name], Scope::Default) {
+                return;
+            }
+            self.generate_implied_end_tags(name);
+            self.pop_until(&[name]);
+        } else if is_one_of(name, &HEADINGS) {
+            if !self.in_scope(&HEADINGS, Scope::Default) {
+                return;
+            }
+            self.generate_implied_end_tags("");
+            self.pop_until(&HEADINGS);
+        } else if is_one_of(name, &FORMATTING) {
+            if !self.adoption_agency(name) {
+                self.any_other_end_tag(name);
+            }
+        } else if is_one_of(name, &["applet", "marquee", "object"]) {
+            if !self.in_scope(&[name], Scope::Default) {
+                return;
+            }
+            self.generate_implied_end_tags("");
+            self.pop_until(&[name]);
+            self.clear_formatting_to_last_marker();
+        } else if is_one_of(name, &["br"]) {
+            self.in_body_start_tag(Tag::implied("br", self.position));
+        } else {
+            self.any_other_end_tag(name);
+        }
+    }
+
+    fn any_other_end_tag(&mut self, name: &str) {
+        for index in (0..self.open.len()).rev() {
+            let node = self.open[index];
+            if self.is_html(node, &[name]) {
+                self.generate_implied_end_tags(name);
+                self.pop_until_node(node);
+                return;
+            }
+            if self.is_special(node) {
+                return;
+            }
+        }
+    }
+
+    fn in_text(&mut self, token: Token<'html>) {
+        match token {
+            Token::Text(text) => self.insert_text(text),
+            Token::Eof => {
+                self.pop();
+                self.mode = self.original_mode;
+                self.reprocess(Token::Eof);
+            }
+            _ => {
+                self.pop();
+                self.mode = self.original_mode;
+            }
+        }
+    }
+
+    fn in_table(&mut self, token: Token<'html>) {
+        match token {
+            Token::Text(text)
+                if self.current_is(&["table", "tbody", "template", "tfoot", "thead", "tr"]) =>
+            {
+                self.pending_table_text.clear();
+                self.original_mode = self.mode;
+                self.mode = InsertionMode::InTableText;
+                self.reprocess(Token::Text(text));
+            }
+            Token::Comment | Token::Doctype(_) => {}
+            Token::StartTag(tag) if is(&tag, &["caption"]) => {
+                self.clear_to_context(&["table", "template", "html"]);
+                self.formatting.push(None);
+                self.insert_html(&tag);
+                self.mode = InsertionMode::InCaption;
+            }
+            Token::StartTag(tag) if is(&tag, &["colgroup"]) => {
+                self.clear_to_context(&["table", "template", "html"]);
+                self.insert_html(&tag);
+                self.mode = InsertionMode::InColumnGroup;
+            }
+            Token::StartTag(tag) if is(&tag, &["col"]) => {
+                self.clear_to_context(&["table", "template", "html"]);
+                self.insert_html(&Tag::implied("colgroup", self.position));
+                self.mode = InsertionMode::InColumnGroup;
+                self.reprocess(Token::StartTag(tag));
+            }
+            Token::StartTag(tag) if is(&tag, &["tbody", "tfoot", "thead"]) => {
+                self.clear_to_context(&["table", "template", "html"]);
+                self.insert_html(&tag);
+                self.mode = InsertionMode::InTableBody;
+            }
+            Token::StartTag(tag) if is(&tag, &["td", "th", "tr"]) => {
+                self.clear_to_context(&["table", "template", "html"]);
+                self.insert_html(&Tag::implied("tbody", self.position));
+                self.mode = InsertionMode::InTableBody;
+                self.reprocess(Token::StartTag(tag));
+            }
+            Token::StartTag(tag) if is(&tag, &["table"]) => {
+                if !self.in_scope(&["table"], Scope::Table) {
+                    return;
+                }
+                self.pop_until(&["table"]);
+                self.reset_insertion_mode();
+                self.reprocess(Token::StartTag(tag));
+            }
+            Token::EndTag(name) if is_one_of(name, &["table"]) => {
+                if !self.in_scope(&["table"], Scope::Table) {
+                    return;
+                }
+                self.pop_until(&["table"]);
+                self.reset_insertion_mode();
+            }
+            Token::EndTag(name)
+                if is_one_of(
+                    name,
+                    &[
+                        "body", "caption", "col", "colgroup", "html", "tbody", "td", "tfoot", "th",
+                        "thead", "tr",
+                    ],
+                ) => {}
+            Token::StartTag(tag) if is(&tag, &["style", "script", "template"]) => {
+                self.in_head(Token::StartTag(tag))
+            }
+            Token::EndTag(name) if is_one_of(name, &["template"]) => {
+                self.in_head(Token::EndTag(name))
+            }
+            Token::StartTag(tag) if is(&tag, &["input"]) && self.is_hidden_input(&tag) => {
+                self.insert_html(&tag);
+                self.pop();
+            }
+            Token::StartTag(tag) if is(&tag, &["form"]) => {
+                if self.has_open(&["template"]) || self.form.is_some() {
+                    return;
+                }
+                self.form = Some(self.insert_html(&tag));
+                self.pop();
+            }
+            Token::Eof => self.in_body(Token::Eof),
+            token => {
+                self.foster_parenting = true;
+                self.in_body(token);
+                self.foster_parenting = false;
+            }
+        }
+    }
+
+    fn in_table_text(&mut self, token: Token<'html>) {
+        if let Token::Text(text) = token {
+            self.pending_table_text.push(text);
+            return;
+        }
+
+        let pending = std::mem::take(&mut self.pending_table_text);
+        if pending.iter().any(|text| !text.is_whitespace()) {
+            for text in pending {
+                self.foster_parenting = true;
+                self.in_body(Token::Text(text));
+                self.foster_parenting = false;
+            }
+        } else {
+            for text in pending {
+                self.insert_text(text);
+            }
+        }
+        self.mode = self.original_mode;
+        self.reprocess(token);
+    }
+
+    fn in_caption(&mut self, token: Token<'html>) {
+        match token {
+            Token::EndTag(name) if is_one_of(name, &["caption"]) => {
+                self.close_caption();
+            }
+            Token::StartTag(tag)
+                if is(
+                    &tag,
+                    &[
+                        "caption", "col", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr",
+                    ],
+                ) =>
+            {
+                if self.close_caption() {
+                    self.reprocess(Token::StartTag(tag));
+                }
+            }
+            Token::EndTag(name) if is_one_of(name, &["table"]) => {
+                if self.close_caption() {
+                    self.reprocess(Token::EndTag(name));
+                }
+            }
+            Token::EndTag(name)
+                if is_one_of(
+                    name,
+                    &[
+                        "body", "col", "colgroup", "html", "tbody", "td", "tfoot", "th", "thead",
+                        "tr",
+                    ],
+                ) => {}
+            token => self.in_body(token),
+        }
+    }
+
+    fn close_caption(&mut self) -> bool {
+        if !self.in_scope(&["caption"], Scope::Table) {
+            return false;
+        }
+        self.generate_implied_end_tags("");
+        self.pop_until(&["caption"]);
+        self.clear_formatting_to_last_marker();
+        self.mode = InsertionMode::InTable;
+        true
+    }
+
+    fn in_column_group(&mut self, token: Token<'html>) {
+        match token {
+            Token::Text(text) if text.is_whitespace() => self.insert_text(text),
+            Token::Comment | Token::Doctype(_) => {}
+            Token::StartTag(tag) if is(&tag, &["html"]) => self.in_body(Token::StartTag(tag)),
+            Token::StartTag(tag) if is(&tag, &["col"]) => {
+                self.insert_html(&tag);
+                self.pop();
+            }
+            Token::EndTag(name) if is_one_of(name, &["colgroup"]) => {
+                if self.current_is(&["colgroup"]) {
+                    self.pop();
+                    self.mode = InsertionMode::InTable;
+                }
+            }
+            Token::EndTag(name) if is_one_of(name, &["col"]) => {}
+            Token::StartTag(tag) if is(&tag, &["template"]) => self.in_head(Token::StartTag(tag)),
+            Token::EndTag(name) if is_one_of(name, &["template"]) => {
+                self.in_head(Token::EndTag(name))
+            }
+            Token::Eof => self.in_body(Token::Eof),
+            token => {
+                if !self.current_is(&["colgroup"]) {
+                    return;
+                }
+                self.pop();
+                self.mode = InsertionMode::InTable;
+                self.reprocess(token);
+            }
+        }
+    }
+
+    fn in_table_body(&mut self, token: Token<'html>) {
+        const CONTEXT: [&str; 5] = ["tbody", "tfoot", "thead", "template", "html"];
+        match token {
+            Token::StartTag(tag) if is(&tag, &["tr"]) => {
+                self.clear_to_context(&CONTEXT);
+                self.insert_html(&tag);
+                self.mode = InsertionMode::InRow;
+            }
+            Token::StartTag(tag) if is(&tag, &["th", "td"]) => {
+                self.clear_to_context(&CONTEXT);
+                self.insert_html(&Tag::implied("tr", self.position));
+                self.mode = InsertionMode::InRow;
+                self.reprocess(Token::StartTag(tag));
+            }
+            Token::EndTag(name) if is_one_of(name, &["tbody", "tfoot", "thead"]) => {
+                if !self.in_scope(&[name], Scope::Table) {
+                    return;
+                }
+                self.clear_to_context(&CONTEXT);
+                self.pop();
+                self.mode = InsertionMode::InTable;
+            }
+            Token::StartTag(_) | Token::EndTag(_)
+                if matches!(&token, Token::StartTag(tag) if is(tag, &["caption", "col", "colgroup", "tbody", "tfoot", "thead"]))
+                    || matches!(&token, Token::EndTag(name) if is_one_of(name, &["table"])) =>
+            {
+                if !self.in_scope(&["tbody", "thead", "tfoot"], Scope::Table) {
+                    return;
+                }
+                self.clear_to_context(&CONTEXT);
+                self.pop();
+                self.mode = InsertionMode::InTable;
+                self.reprocess(token);
+            }
+            Token::EndTag(name)
+                if is_one_of(
+                    name,
+                    &[
+                        "body", "caption", "col", "colgroup", "html", "td", "th", "tr",
+                    ],
+                ) => {}
+            token => self.in_table(token),
+        }
+    }
+
+    fn in_row(&mut self, token: Token<'html>) {
+        const CONTEXT: [&str; 3] = ["tr", "template", "html"];
+        match token {
+            Token::StartTag(tag) if is(&tag, &["th", "td"]) => {
+                self.clear_to_context(&CONTEXT);
+                self.insert_html(&tag);
+                self.mode = InsertionMode::InCell;
+                self.formatting.push(None);
+            }
+            Token::EndTag(name) if is_one_of(name, &["tr"]) => {
+                self.close_row();
+            }
+            Token::StartTag(_) | Token::EndTag(_)
+                if matches!(&token, Token::StartTag(tag) if is(tag, &["caption", "col", "colgroup", "tbody", "tfoot", "thead", "tr"]))
+                    || matches!(&token, Token::EndTag(name) if is_one_of(name, &["table"])) =>
+            {
+                if self.close_row() {
+                    self.reprocess(token);
+                }
+            }
+            Token::EndTag(name) if is_one_of(name, &["tbody", "tfoot", "thead"]) => {
+                if !self.in_scope(&[name], Scope::Table) {
+                    return;
+                }
+                if self.close_row() {
+                    self.reprocess(Token::EndTag(name));
+                }
+            }
+            Token::EndTag(name)
+                if is_one_of(
+                    name,
+                    &["body", "caption", "col", "colgroup", "html", "td", "th"],
+                ) => {}
+            token => self.in_table(token),
+        }
+    }
+
+    fn close_row(&mut self) -> bool {
+        if !self.in_scope(&["tr"], Scope::Table) {
+            return false;
+        }
+        self.clear_to_context(&["tr", "template", "html"]);
+        self.pop();
+        self.mode = InsertionMode::InTableBody;
+        true
+    }
+
+    fn in_cell(&mut self, token: Token<'html>) {
+        match token {
+            Token::EndTag(name) if is_one_of(name, &["td", "th"]) => {
+                if !self.in_scope(&[name], Scope::Table) {
+                    return;
+                }
+                self.generate_implied_end_tags("");
+                self.pop_until(&[name]);
+                self.clear_formatting_to_last_marker();
+                self.mode = InsertionMode::InRow;
+            }
+            Token::StartTag(tag)
+                if is(
+                    &tag,
+                    &[
+                        "caption", "col", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr",
+                    ],
+                ) =>
+            {
+                if !self.in_scope(&["td", "th"], Scope::Table) {
+                    return;
+                }
+                self.close_cell();
+                self.reprocess(Token::StartTag(tag));
+            }
+            Token::EndTag(name)
+                if is_one_of(name, &["body", "caption", "col", "colgroup", "html"]) => {}
+            Token::EndTag(name) if is_one_of(name, &["table", "tbody", "tfoot", "thead", "tr"]) => {
+                if !self.in_scope(&[name], Scope::Table) {
+                    return;
+                }
+                self.close_cell();
+                self.reprocess(Token::EndTag(name));
+            }
+            token => self.in_body(token),
+        }
+    }
+
+    fn close_cell(&mut self) {
+        self.generate_implied_end_tags("");
+        self.pop_until(&["td", "th"]);
+        self.clear_formatting_to_last_marker();
+        self.mode = InsertionMode::InRow;
+    }
+
+    fn in_select(&mut self, token: Token<'html>) {
+        match token {
+            Token::Text(text) => self.insert_text(text),
+            Token::Comment | Token::Doctype(_) => {}
+            Token::StartTag(tag) if is(&tag, &["html"]) => self.in_body(Token::StartTag(tag)),
+            Token::StartTag(tag) if is(&tag, &["option"]) => {
+                if self.current_is(&["option"]) {
+                    self.pop();
+                }
+                self.insert_html(&tag);
+            }
+            Token::StartTag(tag) if is(&tag, &["optgroup"]) => {
+                if self.current_is(&["option"]) {
+                    self.pop();
+                }
+                if self.current_is(&["optgroup"]) {
+                    self.pop();
+                }
+                self.insert_html(&tag);
+            }
+            Token::StartTag(tag) if is(&tag, &["hr"]) => {
+                if self.current_is(&["option"]) {
+                    self.pop();
+                }
+                if self.current_is(&["optgroup"]) {
+                    self.pop();
+                }
+                self.insert_html(&tag);
+                self.pop();
+            }
+            Token::EndTag(name) if is_one_of(name, &["optgroup"]) => {
+                let length = self.open.len();
+                if self.current_is(&["option"])
+                    && length > 1
+                    && self.is_html(self.open[length - 2], &["optgroup"])
+                {
+                    self.pop();
+                }
+                if self.current_is(&["optgroup"]) {
+                    self.pop();
+                }
+            }
+            Token::EndTag(name) if is_one_of(name, &["option"]) && self.current_is(&["option"]) => {
+                self.pop();
+            }
+            Token::EndTag(name) if is_one_of(name, &["select"]) => {
+                if !self.in_scope(&["select"], Scope::Select) {
+                    return;
+                }
+                self.pop_until(&["select"]);
+                self.reset_insertion_mode();
+            }
+            Token::StartTag(tag) if is(&tag, &["select"]) => {
+                if !self.in_scope(&["select"], Scope::Select) {
+                    return;
+                }
+                self.pop_until(&["select"]);
+                self.reset_insertion_mode();
+            }
+            Token::StartTag(tag) if is(&tag, &["input", "keygen", "textarea"]) => {
+                if !self.in_scope(&["select"], Scope::Select) {
+                    return;
+                }
+                self.pop_until(&["select"]);
+                self.reset_insertion_mode();
+                self.reprocess(Token::StartTag(tag));
+            }
+            Token::StartTag(tag) if is(&tag, &["script", "template"]) => {
+                self.in_head(Token::StartTag(tag))
+            }
+            Token::EndTag(name) if is_one_of(name, &["template"]) => {
+                self.in_head(Token::EndTag(name))
+            }
+            Token::Eof => self.in_body(Token::Eof),
+            _ => {}
+        }
+    }
+
+    fn in_select_in_table(&mut self, token: Token<'html>) {
+        const TABLE: [&str; 8] = [
+            "caption", "table", "tbody", "tfoot", "thead", "tr", "td", "th",
+        ];
+        match token {
+            Token::StartTag(tag) if is(&tag, &TABLE) => {
+                self.pop_until(&["select"]);
+                self.reset_insertion_mode();
+                self.reprocess(Token::StartTag(tag));
+            }
+            Token::EndTag(name) if is_one_of(name, &TABLE) => {
+                if !self.in_scope(&[name], Scope::Table) {
+                    return;
+                }
+                self.pop_until(&["select"]);
+                self.reset_insertion_mode();
+                self.reprocess(Token::EndTag(name));
+            }
+            token => self.in_select(token),
+        }
+    }
+
+    fn in_template(&mut self, token: Token<'html>) {
+        let next_mode = match &token {
+            Token::Text(_) | Token::Comment | Token::Doctype(_) => return self.in_body(token),
+            Token::StartTag(tag)
+                if is(
+                    tag,
+                    &[
+                        "base", "basefont", "bgsound", "link", "meta", "noframes", "script",
+                        "style", "template", "title",
+                    ],
+                ) =>
+            {
+                return self.in_head(token);
+            }
+            Token::EndTag(name) if is_one_of(name, &["template"]) => return self.in_head(token),
+            Token::StartTag(tag)
+                if is(tag, &["caption", "colgroup", "tbody", "tfoot", "thead"]) =>
+            {
+                InsertionMode::InTable
+            }
+            Token::StartTag(tag) if is(tag, &["col"]) => InsertionMode::InColumnGroup,
+            Token::StartTag(tag) if is(tag, &["tr"]) => InsertionMode::InTableBody,
+            Token::StartTag(tag) if is(tag, &["td", "th"]) => InsertionMode::InRow,
+            Token::StartTag(_) => InsertionMode::InBody,
+            Token::EndTag(_) => return,
+            Token::Eof => {
+                if !self.has_open(&["template"]) {
+                    return;
+                }
+                self.pop_until(&["template"]);
+                self.clear_formatting_to_last_marker();
+                self.template_modes.pop();
+                self.reset_insertion_mode();
+                return self.reprocess(Token::Eof);
+            }
+        };
+        self.template_modes.pop();
+        self.template_modes.push(next_mode);
+        self.mode = next_mode;
+        self.reprocess(token);
+    }
+
+    fn after_body(&mut self, token: Token<'html>) {
+        match token {
+            Token::Text(text) if text.is_whitespace() => self.in_body(Token::Text(text)),
+            Token::Comment | Token::Doctype(_) | Token::Eof => {}
+            Token::StartTag(tag) if is(&tag, &["html"]) => self.in_body(Token::StartTag(tag)),
+            Token::EndTag(name) if is_one_of(name, &["html"]) => {
+                self.mode = InsertionMode::AfterAfterBody;
+            }
+            token => {
+                self.mode = InsertionMode::InBody;
+                self.reprocess(token);
+            }
+        }
+    }
+
+    fn in_frameset(&mut self, token: Token<'html>) {
+        match token {
+            Token::Text(text) if text.is_whitespace() => self.insert_text(text),
+            Token::StartTag(tag) if is(&tag, &["html"]) => self.in_body(Token::StartTag(tag)),
+            Token::StartTag(tag) if is(&tag, &["frameset"]) => {
+                self.insert_html(&tag);
+            }
+            Token::EndTag(name) if is_one_of(name, &["frameset"]) => {
+                if self.current_is(&["html"]) {
+                    return;
+                }
+                self.pop();
+                if !self.current_is(&["frameset"]) {
+                    self.mode = InsertionMode::AfterFrameset;
+                }
+            }
+            Token::StartTag(tag) if is(&tag, &["frame"]) => {
+                self.insert_html(&tag);
+                self.pop();
+            }
+            Token::StartTag(tag) if is(&tag, &["noframes"]) => self.in_head(Token::StartTag(tag)),
+            _ => {}
+        }
+    }
+
+    fn after_frameset(&mut self, token: Token<'html>) {
+        match token {
+            Token::Text(text) if text.is_whitespace() => self.insert_text(text),
+            Token::StartTag(tag) if is(&tag, &["html"]) => self.in_body(Token::StartTag(tag)),
+            Token::EndTag(name) if is_one_of(name, &["html"]) => {
+                self.mode = InsertionMode::AfterAfterFrameset;
+            }
+            Token::StartTag(tag) if is(&tag, &["noframes"]) => self.in_head(Token::StartTag(tag)),
+            _ => {}
+        }
+    }
+
+    fn after_after_body(&mut self, token: Token<'html>) {
+        match token {
+            Token::Comment | Token::Eof => {}
+            Token::Text(text) if text.is_whitespace() => self.in_body(Token::Text(text)),
+            Token::Doctype(_) => {}
+            Token::StartTag(tag) if is(&tag, &["html"]) => self.in_body(Token::StartTag(tag)),
+            token => {
+                self.mode = InsertionMode::InBody;
+                self.reprocess(token);
+            }
+        }
+    }
+
+    fn after_after_frameset(&mut self, token: Token<'html>) {
+        match token {
+            Token::Text(text) if text.is_whitespace() => self.in_body(Token::Text(text)),
+            Token::StartTag(tag) if is(&tag, &["html"]) => self.in_body(Token::StartTag(tag)),
+            Token::StartTag(tag) if is(&tag, &["noframes"]) => self.in_head(Token::StartTag(tag)),
+            _ => {}
+        }
+    }
+
+    fn process_foreign(&mut self, token: Token<'html>) {
+        match token {
+            Token::Text(text) => {
+                if !text.is_whitespace() {
+                    self.frameset_ok = false;
+                }
+                self.insert_text(text);
+            }
+            Token::Comment | Token::Doctype(_) => {}
+            Token::StartTag(tag)
+                if is(&tag, &FOREIGN_BREAKOUTS)
+                    || is(&tag, &["font"])
+                        && self.tree.attributes[tag.attributes.clone()].iter().any(
+                            |attribute| is_one_of(attribute.key, &["color", "face", "size"]),
+                        ) =>
+            {
+                self.pop_to_html_content();
+                self.reprocess(Token::StartTag(tag));
+            }
+            Token::EndTag(name) if is_one_of(name, &["br", "p"]) => {
+                self.pop_to_html_content();
+                self.reprocess(Token::EndTag(name));
+            }
+            Token::StartTag(tag) => {
+                let namespace = self
+                    .adjusted_current_element()
+                    .and_then(|element| element.namespace);
+                self.insert_element(&tag, namespace);
+                if tag.self_closing {
+                    self.pop();
+                }
+            }
+            Token::EndTag(name) => {
+                for index in (0..self.open.len()).rev() {
+                    let node = self.open[index];
+                    if index == 0 {
+                        return;
+                    }
+                    if self.element(node).name.eq_ignore_ascii_case(name) {
+                        self.pop_until_node(node);
+                        return;
+                    }
+                    if self.element(self.open[index - 1]).namespace.is_none() {
+                        return self.process(self.mode, Token::EndTag(name));
+                    }
+                }
+            }
+            Token::Eof => self.process(self.mode, Token::Eof),
+        }
+    }
+
+    /// Pops the foreign elements a breakout start tag ends.
+    fn pop_to_html_content(&mut self) {
+        while let Some(&current) = self.open.last() {
+            let element = self.element(current);
+            let Some(namespace) = element.namespace else {
+                return;
+            };
+            if namespace == MATHML_NAMESPACE
+                && is_one_of(element.name, &MATHML_TEXT_INTEGRATION_POINTS)
+                || self.is_html_integration_point(current)
+            {
+                return;
+            }
+            self.pop();
+        }
+    }
+
+    fn is_html_integration_point(&self, id: NodeId) -> bool {
+        let element = self.element(id);
+        match element.namespace {
+            Some(namespace) if namespace == SVG_NAMESPACE => {
+                is_one_of(element.name, &SVG_HTML_INTEGRATION_POINTS)
+            }
+            Some(_) => {
+                element.name.eq_ignore_ascii_case("annotation-xml")
+                    && self.tree.attributes[element.attributes.clone()]
+                        .iter()
+                        .any(|attribute| {
+                            attribute.key.eq_ignore_ascii_case("encoding")
+                                && attribute.value.as_deref().is_some_and(|encoding| {
+                                    is_one_of(encoding, &["text/html", "application/xhtml+xml"])
+                                })
+                        })
+            }
+            None => false,
+        }
+    }
+
+    fn adjusted_current_element(&self) -> Option<&TreeElement<'html>> {
+        self.open.last().map(|&id| self.element(id))
+    }
+
+    fn element(&self, id: NodeId) -> &TreeElement<'html> {
+        match &self.tree.nodes[id].data {
+            NodeData::Element(element) => element,
+            _ => unreachable!("only elements are open"),
+        }
+    }
+
+    fn element_mut(&mut self, id: NodeId) -> &mut TreeElement<'html> {
+        match &mut self.tree.nodes[id].data {
+            NodeData::Element(element) => element,
+            _ => unreachable!("only elements are open"),
+        }
+    }
+
+    fn is_html(&self, id: NodeId, names: &[&str]) -> bool {
+        let element = self.element(id);
+        element.namespace.is_none() && is_one_of(element.name, names)
+    }
+
+    fn current_is(&self, names: &[&str]) -> bool {
+        self.open.last().is_some_and(|&id| self.is_html(id, names))
+    }
+
+    fn has_open(&self, names: &[&str]) -> bool {
+        self.open.iter().any(|&id| self.is_html(id, names))
+    }
+
+    fn is_special(&self, id: NodeId) -> bool {
+        let element = self.element(id);
+        match element.namespace {
+            None => is_one_of(element.name, &SPECIAL),
+            Some(namespace) if namespace == SVG_NAMESPACE => {
+                is_one_of(element.name, &SVG_HTML_INTEGRATION_POINTS)
+            }
+            Some(_) => {
+                is_one_of(element.name, &MATHML_TEXT_INTEGRATION_POINTS)
+                    || element.name.eq_ignore_ascii_case("annotation-xml")
+            }
+        }
+    }
+
+    fn is_scope_barrier(&self, id: NodeId, scope: Scope) -> bool {
+        let element = self.element(id);
+        let html = |names: &[&str]| element.namespace.is_none() && is_one_of(element.name, names);
+        match scope {
+            Scope::Select => !html(&["optgroup", "option"]),
+            Scope::Table => html(&["html", "table", "template"]),
+            _ => {
+                html(&[
+                    "applet", "caption", "html", "table", "td", "th", "marquee", "object",
+                    "template",
+                ]) || element.namespace.is_some() && self.is_special(id)
+                    || scope == Scope::ListItem && html(&["ol", "ul"])
+                    || scope == Scope::Button && html(&["button"])
+            }
+        }
+    }
+
+    fn in_scope(&self, names: &[&str], scope: Scope) -> bool {
+        for &id in self.open.iter().rev() {
+            if self.is_html(id, names) {
+                return true;
+            }
+            if self.is_scope_barrier(id, scope) {
+                return false;
+            }
+        }
+        false
+    }
+
+    fn node_in_scope(&self, node: NodeId, scope: Scope) -> bool {
+        for &id in self.open.iter().rev() {
+            if id == node {
+                return true;
+            }
+            if self.is_scope_barrier(id, scope) {
+                return false;
+            }
+        }
+        false
+    }
+
+    fn is_hidden_input(&self, tag: &Tag<'html>) -> bool {
+        self.tree.attributes[tag.attributes.clone()]
+            .iter()
+            .any(|attribute| {
+                attribute.key.eq_ignore_ascii_case("type")
+                    && attribute
+                        .value
+                        .as_deref()
+                        .is_some_and(|value| value.eq_ignore_ascii_case("hidden"))
+            })
+    }
+
+    /// Where a new node goes: appended to `target` (the current node by
+    /// default), unless it is foster parented out of a table.
+    fn appropriate_place(&self, target: Option<NodeId>) -> (NodeId, Option<NodeId>) {
+        let target = match target.or_else(|| self.open.last().copied()) {
+            Some(target) => target,
+            None => return (DOCUMENT, None),
+        };
+        if !self.foster_parenting
+            || !self.is_html(target, &["table", "tbody", "tfoot", "thead", "tr"])
+        {
+            return (target, None);
+        }
+
+        let last_template = self
+            .open
+            .iter()
+            .rposition(|&id| self.is_html(id, &["template"]));
+        let last_table = self
+            .open
+            .iter()
+            .rposition(|&id| self.is_html(id, &["table"]));
+        match (last_template, last_table) {
+            (Some(template), table) if table.is_none_or(|table| template > table) => {
+                (self.open[template], None)
+            }
+            (_, None) => (self.open[0], None),
+            (_, Some(table)) => match self.tree.nodes[self.open[table]].parent {
+                Some(parent) => (parent, Some(self.open[table])),
+                None => (self.open[table - 1], None),
+            },
+        }
+    }
+
+    fn append(&mut self, parent: NodeId, before: Option<NodeId>, child: NodeId) {
+        self.tree.nodes[child].parent = Some(parent);
+        let children = &mut self.tree.nodes[parent].children;
+        match before.and_then(|before| children.iter().position(|&id| id == before)) {
+            Some(index) => children.insert(index, child),
+            None => children.push(child),
+        }
+    }
+
+    fn detach(&mut self, child: NodeId) {
+        if let Some(parent) = self.tree.nodes[child].parent.take() {
+            self.tree.nodes[parent].children.retain(|&id| id != child);
+        }
+    }
+
+    fn create_element(&mut self, tag: &Tag<'html>, namespace: Option<&'static str>) -> NodeId {
+        self.tree.nodes.push(Node {
+            data: NodeData::Element(TreeElement {
+                name: tag.name,
+                namespace,
+                id: tag.id,
+                class: tag.class,
+                attributes: tag.attributes.clone(),
+                self_closing: tag.self_closing,
+                start_tag: tag.start..tag.end,
+                content_start: tag.end,
+                end: tag.end,
+                outer_end: tag.end,
+            }),
+            children: Vec::new(),
+            parent: None,
+        });
+        self.tree.nodes.len() - 1
+    }
+
+    /// A new element for the token `id` was created for, opened here.
+    fn clone_element(&mut self, id: NodeId) -> NodeId {
+        let mut element = self.element(id).clone();
+        element.start_tag = self.position..self.position;
+        element.content_start = self.position;
+        element.end = self.position;
+        element.outer_end = self.position;
+        self.tree.nodes.push(Node {
+            data: NodeData::Element(element),
+            children: Vec::new(),
+            parent: None,
+        });
+        self.tree.nodes.len() - 1
+    }
+
+    fn insert_element(&mut self, tag: &Tag<'html>, namespace: Option<&'static str>) -> NodeId {
+        let (parent, before) = self.appropriate_place(None);
+        let id = self.create_element(tag, namespace);
+        self.append(parent, before, id);
+        self.open.push(id);
+        id
+    }
+
+    fn insert_html(&mut self, tag: &Tag<'html>) -> NodeId {
+        self.insert_element(tag, None)
+    }
+
+    /// Inserts a raw text or RCDATA element, whose text is read up to its
+    /// end tag.
+    fn insert_text_element(&mut self, tag: &Tag<'html>) {
+        self.insert_html(tag);
+        self.text_state = TextState::after_open_tag(tag.name);
+        self.original_mode = self.mode;
+        self.mode = InsertionMode::Text;
+    }
+
+    fn insert_text(&mut self, text: Text) {
+        let (parent, before) = self.appropriate_place(None);
+        if parent == DOCUMENT || text.range.is_empty() {
+            return;
+        }
+        self.tree.nodes.push(Node {
+            data: NodeData::Text {
+                range: text.range,
+                raw: text.raw,
+            },
+            children: Vec::new(),
+            parent: None,
+        });
+        let id = self.tree.nodes.len() - 1;
+        self.append(parent, before, id);
+    }
+
+    /// Records where an element stopped being open.
+    fn close(&mut self, id: NodeId) {
+        let position = self.position;
+        let end_tag = self.end_tag;
+        let element = self.element_mut(id);
+        element.end = position.max(element.content_start);
+        element.outer_end = match end_tag {
+            Some((name, end)) if name.eq_ignore_ascii_case(element.name) => end,
+            _ => element.end,
+        };
+    }
+
+    fn pop(&mut self) -> Option<NodeId> {
+        let id = self.open.pop()?;
+        self.close(id);
+        Some(id)
+    }
+
+    fn pop_until(&mut self, names: &[&str]) {
+        while let Some(id) = self.pop() {
+            if self.is_html(id, names) {
+                return;
+            }
+        }
+    }
+
+    fn pop_until_node(&mut self, node: NodeId) {
+        while let Some(id) = self.pop() {
+            if id == node {
+                return;
+            }
+        }
+    }
+
+    fn clear_to_context(&mut self, names: &[&str]) {
+        while !self.current_is(names) {
+            self.pop();
+        }
+    }
+
+    fn generate_implied_end_tags(&mut self, except: &str) {
+        while self.current_is(&IMPLIED_END_TAGS) && !self.current_is(&[except]) {
+            self.pop();
+        }
+    }
+
+    fn generate_all_implied_end_tags(&mut self) {
+        while self.current_is(&IMPLIED_END_TAGS)
+            || self.current_is(&[
+                "caption", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr",
+            ])
+        {
+            self.pop();
+        }
+    }
+
+    fn close_p(&mut self) {
+        self.generate_implied_end_tags("p");
+        self.pop_until(&["p"]);
+    }
+
+    fn close_p_in_button_scope(&mut self) {
+        if self.in_scope(&["p"], Scope::Button) {
+            self.close_p();
+        }
+    }
+
+    fn reset_insertion_mode(&mut self) {
+        for index in (0..self.open.len()).rev() {
+            let node = self.open[index];
+            let last = index == 0;
+            let html = |names: &[&str]| self.is_html(node, names);
+            self.mode = if html(&["select"]) {
+                let in_table = self.open[..index]
+                    .iter()
+                    .rev()
+                    .take_while(|&&ancestor| !self.is_html(ancestor, &["template"]))
+                    .any(|&ancestor| self.is_html(ancestor, &["table"]));
+                if !last && in_table {
+                    InsertionMode::InSelectInTable
+                } else {
+                    InsertionMode::InSelect
+                }
+            } else if html(&["td", "th"]) && !last {
+                InsertionMode::InCell
+            } else if html(&["tr"]) {
+                InsertionMode::InRow
+            } else if html(&["tbody", "thead", "tfoot"]) {
+                InsertionMode::InTableBody
+            } else if html(&["caption"]) {
+                InsertionMode::InCaption
+            } else if html(&["colgroup"]) {
+                InsertionMode::InColumnGroup
+            } else if html(&["table"]) {
+                InsertionMode::InTable
+            } else if html(&["template"]) {
+                self.template_modes
+                    .last()
+                    .copied()
+                    .unwrap_or(InsertionMode::InTemplate)
+            } else if html(&["head"]) && !last {
+                InsertionMode::InHead
+            } else if html(&["body"]) {
+                InsertionMode::InBody
+            } else if html(&["frameset"]) {
+                InsertionMode::InFrameset
+            } else if html(&["html"]) {
+                if self.head.is_none() {
+                    InsertionMode::BeforeHead
+                } else {
+                    InsertionMode::AfterHead
+                }
+            } else if last {
+                InsertionMode::InBody
+            } else {
+                continue;
+            };
+            return;
+        }
+    }
+
+    fn last_marker(&self) -> usize {
+        self.formatting
+            .iter()
+            .rposition(Option::is_none)
+            .map_or(0, |marker| marker + 1)
+    }
+
+    fn clear_formatting_to_last_marker(&mut self) {
+        while let Some(entry) = self.formatting.pop() {
+            if entry.is_none() {
+                return;
+            }
+        }
+    }
+
+    fn remove_formatting(&mut self, id: NodeId) {
+        if let Some(index) = self.formatting.iter().position(|&entry| entry == Some(id)) {
+            self.formatting.remove(index);
+        }
+    }
+
+    /// Pushes a formatting element, keeping at most three identical ones
+    /// after the last marker (the Noah's Ark clause).
+    fn push_formatting(&mut self, id: NodeId) {
+        let start = self.last_marker();
+        let identical = (start..self.formatting.len())
+            .filter(|&index| {
+                self.formatting[index].is_some_and(|entry| self.is_identical(entry, id))
+            })
+            .collect::<Vec<_>>();
+        if identical.len() >= 3 {
+            self.formatting.remove(identical[0]);
+        }
+        self.formatting.push(Some(id));
+    }
+
+    fn is_identical(&self, a: NodeId, b: NodeId) -> bool {
+        let (a, b) = (self.element(a), self.element(b));
+        let attributes =
+            |element: &TreeElement<'html>| &self.tree.attributes[element.attributes.clone()];
+        a.name.eq_ignore_ascii_case(b.name)
+            && a.namespace == b.namespace
+            && a.id == b.id
+            && a.class == b.class
+            && attributes(a).len() == attributes(b).len()
+            && attributes(a).iter().all(|attribute| {
+                attributes(b).iter().any(|other| {
+                    other.key.eq_ignore_ascii_case(attribute.key) && other.value == attribute.value
+                })
+            })
+    }
+
+    fn reconstruct_formatting(&mut self) {
+        let Some(&Some(last)) = self.formatting.last() else {
+            return;
+        };
+        if self.open.contains(&last) {
+            return;
+        }
+
+        let mut index = self.formatting.len() - 1;
+        while index > 0 {
+            match self.formatting[index - 1] {
+                Some(entry) if !self.open.contains(&entry) => index -= 1,
+                _ => break,
+            }
+        }
+        for index in index..self.formatting.len() {
+            let Some(entry) = self.formatting[index] else {
+                continue;
+            };
+            let (parent, before) = self.appropriate_place(None);
+            let clone = self.clone_element(entry);
+            self.append(parent, before, clone);
+            self.open.push(clone);
+            self.formatting[index] = Some(clone);
+        }
+    }
+
+    /// Runs the adoption agency algorithm for an end tag of a formatting
+    /// element. Returns `false` when the end tag should be handled like any
+    /// other.
+    fn adoption_agency(&mut self, subject: &str) -> bool {
+        if let Some(&current) = self.open.last()
+            && self.is_html(current, &[subject])
+            && !self.formatting.contains(&Some(current))
+        {
+            self.pop();
+            return true;
+        }
+
+        for _ in 0..8 {
+            let start = self.last_marker();
+            let Some(formatting) = self.formatting[start..]
+                .iter()
+                .rev()
+                .flatten()
+                .copied()
+                .find(|&id| self.is_html(id, &[subject]))
+            else {
+                return false;
+            };
+            let Some(formatting_index) = self.open.iter().position(|&id| id == formatting) else {
+                self.remove_formatting(formatting);
+                return true;
+            };
+            if !self.node_in_scope(formatting, Scope::Default) {
+                return true;
+            }
+
+            let Some(furthest_index) = (formatting_index + 1..self.open.len())
+                .find(|&index| self.is_special(self.open[index]))
+            else {
+                self.pop_until_node(formatting);
+                self.remove_formatting(formatting);
+                return true;
+            };
+            let furthest_block = self.open[furthest_index];
+            let common_ancestor = self.open[formatting_index - 1];
+            // The new formatting element goes after this entry of the list,
+            // or where the old one was.
+            let mut bookmark = None;
+
+            let mut node_index = furthest_index;
+            let mut last_node = furthest_block;
+            let mut counter = 0;
+            loop {
+                counter += 1;
+                node_index -= 1;
+                let node = self.open[node_index];
+                if node == formatting {
+                    break;
+                }
+                let mut list_index = self
+                    .formatting
+                    .iter()
+                    .position(|&entry| entry == Some(node));
+                if counter > 3
+                    && let Some(index) = list_index.take()
+                {
+                    self.formatting.remove(index);
+                }
+                let Some(list_index) = list_index else {
+                    self.open.remove(node_index);
+                    self.close(node);
+                    continue;
+                };
+
+                let clone = self.clone_element(node);
+                self.formatting[list_index] = Some(clone);
+                self.open[node_index] = clone;
+                self.close(node);
+                if last_node == furthest_block {
+                    bookmark = Some(clone);
+                }
+                self.detach(last_node);
+                self.append(clone, None, last_node);
+                last_node = clone;
+            }
+
+            let (parent, before) = self.appropriate_place(Some(common_ancestor));
+            self.detach(last_node);
+            self.append(parent, before, last_node);
+
+            let clone = self.clone_element(formatting);
+            let children = std::mem::take(&mut self.tree.nodes[furthest_block].children);
+            for &child in &children {
+                self.tree.nodes[child].parent = Some(clone);
+            }
+            self.tree.nodes[clone].children = children;
+            self.append(furthest_block, None, clone);
+
+            let formatting_entry = self
+                .formatting
+                .iter()
+                .position(|&entry| entry == Some(formatting));
+            match (bookmark, formatting_entry) {
+                (None, Some(index)) => self.formatting[index] = Some(clone),
+                (Some(after), entry) => {
+                    if let Some(index) = entry {
+                        self.formatting.remove(index);
+                    }
+                    let index = self
+                        .formatting
+                        .iter()
+                        .position(|&entry| entry == Some(after))
+                        .map_or(self.formatting.len(), |index| index + 1);
+                    self.formatting.insert(index, Some(clone));
+                }
+                (None, None) => {}
+            }
+
+            if let Some(index) = self.open.iter().position(|&id| id == formatting) {
+                self.open.remove(index);
+                self.close(formatting);
+            }
+            let furthest_index = self
+                .open
+                .iter()
+                .position(|&id| id == furthest_block)
+                .map_or(self.open.len(), |index| index + 1);
+            self.open.insert(furthest_index, clone);
+        }
+        true
+    }
+}
+
+fn is(tag: &Tag<'_>, names: &[&str]) -> bool {
+    is_one_of(tag.name, names)
+}
+
+/// Whether a DOCTYPE puts the document in quirks mode, where a `<table>`
+/// doesn't close an open `<p>`. A missing DOCTYPE does too.
+fn is_quirky(doctype: &Doctype<'_>) -> bool {
+    const PUBLIC_PREFIXES: [&str; 55] = [
+        "+//silmaril//dtd html pro v0r11 19970101//",
+        "-//as//dtd html 3.0 aswedit + extensions//",
+        "-//advasoft ltd//dtd html 3.0 aswedit + extensions//",
+        "-//ietf//dtd html 2.0 level 1//",
+        "-//ietf//dtd html 2.0 level 2//",
+        "-//ietf//dtd html 2.0 strict level 1//",
+        "-//ietf//dtd html 2.0 strict level 2//",
+        "-//ietf//dtd html 2.0 strict//",
+        "-//ietf//dtd html 2.0//",
+        "-//ietf//dtd html 2.1e//",
+        "-//ietf//dtd html 3.0//",
+        "-//ietf//dtd html 3.2 final//",
+        "-//ietf//dtd html 3.2//",
+        "-//ietf//dtd html 3//",
+        "-//ietf//dtd html level 0//",
+        "-//ietf//dtd html level 1//",
+        "-//ietf//dtd html level 2//",
+        "-//ietf//dtd html level 3//",
+        "-//ietf//dtd html strict level 0//",
+        "-//ietf//dtd html strict level 1//",
+        "-//ietf//dtd html strict level 2//",
+        "-//ietf//dtd html strict level 3//",
+        "-//ietf//dtd html strict//",
+        "-//ietf//dtd html//",
+        "-//metrius//dtd metrius presentational//",
+        "-//microsoft//dtd internet explorer 2.0 html strict//",
+        "-//microsoft//dtd internet explorer 2.0 html//",
+        "-//microsoft//dtd internet explorer 2.0 tables//",
+        "-//microsoft//dtd internet explorer 3.0 html strict//",
+        "-//microsoft//dtd internet explorer 3.0 html//",
+        "-//microsoft//dtd internet explorer 3.0 tables//",
+        "-//netscape comm. corp.//dtd html//",
+        "-//netscape comm. corp.//dtd strict html//",
+        "-//o'reilly and associates//dtd html 2.0//",
+        "-//o'reilly and associates//dtd html extended 1.0//",
+        "-//o'reilly and associates//dtd html extended relaxed 1.0//",
+        "-//sq//dtd html 2.0 hotmetal + extensions//",
+        "-//softquad software//dtd hotmetal pro 6.0::19990601::extensions to html 4.0//",
+        "-//softquad//dtd hotmetal pro 4.0::19971010::extensions to html 4.0//",
+        "-//spyglass//dtd html 2.0 extended//",
+        "-//sun microsystems corp.//dtd hotjava html//",
+        "-//sun microsystems corp.//dtd hotjava strict html//",
+        "-//w3c//dtd html 3 1995-03-24//",
+        "-//w3c//dtd html 3.2 draft//",
+        "-//w3c//dtd html 3.2 final//",
+        "-//w3c//dtd html 3.2//",
+        "-//w3c//dtd html 3.2s draft//",
+        "-//w3c//dtd html 4.0 frameset//",
+        "-//w3c//dtd html 4.0 transitional//",
+        "-//w3c//dtd html experimental 19960712//",
+        "-//w3c//dtd html experimental 970421//",
+        "-//w3c//dtd w3 html//",
+        "-//w3o//dtd w3 html 3.0//",
+        "-//webtechs//dtd mozilla html 2.0//",
+        "-//webtechs//dtd mozilla html//",
+    ];
+
+    if !doctype
+        .name
+        .is_some_and(|name| name.eq_ignore_ascii_case("html"))
+    {
+        return true;
+    }
+    let public_id = doctype.public_id.unwrap_or_default().to_ascii_lowercase();
+    let starts_with = |prefix: &str| public_id.starts_with(prefix);
+    is_one_of(
+        &public_id,
+        &[
+            "-//w3o//dtd w3 html strict 3.0//en//",
+            "-/w3c/dtd html 4.0 transitional/en",
+            "html",
+        ],
+    ) || doctype.system_id.is_some_and(|system_id| {
+        system_id.eq_ignore_ascii_case("http://www.ibm.com/data/dtd/v11/ibmxhtml1-transitional.dtd")
+    }) || PUBLIC_PREFIXES.iter().any(|prefix| starts_with(prefix))
+        || doctype.system_id.is_none()
+            && (starts_with("-//w3c//dtd html 4.01 frameset//")
+                || starts_with("-//w3c//dtd html 4.01 transitional//"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(html: &str) -> Tree<'_> {
+        TreeBuilder::default().build(&mut Reader::new(html))
+    }
+
+    /// The tree's elements in document order, as `name(children...)`.
+    fn outline(tree: &Tree<'_>, node: NodeId) -> String {
+        tree.nodes[node]
+            .children
+            .iter()
+            .filter_map(|&child| match &tree.nodes[child].data {
+                NodeData::Element(element) => {
+                    Some(format!("{}({})", element.name, outline(tree, child)))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn element<'a, 'html>(tree: &'a Tree<'html>, name: &str) -> &'a TreeElement<'html> {
+        tree.nodes
+            .iter()
+            .find_map(|node| match &node.data {
+                NodeData::Element(element) if element.name == name => Some(element),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_implied_elements() {
+        assert_eq!(outline(&build(""), DOCUMENT), "html(head() body())");
+        assert_eq!(
+            outline(&build("<table><td>x"), DOCUMENT),
+            "html(head() body(table(tbody(tr(td())))))"
+        );
+    }
+
+    #[test]
+    fn test_positions() {
+        let html = "<div>a<p>b</div>c";
+        let tree = build(html);
+
+        let div = element(&tree, "div");
+        assert_eq!(&html[div.content_start..div.end], "a<p>b");
+        // Implied and EOF closes end where they happen.
+        let p = element(&tree, "p");
+        assert_eq!(&html[p.content_start..p.end], "b");
+        let body = element(&tree, "body");
+        assert_eq!((body.content_start, body.end), (0, html.len()));
+    }
+
+    #[test]
+    fn test_text_ranges() {
+        let html = "<pre>\nx &amp; y</pre><script>a<b</script>";
+        let tree = build(html);
+
+        let texts = tree
+            .nodes
+            .iter()
+            .filter_map(|node| match &node.data {
+                NodeData::Text { range, raw } => Some((&html[range.clone()], *raw)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(texts, vec![("x &amp; y", false), ("a<b", true)]);
+    }
+
+    #[test]
+    fn test_quirks_mode() {
+        let p_and_table = |html| outline(&build(html), DOCUMENT);
+        assert_eq!(p_and_table("<p><table>"), "html(head() body(p(table())))");
+        assert_eq!(
+            p_and_table("<!DOCTYPE html><p><table>"),
+            "html(head() body(p() table()))"
+        );
+    }
+}
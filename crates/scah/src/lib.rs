@@ -13,7 +13,7 @@
 //! | **Streaming core** | Built on StAX: constant memory regardless of document size |
 //! | **Familiar API** | CSS selectors including `>` (child) and ` ` (descendant) combinators |
 //! | **Composable queries** | Chain selections with [`QueryBuilder::then`] for hierarchical data extraction |
-//! | **Zero-copy** | Element names, attributes, and inner HTML are `&str` slices into the source |
+//! | **Zero-copy** | Element names, attributes, and inner HTML are `&str` slices into the source; decoding `&amp;` only allocates when there is one |
 //! | **Multi-language** | Rust core with Python and TypeScript/JavaScript bindings |
 //!
 //! ## Quick Start
@@ -90,6 +90,9 @@
 //! | **Tag name** | `a`, `div` | Working |
 //! | **ID** | `#my-id` | Working |
 //! | **Class** | `.my-class` | Working |
+//! | **Universal** | `main > * > a`, `*[data-id]` | Working |
+//! | **Namespace** | `svg\|rect`, `*\|*`, `\|rect` | Working |
+//! | **Escapes and Unicode** | `.md\:flex`, `#\31 23`, `.café` | Working |
 //! | **Descendant combinator** | `main section a` | Working |
 //! | **Child combinator** | `main > section` | Working |
 //! | **Attribute presence** | `a[href]` | Working |
@@ -97,10 +100,35 @@
 //! | **Attribute prefix** | `a[href^="https"]` | Working |
 //! | **Attribute suffix** | `a[href$=".com"]` | Working |
 //! | **Attribute substring** | `a[href*="example"]` | Working |
-//! | **Adjacent sibling** | `h1 + p` | Coming soon |
-//! | **General sibling** | `h1 ~ p` | Coming soon |
+//! | **Attribute case modifier** | `input[type="submit" i]`, `a[rel=next s]` | Working |
+//! | **Adjacent sibling** | `h1 + p` | Working |
+//! | **General sibling** | `h1 ~ p` | Working |
+//! | **Selector list** | `h1, h2, h3` | Working |
+//! | **Negation** | `a:not([rel=nofollow], .ad)` | Working |
+//! | **Matches-any** | `article :is(h1, h2) > a`, `:where(.a, .b)` | Working |
+//! | **Structural** | `tr > td:nth-child(2n+1)`, `p:first-of-type`, `:nth-child(2 of .x)` | Working |
+//! | **Structural, from the end** | `li:last-child`, `p:nth-last-of-type(2)` | Working, `all` only, last compound only |
+//! | **Relational** | `li:has(> a.download)`, `div.card:has(img)` | Working, `all` only, last compound only |
+//!
+//! End-relative pseudo-classes (`:last-child`, ...) need the siblings that follow
+//! the element, so a candidate is only confirmed in the [`Store`] once its parent closes.
+//! Likewise `:has()` is confirmed once the element itself closes.
+//!
+//! Namespace prefixes resolve against [`Namespaces::default`] (`html`, `svg` and `math`),
+//! or the map given to [`Query::with_namespaces`].
+//!
+//! [`parse`] closes elements as their tags arrive. [`parse_spec_tree`] builds the
+//! document with the WHATWG tree construction rules first, so selectors see the
+//! tree a browser would build, including implied `<tbody>`s and content moved out
+//! of tables.
+//!
+//! [`StreamingParser`] reads a document fed to it in chunks, and copies the
+//! matches out into an [`OwnedStore`] as they complete instead of keeping the
+//! whole source.
 
 pub mod debug;
+#[cfg(feature = "encoding")]
+mod encoding;
 mod engine;
 mod html;
 mod store;
@@ -108,20 +136,37 @@ mod support;
 
 #[cfg(all(any(debug_assertions, test), feature = "otel"))]
 mod otel;
+#[cfg(any(feature = "futures", feature = "tokio"))]
+mod read_async;
 
+#[cfg(feature = "encoding")]
+pub use encoding::{DecodedHtml, decode_html, sniff_encoding};
+#[cfg(feature = "encoding")]
+pub use encoding_rs::Encoding;
 pub use engine::multiplexer::QueryMultiplexer;
+pub use html::char_ref::{decode_attribute_value, decode_text};
 pub use html::element::builder::XHtmlElement;
+pub use html::markup::Doctype;
 pub use html::parser::XHtmlParser;
+pub use html::streaming::StreamingParser;
+#[cfg(feature = "tokio")]
+pub use read_async::parse_tokio_read;
+#[cfg(feature = "futures")]
+pub use read_async::{parse_async_read, parse_stream};
 pub use scah_macros::query;
 pub use scah_query_ir::lazy;
 pub use scah_query_ir::{
-    Attribute, AttributeSelection, AttributeSelectionKind, AttributeSelections, ClassSelections,
-    Combinator, ElementPredicate, IElement, Position, Query, QueryBuilder, QueryFactory,
-    QuerySection, QuerySectionId, QuerySpec, Save, SelectionKind, SelectorParseError, StaticQuery,
-    Transition, TransitionId,
+    Attribute, AttributeSelection, AttributeSelectionKind, AttributeSelections, CaseSensitivity,
+    ClassSelections, Combinator, ElementPredicate, HTML_NAMESPACE, IElement, MATHML_NAMESPACE,
+    Namespaces, Nth, Position, PredicateSelections, PseudoClass, PseudoClassSelections, Query,
+    QueryBuilder, QueryFactory, QuerySection, QuerySectionId, QuerySpec, SVG_NAMESPACE, Save,
+    SelectionKind, SelectorParseError, SiblingFilter, StaticQuery, Transition, TransitionId,
 };
 pub use scah_reader::Reader;
-pub use store::{Element, ElementId, Store};
+pub use store::{
+    Element, ElementId, OwnedAttribute, OwnedDoctype, OwnedElement, OwnedQueryNode, OwnedStore,
+    SourceLocation, Store,
+};
 
 /// Parse an HTML string against one or more pre-built [`Query`] objects and
 /// return a [`Store`] containing all matched elements.
@@ -160,17 +205,160 @@ pub fn parse<'a: 'query, 'html: 'query, 'query: 'html, Q>(
     html: &'html str,
     queries: &'a [Q],
 ) -> Store<'html, 'query>
+where
+    Q: QuerySpec<'query>,
+{
+    run_parser(html, queries, |parser| parser)
+}
+
+/// Like [`parse`], but reads `xml` as XML: tag and attribute names compare
+/// exactly, `/>` closes any element, and no element is void, raw text or
+/// closed implicitly.
+///
+/// # Example
+///
+/// ```rust
+/// use scah::{Query, Save, parse_xml};
+///
+/// let xml = "<feed><Entry/><entry><title>First</title></entry></feed>";
+/// let queries = &[Query::all("entry > title", Save::all())
+///     .expect("valid selector")
+///     .build()];
+/// let store = parse_xml(xml, queries);
+///
+/// let titles: Vec<_> = store.get("entry > title").unwrap().collect();
+/// assert_eq!(titles.len(), 1);
+/// assert_eq!(titles[0].text_content(&store), Some("First"));
+/// ```
+pub fn parse_xml<'a: 'query, 'html: 'query, 'query: 'html, Q>(
+    xml: &'html str,
+    queries: &'a [Q],
+) -> Store<'html, 'query>
+where
+    Q: QuerySpec<'query>,
+{
+    run_parser(xml, queries, XHtmlParser::xml)
+}
+
+/// Like [`parse`], but builds the document with the WHATWG tree
+/// construction rules first, so the selectors see the elements a browser
+/// would: implied `<tbody>`s, reopened formatting elements, and content
+/// moved out of tables. It keeps the whole document's tree in memory
+/// while it runs.
+///
+/// # Example
+///
+/// ```rust
+/// use scah::{Query, Save, parse_spec_tree};
+///
+/// let html = "<table><tr><td>One</td></tr></table>";
+/// let queries = &[Query::all("table > tbody > tr > td", Save::all())
+///     .expect("valid selector")
+///     .build()];
+/// let store = parse_spec_tree(html, queries);
+///
+/// let cells: Vec<_> = store.get("table > tbody > tr > td").unwrap().collect();
+/// assert_eq!(cells.len(), 1);
+/// assert_eq!(cells[0].text_content(&store), Some("One"));
+/// ```
+pub fn parse_spec_tree<'a: 'query, 'html: 'query, 'query: 'html, Q>(
+    html: &'html str,
+    queries: &'a [Q],
+) -> Store<'html, 'query>
+where
+    Q: QuerySpec<'query>,
+{
+    run_parser(html, queries, XHtmlParser::spec_tree)
+}
+
+/// How much [`parse_reader`] reads at once.
+const READ_BUFFER_LEN: usize = 8 * 1024;
+
+/// Like [`parse`], but pulls the HTML from `reader` in blocks, through a
+/// [`StreamingParser`], such as the output of a decompressor or a file. The
+/// matches are copied out of the source, into an [`OwnedStore`].
+///
+/// Reading stops at the end of the input, or as soon as every query is done
+/// with the document. Errors of `reader` other than
+/// [`Interrupted`](std::io::ErrorKind::Interrupted) are returned as they are.
+///
+/// # Example
+///
+/// ```rust
+/// use scah::{Query, Save, parse_reader};
+///
+/// let html: &[u8] = b"<div><a href='link'>Hello</a></div>";
+/// let queries = &[Query::all("a", Save::all())
+///     .expect("valid selector")
+///     .build()];
+/// let store = parse_reader(html, queries).unwrap();
+///
+/// let a = store.get("a").unwrap().next().unwrap();
+/// assert_eq!(a.attribute(&store, "href"), Some("link"));
+/// ```
+pub fn parse_reader<'query, R, Q>(
+    mut reader: R,
+    queries: &'query [Q],
+) -> std::io::Result<OwnedStore>
+where
+    R: std::io::Read,
+    Q: QuerySpec<'query>,
+{
+    let mut parser = StreamingParser::new(queries);
+    let mut buffer = vec![0; READ_BUFFER_LEN];
+    while !parser.is_done() {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(len) => parser.feed(&buffer[..len]),
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(parser.finish())
+}
+
+/// Like [`parse_reader`], but reads the blocks `reader` already buffers
+/// instead of copying them into its own.
+pub fn parse_buf_reader<'query, R, Q>(
+    mut reader: R,
+    queries: &'query [Q],
+) -> std::io::Result<OwnedStore>
+where
+    R: std::io::BufRead,
+    Q: QuerySpec<'query>,
+{
+    let mut parser = StreamingParser::new(queries);
+    while !parser.is_done() {
+        let len = match reader.fill_buf() {
+            Ok([]) => break,
+            Ok(block) => {
+                parser.feed(block);
+                block.len()
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+        reader.consume(len);
+    }
+    Ok(parser.finish())
+}
+
+fn run_parser<'a: 'query, 'html: 'query, 'query: 'html, Q>(
+    html: &'html str,
+    queries: &'a [Q],
+    configure: impl FnOnce(XHtmlParser<'html, 'query, Q>) -> XHtmlParser<'html, 'query, Q>,
+) -> Store<'html, 'query>
 where
     Q: QuerySpec<'query>,
 {
     let selectors = QueryMultiplexer::new(queries);
 
     let no_extra_allocations = queries.iter().all(|q| q.exit_at_section_end().is_some());
-    let mut parser = if no_extra_allocations {
+    let mut parser = configure(if no_extra_allocations {
         XHtmlParser::new(selectors)
     } else {
         XHtmlParser::with_capacity(selectors, html.len())
-    };
+    });
 
     let mut reader = Reader::new(html);
     parser.trace_parse_started(html.len(), queries.len());
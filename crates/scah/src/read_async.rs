@@ -0,0 +1,112 @@
+use std::future::poll_fn;
+use std::io;
+use std::pin::Pin;
+
+use crate::{OwnedStore, QuerySpec, READ_BUFFER_LEN, StreamingParser};
+
+/// Like [`parse_reader`](crate::parse_reader), but awaits the blocks of a
+/// [`futures_io::AsyncRead`], such as an HTTP response body, instead of
+/// buffering all of it first.
+///
+/// Reading stops at the end of the input, or as soon as every query is done
+/// with the document: a query of [`Query::first`](crate::Query::first)
+/// sections doesn't wait for the rest of the body once its element has
+/// closed.
+///
+/// # Example
+///
+/// ```rust
+/// use scah::{Query, Save, parse_async_read};
+///
+/// let body: &[u8] = b"<div><a href='link'>Hello</a></div>";
+/// let queries = &[Query::all("a", Save::all())
+///     .expect("valid selector")
+///     .build()];
+/// let store = futures::executor::block_on(parse_async_read(body, queries)).unwrap();
+///
+/// let a = store.get("a").unwrap().next().unwrap();
+/// assert_eq!(a.attribute(&store, "href"), Some("link"));
+/// ```
+#[cfg(feature = "futures")]
+pub async fn parse_async_read<'query, R, Q>(
+    mut reader: R,
+    queries: &'query [Q],
+) -> io::Result<OwnedStore>
+where
+    R: futures_io::AsyncRead + Unpin,
+    Q: QuerySpec<'query>,
+{
+    let mut parser = StreamingParser::new(queries);
+    let mut buffer = vec![0; READ_BUFFER_LEN];
+    while !parser.is_done() {
+        match poll_fn(|cx| Pin::new(&mut reader).poll_read(cx, &mut buffer)).await {
+            Ok(0) => break,
+            Ok(len) => parser.feed(&buffer[..len]),
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(parser.finish())
+}
+
+/// Like [`parse_async_read`], but takes the chunks of a
+/// [`Stream`](futures_core::Stream), such as the `bytes_stream()` of an HTTP
+/// client, and returns the first error it yields.
+///
+/// # Example
+///
+/// ```rust
+/// use scah::{Query, Save, parse_stream};
+///
+/// let chunks = ["<ul><li>O", "ne</li><li>Two</li></ul>"].map(Ok::<_, std::io::Error>);
+/// let queries = &[Query::all("li", Save::all())
+///     .expect("valid selector")
+///     .build()];
+/// let store = futures::executor::block_on(parse_stream(futures::stream::iter(chunks), queries))
+///     .unwrap();
+///
+/// assert_eq!(store.get("li").unwrap().count(), 2);
+/// ```
+#[cfg(feature = "futures")]
+pub async fn parse_stream<'query, S, B, E, Q>(
+    mut stream: S,
+    queries: &'query [Q],
+) -> Result<OwnedStore, E>
+where
+    S: futures_core::Stream<Item = Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+    Q: QuerySpec<'query>,
+{
+    let mut parser = StreamingParser::new(queries);
+    while !parser.is_done() {
+        match poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+            Some(chunk) => parser.feed(chunk?.as_ref()),
+            None => break,
+        }
+    }
+    Ok(parser.finish())
+}
+
+/// Like [`parse_async_read`], for a [`tokio::io::AsyncRead`].
+#[cfg(feature = "tokio")]
+pub async fn parse_tokio_read<'query, R, Q>(
+    mut reader: R,
+    queries: &'query [Q],
+) -> io::Result<OwnedStore>
+where
+    R: tokio::io::AsyncRead + Unpin,
+    Q: QuerySpec<'query>,
+{
+    let mut parser = StreamingParser::new(queries);
+    let mut buffer = vec![0; READ_BUFFER_LEN];
+    while !parser.is_done() {
+        let mut block = tokio::io::ReadBuf::new(&mut buffer);
+        match poll_fn(|cx| Pin::new(&mut reader).poll_read(cx, &mut block)).await {
+            Ok(()) if block.filled().is_empty() => break,
+            Ok(()) => parser.feed(block.filled()),
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(parser.finish())
+}
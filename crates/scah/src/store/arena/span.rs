@@ -24,6 +24,10 @@ impl<T: Copy + PartialOrd + Into<usize>> Span<T> {
         self.end
     }
 
+    pub fn set_start(&mut self, value: T) {
+        self.start = value
+    }
+
     pub fn set_end(&mut self, value: T) {
         assert!(self.start <= value);
         self.end = value
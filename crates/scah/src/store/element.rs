@@ -1,7 +1,7 @@
 use std::ops::{Deref, Range};
 
 use super::arena::{Arena, Node, id};
-use super::{Attribute, Store};
+use super::{Attribute, Nullable, SourceLocation, Store};
 
 /// A matched HTML element stored in the [`Store`](crate::Store).
 ///
@@ -13,31 +13,52 @@ use super::{Attribute, Store};
 ///
 /// | Data | How to access |
 /// |------|---------------|
-/// | Tag name | `element.name` |
-/// | Class | `element.class` |
-/// | ID | `element.id` |
-/// | Inner HTML | `element.inner_html` |
+/// | Tag name | `element.name`, [`element.name()`](Element::name) |
+/// | Class | `element.class`, [`element.class()`](Element::class) |
+/// | ID | `element.id`, [`element.id()`](Element::id) |
+/// | Inner HTML | `element.inner_html`, [`element.inner_html(&store)`](Element::inner_html) |
+/// | Outer HTML | `element.outer_html`, [`element.outer_html(&store)`](Element::outer_html) |
 /// | Text content | [`element.text_content(&store)`](Element::text_content) |
 /// | All attributes | [`element.attributes(&store)`](Element::attributes) |
 /// | Single attribute | [`element.attribute(&store, "href")`](Element::attribute) |
+/// | Single attribute, as written | [`element.raw_attribute(&store, "href")`](Element::raw_attribute) |
 /// | Child query results | [`element.get(&store, "selector")`](Element::get) |
+/// | Where it is in the source | `element.start_tag_range`, `element.outer_range`, [`element.location(&store)`](Element::location) |
+///
+/// The accessor methods are also those of an
+/// [`OwnedElement`](crate::OwnedElement), so code using them works with
+/// either kind of store.
 #[derive(Default, Debug, PartialEq)]
 pub struct Element<'html> {
     /// The tag name (e.g. `"a"`, `"div"`, `"section"`).
     pub name: &'html str,
-    /// The value of the `class` attribute, if present.
+    /// The value of the `class` attribute as written in the source, if
+    /// present.
     pub class: Option<&'html str>,
-    /// The value of the `id` attribute, if present.
+    /// The value of the `id` attribute as written in the source, if present.
     pub id: Option<&'html str>,
     /// The raw HTML between the element's opening and closing tags.
     /// Only populated when [`Save::inner_html`](crate::Save::inner_html) was `true`.
     pub inner_html: Option<&'html str>,
+    /// The raw HTML of the whole element, the source within
+    /// [`Element::outer_range`]. Only populated when
+    /// [`Save::outer_html`](crate::Save::outer_html) was `true`.
+    pub outer_html: Option<&'html str>,
     /// Internal range into the shared text-content buffer.
     /// Use [`Element::text_content`] to get the actual `&str`.
     pub text_content: Option<Range<usize>>,
     /// Internal range into the attribute arena.
     /// Use [`Element::attributes`] or [`Element::attribute`] instead.
     pub attributes: Option<Range<u32>>,
+    /// Byte range of the start tag in the source, from its `<` to after its
+    /// `>`. Empty, where the element was implied, when it has none (the
+    /// `<tbody>`s [`parse_spec_tree`](crate::parse_spec_tree) adds).
+    pub start_tag_range: Range<usize>,
+    /// Byte range of the whole element in the source: from its start tag to
+    /// after its end tag, or to where it was closed without one (the next
+    /// `<li>` for an `<li>`, the end of the document). Void and
+    /// self-closing elements end with their start tag.
+    pub outer_range: Range<usize>,
 
     pub first_child_query: Option<id::QueryId>,
     pub next_sibling: Option<id::ElementId>,
@@ -74,9 +95,40 @@ impl<'html> Element<'html> {
         first_query_id
             .and_then(|id| dom.queries.iter_from(id).find(|q| q.query == key))
             .map(|query_node| query_node.elements.start())
+            .filter(|element_id| !element_id.is_null())
             .map(|element_id| dom.elements.iter_from(element_id))
     }
 
+    /// The tag name, as in the `name` field.
+    pub fn name(&self) -> &'html str {
+        self.name
+    }
+
+    /// The value of the `class` attribute, as in the `class` field.
+    pub fn class(&self) -> Option<&'html str> {
+        self.class
+    }
+
+    /// The value of the `id` attribute, as in the `id` field.
+    pub fn id(&self) -> Option<&'html str> {
+        self.id
+    }
+
+    /// The element's inner HTML, as in the `inner_html` field.
+    pub fn inner_html(&self, _dom: &'html Store) -> Option<&'html str> {
+        self.inner_html
+    }
+
+    /// The element's outer HTML, as in the `outer_html` field.
+    pub fn outer_html(&self, _dom: &'html Store) -> Option<&'html str> {
+        self.outer_html
+    }
+
+    /// The line and column where the element's start tag begins.
+    pub fn location(&self, dom: &Store) -> SourceLocation {
+        dom.location(self.start_tag_range.start)
+    }
+
     /// Return all attributes of this element as a slice.
     ///
     /// Returns `None` if the element had no extra attributes beyond
@@ -86,7 +138,9 @@ impl<'html> Element<'html> {
             .as_ref()
             .map(|range| &dom.attributes.deref()[(range.start as usize)..(range.end as usize)])
     }
-    /// Look up a single attribute value by name.
+    /// Look up a single attribute value by name, with its character
+    /// references decoded. An exact match of `key` comes first, then one
+    /// that differs in ASCII case, as HTML attribute names do.
     ///
     /// Returns the attribute's value, or `None` if the attribute is not
     /// present or has no value.
@@ -96,27 +150,55 @@ impl<'html> Element<'html> {
     /// ```rust
     /// use scah::{Query, Save, parse};
     ///
-    /// let html = r#"<a href="https://example.com">Link</a>"#;
+    /// let html = r#"<a href="https://example.com/?a=1&amp;b=2">Link</a>"#;
     /// let queries = &[Query::all("a", Save::all())
     ///     .expect("valid selector")
     ///     .build()];
     /// let store = parse(html, queries);
     ///
     /// let a = store.get("a").unwrap().next().unwrap();
-    /// assert_eq!(a.attribute(&store, "href"), Some("https://example.com"));
+    /// assert_eq!(a.attribute(&store, "href"), Some("https://example.com/?a=1&b=2"));
     /// ```
     pub fn attribute(&self, dom: &'html Store, key: &str) -> Option<&'html str> {
-        self.attributes.as_ref().and_then(|range| {
-            dom.attributes.deref()[(range.start as usize)..(range.end as usize)]
+        self.find_attribute(dom, key)
+            .and_then(|attribute| attribute.value.as_deref())
+    }
+
+    /// Like [`Element::attribute`], but returns the value as written in the
+    /// source.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use scah::{Query, Save, parse};
+    ///
+    /// let html = r#"<a href="/?a=1&amp;b=2">Link</a>"#;
+    /// let queries = &[Query::all("a", Save::all())
+    ///     .expect("valid selector")
+    ///     .build()];
+    /// let store = parse(html, queries);
+    ///
+    /// let a = store.get("a").unwrap().next().unwrap();
+    /// assert_eq!(a.raw_attribute(&store, "href"), Some("/?a=1&amp;b=2"));
+    /// ```
+    pub fn raw_attribute(&self, dom: &'html Store, key: &str) -> Option<&'html str> {
+        self.find_attribute(dom, key)
+            .and_then(|attribute| attribute.raw_value)
+    }
+
+    fn find_attribute(&self, dom: &'html Store, key: &str) -> Option<&'html Attribute<'html>> {
+        let attributes = self.attributes(dom)?;
+        attributes.iter().find(|attr| attr.key == key).or_else(|| {
+            attributes
                 .iter()
-                .find(|attr| attr.key == key)
-                .and_then(|kv| kv.value)
+                .find(|attr| attr.key.eq_ignore_ascii_case(key))
         })
     }
     /// Get the element's concatenated text content.
     ///
     /// Returns the whitespace-trimmed, concatenated text nodes within
-    /// this element. Only populated when [`Save::text_content`](crate::Save::text_content) was `true`.
+    /// this element, with their character references decoded (the markup as
+    /// written is in [`Element::inner_html`]). Only populated when [`Save::text_content`](crate::Save::text_content) was `true`.
     pub fn text_content(&self, dom: &'html Store) -> Option<&'html str> {
         self.text_content
             .as_ref()
@@ -0,0 +1,164 @@
+use std::sync::OnceLock;
+
+/// A position in the source document, counted as editors do.
+///
+/// # Example
+///
+/// ```rust
+/// use scah::{Query, Save, SourceLocation, parse};
+///
+/// let html = "<main>\n  <a href='x'>Link</a>\n</main>";
+/// let queries = &[Query::all("a", Save::none())
+///     .expect("valid selector")
+///     .build()];
+/// let store = parse(html, queries);
+///
+/// let a = store.get("a").unwrap().next().unwrap();
+/// assert_eq!(a.location(&store), SourceLocation { line: 2, column: 3 });
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// 1-based line number. Lines end at `\n`.
+    pub line: usize,
+    /// 1-based column, in characters from the start of the line.
+    pub column: usize,
+}
+
+/// Where the lines of a document start, found the first time a location is
+/// asked for.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct LineIndex(OnceLock<Vec<usize>>);
+
+impl LineIndex {
+    pub fn location(&self, source: &str, offset: usize) -> SourceLocation {
+        let line_starts = self.0.get_or_init(|| {
+            std::iter::once(0)
+                .chain(
+                    source
+                        .bytes()
+                        .enumerate()
+                        .filter(|&(_, byte)| byte == b'\n')
+                        .map(|(index, _)| index + 1),
+                )
+                .collect()
+        });
+
+        let offset = offset.min(source.len());
+        let line = line_starts.partition_point(|&start| start <= offset);
+        let line_start = line_starts[line - 1];
+        // UTF-8 continuation bytes don't start a character.
+        let column = source.as_bytes()[line_start..offset]
+            .iter()
+            .filter(|&&byte| byte & 0xC0 != 0x80)
+            .count();
+
+        SourceLocation {
+            line,
+            column: column + 1,
+        }
+    }
+}
+
+/// A part of a document read on its own, which starts `offset` bytes and at
+/// `location` into the whole of it.
+pub(crate) struct SourceWindow<'a> {
+    pub source: &'a str,
+    pub offset: usize,
+    pub location: SourceLocation,
+    lines: LineIndex,
+}
+
+impl<'a> SourceWindow<'a> {
+    pub fn new(source: &'a str, offset: usize, location: SourceLocation) -> Self {
+        Self {
+            source,
+            offset,
+            location,
+            lines: LineIndex::default(),
+        }
+    }
+
+    /// Where a byte offset into the window is in the whole document.
+    pub fn location(&self, offset: usize) -> SourceLocation {
+        let SourceLocation { line, column } = self.lines.location(self.source, offset);
+        if line == 1 {
+            SourceLocation {
+                line: self.location.line,
+                column: self.location.column + column - 1,
+            }
+        } else {
+            SourceLocation {
+                line: self.location.line + line - 1,
+                column,
+            }
+        }
+    }
+
+    /// The locations of many byte offsets into the window, found in a
+    /// single pass over it rather than by scanning each one's line.
+    pub fn locations(&self, offsets: &[usize]) -> Vec<SourceLocation> {
+        let mut order: Vec<usize> = (0..offsets.len()).collect();
+        order.sort_unstable_by_key(|&index| offsets[index]);
+
+        let mut locations = vec![self.location; offsets.len()];
+        let mut location = self.location;
+        let mut position = 0;
+        for index in order {
+            let offset = offsets[index].min(self.source.len());
+            for &byte in &self.source.as_bytes()[position..offset] {
+                if byte == b'\n' {
+                    location.line += 1;
+                    location.column = 1;
+                } else if byte & 0xC0 != 0x80 {
+                    // UTF-8 continuation bytes don't start a character.
+                    location.column += 1;
+                }
+            }
+            position = offset;
+            locations[index] = location;
+        }
+        locations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locations() {
+        let source = "ab\ncé\n\nd";
+        let index = LineIndex::default();
+        let location = |offset| {
+            let SourceLocation { line, column } = index.location(source, offset);
+            (line, column)
+        };
+
+        assert_eq!(location(0), (1, 1));
+        assert_eq!(location(2), (1, 3));
+        assert_eq!(location(3), (2, 1));
+        assert_eq!(location(6), (2, 3));
+        assert_eq!(location(7), (3, 1));
+        assert_eq!(location(8), (4, 1));
+        assert_eq!(location(100), (4, 2));
+    }
+
+    #[test]
+    fn test_window_locations() {
+        let window = SourceWindow::new("é\nab", 7, SourceLocation { line: 3, column: 4 });
+        let location = |offset| {
+            let SourceLocation { line, column } = window.location(offset);
+            (line, column)
+        };
+
+        assert_eq!(location(0), (3, 4));
+        assert_eq!(location(2), (3, 5));
+        assert_eq!(location(3), (4, 1));
+        assert_eq!(location(5), (4, 3));
+        assert_eq!(
+            window.locations(&[5, 0, 3, 2, 100]),
+            [(4, 3), (3, 4), (4, 1), (3, 5), (4, 3)]
+                .map(|(line, column)| SourceLocation { line, column })
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use crate::Attribute;
+use crate::Doctype;
 use crate::QuerySection;
 use std::ops::Range;
 
@@ -7,6 +8,8 @@ pub(crate) use text_content::TextContent;
 mod arena;
 mod attributes;
 mod element;
+mod location;
+mod owned;
 mod query_node;
 
 pub(crate) use arena::id::Nullable;
@@ -17,6 +20,10 @@ pub use arena::{
 };
 
 pub use element::Element;
+use location::LineIndex;
+pub use location::SourceLocation;
+pub(crate) use location::SourceWindow;
+pub use owned::{OwnedAttribute, OwnedDoctype, OwnedElement, OwnedQueryNode, OwnedStore};
 pub use query_node::QueryNode;
 
 /// The result set returned by [`parse`](crate::parse).
@@ -53,10 +60,31 @@ pub struct Store<'html, 'query> {
     pub queries: Arena<QueryNode<'query>, QueryId>,
     /// Accumulated text-content buffer shared by all elements.
     pub text_content: TextContent,
+    /// The document's first `<!DOCTYPE>` outside of any element, if any.
+    pub doctype: Option<Doctype<'html>>,
+    /// The document the elements were read from.
+    pub source: &'html str,
+    /// The name of the encoding the document was decoded from, such as
+    /// `"Shift_JIS"`, when it was read with `decode_html` (with the
+    /// `encoding` feature). `"UTF-8"` for a document given as a `&str`.
+    pub encoding: &'static str,
+    lines: LineIndex,
+    // Whether [`Store::unlink`] left an element behind for
+    // [`Store::compact`] to drop.
+    unlinked: bool,
     #[cfg(any(debug_assertions, test))]
     pub trace: crate::debug::TraceStore<'html, 'query>,
 }
 
+/// Where [`Store::push_linked`] appended an element to its query's list, so
+/// [`Store::unlink`] can take it out again.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ElementLink {
+    query: QueryId,
+    // The list's last element before the push; null when it was empty.
+    previous: ElementId,
+}
+
 impl<'html, 'query: 'html> Default for Store<'html, 'query> {
     fn default() -> Self {
         Self {
@@ -64,6 +92,11 @@ impl<'html, 'query: 'html> Default for Store<'html, 'query> {
             queries: Arena::new(),
             text_content: TextContent::new(),
             attributes: Arena::new(),
+            doctype: None,
+            source: "",
+            encoding: "UTF-8",
+            lines: LineIndex::default(),
+            unlinked: false,
             #[cfg(any(debug_assertions, test))]
             trace: crate::debug::TraceStore::new(),
         }
@@ -77,11 +110,23 @@ impl<'html, 'query: 'html> Store<'html, 'query> {
             queries: Arena::new(),
             text_content: TextContent::with_capacity(capacity / 3),
             attributes: Arena::with_capacity(capacity / 3),
+            doctype: None,
+            source: "",
+            encoding: "UTF-8",
+            lines: LineIndex::default(),
+            unlinked: false,
             #[cfg(any(debug_assertions, test))]
             trace: crate::debug::TraceStore::with_capacity(capacity.min(4096)),
         }
     }
 
+    /// The line and column of a byte offset into [`Store::source`], such as
+    /// one of [`Element::start_tag_range`]. The lines are indexed the first
+    /// time a location is asked for.
+    pub fn location(&self, offset: usize) -> SourceLocation {
+        self.lines.location(self.source, offset)
+    }
+
     #[inline(always)]
     #[cfg_attr(not(any(debug_assertions, test)), allow(dead_code))]
     pub(crate) fn trace_event(
@@ -126,9 +171,41 @@ impl<'html, 'query: 'html> Store<'html, 'query> {
             .iter_from(QueryId(0))
             .find(|q| q.query == query)
             .map(|query_node| query_node.elements.start())
+            .filter(|element_id| !element_id.is_null())
             .map(|element_id| self.elements.iter_from(element_id))
     }
 
+    /// Copies the matches out into an [`OwnedStore`], which has the same
+    /// accessors but borrows neither the document nor the queries, so it can
+    /// outlive both, or be sent to another thread.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use scah::{OwnedStore, Query, Save, parse};
+    ///
+    /// fn links(html: String) -> OwnedStore {
+    ///     let queries = &[Query::all("a", Save::all())
+    ///         .expect("valid selector")
+    ///         .build()];
+    ///     parse(&html, queries).into_owned()
+    /// }
+    ///
+    /// let store = links("<div><a href='x'>Link</a></div>".to_string());
+    /// let a = store.get("a").unwrap().next().unwrap();
+    /// assert_eq!(a.attribute(&store, "href"), Some("x"));
+    /// assert_eq!(a.location.line, 1);
+    /// ```
+    pub fn into_owned(self) -> OwnedStore {
+        let mut owned = OwnedStore {
+            encoding: self.encoding,
+            ..OwnedStore::default()
+        };
+        let window = SourceWindow::new(self.source, 0, SourceLocation { line: 1, column: 1 });
+        owned.extend_from(&self, &window);
+        owned
+    }
+
     fn link_query_to_query(&mut self, query: QueryId, mut root: QueryId) {
         loop {
             if root == query {
@@ -161,6 +238,11 @@ impl<'html, 'query: 'html> Store<'html, 'query> {
     fn link_element_to_query(&mut self, query: QueryId, element: ElementId) {
         let id = self.queries[query].elements.end();
 
+        // Every element of the list was unlinked.
+        if id.is_null() {
+            self.queries[query].elements = Span::new(element);
+            return;
+        }
         if id == element {
             return;
         }
@@ -176,10 +258,21 @@ impl<'html, 'query: 'html> Store<'html, 'query> {
         selection: &QuerySection<'query>,
         element: crate::XHtmlElement<'html>,
     ) -> ElementId {
+        self.push_linked(from, selection, &element).0
+    }
+
+    pub(crate) fn push_linked(
+        &mut self,
+        from: ElementId,
+        selection: &QuerySection<'query>,
+        element: &crate::XHtmlElement<'html>,
+    ) -> (ElementId, ElementLink) {
         let new_element = Element {
             name: element.name,
             class: element.class,
             id: element.id,
+            start_tag_range: element.start_tag.clone(),
+            outer_range: element.start_tag.clone(),
             attributes: self.attributes.attribute_slice_to_range(element.attributes),
             ..Default::default()
         };
@@ -207,6 +300,8 @@ impl<'html, 'query: 'html> Store<'html, 'query> {
         let index = ElementId(self.elements.len());
         self.elements.push(new_element);
 
+        let previous =
+            existing_id.map_or(ElementId::default(), |id| self.queries[id].elements.end());
         let query_id = match existing_id {
             Some(id) => id,
             None => {
@@ -237,7 +332,160 @@ impl<'html, 'query: 'html> Store<'html, 'query> {
         // element.next_sibling = Some(index);
         // children.last_element = index;
 
-        index
+        (
+            index,
+            ElementLink {
+                query: query_id,
+                previous,
+            },
+        )
+    }
+
+    /// Takes an element pushed with [`Store::push_linked`] back out of its
+    /// query's list. Elements pushed after it must have been unlinked first
+    /// if they are to go too, as `link` is only valid until then.
+    ///
+    /// When nothing was saved after it, the element is rolled back out of the
+    /// arena. Otherwise it stays, along with the results of its child queries,
+    /// until [`Store::compact`] drops them. Its attributes always wait for
+    /// `compact`, as other queries may have saved the same element, or be
+    /// waiting to, with the same range of them.
+    pub(crate) fn unlink(&mut self, element: ElementId, link: ElementLink) {
+        let next = self.elements[element].next_sibling.take();
+        if link.previous.is_null() {
+            self.queries[link.query]
+                .elements
+                .set_start(next.unwrap_or_default());
+        } else {
+            self.elements[link.previous].next_sibling = next;
+        }
+
+        let elements = &mut self.queries[link.query].elements;
+        if elements.end() == element {
+            if link.previous.is_null() {
+                *elements = Span::new(ElementId::default());
+            } else {
+                elements.set_end(link.previous);
+            }
+        }
+
+        if element.index() + 1 != self.elements.len()
+            || self.elements[element].first_child_query.is_some()
+        {
+            self.unlinked = true;
+            return;
+        }
+        if let Some(removed) = self.elements.pop()
+            && removed.attributes.is_some()
+        {
+            self.unlinked = true;
+        }
+        // Its id will be reused.
+        self.text_content
+            .detached_readers
+            .retain(|&reader| reader != element);
+    }
+
+    /// Drops the elements [`Store::unlink`] left in the arenas, with their
+    /// attributes and the results of their child queries, and renumbers the
+    /// others. Only done once parsing has ended, as the parser holds on to
+    /// element ids until then.
+    pub(crate) fn compact(&mut self) {
+        if !std::mem::take(&mut self.unlinked) {
+            return;
+        }
+
+        // Whatever the top-level queries still lead to.
+        let mut live_elements = vec![false; self.elements.len()];
+        let mut live_queries = vec![false; self.queries.len()];
+        let mut lists = Vec::new();
+        if !self.queries.is_empty() {
+            lists.push(QueryId(0));
+        }
+        while let Some(first) = lists.pop() {
+            let mut query = Some(first);
+            while let Some(query_id) = query {
+                live_queries[query_id.index()] = true;
+                let mut element_id = self.queries[query_id].elements.start();
+                while !element_id.is_null() {
+                    live_elements[element_id.index()] = true;
+                    let element = &self.elements[element_id];
+                    lists.extend(element.first_child_query);
+                    element_id = element.next_sibling.unwrap_or_default();
+                }
+                query = self.queries[query_id].next_sibling;
+            }
+        }
+        let mut live_attributes = vec![false; self.attributes.len()];
+        for (element, live) in self.elements.iter().zip(&live_elements) {
+            if let Some(range) = element.attributes.as_ref().filter(|_| *live) {
+                live_attributes[range.start as usize..range.end as usize].fill(true);
+            }
+        }
+
+        let element_ids = compacted_ids(&mut self.elements, &live_elements);
+        let query_ids = compacted_ids(&mut self.queries, &live_queries);
+        let attribute_ids = compacted_ids(&mut self.attributes, &live_attributes);
+        let element_id = |id: ElementId| {
+            if id.is_null() {
+                id
+            } else {
+                ElementId(element_ids[id.index()])
+            }
+        };
+        for element in self.elements.iter_mut() {
+            element.next_sibling = element.next_sibling.map(element_id);
+            element.first_child_query = element
+                .first_child_query
+                .map(|id| QueryId(query_ids[id.index()]));
+            element.attributes = element.attributes.as_ref().map(|range| {
+                attribute_ids[range.start as usize] as u32..attribute_ids[range.end as usize] as u32
+            });
+        }
+        for query in self.queries.iter_mut() {
+            query.next_sibling = query.next_sibling.map(|id| QueryId(query_ids[id.index()]));
+            let end = element_id(query.elements.end());
+            query.elements = Span::new(element_id(query.elements.start()));
+            query.elements.set_end(end);
+        }
+    }
+
+    /// Moves the text content read since `start`, the contents of a
+    /// `<template>` that just closed, out of the way of the elements still
+    /// open around it. The elements saved from `first` on were inside the
+    /// template, and keep their text. See [`Store::reattach_text`].
+    pub(crate) fn detach_text(&mut self, start: usize, first: ElementId) {
+        let text = &mut self.text_content;
+        if text.readers == 0 || text.len() <= start {
+            return;
+        }
+        let offset = text.detached.len();
+        text.detached.extend_from_slice(&text.content[start..]);
+        text.content.truncate(start);
+        for index in first.index()..self.elements.len() {
+            let id = ElementId(index);
+            // Those of templates inside it were moved already.
+            if self.text_content.detached_readers.contains(&id) {
+                continue;
+            }
+            if let Some(range) = &mut self.elements[id].text_content {
+                *range = (range.start - start + offset)..(range.end - start + offset);
+                self.text_content.detached_readers.push(id);
+            }
+        }
+    }
+
+    /// Appends the text [`Store::detach_text`] held back, once no element
+    /// saving its text content is open anymore.
+    pub(crate) fn reattach_text(&mut self) {
+        let text = &mut self.text_content;
+        let offset = text.len();
+        text.content.append(&mut text.detached);
+        for id in std::mem::take(&mut text.detached_readers) {
+            if let Some(range) = &mut self.elements[id].text_content {
+                *range = (range.start + offset)..(range.end + offset);
+            }
+        }
     }
 
     pub fn set_content(
@@ -272,6 +520,22 @@ impl<'html, 'query: 'html> Store<'html, 'query> {
     }
 }
 
+/// Keeps the items of `arena` marked `live`, and returns the new index of
+/// each old one, or of the end of the arena for `live.len()`.
+fn compacted_ids<T, I>(arena: &mut Arena<T, I>, live: &[bool]) -> Vec<usize> {
+    let mut ids = Vec::with_capacity(live.len() + 1);
+    let mut kept = 0;
+    for &live in live {
+        ids.push(kept);
+        kept += usize::from(live);
+    }
+    ids.push(kept);
+
+    let mut live = live.iter();
+    arena.retain(|_| live.next().copied().unwrap_or(true));
+    ids
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{Query, Save};
@@ -635,4 +899,63 @@ mod tests {
         assert!(store.get("a").is_some());
         assert_eq!(store.get("a").iter().count(), 1);
     }
+
+    #[test]
+    fn test_unlink_takes_elements_out_of_their_query() {
+        fn push<'a>(
+            store: &mut Store<'a, 'a>,
+            section: &QuerySection<'a>,
+            name: &'a str,
+        ) -> (ElementId, ElementLink) {
+            let element = crate::XHtmlElement {
+                name,
+                ..Default::default()
+            };
+            store.push_linked(ElementId::default(), section, &element)
+        }
+        fn names<'a>(store: &'a Store) -> Vec<&'a str> {
+            let start = store.queries[QueryId(0)].elements.start();
+            store.elements.iter_from(start).map(|e| e.name).collect()
+        }
+
+        let mut store = Store::default();
+        let q = Query::all("li", Save::all()).unwrap();
+        let section = &q.selection[0];
+        let (a, a_link) = push(&mut store, section, "a");
+        let (b, b_link) = push(&mut store, section, "b");
+        let (c, c_link) = push(&mut store, section, "c");
+
+        // Latest first, as the parser resolves them.
+        store.unlink(c, c_link);
+        assert_eq!(names(&store), vec!["a", "b"]);
+        store.unlink(b, b_link);
+        assert_eq!(names(&store), vec!["a"]);
+        store.unlink(a, a_link);
+        assert!(names(&store).is_empty());
+        assert!(store.get("li").is_none());
+        // Nothing was saved after them, so they were rolled back.
+        assert!(store.elements.is_empty());
+
+        push(&mut store, section, "d");
+        assert_eq!(names(&store), vec!["d"]);
+
+        let (e, e_link) = push(&mut store, section, "e");
+        push(&mut store, section, "f");
+        store.unlink(e, e_link);
+        assert_eq!(names(&store), vec!["d", "f"]);
+        assert_eq!(store.elements.len(), 3);
+        assert_eq!(
+            store.queries[QueryId(0)].elements,
+            Span::from(ElementId(0), ElementId(2))
+        );
+
+        // `e` is left behind `f` until the store is compacted.
+        store.compact();
+        assert_eq!(names(&store), vec!["d", "f"]);
+        assert_eq!(store.elements.len(), 2);
+        assert_eq!(
+            store.queries[QueryId(0)].elements,
+            Span::from(ElementId(0), ElementId(1))
+        );
+    }
 }
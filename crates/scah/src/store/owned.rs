@@ -0,0 +1,472 @@
+use std::ops::{Deref, Range};
+
+use super::arena::span::Span;
+use super::arena::{Arena, Node, id};
+use super::location::SourceWindow;
+use super::{Element, Nullable, SourceLocation, Store};
+use crate::Doctype;
+
+/// The result set of a parse that doesn't keep its source, as returned by
+/// [`StreamingParser::finish`](crate::StreamingParser::finish) and
+/// [`Store::into_owned`].
+///
+/// It has the layout and accessors of a [`Store`], with every string copied
+/// out of the source: only the elements that can be reached from
+/// [`OwnedStore::get`] are kept.
+///
+/// # Example
+///
+/// ```rust
+/// use scah::{Query, Save, StreamingParser};
+///
+/// let queries = &[Query::all("a", Save::all())
+///     .expect("valid selector")
+///     .build()];
+/// let mut parser = StreamingParser::new(queries);
+/// parser.feed(b"<div><a href='x'>Li");
+/// parser.feed(b"nk</a></div>");
+/// let store = parser.finish();
+///
+/// let a = store.get("a").unwrap().next().unwrap();
+/// assert_eq!(a.attribute(&store, "href"), Some("x"));
+/// assert_eq!(a.text_content(&store), Some("Link"));
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct OwnedStore {
+    /// Arena of matched elements.
+    pub elements: Arena<OwnedElement, id::ElementId>,
+    /// Arena of attributes belonging to matched elements.
+    pub attributes: Arena<OwnedAttribute, id::AttributeId>,
+    /// Arena of query nodes that link selectors to their matched elements.
+    pub queries: Arena<OwnedQueryNode, id::QueryId>,
+    /// The text content of the matched elements, one after the other.
+    pub text_content: String,
+    /// The parts of the source the saved `inner_html` and `outer_html` of
+    /// the matched elements cover, each copied once.
+    pub html: String,
+    /// The document's first `<!DOCTYPE>` outside of any element, if any.
+    pub doctype: Option<OwnedDoctype>,
+    /// See [`Store::encoding`].
+    pub encoding: &'static str,
+}
+
+impl Default for OwnedStore {
+    fn default() -> Self {
+        Self {
+            elements: Arena::new(),
+            attributes: Arena::new(),
+            queries: Arena::new(),
+            text_content: String::new(),
+            html: String::new(),
+            doctype: None,
+            encoding: "UTF-8",
+        }
+    }
+}
+
+/// An [`Element`] whose strings are owned, see [`OwnedStore`]. Its accessor
+/// methods are those of an [`Element`], with an [`OwnedStore`] in place of
+/// the [`Store`].
+#[derive(Debug, PartialEq)]
+pub struct OwnedElement {
+    /// The tag name (e.g. `"a"`, `"div"`, `"section"`).
+    pub name: String,
+    /// The value of the `class` attribute as written in the source, if
+    /// present.
+    pub class: Option<String>,
+    /// The value of the `id` attribute as written in the source, if present.
+    pub id: Option<String>,
+    /// Internal range into [`OwnedStore::html`].
+    /// Use [`OwnedElement::inner_html`] to get the actual `&str`.
+    pub inner_html: Option<Range<usize>>,
+    /// Internal range into [`OwnedStore::html`].
+    /// Use [`OwnedElement::outer_html`] to get the actual `&str`.
+    pub outer_html: Option<Range<usize>>,
+    /// Internal range into [`OwnedStore::text_content`].
+    /// Use [`OwnedElement::text_content`] to get the actual `&str`.
+    pub text_content: Option<Range<usize>>,
+    /// Internal range into the attribute arena.
+    /// Use [`OwnedElement::attributes`] or [`OwnedElement::attribute`] instead.
+    pub attributes: Option<Range<u32>>,
+    /// See [`Element::start_tag_range`].
+    pub start_tag_range: Range<usize>,
+    /// See [`Element::outer_range`].
+    pub outer_range: Range<usize>,
+    /// The line and column where the element's start tag begins.
+    pub location: SourceLocation,
+
+    pub first_child_query: Option<id::QueryId>,
+    pub next_sibling: Option<id::ElementId>,
+}
+
+/// An [`Attribute`](crate::Attribute) whose strings are owned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedAttribute {
+    pub key: String,
+    /// The value with its character references decoded.
+    pub value: Option<String>,
+    /// The value as written in the source.
+    pub raw_value: Option<String>,
+}
+
+/// A [`Doctype`] whose strings are owned.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct OwnedDoctype {
+    /// The root element name (`"html"`), if any.
+    pub name: Option<String>,
+    /// The quoted identifier after `PUBLIC`, if any.
+    pub public_id: Option<String>,
+    /// The quoted identifier after `SYSTEM`, or after the public one.
+    pub system_id: Option<String>,
+}
+
+/// A `QueryNode` whose selector is owned.
+#[derive(Debug, PartialEq, Default)]
+pub struct OwnedQueryNode {
+    pub query: String,
+    pub next_sibling: Option<id::QueryId>,
+    pub elements: Span<id::ElementId>,
+}
+
+impl Node<id::ElementId> for OwnedElement {
+    fn next_sibling(&self) -> Option<id::ElementId> {
+        self.next_sibling
+    }
+}
+
+impl Node<id::QueryId> for OwnedQueryNode {
+    fn next_sibling(&self) -> Option<id::QueryId> {
+        self.next_sibling
+    }
+}
+
+impl From<&Doctype<'_>> for OwnedDoctype {
+    fn from(doctype: &Doctype<'_>) -> Self {
+        Self {
+            name: doctype.name.map(str::to_string),
+            public_id: doctype.public_id.map(str::to_string),
+            system_id: doctype.system_id.map(str::to_string),
+        }
+    }
+}
+
+impl OwnedStore {
+    /// Look up all elements that matched a given CSS selector string, as
+    /// [`Store::get`] does.
+    pub fn get<'a>(&'a self, query: &str) -> Option<impl Iterator<Item = &'a OwnedElement>> {
+        if self.queries.is_empty() {
+            return None;
+        }
+
+        self.queries
+            .iter_from(id::QueryId(0))
+            .find(|q| q.query == query)
+            .map(|query_node| query_node.elements.start())
+            .filter(|element_id| !element_id.is_null())
+            .map(|element_id| self.elements.iter_from(element_id))
+    }
+
+    /// Copies the elements `store` matched in `window` after the ones
+    /// already here, merging its top-level queries into these by selector.
+    pub(crate) fn extend_from(&mut self, store: &Store<'_, '_>, window: &SourceWindow<'_>) {
+        if self.doctype.is_none() {
+            self.doctype = store.doctype.as_ref().map(OwnedDoctype::from);
+        }
+        if store.queries.is_empty() {
+            return;
+        }
+
+        let plan = CopyPlan {
+            locations: window.locations(
+                &store
+                    .elements
+                    .iter()
+                    .map(|element| element.start_tag_range.start)
+                    .collect::<Vec<_>>(),
+            ),
+            html: self.copy_html(store, window.source),
+        };
+        for query_node in store.queries.iter_from(id::QueryId(0)) {
+            let root = self.root_query(query_node.query);
+            self.extend_query(root, store, query_node.elements.start(), window, &plan);
+        }
+    }
+
+    /// Appends the parts of `source` that the elements' saved HTML covers to
+    /// [`OwnedStore::html`], once each however many elements they hold.
+    fn copy_html(&mut self, store: &Store<'_, '_>, source: &str) -> Vec<CopiedHtml> {
+        let mut ranges: Vec<_> = store
+            .elements
+            .iter()
+            .flat_map(|element| [element.inner_html, element.outer_html])
+            .flatten()
+            .filter_map(|html| range_in(source, html))
+            .collect();
+        ranges.sort_unstable_by_key(|range| (range.start, std::cmp::Reverse(range.end)));
+
+        let mut copied: Vec<CopiedHtml> = Vec::new();
+        for range in ranges {
+            if let Some(last) = copied.last_mut()
+                && range.start < last.source.end
+            {
+                last.source.end = last.source.end.max(range.end);
+                continue;
+            }
+            copied.push(CopiedHtml {
+                source: range,
+                at: 0,
+            });
+        }
+        for html in &mut copied {
+            html.at = self.html.len();
+            self.html.push_str(&source[html.source.clone()]);
+        }
+        copied
+    }
+
+    /// The top-level query node of `query`, added if there is none.
+    fn root_query(&mut self, query: &str) -> id::QueryId {
+        let mut last = None;
+        if !self.queries.is_empty() {
+            for query_node in self.queries.iter_from(id::QueryId(0)) {
+                let query_id = unsafe { self.queries.index_of(query_node) };
+                if query_node.query == query {
+                    return query_id;
+                }
+                last = Some(query_id);
+            }
+        }
+        let query_id = self.push_query(query);
+        if let Some(last) = last {
+            self.queries[last].next_sibling = Some(query_id);
+        }
+        query_id
+    }
+
+    fn push_query(&mut self, query: &str) -> id::QueryId {
+        self.queries.push(OwnedQueryNode {
+            query: query.to_string(),
+            ..Default::default()
+        });
+        id::QueryId(self.queries.len() - 1)
+    }
+
+    /// Copies the list of elements from `first` on to the end of `query`'s.
+    fn extend_query(
+        &mut self,
+        query: id::QueryId,
+        store: &Store<'_, '_>,
+        first: id::ElementId,
+        window: &SourceWindow<'_>,
+        plan: &CopyPlan,
+    ) {
+        if first.is_null() {
+            return;
+        }
+        for element in store.elements.iter_from(first) {
+            let copy = self.copy_element(store, element, window, plan);
+            let last = self.queries[query].elements.end();
+            if last.is_null() {
+                self.queries[query].elements = Span::new(copy);
+            } else {
+                self.elements[last].next_sibling = Some(copy);
+                self.queries[query].elements.set_end(copy);
+            }
+        }
+    }
+
+    fn copy_element(
+        &mut self,
+        store: &Store<'_, '_>,
+        element: &Element<'_>,
+        window: &SourceWindow<'_>,
+        plan: &CopyPlan,
+    ) -> id::ElementId {
+        let attributes = element.attributes(store).map(|attributes| {
+            let start = self.attributes.len() as u32;
+            self.attributes
+                .extend(attributes.iter().map(|attribute| OwnedAttribute {
+                    key: attribute.key.to_string(),
+                    value: attribute.value.as_deref().map(str::to_string),
+                    raw_value: attribute.raw_value.map(str::to_string),
+                }));
+            start..self.attributes.len() as u32
+        });
+        let text_content = element.text_content(store).map(|text| {
+            let start = self.text_content.len();
+            self.text_content.push_str(text);
+            start..self.text_content.len()
+        });
+        let mut html = |html: Option<&str>| html.map(|html| self.html_range(html, window, plan));
+        let inner_html = html(element.inner_html);
+        let outer_html = html(element.outer_html);
+        let offset = |range: &Range<usize>| range.start + window.offset..range.end + window.offset;
+
+        let element_id = id::ElementId(self.elements.len());
+        self.elements.push(OwnedElement {
+            name: element.name.to_string(),
+            class: element.class.map(str::to_string),
+            id: element.id.map(str::to_string),
+            inner_html,
+            outer_html,
+            text_content,
+            attributes,
+            start_tag_range: offset(&element.start_tag_range),
+            outer_range: offset(&element.outer_range),
+            location: plan.locations[unsafe { store.elements.index_of(element) }.index()],
+            first_child_query: None,
+            next_sibling: None,
+        });
+
+        let Some(first_child_query) = element.first_child_query else {
+            return element_id;
+        };
+        let mut last: Option<id::QueryId> = None;
+        for query_node in store.queries.iter_from(first_child_query) {
+            let query = self.push_query(query_node.query);
+            match last {
+                Some(last) => self.queries[last].next_sibling = Some(query),
+                None => self.elements[element_id].first_child_query = Some(query),
+            }
+            last = Some(query);
+            self.extend_query(query, store, query_node.elements.start(), window, plan);
+        }
+        element_id
+    }
+
+    /// Where a saved `html` string of the window is in [`OwnedStore::html`].
+    fn html_range(
+        &mut self,
+        html: &str,
+        window: &SourceWindow<'_>,
+        plan: &CopyPlan,
+    ) -> Range<usize> {
+        let Some(range) = range_in(window.source, html) else {
+            // Not a slice of the source: copy it on its own.
+            let start = self.html.len();
+            self.html.push_str(html);
+            return start..self.html.len();
+        };
+        let index = plan
+            .html
+            .partition_point(|copied| copied.source.start <= range.start)
+            - 1;
+        let copied = &plan.html[index];
+        let start = copied.at + range.start - copied.source.start;
+        start..start + range.len()
+    }
+}
+
+/// What [`OwnedStore::extend_from`] works out once for the whole store.
+struct CopyPlan {
+    /// The location of each element, by id.
+    locations: Vec<SourceLocation>,
+    /// The parts of the source copied to [`OwnedStore::html`], in order.
+    html: Vec<CopiedHtml>,
+}
+
+struct CopiedHtml {
+    source: Range<usize>,
+    // Where it starts in `OwnedStore::html`.
+    at: usize,
+}
+
+/// The range of `slice` in `source`, if it is a part of it.
+fn range_in(source: &str, slice: &str) -> Option<Range<usize>> {
+    let start = (slice.as_ptr() as usize).checked_sub(source.as_ptr() as usize)?;
+    (start + slice.len() <= source.len()).then_some(start..start + slice.len())
+}
+
+impl OwnedElement {
+    /// The tag name, as [`Element::name`] returns it.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The value of the `class` attribute as written, as [`Element::class`]
+    /// returns it.
+    pub fn class(&self) -> Option<&str> {
+        self.class.as_deref()
+    }
+
+    /// The value of the `id` attribute as written, as [`Element::id`]
+    /// returns it.
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// The line and column where the element's start tag begins, as
+    /// [`Element::location`] returns it.
+    pub fn location(&self, _store: &OwnedStore) -> SourceLocation {
+        self.location
+    }
+
+    /// Look up child elements matched by a **nested query**, as
+    /// [`Element::get`] does.
+    pub fn get<'a>(
+        &self,
+        store: &'a OwnedStore,
+        key: &str,
+    ) -> Option<impl Iterator<Item = &'a OwnedElement>> {
+        self.first_child_query
+            .and_then(|id| store.queries.iter_from(id).find(|q| q.query == key))
+            .map(|query_node| query_node.elements.start())
+            .filter(|element_id| !element_id.is_null())
+            .map(|element_id| store.elements.iter_from(element_id))
+    }
+
+    /// Return all attributes of this element as a slice, as
+    /// [`Element::attributes`] does.
+    pub fn attributes<'a>(&self, store: &'a OwnedStore) -> Option<&'a [OwnedAttribute]> {
+        self.attributes
+            .as_ref()
+            .map(|range| &store.attributes.deref()[(range.start as usize)..(range.end as usize)])
+    }
+
+    /// Look up a single attribute value by name, with its character
+    /// references decoded, as [`Element::attribute`] does.
+    pub fn attribute<'a>(&self, store: &'a OwnedStore, key: &str) -> Option<&'a str> {
+        self.find_attribute(store, key)
+            .and_then(|attribute| attribute.value.as_deref())
+    }
+
+    /// Like [`OwnedElement::attribute`], but returns the value as written in
+    /// the source.
+    pub fn raw_attribute<'a>(&self, store: &'a OwnedStore, key: &str) -> Option<&'a str> {
+        self.find_attribute(store, key)
+            .and_then(|attribute| attribute.raw_value.as_deref())
+    }
+
+    fn find_attribute<'a>(&self, store: &'a OwnedStore, key: &str) -> Option<&'a OwnedAttribute> {
+        let attributes = self.attributes(store)?;
+        attributes.iter().find(|attr| attr.key == key).or_else(|| {
+            attributes
+                .iter()
+                .find(|attr| attr.key.eq_ignore_ascii_case(key))
+        })
+    }
+
+    /// The element's inner HTML, if it was saved, as
+    /// [`Element::inner_html`] is.
+    pub fn inner_html<'a>(&self, store: &'a OwnedStore) -> Option<&'a str> {
+        self.inner_html
+            .as_ref()
+            .map(|range| &store.html[range.clone()])
+    }
+
+    /// The element's outer HTML, if it was saved, as
+    /// [`Element::outer_html`] is.
+    pub fn outer_html<'a>(&self, store: &'a OwnedStore) -> Option<&'a str> {
+        self.outer_html
+            .as_ref()
+            .map(|range| &store.html[range.clone()])
+    }
+
+    /// Get the element's concatenated text content, as
+    /// [`Element::text_content`] does.
+    pub fn text_content<'a>(&self, store: &'a OwnedStore) -> Option<&'a str> {
+        self.text_content
+            .as_ref()
+            .map(|range| &store.text_content[range.clone()])
+    }
+}
@@ -1,10 +1,21 @@
 use crate::Reader;
+use crate::html::char_ref::decode_text_into;
+use crate::store::ElementId;
 use std::ops::Range;
 
 #[derive(Debug, PartialEq)]
 pub struct TextContent {
     pub(crate) content: Vec<u8>,
     pub(crate) text_start: Option<usize>,
+    // Open elements saving their text content. Text read while there are
+    // none is dropped, as no element would ever see it.
+    pub(crate) readers: usize,
+    // Text of `<template>` contents read while elements around the template
+    // were saving theirs, and the elements saved inside it whose ranges
+    // point into it. It is held back until those elements close, as a
+    // template's contents aren't part of its ancestors' text.
+    pub(crate) detached: Vec<u8>,
+    pub(crate) detached_readers: Vec<ElementId>,
 }
 
 impl TextContent {
@@ -12,6 +23,9 @@ impl TextContent {
         Self {
             content: Vec::new(),
             text_start: None,
+            readers: 0,
+            detached: Vec::new(),
+            detached_readers: Vec::new(),
         }
     }
 
@@ -19,6 +33,9 @@ impl TextContent {
         Self {
             content: Vec::with_capacity(capacity),
             text_start: None,
+            readers: 0,
+            detached: Vec::new(),
+            detached_readers: Vec::new(),
         }
     }
 
@@ -40,11 +57,36 @@ impl TextContent {
         self.content.len() - 1
     }
 
+    /// Appends the text since the start position, decoding its character
+    /// references.
     pub fn push<'html>(&mut self, reader: &Reader<'html>, end_position: usize) -> Option<usize> {
+        self.push_text(reader, end_position, true)
+    }
+
+    /// Appends the text since the start position as written, for the
+    /// contents of raw text elements such as `<script>` and `<style>`.
+    pub fn push_raw<'html>(
+        &mut self,
+        reader: &Reader<'html>,
+        end_position: usize,
+    ) -> Option<usize> {
+        self.push_text(reader, end_position, false)
+    }
+
+    fn push_text<'html>(
+        &mut self,
+        reader: &Reader<'html>,
+        end_position: usize,
+        decode: bool,
+    ) -> Option<usize> {
         // It has to be inside an element, so this is an impossible case other than at initialization
         let Some(start_position) = self.text_start else {
             unreachable!("Their has to be a start position set before pushing text content")
         };
+        if self.readers == 0 {
+            self.text_start = None;
+            return None;
+        }
         let text = reader.slice(start_position..end_position).trim();
 
         // TODO: In browsers `\n` is ignored and multiple ` ` are tretead as one.
@@ -57,7 +99,11 @@ impl TextContent {
             return None;
         }
 
-        self.content.extend_from_slice(text.as_bytes());
+        if decode {
+            decode_text_into(text, &mut self.content);
+        } else {
+            self.content.extend_from_slice(text.as_bytes());
+        }
         self.content.push(b' ');
         Some(self.get_position())
     }
@@ -83,6 +129,7 @@ mod tests {
     #[test]
     fn test_textcontent() {
         let mut content = TextContent::new();
+        content.readers = 1;
         let reader = Reader::new("Hello World");
         content.set_start(0);
         content.set_start(0);
@@ -97,4 +144,13 @@ mod tests {
         content.push(&reader, 11);
         assert_eq!(content.content, b"Hello Hello World ");
     }
+
+    #[test]
+    fn test_textcontent_without_readers() {
+        let mut content = TextContent::new();
+        let reader = Reader::new("Hello World");
+        content.set_start(0);
+        assert_eq!(content.push(&reader, 5), None);
+        assert!(content.content.is_empty());
+    }
 }
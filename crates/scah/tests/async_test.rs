@@ -0,0 +1,136 @@
+#![cfg(any(feature = "futures", feature = "tokio"))]
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::executor::block_on;
+use scah::{OwnedStore, Query, Save};
+
+const HTML: &str = "<ul><li><a href='/one'>Oné</a></li><li><a href='/two'>Two</a></li></ul>";
+
+/// Yields one byte per read, and is pending before each of them.
+struct Trickle {
+    html: &'static [u8],
+    pending: bool,
+}
+
+impl Trickle {
+    fn new(html: &'static str) -> Self {
+        Self {
+            html: html.as_bytes(),
+            pending: false,
+        }
+    }
+
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Option<u8>> {
+        self.pending = !self.pending;
+        if self.pending {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        let Some((&byte, rest)) = self.html.split_first() else {
+            return Poll::Ready(None);
+        };
+        self.html = rest;
+        Poll::Ready(Some(byte))
+    }
+}
+
+#[cfg(feature = "futures")]
+impl futures::io::AsyncRead for Trickle {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buffer: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().poll(cx).map(|byte| {
+            Ok(byte.map_or(0, |byte| {
+                buffer[0] = byte;
+                1
+            }))
+        })
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncRead for Trickle {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buffer: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.get_mut().poll(cx).map(|byte| {
+            if let Some(byte) = byte {
+                buffer.put_slice(&[byte]);
+            }
+            Ok(())
+        })
+    }
+}
+
+fn queries() -> [Query<'static>; 1] {
+    [Query::all("li > a", Save::all()).unwrap().build()]
+}
+
+fn links(store: &OwnedStore) -> Vec<(&str, &str)> {
+    store
+        .get("li > a")
+        .unwrap()
+        .map(|a| {
+            (
+                a.attribute(store, "href").unwrap(),
+                a.text_content(store).unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn test_parse_async_read_one_byte_at_a_time() {
+    let queries = queries();
+    let store = block_on(scah::parse_async_read(Trickle::new(HTML), &queries)).unwrap();
+    assert_eq!(links(&store), [("/one", "Oné"), ("/two", "Two")]);
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn test_parse_stream_of_chunks() {
+    let queries = queries();
+    let chunks = HTML.as_bytes().chunks(3).map(Ok::<_, io::Error>);
+    let store = block_on(scah::parse_stream(futures::stream::iter(chunks), &queries)).unwrap();
+    assert_eq!(links(&store), [("/one", "Oné"), ("/two", "Two")]);
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn test_parse_stream_returns_its_error() {
+    let queries = queries();
+    let chunks = [Ok("<ul><li>"), Err("connection reset")];
+    let error = block_on(scah::parse_stream(futures::stream::iter(chunks), &queries)).unwrap_err();
+    assert_eq!(error, "connection reset");
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn test_parse_stream_stops_once_queries_are_done() {
+    use futures::StreamExt;
+
+    let queries = [Query::first("a", Save::all()).unwrap().build()];
+    let body = futures::stream::iter(["<div><a href='/first'>First</a>"])
+        .chain(futures::stream::repeat("<p>filler</p>"))
+        .map(Ok::<_, io::Error>);
+    let store = block_on(scah::parse_stream(body, &queries)).unwrap();
+
+    let a = store.get("a").unwrap().next().unwrap();
+    assert_eq!(a.attribute(&store, "href"), Some("/first"));
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn test_parse_tokio_read_one_byte_at_a_time() {
+    let queries = queries();
+    let store = block_on(scah::parse_tokio_read(Trickle::new(HTML), &queries)).unwrap();
+    assert_eq!(links(&store), [("/one", "Oné"), ("/two", "Two")]);
+}
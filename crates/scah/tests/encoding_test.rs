@@ -0,0 +1,87 @@
+#![cfg(feature = "encoding")]
+
+use scah::{Encoding, Query, Save, decode_html, parse};
+
+fn encoding(label: &str) -> &'static Encoding {
+    Encoding::for_label(label.as_bytes()).unwrap()
+}
+
+fn texts(bytes: &[u8], transport_label: Option<&str>) -> (&'static str, Vec<String>) {
+    let queries = &[Query::all("p", Save::all()).unwrap().build()];
+    let document = decode_html(bytes, transport_label);
+    let store = document.parse(queries);
+    let texts = store
+        .get("p")
+        .unwrap()
+        .map(|p| p.text_content(&store).unwrap().to_string())
+        .collect();
+    (store.encoding, texts)
+}
+
+#[test]
+fn test_shift_jis_meta_charset() {
+    let (bytes, _, _) = encoding("shift_jis").encode("<p>日本語</p><p>テスト</p>");
+    let mut html = b"<head><meta charset=\"Shift_JIS\"></head>".to_vec();
+    html.extend_from_slice(&bytes);
+    assert_eq!(
+        texts(&html, None),
+        (
+            "Shift_JIS",
+            vec!["日本語".to_string(), "テスト".to_string()]
+        )
+    );
+}
+
+#[test]
+fn test_windows_1252_from_http_equiv() {
+    let html = b"<meta http-equiv=Content-Type content='text/html; charset=iso-8859-1'><p>na\xEFve \x93quotes\x94</p>";
+    assert_eq!(
+        texts(html, None),
+        (
+            "windows-1252",
+            vec!["naïve \u{201C}quotes\u{201D}".to_string()]
+        )
+    );
+}
+
+#[test]
+fn test_utf16_with_byte_order_mark() {
+    let mut html = vec![0xFF, 0xFE];
+    for unit in "<p>Ünïcödé</p>".encode_utf16() {
+        html.extend_from_slice(&unit.to_le_bytes());
+    }
+    assert_eq!(
+        texts(&html, None),
+        ("UTF-16LE", vec!["Ünïcödé".to_string()])
+    );
+}
+
+#[test]
+fn test_transport_label() {
+    let (html, _, _) = encoding("euc-kr").encode("<meta charset=utf-8><p>한국어</p>");
+    assert_eq!(
+        texts(&html, Some("euc-kr")),
+        ("EUC-KR", vec!["한국어".to_string()])
+    );
+}
+
+#[test]
+fn test_utf8_input_is_borrowed() {
+    let html = "<p>déjà vu</p>".as_bytes();
+    let document = decode_html(html, None);
+    assert!(matches!(document.html, std::borrow::Cow::Borrowed(_)));
+    assert_eq!(texts(html, None), ("UTF-8", vec!["déjà vu".to_string()]));
+
+    let queries = &[Query::all("p", Save::all()).unwrap().build()];
+    assert_eq!(parse("<p></p>", queries).encoding, "UTF-8");
+}
+
+#[test]
+fn test_invalid_utf8_is_replaced() {
+    // Valid UTF-8 up to a stray byte, declared as UTF-8.
+    let html = b"<meta charset=utf-8><p>caf\xC3\xA9 \xFF</p>";
+    assert_eq!(
+        texts(html, None),
+        ("UTF-8", vec!["café \u{FFFD}".to_string()])
+    );
+}
@@ -0,0 +1,112 @@
+use super::helpers::{attr, elements, parse_all, texts};
+
+type Attributes = &'static [(&'static str, Option<&'static str>)];
+
+// Open tags of an `<x>` element, and the attributes the tokenizer reads from
+// them (`class` and `id` aside).
+const CORPUS: &[(&str, Attributes)] = &[
+    ("<x>", &[]),
+    ("<x a>", &[("a", None)]),
+    ("<x a=b>", &[("a", Some("b"))]),
+    ("<x a='b'>", &[("a", Some("b"))]),
+    ("<x a=\"b\">", &[("a", Some("b"))]),
+    ("<x\ta\n=\r\n'b'\x0cc>", &[("a", Some("b")), ("c", None)]),
+    ("<x a  =  b   c>", &[("a", Some("b")), ("c", None)]),
+    ("<x a=>", &[("a", Some(""))]),
+    ("<x a=''>", &[("a", Some(""))]),
+    ("<x a=\"b's\">", &[("a", Some("b's"))]),
+    ("<x a='say \"hi\"'>", &[("a", Some("say \"hi\""))]),
+    ("<x k'ey=\"value\">", &[("k'ey", Some("value"))]),
+    ("<x \"key\"=value>", &[("\"key\"", Some("value"))]),
+    (
+        "<x key=\"v\"alue\">",
+        &[("key", Some("v")), ("alue\"", None)],
+    ),
+    ("<x a='b'c='d'>", &[("a", Some("b")), ("c", Some("d"))]),
+    ("<x a=b\"c'd>", &[("a", Some("b\"c'd"))]),
+    ("<x a=b=c>", &[("a", Some("b=c"))]),
+    ("<x =a>", &[("=a", None)]),
+    ("<x a<b>", &[("a<b", None)]),
+    ("<x a='>'>", &[("a", Some(">"))]),
+    ("<x a/b>", &[("a", None), ("b", None)]),
+    ("<x a=/b/>", &[("a", Some("/b/"))]),
+    ("<x a='b'/>", &[("a", Some("b"))]),
+    ("<x / a / >", &[("a", None)]),
+    ("<x a=1 b=2 a=3>", &[("a", Some("1")), ("b", Some("2"))]),
+    ("<x a b=1 a=2>", &[("a", None), ("b", Some("1"))]),
+];
+
+#[test]
+fn attribute_corpus() {
+    for (html, expected) in CORPUS {
+        let store = parse_all(html, &["x"]);
+        let x = elements(&store, "x");
+        assert_eq!(x.len(), 1, "{html}");
+
+        let attributes = x[0]
+            .attributes(&store)
+            .unwrap_or_default()
+            .iter()
+            .map(|attribute| (attribute.key, attribute.raw_value))
+            .collect::<Vec<_>>();
+        assert_eq!(&attributes, expected, "{html}");
+    }
+}
+
+#[test]
+fn whitespace_between_attributes_is_not_part_of_names() {
+    let html = "<a\n\tclass=\"link\"\n\thref=\"/x\"\n>go</a>";
+    let store = parse_all(html, &["a.link", "a[href='/x']"]);
+
+    assert_eq!(texts(&store, "a.link"), vec![Some("go")]);
+    assert_eq!(elements(&store, "a[href='/x']").len(), 1);
+}
+
+#[test]
+fn duplicate_attributes_keep_the_first() {
+    let html = "<p id=a class=x id=b class=y title=1 title=2>t</p>";
+    let store = parse_all(html, &["#a.x", "#b", ".y", "p"]);
+
+    assert_eq!(elements(&store, "#a.x").len(), 1);
+    assert_eq!(elements(&store, "#b").len(), 0);
+    assert_eq!(elements(&store, ".y").len(), 0);
+    assert_eq!(attr(&store, "p", "title"), vec![Some("1")]);
+}
+
+#[test]
+fn self_closing_slash_only_closes_foreign_elements() {
+    let html = "<div/><span>inside</span></div><svg><rect/><circle/></svg>";
+    let store = parse_all(html, &["div > span", "rect > circle", "svg > circle"]);
+
+    // `<div/>` is an open tag, so the span is its child.
+    assert_eq!(elements(&store, "div > span").len(), 1);
+    assert_eq!(elements(&store, "rect > circle").len(), 0);
+    assert_eq!(elements(&store, "svg > circle").len(), 1);
+}
+
+#[test]
+fn void_elements_with_a_trailing_slash() {
+    let html = "<p><img src=a.png/><br/>after</p>";
+    let store = parse_all(html, &["img", "p"]);
+
+    // The `/` belongs to the unquoted value.
+    assert_eq!(attr(&store, "img", "src"), vec![Some("a.png/")]);
+    assert_eq!(texts(&store, "p"), vec![Some("after")]);
+}
+
+#[test]
+fn end_tags_ignore_their_attributes() {
+    let html = "<div><span>a</span class='x>y'><b>b</b></div\n>";
+    let store = parse_all(html, &["div > b", "span"]);
+
+    assert_eq!(texts(&store, "span"), vec![Some("a")]);
+    assert_eq!(texts(&store, "div > b"), vec![Some("b")]);
+}
+
+#[test]
+fn less_than_sign_without_a_tag_name_is_text() {
+    let html = "<p>a < b <3 c</p><p>next</p>";
+    let store = parse_all(html, &["p"]);
+
+    assert_eq!(texts(&store, "p"), vec![Some("a < b <3 c"), Some("next")]);
+}
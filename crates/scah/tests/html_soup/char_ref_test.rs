@@ -0,0 +1,49 @@
+use super::helpers::{attr, elements, inner_htmls, parse_all, texts};
+
+#[test]
+fn text_content_is_decoded() {
+    let html = "<p>Fish &amp; Chips &lt;3 &copy 2024 &#x1F41F;&#127839;</p><p>a &bogus; b</p>";
+    let store = parse_all(html, &["p"]);
+
+    assert_eq!(
+        texts(&store, "p"),
+        vec![Some("Fish & Chips <3 © 2024 🐟🍟"), Some("a &bogus; b")]
+    );
+    assert_eq!(
+        inner_htmls(&store, "p")[0],
+        Some("Fish &amp; Chips &lt;3 &copy 2024 &#x1F41F;&#127839;")
+    );
+}
+
+#[test]
+fn attribute_values_are_decoded_and_keep_their_raw_form() {
+    let html = "<a href='?a=1&amp;b=2&copy=3' title='&quot;hi&quot;'>x</a>";
+    let store = parse_all(html, &["a"]);
+
+    assert_eq!(attr(&store, "a", "href"), vec![Some("?a=1&b=2&copy=3")]);
+    assert_eq!(attr(&store, "a", "title"), vec![Some("\"hi\"")]);
+
+    let a = elements(&store, "a")[0];
+    assert_eq!(a.raw_attribute(&store, "href"), Some("?a=1&amp;b=2&copy=3"));
+    assert_eq!(a.raw_attribute(&store, "title"), Some("&quot;hi&quot;"));
+}
+
+#[test]
+fn attribute_selectors_match_decoded_values() {
+    let html = "<a href='?a=1&amp;b=2'>1</a><a href='?a=1&amp;amp;b=2'>2</a>";
+    let store = parse_all(html, &["a[href='?a=1&b=2']", "a[href$='&amp;b=2']"]);
+
+    assert_eq!(texts(&store, "a[href='?a=1&b=2']"), vec![Some("1")]);
+    assert_eq!(texts(&store, "a[href$='&amp;b=2']"), vec![Some("2")]);
+}
+
+#[test]
+fn script_content_is_not_decoded() {
+    let html = "<script>if (a &amp;&amp; b) {}</script>";
+    let store = parse_all(html, &["script"]);
+
+    assert_eq!(
+        texts(&store, "script"),
+        vec![Some("if (a &amp;&amp; b) {}")]
+    );
+}
@@ -0,0 +1,32 @@
+use super::helpers::{attr, elements, parse_all};
+
+#[test]
+fn escaped_class_and_id_match_the_decoded_name() {
+    let html = "<div class='md:flex w-1/2' id='123'>a</div><div class='md'>b</div>";
+    let store = parse_all(html, &[".md\\:flex", ".w-1\\/2", "#\\31 23", "div.md"]);
+
+    assert_eq!(elements(&store, ".md\\:flex").len(), 1);
+    assert_eq!(elements(&store, ".w-1\\/2").len(), 1);
+    assert_eq!(elements(&store, "#\\31 23").len(), 1);
+    assert_eq!(elements(&store, "div.md").len(), 1);
+}
+
+#[test]
+fn non_ascii_identifiers_match() {
+    let html = "<p class='café'>1</p><p class='naïve' data-ünï='x'>2</p><p class='cafe'>3</p>";
+    let store = parse_all(html, &[".café", ".caf\\e9", "p.na\\EF ve", "[data-ünï]"]);
+
+    assert_eq!(elements(&store, ".café").len(), 1);
+    assert_eq!(elements(&store, ".caf\\e9").len(), 1);
+    assert_eq!(elements(&store, "p.na\\EF ve").len(), 1);
+    assert_eq!(attr(&store, "[data-ünï]", "data-ünï"), vec![Some("x")]);
+}
+
+#[test]
+fn escaped_attribute_names_and_values() {
+    let html = "<a xml:lang='en' title='say \"hi\"'></a>";
+    let store = parse_all(html, &["[xml\\:lang=en]", "[title='say \\\"hi\\\"']"]);
+
+    assert_eq!(elements(&store, "[xml\\:lang=en]").len(), 1);
+    assert_eq!(elements(&store, "[title='say \\\"hi\\\"']").len(), 1);
+}
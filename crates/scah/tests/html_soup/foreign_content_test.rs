@@ -0,0 +1,107 @@
+use scah::parse_spec_tree;
+
+use super::helpers::{attr, elements, inner_htmls, parse_all, parse_all_with};
+
+const ICON: &str = r#"<body><svg viewBox="0 0 24 24"><g><path d="M0 0"/><circle r="1"/><circle r="2" /></g></svg><p>after</p></body>"#;
+
+#[test]
+fn self_closing_foreign_elements_have_no_children() {
+    let store = parse_all(
+        ICON,
+        &[
+            "svg > g > path[d]",
+            "g > circle",
+            "path + circle",
+            "body > p",
+        ],
+    );
+
+    assert_eq!(attr(&store, "svg > g > path[d]", "d"), vec![Some("M0 0")]);
+    assert_eq!(attr(&store, "g > circle", "r"), vec![Some("1"), Some("2")]);
+    assert_eq!(elements(&store, "path + circle").len(), 1);
+    assert_eq!(elements(&store, "body > p").len(), 1);
+}
+
+#[test]
+fn void_rules_only_apply_to_html() {
+    let html = "<svg><image><title>t</title></image><br></svg>";
+    let store = parse_all(html, &["image > title", "svg > br"]);
+
+    assert_eq!(elements(&store, "image > title").len(), 1);
+    assert_eq!(elements(&store, "svg > br").len(), 0);
+}
+
+#[test]
+fn foreign_names_are_case_sensitive() {
+    let html = r#"<svg viewBox="0 0 1 1"><linearGradient id="a"></linearGradient><clipPath id="b"></clipPath></svg>"#;
+    let store = parse_all(
+        html,
+        &[
+            "linearGradient",
+            "lineargradient",
+            "clipPath",
+            "svg[viewBox]",
+            "svg[viewbox]",
+        ],
+    );
+
+    let gradients = elements(&store, "linearGradient");
+    assert_eq!(gradients.len(), 1);
+    assert_eq!(gradients[0].id, Some("a"));
+    assert!(elements(&store, "lineargradient").is_empty());
+    assert_eq!(elements(&store, "clipPath").len(), 1);
+    assert_eq!(elements(&store, "svg[viewBox]").len(), 1);
+    assert!(elements(&store, "svg[viewbox]").is_empty());
+}
+
+#[test]
+fn foreign_names_take_their_adjusted_spelling() {
+    let html = r#"<SVG VIEWBOX="0 0 1 1"><LINEARGRADIENT GRADIENTUNITS="userSpaceOnUse"></LINEARGRADIENT></SVG><math definitionurl="u"></math>"#;
+    let store = parse_all(
+        html,
+        &[
+            "svg[viewBox]",
+            "linearGradient[gradientUnits]",
+            "math[definitionURL]",
+        ],
+    );
+
+    assert_eq!(elements(&store, "svg[viewBox]").len(), 1);
+    let gradients = elements(&store, "linearGradient[gradientUnits]");
+    assert_eq!(gradients.len(), 1);
+    assert_eq!(gradients[0].name, "linearGradient");
+    assert_eq!(elements(&store, "math[definitionURL]").len(), 1);
+}
+
+#[test]
+fn html_start_tags_break_out_of_foreign_content() {
+    let html = "<div><svg><g><p>text</p><font color=red>a</font></g></svg></div>";
+    let store = parse_all(html, &["div > p", "svg p", "div > font"]);
+
+    assert_eq!(inner_htmls(&store, "div > p"), vec![Some("text")]);
+    assert!(elements(&store, "svg p").is_empty());
+    assert_eq!(elements(&store, "div > font").len(), 1);
+}
+
+#[test]
+fn integration_points_keep_html_inside_foreign_content() {
+    let html = "<svg><foreignObject><p>a</p></foreignObject></svg><math><mtext><div>b</div></mtext></math>";
+    let store = parse_all(html, &["foreignObject > p", "mtext > div"]);
+
+    assert_eq!(elements(&store, "foreignObject > p").len(), 1);
+    assert_eq!(elements(&store, "mtext > div").len(), 1);
+}
+
+#[test]
+fn spec_tree_adjusts_foreign_names() {
+    let html = "<svg viewbox='0 0 1 1'><lineargradient></lineargradient><path/><p>x</svg>";
+    let store = parse_all_with(
+        parse_spec_tree,
+        html,
+        &["svg[viewBox] > linearGradient", "svg > path", "body > p"],
+    );
+
+    assert_eq!(elements(&store, "svg[viewBox] > linearGradient").len(), 1);
+    assert_eq!(elements(&store, "svg > path").len(), 1);
+    assert_eq!(elements(&store, "body > p").len(), 1);
+}
@@ -2,26 +2,38 @@
 
 use scah::{Element, Query, Save, Store, parse};
 
+/// One of the parser entry points: `parse`, `parse_xml` or `parse_spec_tree`.
+pub type Parser<'a> = fn(&'a str, &'a [Query<'a>]) -> Store<'a, 'a>;
+
 pub fn parse_all<'a>(html: &'a str, selectors: &[&'a str]) -> Store<'a, 'a> {
+    parse_all_with(parse, html, selectors)
+}
+
+pub fn parse_all_with<'a>(
+    parser: Parser<'a>,
+    html: &'a str,
+    selectors: &[&'a str],
+) -> Store<'a, 'a> {
     let queries = selectors
         .iter()
-        .map(|selector| Query::all(selector, Save::all()).unwrap().build())
-        .collect::<Vec<_>>()
-        .into_boxed_slice();
-    let queries = Box::leak(queries);
+        .map(|selector| (*selector, Save::all()))
+        .collect::<Vec<_>>();
 
-    parse(html, queries)
+    parser(html, leak_queries(&queries))
 }
 
 pub fn parse_with_saves<'a>(html: &'a str, queries: &[(&'a str, Save)]) -> Store<'a, 'a> {
+    parse(html, leak_queries(queries))
+}
+
+fn leak_queries<'a>(queries: &[(&'a str, Save)]) -> &'a [Query<'a>] {
     let queries = queries
         .iter()
         .map(|(selector, save)| Query::all(selector, *save).unwrap().build())
         .collect::<Vec<_>>()
         .into_boxed_slice();
-    let queries = Box::leak(queries);
 
-    parse(html, queries)
+    Box::leak(queries)
 }
 
 pub fn elements<'a>(store: &'a Store<'a, 'a>, selector: &str) -> Vec<&'a Element<'a>> {
@@ -8,10 +8,7 @@ fn implied_p_close_splits_content_correctly() {
     assert_eq!(elements(&store, "p").len(), 1);
     assert_eq!(texts(&store, "p"), vec![Some("Hello")]);
     assert_eq!(inner_htmls(&store, "p"), vec![Some("Hello")]);
-    assert_eq!(
-        texts(&store, "div"),
-        vec![Some("Hello World"), Some("World"), Some("World")]
-    );
+    assert_eq!(texts(&store, "div"), vec![Some("Hello World"), Some("World")]);
     assert_eq!(elements(&store, "div > p").len(), 1);
     assert_eq!(elements(&store, "div > div").len(), 1);
 }
@@ -0,0 +1,70 @@
+use super::helpers::{elements, parse_all, texts};
+
+#[test]
+fn comments_end_at_their_closing_dashes() {
+    let html = "<div><!-- a > b <p>hidden</p> --><p>shown</p></div>";
+    let store = parse_all(html, &["p", "div"]);
+
+    assert_eq!(texts(&store, "p"), vec![Some("shown")]);
+    assert_eq!(texts(&store, "div"), vec![Some("shown")]);
+}
+
+#[test]
+fn text_after_a_comment_is_not_read_as_a_tag() {
+    let html = "<div><!--c-->hello<b>bold</b></div>";
+    let store = parse_all(html, &["div", "b"]);
+
+    assert_eq!(texts(&store, "b"), vec![Some("bold")]);
+    assert_eq!(texts(&store, "div"), vec![Some("hello bold")]);
+}
+
+#[test]
+fn conditional_comments_hide_their_markup() {
+    let html = "<head><!--[if lt IE 9]><script src='shiv.js'></script><![endif]--></head><body><script></script></body>";
+    let store = parse_all(html, &["script", "head script"]);
+
+    assert_eq!(elements(&store, "script").len(), 1);
+    assert_eq!(elements(&store, "head script").len(), 0);
+}
+
+#[test]
+fn unclosed_comment_runs_to_the_end_of_the_document() {
+    let html = "<p>before</p><!-- <p>after</p>";
+    let store = parse_all(html, &["p"]);
+
+    assert_eq!(texts(&store, "p"), vec![Some("before")]);
+}
+
+#[test]
+fn processing_instructions_and_bogus_comments_are_skipped() {
+    let html = "<?xml version='1.0'?><![if !IE]><p>a</p><![endif]></ p><p>b</p>";
+    let store = parse_all(html, &["p"]);
+
+    assert_eq!(texts(&store, "p"), vec![Some("a"), Some("b")]);
+}
+
+#[test]
+fn cdata_is_text_in_foreign_content() {
+    let html = "<svg><text><![CDATA[x > 1 && <tspan>]]></text></svg><p><![CDATA[y>z]]></p>";
+    let store = parse_all(html, &["text", "tspan", "p"]);
+
+    assert_eq!(elements(&store, "tspan").len(), 0);
+    assert_eq!(texts(&store, "text"), vec![Some("x > 1 && <tspan>")]);
+    // Outside of foreign content it's a bogus comment, up to the first `>`.
+    assert_eq!(texts(&store, "p"), vec![Some("z]]>")]);
+}
+
+#[test]
+fn doctype_is_exposed_on_the_store() {
+    let html = "<!DOCTYPE html SYSTEM \"about:legacy-compat\"><html><!DOCTYPE other></html>";
+    let store = parse_all(html, &["html"]);
+
+    let doctype = store.doctype.unwrap();
+    assert_eq!(doctype.name, Some("html"));
+    assert_eq!(doctype.public_id, None);
+    assert_eq!(doctype.system_id, Some("about:legacy-compat"));
+    assert_eq!(elements(&store, "html").len(), 1);
+
+    let store = parse_all("<p>no doctype</p>", &["p"]);
+    assert_eq!(store.doctype, None);
+}
@@ -1,6 +1,21 @@
+mod attribute_tokenizer_test;
+mod char_ref_test;
 mod eof_recovery_test;
+mod escaped_selector_test;
+mod foreign_content_test;
 mod helpers;
 mod implied_close_test;
+mod markup_test;
 mod misnesting_test;
+mod name_case_test;
+mod outer_html_test;
+mod pseudo_class_test;
+mod rawtext_state_test;
+mod selector_list_test;
 mod selector_stability_test;
+mod sibling_combinator_test;
+mod source_span_test;
+mod spec_tree_test;
+mod template_content_test;
+mod type_selector_test;
 mod void_rawtext_test;
@@ -0,0 +1,106 @@
+use scah::parse_xml;
+
+use super::helpers::{attr, elements, parse_all, parse_all_with, texts};
+
+#[test]
+fn uppercase_tags_and_attributes_match_lowercase_selectors() {
+    let html = "<DIV CLASS='x' ID='main'><A HREF='/a' Data-Id=1>link</A></DIV>";
+    let store = parse_all(html, &["div.x", "#main > a[href][data-id]"]);
+
+    assert_eq!(elements(&store, "div.x").len(), 1);
+    assert_eq!(
+        texts(&store, "#main > a[href][data-id]"),
+        vec![Some("link")]
+    );
+    assert_eq!(
+        attr(&store, "#main > a[href][data-id]", "href"),
+        vec![Some("/a")]
+    );
+    assert_eq!(
+        attr(&store, "#main > a[href][data-id]", "data-id"),
+        vec![Some("1")]
+    );
+}
+
+#[test]
+fn names_are_kept_as_written() {
+    let html = "<Section><P>text</P></Section>";
+    let store = parse_all(html, &["section", "SECTION > p"]);
+
+    let sections = elements(&store, "section");
+    assert_eq!(sections.len(), 1);
+    assert_eq!(sections[0].name, "Section");
+    assert_eq!(elements(&store, "SECTION > p")[0].name, "P");
+}
+
+#[test]
+fn uppercase_void_elements_do_not_open() {
+    let html = "<p>a<BR>b<IMG SRC=x>c</p><p>next</p>";
+    let store = parse_all(html, &["br", "img", "p"]);
+
+    assert_eq!(texts(&store, "br"), vec![None]);
+    assert_eq!(texts(&store, "img"), vec![None]);
+    assert_eq!(texts(&store, "p"), vec![Some("a b c"), Some("next")]);
+}
+
+#[test]
+fn implied_end_tags_ignore_case() {
+    let html = "<UL><LI>one<li>two</Ul><P>para<DIV>block</div></p>";
+    let store = parse_all(html, &["ul > li", "p", "p div"]);
+
+    assert_eq!(texts(&store, "ul > li"), vec![Some("one"), Some("two")]);
+    assert_eq!(texts(&store, "p"), vec![Some("para")]);
+    assert_eq!(elements(&store, "p div").len(), 0);
+}
+
+#[test]
+fn uppercase_raw_text_elements() {
+    let html = "<SCRIPT>if (a <b) {}</SCRIPT><b>bold</b>";
+    let store = parse_all(html, &["script", "b"]);
+
+    assert_eq!(texts(&store, "b"), vec![Some("bold")]);
+}
+
+#[test]
+fn duplicate_attributes_differing_in_case_keep_the_first() {
+    let html = "<p title=a TITLE=b Class=x class=y>t</p>";
+    let store = parse_all(html, &["p.x", "p.y"]);
+
+    assert_eq!(attr(&store, "p.x", "title"), vec![Some("a")]);
+    assert_eq!(elements(&store, "p.y").len(), 0);
+}
+
+#[test]
+fn xml_names_are_case_sensitive() {
+    let xml = "<feed><Entry n='1'/><entry Lang='en' n='2'/></feed>";
+    let store = parse_all_with(parse_xml, xml, &["entry", "Entry", "[lang]", "[Lang]"]);
+
+    assert_eq!(attr(&store, "entry", "n"), vec![Some("2")]);
+    assert_eq!(attr(&store, "Entry", "n"), vec![Some("1")]);
+    assert_eq!(elements(&store, "[lang]").len(), 0);
+    assert_eq!(elements(&store, "[Lang]").len(), 1);
+}
+
+#[test]
+fn xml_has_no_html_tree_rules() {
+    let xml = "<doc><p>a<div>b</div></p><br><x/></br><script><i>c</i></script><item/><item/></doc>";
+    let store = parse_all_with(
+        parse_xml,
+        xml,
+        &["p > div", "br > x", "script > i", "doc > item"],
+    );
+
+    assert_eq!(elements(&store, "p > div").len(), 1);
+    assert_eq!(elements(&store, "br > x").len(), 1);
+    assert_eq!(texts(&store, "script > i"), vec![Some("c")]);
+    assert_eq!(elements(&store, "doc > item").len(), 2);
+}
+
+#[test]
+fn xml_cdata_is_text() {
+    let xml = "<note><body><![CDATA[1 < 2 && <b>]]></body></note>";
+    let store = parse_all_with(parse_xml, xml, &["body", "b"]);
+
+    assert_eq!(texts(&store, "body"), vec![Some("1 < 2 && <b>")]);
+    assert_eq!(elements(&store, "b").len(), 0);
+}
@@ -0,0 +1,103 @@
+use scah::{Save, parse_spec_tree};
+
+use super::helpers::{elements, parse_all, parse_all_with, parse_with_saves};
+
+fn outer_htmls<'a>(store: &'a scah::Store<'a, 'a>, selector: &str) -> Vec<Option<&'a str>> {
+    elements(store, selector)
+        .into_iter()
+        .map(|element| element.outer_html)
+        .collect()
+}
+
+#[test]
+fn outer_html_spans_from_the_start_tag_to_the_end_tag() {
+    let html = "<main><a href='/one'>One <b>bold</b></a><p>text</p></main>";
+    let store = parse_all(html, &["a", "b", "main"]);
+
+    assert_eq!(
+        outer_htmls(&store, "a"),
+        vec![Some("<a href='/one'>One <b>bold</b></a>")]
+    );
+    assert_eq!(outer_htmls(&store, "b"), vec![Some("<b>bold</b>")]);
+    assert_eq!(outer_htmls(&store, "main"), vec![Some(html)]);
+}
+
+#[test]
+fn void_and_self_closing_elements_end_with_their_start_tag() {
+    let html = "<p>a<br>b<img src=x /><input type=text></p><svg><path d='M0'/></svg>";
+    let store = parse_all(html, &["br", "img", "input", "path"]);
+
+    assert_eq!(outer_htmls(&store, "br"), vec![Some("<br>")]);
+    assert_eq!(outer_htmls(&store, "img"), vec![Some("<img src=x />")]);
+    assert_eq!(
+        outer_htmls(&store, "input"),
+        vec![Some("<input type=text>")]
+    );
+    assert_eq!(outer_htmls(&store, "path"), vec![Some("<path d='M0'/>")]);
+}
+
+#[test]
+fn implied_closes_end_before_the_tag_that_closed_the_element() {
+    let html = "<ul><li>one<li>two</ul><dl><dt>term<dd>def</dl><div><p>a</div>";
+    let store = parse_all(html, &["li", "dt", "dd", "p"]);
+
+    assert_eq!(
+        outer_htmls(&store, "li"),
+        vec![Some("<li>one"), Some("<li>two")]
+    );
+    assert_eq!(outer_htmls(&store, "dt"), vec![Some("<dt>term")]);
+    assert_eq!(outer_htmls(&store, "dd"), vec![Some("<dd>def")]);
+    assert_eq!(outer_htmls(&store, "p"), vec![Some("<p>a")]);
+}
+
+#[test]
+fn elements_still_open_at_eof_end_with_the_document() {
+    let html = "<section><p>unclosed <i>text";
+    let store = parse_all(html, &["section", "p", "i"]);
+
+    assert_eq!(outer_htmls(&store, "section"), vec![Some(html)]);
+    assert_eq!(outer_htmls(&store, "p"), vec![Some("<p>unclosed <i>text")]);
+    assert_eq!(outer_htmls(&store, "i"), vec![Some("<i>text")]);
+}
+
+#[test]
+fn outer_html_is_only_saved_when_requested() {
+    let html = "<div><a href='/x'>Link</a><br></div>";
+    let store = parse_with_saves(
+        html,
+        &[
+            ("a", Save::only_inner_html()),
+            ("br", Save::none()),
+            ("div", Save::only_outer_html()),
+        ],
+    );
+
+    assert_eq!(outer_htmls(&store, "a"), vec![None]);
+    assert_eq!(outer_htmls(&store, "br"), vec![None]);
+    let div = elements(&store, "div")[0];
+    assert_eq!(div.outer_html, Some(html));
+    assert_eq!(div.inner_html, None);
+    assert_eq!(div.text_content(&store), None);
+
+    let store = parse_with_saves(html, &[("a", Save::only_text_content().with_outer_html())]);
+    let a = elements(&store, "a")[0];
+    assert_eq!(a.outer_html, Some("<a href='/x'>Link</a>"));
+    assert_eq!(a.text_content(&store), Some("Link"));
+}
+
+#[test]
+fn outer_html_matches_the_outer_range() {
+    let html = "<table><tr><td>1<td>2</table>";
+    let store = parse_all_with(parse_spec_tree, html, &["table", "td"]);
+
+    for element in elements(&store, "table")
+        .into_iter()
+        .chain(elements(&store, "td"))
+    {
+        assert_eq!(element.outer_html, Some(&html[element.outer_range.clone()]));
+    }
+    assert_eq!(
+        outer_htmls(&store, "td"),
+        vec![Some("<td>1"), Some("<td>2")]
+    );
+}
@@ -0,0 +1,287 @@
+use super::helpers::{elements, parse_all, texts};
+use scah::{Query, Save, parse};
+
+#[test]
+fn not_excludes_matching_compounds() {
+    let html =
+        "<div class=\"item\">a</div><div class=\"item ad\">b</div><div class=\"item\">c</div>";
+    let store = parse_all(html, &["div.item:not(.ad)"]);
+
+    assert_eq!(
+        texts(&store, "div.item:not(.ad)"),
+        vec![Some("a"), Some("c")]
+    );
+}
+
+#[test]
+fn not_with_attribute_selector() {
+    let html = "<a href=\"/1\" rel=\"nofollow\">a</a><a href=\"/2\">b</a><a href=\"/3\" rel=\"next\">c</a>";
+    let store = parse_all(html, &["a:not([rel=nofollow])"]);
+
+    assert_eq!(
+        texts(&store, "a:not([rel=nofollow])"),
+        vec![Some("b"), Some("c")]
+    );
+}
+
+#[test]
+fn not_with_compound_list_excludes_any_match() {
+    let html = "<p>a</p><p class=\"x\">b</p><p id=\"y\">c</p><span>d</span>";
+    let store = parse_all(html, &["p:not(.x, #y)", ":not(p)"]);
+
+    assert_eq!(texts(&store, "p:not(.x, #y)"), vec![Some("a")]);
+    assert_eq!(texts(&store, ":not(p)"), vec![Some("d")]);
+}
+
+#[test]
+fn not_inside_a_combinator_chain() {
+    let html = "<ul class=\"nav\"><li><a>a</a></li></ul><ul><li><a>b</a></li></ul>";
+    let store = parse_all(html, &["ul:not(.nav) > li a"]);
+
+    assert_eq!(texts(&store, "ul:not(.nav) > li a"), vec![Some("b")]);
+}
+
+#[test]
+fn is_matches_any_compound_inside_a_chain() {
+    let html = "<article><h2><a>a</a></h2><h3><p><a>no</a></p></h3><h4><a>no</a></h4></article><h1><a>no</a></h1><article><h1><a>b</a></h1></article>";
+    let store = parse_all(html, &["article :is(h1, h2, h3) > a"]);
+
+    assert_eq!(
+        texts(&store, "article :is(h1, h2, h3) > a"),
+        vec![Some("a"), Some("b")]
+    );
+}
+
+#[test]
+fn where_matches_like_is() {
+    let html = "<ul><li class=\"a\">a</li><li class=\"b\">b</li><li>c</li></ul>";
+    let store = parse_all(html, &["li:where(.a, .b):not(.b)"]);
+
+    assert_eq!(texts(&store, "li:where(.a, .b):not(.b)"), vec![Some("a")]);
+}
+
+#[test]
+fn nth_child_counts_cells_across_implied_closes() {
+    let html = "<table><tr><td>1</td><td>2</td><td>3</td></tr><tr><td>4<td>5<td>6</tr></table>";
+    let store = parse_all(html, &["tr > td:nth-child(3)", "td:nth-child(odd)"]);
+
+    assert_eq!(
+        texts(&store, "tr > td:nth-child(3)"),
+        vec![Some("3"), Some("6")]
+    );
+    assert_eq!(
+        texts(&store, "td:nth-child(odd)"),
+        vec![Some("1"), Some("3"), Some("4"), Some("6")]
+    );
+}
+
+#[test]
+fn first_child_and_first_of_type() {
+    let html = "<div><h2>a</h2><p>b</p><p>c</p></div><div><p>d</p></div>";
+    let store = parse_all(
+        html,
+        &["p:first-child", "p:first-of-type", "p:nth-of-type(2)"],
+    );
+
+    assert_eq!(texts(&store, "p:first-child"), vec![Some("d")]);
+    assert_eq!(texts(&store, "p:first-of-type"), vec![Some("b"), Some("d")]);
+    assert_eq!(texts(&store, "p:nth-of-type(2)"), vec![Some("c")]);
+}
+
+#[test]
+fn nth_child_of_selector_counts_matching_siblings() {
+    let html =
+        "<div><p class=\"x\">a</p><p>b</p><span class=\"x\">c</span><p class=\"x\">d</p></div>";
+    let store = parse_all(html, &[":nth-child(2 of .x)", "p:nth-child(-n+2 of p)"]);
+
+    assert_eq!(texts(&store, ":nth-child(2 of .x)"), vec![Some("c")]);
+    assert_eq!(
+        texts(&store, "p:nth-child(-n+2 of p)"),
+        vec![Some("a"), Some("b")]
+    );
+}
+
+#[test]
+fn last_child_is_confirmed_when_the_parent_closes() {
+    let html = "<ul><li>a</li><li>b</li><li>c</li></ul><ul><li>d</li></ul><p>e</p><p>f</p>";
+    let store = parse_all(
+        html,
+        &["li:last-child", "p:last-child", "li:not(:last-child)"],
+    );
+
+    assert_eq!(texts(&store, "li:last-child"), vec![Some("c"), Some("d")]);
+    assert_eq!(texts(&store, "p:last-child"), vec![Some("f")]);
+    assert_eq!(
+        texts(&store, "li:not(:last-child)"),
+        vec![Some("a"), Some("b")]
+    );
+}
+
+#[test]
+fn last_child_of_an_unclosed_parent_resolves_at_eof() {
+    let html = "<div><span>a</span><br><span>b</span>";
+    let store = parse_all(
+        html,
+        &[
+            "span:last-child",
+            "span:last-of-type",
+            "br:nth-last-child(2)",
+        ],
+    );
+
+    assert_eq!(texts(&store, "span:last-child"), vec![Some("b")]);
+    assert_eq!(texts(&store, "span:last-of-type"), vec![Some("b")]);
+    assert_eq!(elements(&store, "br:nth-last-child(2)").len(), 1);
+}
+
+#[test]
+fn rejected_last_child_matches_are_dropped_with_their_children() {
+    let html = "<ul><li><a>1</a></li><li><a>2</a></li></ul><ul><li><b>3</b></li></ul>";
+    let queries = [Query::all("li:last-child", Save::none())
+        .unwrap()
+        .then(|li| Ok([li.all("a", Save::all())?]))
+        .unwrap()
+        .build()];
+    let store = parse(html, &queries);
+
+    let items = store.get("li:last-child").unwrap().collect::<Vec<_>>();
+    assert_eq!(items.len(), 2);
+    let links = items[0]
+        .get(&store, "a")
+        .unwrap()
+        .map(|a| a.text_content(&store))
+        .collect::<Vec<_>>();
+    assert_eq!(links, vec![Some("2")]);
+    assert!(items[1].get(&store, "a").is_none());
+}
+
+#[test]
+fn no_confirmed_last_child_leaves_the_selector_empty() {
+    let html = "<div><p>a</p><span>b</span></div>";
+    let store = parse_all(html, &["p:last-child"]);
+
+    assert!(store.get("p:last-child").is_none());
+}
+
+#[test]
+fn has_child_only_looks_at_children() {
+    let html = "<ul><li><a class=\"download\">x</a></li><li><span><a class=\"download\">y</a></span></li><li>z</li></ul>";
+    let store = parse_all(html, &["li:has(> a.download)", "li:has(a.download)"]);
+
+    assert_eq!(texts(&store, "li:has(> a.download)"), vec![Some("x")]);
+    assert_eq!(
+        texts(&store, "li:has(a.download)"),
+        vec![Some("x"), Some("y")]
+    );
+}
+
+#[test]
+fn has_matches_any_descendant() {
+    let html = "<div class=\"card\"><p>a</p><img src=\"1\"></div><div class=\"card\"><p>b</p></div><div class=\"card\"><span><img src=\"2\"></span>c</div>";
+    let store = parse_all(html, &["div.card:has(img)", "div.card:not(:has(img))"]);
+
+    assert_eq!(
+        texts(&store, "div.card:has(img)"),
+        vec![Some("a"), Some("c")]
+    );
+    assert_eq!(texts(&store, "div.card:not(:has(img))"), vec![Some("b")]);
+}
+
+#[test]
+fn has_can_wait_for_the_parent_too() {
+    let html = "<ul><li>a<b>1</b></li><li>b<b>2</b></li><li>c</li></ul>";
+    let store = parse_all(
+        html,
+        &["li:has(b):last-of-type", "li:has(b):nth-last-child(2)"],
+    );
+
+    assert!(elements(&store, "li:has(b):last-of-type").is_empty());
+    assert_eq!(
+        texts(&store, "li:has(b):nth-last-child(2)"),
+        vec![Some("b 2")]
+    );
+}
+
+#[test]
+fn unconfirmed_has_candidates_are_rolled_back() {
+    let html = "<div id=\"a\" data-x=\"1\"><p>a</p></div><div id=\"b\" data-x=\"2\"><img></div><div id=\"c\" data-x=\"3\"></div>";
+    let store = parse_all(html, &["div:has(img)"]);
+
+    let divs = elements(&store, "div:has(img)");
+    assert_eq!(divs.len(), 1);
+    assert_eq!(divs[0].id, Some("b"));
+    assert_eq!(divs[0].attribute(&store, "data-x"), Some("2"));
+    assert_eq!(store.elements.len(), 1);
+    assert_eq!(store.attributes.len(), 1);
+}
+
+#[test]
+fn unconfirmed_has_candidates_drop_their_children() {
+    let html =
+        "<article><h2>a</h2><a href=\"/1\">1</a></article><article><a href=\"/2\">2</a></article>";
+    let queries = [Query::all("article:has(> h2)", Save::none())
+        .unwrap()
+        .then(|article| Ok([article.all("a", Save::all())?]))
+        .unwrap()
+        .build()];
+    let store = parse(html, &queries);
+
+    let articles = store.get("article:has(> h2)").unwrap().collect::<Vec<_>>();
+    assert_eq!(articles.len(), 1);
+    let links = articles[0]
+        .get(&store, "a")
+        .unwrap()
+        .map(|a| a.attribute(&store, "href"))
+        .collect::<Vec<_>>();
+    assert_eq!(links, vec![Some("/1")]);
+    assert_eq!(store.elements.len(), 2);
+    assert_eq!(store.attributes.len(), 1);
+}
+
+#[test]
+fn unconfirmed_has_candidates_are_dropped_behind_other_matches() {
+    let html = "<div data-x=\"1\"><a href=\"/1\">1</a></div><div data-x=\"2\"><img><a href=\"/2\">2</a></div>";
+    let store = parse_all(html, &["div:has(img)", "a"]);
+
+    let divs = elements(&store, "div:has(img)");
+    assert_eq!(divs.len(), 1);
+    assert_eq!(divs[0].attribute(&store, "data-x"), Some("2"));
+    let links = elements(&store, "a")
+        .iter()
+        .map(|a| a.attribute(&store, "href"))
+        .collect::<Vec<_>>();
+    assert_eq!(links, vec![Some("/1"), Some("/2")]);
+    assert_eq!(store.elements.len(), 3);
+    assert_eq!(store.attributes.len(), 3);
+}
+
+#[test]
+fn unconfirmed_has_candidates_keep_attributes_other_queries_saved() {
+    let store = parse_all(
+        "<ul><li data-x=1>x</li></ul>",
+        &["li:last-child", "li:has(b)"],
+    );
+    let items = elements(&store, "li:last-child");
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].attribute(&store, "data-x"), Some("1"));
+    assert!(elements(&store, "li:has(b)").is_empty());
+    assert_eq!(store.attributes.len(), 1);
+
+    let store = parse_all(
+        "<div><p data-x=1>x</p></div>",
+        &["div > p:last-child", "div p:has(b)"],
+    );
+    let paragraphs = elements(&store, "div > p:last-child");
+    assert_eq!(paragraphs.len(), 1);
+    assert_eq!(paragraphs[0].attribute(&store, "data-x"), Some("1"));
+    assert!(elements(&store, "div p:has(b)").is_empty());
+}
+
+#[test]
+fn has_of_an_unclosed_element_resolves_at_eof() {
+    let html = "<section><div><img></div><div><p>x";
+    let store = parse_all(html, &["div:has(img)", "section:has(p)"]);
+
+    assert_eq!(elements(&store, "div:has(img)").len(), 1);
+    assert_eq!(elements(&store, "section:has(p)").len(), 1);
+}
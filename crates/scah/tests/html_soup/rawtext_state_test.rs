@@ -0,0 +1,109 @@
+use super::helpers::{elements, inner_htmls, parse_all, texts};
+
+#[test]
+fn style_contents_are_raw_text() {
+    let html = "<style>a < b { color: red } p > <div> {}</style><div>real</div>";
+    let store = parse_all(html, &["style", "div", "p"]);
+
+    assert_eq!(elements(&store, "div").len(), 1);
+    assert_eq!(elements(&store, "p").len(), 0);
+    assert_eq!(texts(&store, "div"), vec![Some("real")]);
+    assert_eq!(
+        texts(&store, "style"),
+        vec![Some("a < b { color: red } p > <div> {}")]
+    );
+}
+
+#[test]
+fn script_end_tag_needs_a_delimiter() {
+    let html = "<script>let a = '</scripts>'; let b = '<p>';</script ><p>after</p>";
+    let store = parse_all(html, &["script", "p"]);
+
+    assert_eq!(elements(&store, "p").len(), 1);
+    assert_eq!(texts(&store, "p"), vec![Some("after")]);
+    assert_eq!(
+        texts(&store, "script"),
+        vec![Some("let a = '</scripts>'; let b = '<p>';")]
+    );
+}
+
+#[test]
+fn raw_text_end_tag_is_case_insensitive() {
+    let html = "<style>b { }</STYLE><b>bold</b>";
+    let store = parse_all(html, &["style", "b"]);
+
+    assert_eq!(elements(&store, "style").len(), 1);
+    assert_eq!(texts(&store, "b"), vec![Some("bold")]);
+}
+
+#[test]
+fn xmp_iframe_noembed_and_noframes_are_raw_text() {
+    for name in ["xmp", "iframe", "noembed", "noframes"] {
+        let html = format!("<{name}><b>x</b> &amp; y</{name}><i>after</i>");
+        let store = parse_all(&html, &[name, "b", "i"]);
+
+        assert_eq!(elements(&store, "b").len(), 0, "{name}");
+        assert_eq!(
+            texts(&store, name),
+            vec![Some("<b>x</b> &amp; y")],
+            "{name}"
+        );
+        assert_eq!(texts(&store, "i"), vec![Some("after")], "{name}");
+    }
+}
+
+#[test]
+fn textarea_contents_are_escapable_raw_text() {
+    let html = "<form><textarea><p>draft</p> &lt;3</textarea><p>after</p></form>";
+    let store = parse_all(html, &["textarea", "p"]);
+
+    assert_eq!(elements(&store, "p").len(), 1);
+    assert_eq!(texts(&store, "textarea"), vec![Some("<p>draft</p> <3")]);
+    assert_eq!(
+        inner_htmls(&store, "textarea"),
+        vec![Some("<p>draft</p> &lt;3")]
+    );
+}
+
+#[test]
+fn title_contents_are_escapable_raw_text() {
+    let html = "<head><title>A <b>bold</b> &amp; brave title</title></head><b>body</b>";
+    let store = parse_all(html, &["title", "b"]);
+
+    assert_eq!(elements(&store, "b").len(), 1);
+    assert_eq!(
+        texts(&store, "title"),
+        vec![Some("A <b>bold</b> & brave title")]
+    );
+}
+
+#[test]
+fn plaintext_runs_to_the_end_of_the_document() {
+    let html = "<div><plaintext><p>not a tag</plaintext> &amp;</div>";
+    let store = parse_all(html, &["plaintext", "p", "div"]);
+
+    assert_eq!(elements(&store, "p").len(), 0);
+    assert_eq!(
+        texts(&store, "plaintext"),
+        vec![Some("<p>not a tag</plaintext> &amp;</div>")]
+    );
+    assert_eq!(elements(&store, "div").len(), 1);
+}
+
+#[test]
+fn unclosed_raw_text_runs_to_the_end_of_the_document() {
+    let html = "<div><style>p { } <p>x</p>";
+    let store = parse_all(html, &["style", "p"]);
+
+    assert_eq!(elements(&store, "p").len(), 0);
+    assert_eq!(texts(&store, "style"), vec![Some("p { } <p>x</p>")]);
+}
+
+#[test]
+fn svg_title_and_style_are_not_raw_text() {
+    let html = "<svg><style><rect></rect></style><title><g></g></title></svg>";
+    let store = parse_all(html, &["rect", "g"]);
+
+    assert_eq!(elements(&store, "rect").len(), 1);
+    assert_eq!(elements(&store, "g").len(), 1);
+}
@@ -0,0 +1,96 @@
+use super::helpers::{elements, parse_all, texts};
+use scah::{Query, Save, parse};
+
+#[test]
+fn selector_list_matches_in_document_order() {
+    let html = "<h2>a</h2><h1>b</h1><div><h3>c</h3><h1>d</h1></div>";
+    let store = parse_all(html, &["h1, h2, h3"]);
+
+    assert_eq!(
+        texts(&store, "h1, h2, h3"),
+        vec![Some("a"), Some("b"), Some("c"), Some("d")]
+    );
+}
+
+#[test]
+fn selector_list_saves_an_element_matched_twice_once() {
+    let html = "<h1 class=\"x\">a</h1><div><p class=\"x\">b</p></div>";
+    let store = parse_all(html, &["h1, .x", "div p, div .x"]);
+
+    assert_eq!(texts(&store, "h1, .x"), vec![Some("a"), Some("b")]);
+    assert_eq!(texts(&store, "div p, div .x"), vec![Some("b")]);
+}
+
+#[test]
+fn selector_list_with_combinators() {
+    let html = "<section><h2>a</h2></section><h2>no</h2><ul><li><span>b</span></li></ul>";
+    let store = parse_all(html, &["section > h2, ul span"]);
+
+    assert_eq!(
+        texts(&store, "section > h2, ul span"),
+        vec![Some("a"), Some("b")]
+    );
+}
+
+#[test]
+fn selector_list_first_returns_the_earliest_match() {
+    let html = "<div><h2>a</h2><h1>b</h1></div>";
+    let queries = &[Query::first("h1, h2", Save::all()).unwrap().build()];
+    let store = parse(html, queries);
+
+    assert_eq!(texts(&store, "h1, h2"), vec![Some("a")]);
+}
+
+#[test]
+fn selector_list_first_ignores_matches_inside_the_first() {
+    let html = "<h1><h2>inner</h2></h1><h2>later</h2>";
+    let queries = &[Query::first("h2, h1", Save::none()).unwrap().build()];
+    let store = parse(html, queries);
+
+    let names = elements(&store, "h2, h1")
+        .iter()
+        .map(|element| element.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["h1"]);
+}
+
+#[test]
+fn selector_list_shares_then_children() {
+    let html =
+        "<article><a>1</a></article><section><a>2</a><b>3</b></section><aside><a>no</a></aside>";
+    let queries = &[Query::all("article, section", Save::none())
+        .unwrap()
+        .then(|parent| Ok([parent.all("a, b", Save::all())?]))
+        .unwrap()
+        .build()];
+    let store = parse(html, queries);
+
+    let per_parent = elements(&store, "article, section")
+        .iter()
+        .map(|parent| {
+            parent
+                .get(&store, "a, b")
+                .map(|items| {
+                    items
+                        .map(|item| item.text_content(&store))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        per_parent,
+        vec![vec![Some("1")], vec![Some("2"), Some("3")]]
+    );
+}
+
+#[test]
+fn selector_list_alternative_with_sibling_combinator() {
+    let html = "<h1>t</h1><p>lead</p><h2>t</h2><span>x</span><p>no</p>";
+    let store = parse_all(html, &["h2 ~ span, h1 + p"]);
+
+    assert_eq!(
+        texts(&store, "h2 ~ span, h1 + p"),
+        vec![Some("lead"), Some("x")]
+    );
+}
@@ -44,3 +44,37 @@ fn first_selector_returns_first_recovered_match_only() {
     assert_eq!(elements(&store, "ul > li").len(), 1);
     assert_eq!(texts(&store, "ul > li"), vec![Some("One")]);
 }
+
+#[test]
+fn nested_matches_keep_combinators_relative_to_the_matched_element() {
+    let html = "<div><div><p></p><a id=\"outside-p\">x</a></div></div><div><div><a id=\"child\">y</a></div></div>";
+    let store = parse_all(html, &["div p a", "div > a"]);
+
+    assert!(elements(&store, "div p a").is_empty());
+    let ids = elements(&store, "div > a")
+        .iter()
+        .map(|a| a.id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec![Some("outside-p"), Some("child")]);
+}
+
+#[test]
+fn attribute_case_modifier_applies_to_every_match_kind() {
+    let html = "<form><input type='Submit' lang='EN-us' rel='Next Prev'><input type='submit' lang='en'></form>";
+    let store = parse_all(
+        html,
+        &[
+            "input[type=submit]",
+            "input[type=submit i]",
+            "input[type=submit s]",
+            "input[lang|=en i]",
+            "input[rel~=next i]",
+        ],
+    );
+
+    assert_eq!(elements(&store, "input[type=submit]").len(), 1);
+    assert_eq!(elements(&store, "input[type=submit i]").len(), 2);
+    assert_eq!(elements(&store, "input[type=submit s]").len(), 1);
+    assert_eq!(elements(&store, "input[lang|=en i]").len(), 2);
+    assert_eq!(elements(&store, "input[rel~=next i]").len(), 1);
+}
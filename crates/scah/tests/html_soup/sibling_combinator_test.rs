@@ -0,0 +1,172 @@
+use super::helpers::{elements, parse_all, texts};
+use scah::{Query, Save, parse};
+
+#[test]
+fn next_sibling_skips_text_between_elements() {
+    let html = "<h1>Title</h1> some text <p>Lead</p><p>Body</p>";
+    let store = parse_all(html, &["h1 + p"]);
+
+    assert_eq!(texts(&store, "h1 + p"), vec![Some("Lead")]);
+}
+
+#[test]
+fn next_sibling_requires_the_immediate_element() {
+    let html = "<h1>Title</h1><span>Gap</span><p>Not adjacent</p><h1>Other</h1><p>Adjacent</p>";
+    let store = parse_all(html, &["h1 + p"]);
+
+    assert_eq!(texts(&store, "h1 + p"), vec![Some("Adjacent")]);
+}
+
+#[test]
+fn next_sibling_follows_implied_dt_dd_closes() {
+    let html = "<dl><dt>A<dd>B<dt>C<dd>D</dl>";
+    let store = parse_all(html, &["dt + dd", "dd + dt"]);
+
+    assert_eq!(texts(&store, "dt + dd"), vec![Some("B"), Some("D")]);
+    assert_eq!(texts(&store, "dd + dt"), vec![Some("C")]);
+}
+
+#[test]
+fn next_sibling_follows_implied_li_closes() {
+    let html = "<ul><li>One<li>Two<li>Three</ul>";
+    let store = parse_all(html, &["li + li"]);
+
+    assert_eq!(texts(&store, "li + li"), vec![Some("Two"), Some("Three")]);
+}
+
+#[test]
+fn next_sibling_after_implied_p_close() {
+    let html = "<div><p>Intro<div>Block</div></div>";
+    let store = parse_all(html, &["p + div"]);
+
+    assert_eq!(texts(&store, "p + div"), vec![Some("Block")]);
+}
+
+#[test]
+fn next_sibling_does_not_cross_parents() {
+    let html = "<div><h2>A</h2></div><p id=\"outside\">x</p><div><p id=\"first\">y</p></div>";
+    let store = parse_all(html, &["h2 + p"]);
+
+    assert!(elements(&store, "h2 + p").is_empty());
+}
+
+#[test]
+fn next_sibling_after_void_element() {
+    let html = "<p>Line<br><span id=\"after-br\">A</span><span id=\"not-after-br\">B</span></p>";
+    let store = parse_all(html, &["br + span"]);
+
+    let ids = elements(&store, "br + span")
+        .iter()
+        .map(|span| span.id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec![Some("after-br")]);
+}
+
+#[test]
+fn next_sibling_chain_and_descendant() {
+    let html = "<a></a><b></b><c id=\"hit\"><i>1</i></c><a></a><b></b><d></d><c id=\"miss\"></c>";
+    let store = parse_all(html, &["a + b + c", "a + b + c i"]);
+
+    let ids = elements(&store, "a + b + c")
+        .iter()
+        .map(|c| c.id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec![Some("hit")]);
+    assert_eq!(texts(&store, "a + b + c i"), vec![Some("1")]);
+}
+
+#[test]
+fn next_sibling_inside_child_combinator() {
+    let html = "<section><h1>t</h1><p>yes</p></section><h1>t</h1><p>no</p>";
+    let store = parse_all(html, &["section > h1 + p"]);
+
+    assert_eq!(texts(&store, "section > h1 + p"), vec![Some("yes")]);
+}
+
+#[test]
+fn next_sibling_first_skips_unmatched_anchors() {
+    let html = "<h1>A</h1><span>x</span><h1>B</h1><p>One</p><h1>C</h1><p>Two</p>";
+    let queries = &[Query::first("h1 + p", Save::all()).unwrap().build()];
+    let store = parse(html, queries);
+
+    assert_eq!(texts(&store, "h1 + p"), vec![Some("One")]);
+}
+
+#[test]
+fn next_sibling_in_then_children() {
+    let html = "<article><h2>A</h2><p>a</p></article><article><p>b</p><h2>B</h2><p>c</p></article>";
+    let queries = &[Query::all("article", Save::none())
+        .unwrap()
+        .then(|article| Ok([article.all("h2 + p", Save::all())?]))
+        .unwrap()
+        .build()];
+    let store = parse(html, queries);
+
+    let articles = elements(&store, "article");
+    assert_eq!(articles.len(), 2);
+    let per_article = articles
+        .iter()
+        .map(|article| {
+            article
+                .get(&store, "h2 + p")
+                .map(|items| items.map(|p| p.text_content(&store)).collect::<Vec<_>>())
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(per_article, vec![vec![Some("a")], vec![Some("c")]]);
+}
+
+#[test]
+fn subsequent_sibling_matches_every_later_sibling() {
+    let html = "<div><p>0</p><h1>t</h1><p>1</p><span>x</span><p>2</p></div><p>out</p>";
+    let store = parse_all(html, &["h1 ~ p"]);
+
+    assert_eq!(texts(&store, "h1 ~ p"), vec![Some("1"), Some("2")]);
+}
+
+#[test]
+fn subsequent_sibling_turns_off_when_parent_closes() {
+    let html = "<div><h1>t</h1></div><p>no</p><div><p>no</p><h1>t</h1><p>yes</p></div>";
+    let store = parse_all(html, &["h1 ~ p"]);
+
+    assert_eq!(texts(&store, "h1 ~ p"), vec![Some("yes")]);
+}
+
+#[test]
+fn subsequent_sibling_repeated_anchor_matches_once() {
+    let html = "<h1>a</h1><h1>b</h1><p>One</p><h1>c</h1><p>Two</p>";
+    let store = parse_all(html, &["h1 ~ p"]);
+
+    assert_eq!(texts(&store, "h1 ~ p"), vec![Some("One"), Some("Two")]);
+}
+
+#[test]
+fn subsequent_sibling_first() {
+    let html = "<p>before</p><h1>t</h1><span>x</span><p>One</p><p>Two</p>";
+    let queries = &[Query::first("h1 ~ p", Save::all()).unwrap().build()];
+    let store = parse(html, queries);
+
+    assert_eq!(texts(&store, "h1 ~ p"), vec![Some("One")]);
+}
+
+#[test]
+fn subsequent_sibling_chain_with_child_and_descendant() {
+    let html = "<section><h2>t</h2><p><a>no</a></p><ul><li><a>1</a></li></ul><ul><li><a>2</a></li></ul></section>\
+        <ul><li><a>out</a></li></ul>\
+        <section><div><h2>t</h2><ul><li><a>nested</a></li></ul></div></section>";
+    let store = parse_all(html, &["section > h2 ~ ul a"]);
+
+    assert_eq!(
+        texts(&store, "section > h2 ~ ul a"),
+        vec![Some("1"), Some("2")]
+    );
+}
+
+#[test]
+fn subsequent_sibling_after_next_sibling() {
+    let html = "<div><h1>t</h1><p>lead</p><span>a</span><p>x</p><span>b</span></div>\
+        <div><h1>t</h1><div></div><p>x</p><span>c</span></div>";
+    let store = parse_all(html, &["h1 + p ~ span"]);
+
+    assert_eq!(texts(&store, "h1 + p ~ span"), vec![Some("a"), Some("b")]);
+}
@@ -0,0 +1,102 @@
+use std::ops::Range;
+
+use scah::{SourceLocation, parse_spec_tree, parse_xml};
+
+use super::helpers::{elements, parse_all, parse_all_with};
+
+fn slices(html: &str, ranges: impl Iterator<Item = Range<usize>>) -> Vec<&str> {
+    ranges.map(|range| &html[range]).collect()
+}
+
+#[test]
+fn start_tag_and_outer_ranges() {
+    let html = "<main>\n  <a href='/one'>One</a>\n  <img src=x>\n  <p>text<br/></p>\n</main>";
+    let store = parse_all(html, &["a", "img", "p", "main"]);
+
+    let a = elements(&store, "a")[0];
+    assert_eq!(&html[a.start_tag_range.clone()], "<a href='/one'>");
+    assert_eq!(&html[a.outer_range.clone()], "<a href='/one'>One</a>");
+    let img = elements(&store, "img")[0];
+    assert_eq!(&html[img.outer_range.clone()], "<img src=x>");
+    assert_eq!(img.start_tag_range, img.outer_range);
+    let p = elements(&store, "p")[0];
+    assert_eq!(&html[p.outer_range.clone()], "<p>text<br/></p>");
+    let main = elements(&store, "main")[0];
+    assert_eq!(main.outer_range, 0..html.len());
+}
+
+#[test]
+fn implied_closes_end_where_the_element_was_closed() {
+    let html = "<ul><li>one<li>two</ul><div><p>a</div><section><p>b";
+    let store = parse_all(html, &["li", "p"]);
+
+    assert_eq!(
+        slices(
+            html,
+            elements(&store, "li")
+                .iter()
+                .map(|li| li.outer_range.clone())
+        ),
+        vec!["<li>one", "<li>two"]
+    );
+    assert_eq!(
+        slices(
+            html,
+            elements(&store, "p").iter().map(|p| p.outer_range.clone())
+        ),
+        vec!["<p>a", "<p>b"]
+    );
+}
+
+#[test]
+fn locations_are_lazily_computed_lines_and_columns() {
+    let html = "<main>\n  <a>One</a>\n\t<p>café <b>x</b></p>\n</main>";
+    let store = parse_all(html, &["main", "a", "b"]);
+
+    let location = |selector| elements(&store, selector)[0].location(&store);
+    assert_eq!(location("main"), SourceLocation { line: 1, column: 1 });
+    assert_eq!(location("a"), SourceLocation { line: 2, column: 3 });
+    assert_eq!(
+        location("b"),
+        SourceLocation {
+            line: 3,
+            column: 10
+        }
+    );
+    assert_eq!(store.location(html.len()).line, 4);
+}
+
+#[test]
+fn spec_tree_ranges() {
+    let html = "<table><tr><td>a</td></tr></table><p>x<p>y";
+    let store = parse_all_with(parse_spec_tree, html, &["tbody", "td", "p"]);
+
+    let tbody = elements(&store, "tbody")[0];
+    assert!(tbody.start_tag_range.is_empty());
+    assert_eq!(&html[tbody.outer_range.clone()], "<tr><td>a</td></tr>");
+    let td = elements(&store, "td")[0];
+    assert_eq!(&html[td.outer_range.clone()], "<td>a</td>");
+    assert_eq!(
+        slices(
+            html,
+            elements(&store, "p").iter().map(|p| p.outer_range.clone())
+        ),
+        vec!["<p>x", "<p>y"]
+    );
+}
+
+#[test]
+fn xml_self_closing_ranges() {
+    let xml = "<feed><entry id='1'/><entry id='2'>t</entry></feed>";
+    let store = parse_all_with(parse_xml, xml, &["entry"]);
+
+    assert_eq!(
+        slices(
+            xml,
+            elements(&store, "entry")
+                .iter()
+                .map(|entry| entry.outer_range.clone())
+        ),
+        vec!["<entry id='1'/>", "<entry id='2'>t</entry>"]
+    );
+}
@@ -0,0 +1,73 @@
+use scah::parse_spec_tree;
+
+use super::helpers::{attr, elements, inner_htmls, parse_all, parse_all_with, texts};
+
+#[test]
+fn implied_tbody_matches_child_selectors() {
+    let html = "<table><tr><td>One<td>Two</table>";
+    let store = parse_all_with(parse_spec_tree, html, &["table > tbody > tr > td"]);
+
+    assert_eq!(
+        texts(&store, "table > tbody > tr > td"),
+        vec![Some("One"), Some("Two")]
+    );
+    assert_eq!(
+        inner_htmls(&store, "table > tbody > tr > td"),
+        vec![Some("One"), Some("Two")]
+    );
+}
+
+#[test]
+fn implied_html_head_and_body() {
+    let html = "<title>Page</title><p>text";
+    let store = parse_all_with(
+        parse_spec_tree,
+        html,
+        &["html > head > title", "html > body > p"],
+    );
+
+    assert_eq!(texts(&store, "html > head > title"), vec![Some("Page")]);
+    assert_eq!(texts(&store, "html > body > p"), vec![Some("text")]);
+}
+
+#[test]
+fn misnested_formatting_elements_are_reopened() {
+    let html = "<b>1<p>2</b>3</p>";
+    let store = parse_all_with(parse_spec_tree, html, &["body > b", "p > b", "p"]);
+
+    assert_eq!(texts(&store, "body > b"), vec![Some("1")]);
+    assert_eq!(texts(&store, "p > b"), vec![Some("2")]);
+    assert_eq!(texts(&store, "p"), vec![Some("2 3")]);
+}
+
+#[test]
+fn content_misplaced_in_a_table_is_moved_before_it() {
+    let html = "<div><table><span>x</span><tr><td>y</td></tr></table></div>";
+    let store = parse_all_with(parse_spec_tree, html, &["div > span + table", "table span"]);
+
+    assert_eq!(texts(&store, "div > span + table"), vec![Some("y")]);
+    assert!(elements(&store, "table span").is_empty());
+}
+
+#[test]
+fn foreign_elements_keep_their_namespace() {
+    let html = "<svg><g><path d='M0 0'/></g><p>after";
+    let store = parse_all_with(
+        parse_spec_tree,
+        html,
+        &["svg > g > path[d]", "svg|g", "body > p"],
+    );
+
+    assert_eq!(attr(&store, "svg > g > path[d]", "d"), vec![Some("M0 0")]);
+    assert_eq!(elements(&store, "svg|g").len(), 1);
+    assert_eq!(texts(&store, "body > p"), vec![Some("after")]);
+}
+
+#[test]
+fn streaming_mode_is_unchanged() {
+    let html = "<table><tr><td>One</table>";
+    let store = parse_all(html, &["table > tbody > tr > td", "table > tr > td"]);
+
+    assert!(elements(&store, "table > tbody > tr > td").is_empty());
+    assert_eq!(texts(&store, "table > tr > td"), vec![Some("One")]);
+}
@@ -0,0 +1,125 @@
+use scah::{Query, QueryMultiplexer, Reader, Save, XHtmlParser, parse, parse_spec_tree};
+
+use super::helpers::{elements, inner_htmls, parse_all, parse_all_with, parse_with_saves, texts};
+
+const PAGE: &str =
+    "<main><a href='/live'>Live</a><template><a href='/row'>Row</a><li>x</template></main>";
+
+#[test]
+fn template_contents_are_skipped_by_default() {
+    let store = parse_all(PAGE, &["main a", "template", "template > a", "li"]);
+
+    assert_eq!(inner_htmls(&store, "main a"), vec![Some("Live")]);
+    assert_eq!(elements(&store, "template").len(), 1);
+    assert!(elements(&store, "template > a").is_empty());
+    assert!(elements(&store, "li").is_empty());
+}
+
+#[test]
+fn save_opts_into_template_contents() {
+    let store = parse_with_saves(
+        PAGE,
+        &[
+            ("main a", Save::all().with_template_content()),
+            ("template > li", Save::none().with_template_content()),
+        ],
+    );
+
+    assert_eq!(
+        inner_htmls(&store, "main a"),
+        vec![Some("Live"), Some("Row")]
+    );
+    assert_eq!(elements(&store, "template > li").len(), 1);
+}
+
+#[test]
+fn child_sections_opt_into_template_contents() {
+    let queries = &[Query::all("template", Save::none())
+        .unwrap()
+        .then(|template| Ok([template.all("a", Save::all().with_template_content())?]))
+        .unwrap()
+        .build()];
+    let store = parse(PAGE, queries);
+
+    let template = store.get("template").unwrap().next().unwrap();
+    let links = template.get(&store, "a").unwrap().collect::<Vec<_>>();
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].attribute(&store, "href"), Some("/row"));
+}
+
+#[test]
+fn template_contents_do_not_count_for_has() {
+    let html = "<div id=a><template><img></template></div><div id=b><img></div>";
+    let store = parse_all(html, &["div:has(img)"]);
+
+    let ids = elements(&store, "div:has(img)")
+        .iter()
+        .map(|div| div.id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec![Some("b")]);
+}
+
+#[test]
+fn parser_option_matches_template_contents() {
+    let queries = &[Query::all("main a", Save::all()).unwrap().build()];
+    let mut parser = XHtmlParser::new(QueryMultiplexer::new(queries)).match_template_contents();
+    let mut reader = Reader::new(PAGE);
+    while parser.next(&mut reader) {}
+    let store = parser.finish();
+
+    assert_eq!(store.get("main a").unwrap().count(), 2);
+}
+
+#[test]
+fn implied_end_tags_stay_inside_the_template() {
+    let html = "<ul><li>one<template><li>a<li>b</template><li>two</ul><p>x<template><div></div></template>y</p>";
+    let store = parse_with_saves(
+        html,
+        &[
+            ("ul > li", Save::all()),
+            ("template > li", Save::all().with_template_content()),
+            ("p", Save::all()),
+        ],
+    );
+
+    assert_eq!(elements(&store, "ul > li").len(), 2);
+    assert_eq!(
+        inner_htmls(&store, "template > li"),
+        vec![Some("a"), Some("b")]
+    );
+    assert_eq!(
+        inner_htmls(&store, "p"),
+        vec![Some("x<template><div></div></template>y")]
+    );
+}
+
+#[test]
+fn template_contents_are_not_part_of_ancestors_text() {
+    let html = "<main><p>a</p><template><p>b<template>c</template></p></template><p>d</p></main>";
+    let store = parse_with_saves(
+        html,
+        &[
+            ("main", Save::only_text_content()),
+            ("template", Save::only_text_content()),
+            (
+                "template > p",
+                Save::only_text_content().with_template_content(),
+            ),
+        ],
+    );
+
+    assert_eq!(texts(&store, "main"), vec![Some("a d")]);
+    assert_eq!(texts(&store, "template"), vec![None]);
+    assert_eq!(texts(&store, "template > p"), vec![Some("b")]);
+
+    let store = parse_all_with(parse_spec_tree, html, &["main"]);
+    assert_eq!(texts(&store, "main"), vec![Some("a d")]);
+}
+
+#[test]
+fn spec_tree_skips_template_contents() {
+    let store = parse_all_with(parse_spec_tree, PAGE, &["main a", "template"]);
+
+    assert_eq!(inner_htmls(&store, "main a"), vec![Some("Live")]);
+    assert_eq!(elements(&store, "template").len(), 1);
+}
@@ -0,0 +1,70 @@
+use super::helpers::{attr, elements, parse_all, texts};
+use scah::{Namespaces, Query, SVG_NAMESPACE, Save, parse};
+
+#[test]
+fn universal_selector_matches_any_element() {
+    let html = "<main><section><a>1</a></section><div><a>2</a></div><a>no</a></main>";
+    let store = parse_all(html, &["main > * > a", "*[data-id]"]);
+
+    assert_eq!(texts(&store, "main > * > a"), vec![Some("1"), Some("2")]);
+    assert!(elements(&store, "*[data-id]").is_empty());
+
+    let html = "<ul><li data-id='1'></li><li></li><span data-id='2'></span></ul>";
+    let store = parse_all(html, &["*[data-id]"]);
+    assert_eq!(
+        attr(&store, "*[data-id]", "data-id"),
+        vec![Some("1"), Some("2")]
+    );
+}
+
+#[test]
+fn nested_descendant_matches_are_saved_once() {
+    let html = format!("{}{}", "<div>x".repeat(20), "</div>".repeat(20));
+    let store = parse_all(&html, &["*", "div", "div div", "div * div"]);
+
+    assert_eq!(elements(&store, "*").len(), 20);
+    assert_eq!(elements(&store, "div").len(), 20);
+    assert_eq!(elements(&store, "div div").len(), 19);
+    assert_eq!(elements(&store, "div * div").len(), 18);
+    assert_eq!(store.elements.len(), 77);
+}
+
+#[test]
+fn namespace_prefixes_match_foreign_subtrees() {
+    let html = "<rect id='html'></rect>\
+        <svg><rect id='svg'></rect><foreignObject><rect id='back'></rect></foreignObject></svg>";
+    let store = parse_all(html, &["svg|rect", "html|rect", "*|rect", "|rect"]);
+
+    let ids = |selector| {
+        elements(&store, selector)
+            .iter()
+            .map(|element| element.id)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(ids("svg|rect"), vec![Some("svg")]);
+    assert_eq!(ids("html|rect"), vec![Some("html"), Some("back")]);
+    assert_eq!(ids("*|rect").len(), 3);
+    assert!(ids("|rect").is_empty());
+}
+
+#[test]
+fn custom_prefix_map_applies_to_the_whole_query() {
+    let html = "<svg><g><circle r='1'></circle></g></svg><circle r='2'></circle>";
+    let namespaces = Namespaces::empty()
+        .with_prefix("s", SVG_NAMESPACE)
+        .with_default(SVG_NAMESPACE);
+    let queries = [Query::with_namespaces(namespaces)
+        .all("s|g", Save::none())
+        .unwrap()
+        .then(|g| Ok([g.all("circle", Save::none())?]))
+        .unwrap()
+        .build()];
+    let store = parse(html, &queries);
+
+    let g = elements(&store, "s|g");
+    assert_eq!(g.len(), 1);
+    let circles = g[0].get(&store, "circle").unwrap().collect::<Vec<_>>();
+    assert_eq!(circles.len(), 1);
+
+    assert!(Query::all("s|g", Save::none()).is_err());
+}
@@ -198,18 +198,12 @@ fn test_html_page_first_anchor_tag_selection() {
     let a = children.next().unwrap();
     assert_eq!(
         store.attributes.deref().clone(),
-        vec![Attribute {
-            key: "href",
-            value: Some("link1")
-        }]
+        vec![Attribute::new("href", Some("link1"))]
     );
     assert_eq!(a.name, "a");
     assert_eq!(
         a.attributes(&store).unwrap(),
-        &[Attribute {
-            key: "href",
-            value: Some("link1")
-        }]
+        &[Attribute::new("href", Some("link1"))]
     );
     assert_eq!(a.attribute(&store, "href"), Some("link1"));
     assert_eq!(a.text_content(&store).unwrap(), "Link 1");
@@ -403,3 +397,160 @@ fn test_macro_query_matches_runtime_store_contents() {
         );
     }
 }
+
+#[test]
+fn test_macro_template_content_save() {
+    let html = "<template><a href='/row'>Row</a></template>";
+    let static_query = query! {
+        all("template a", Save::all().with_template_content())
+    };
+    assert_eq!(
+        static_query.queries()[0].save,
+        Save::all().with_template_content()
+    );
+
+    let queries = [static_query];
+    let store = parse(html, &queries);
+    assert_eq!(store.get("template a").unwrap().count(), 1);
+}
+
+#[test]
+fn test_macro_outer_html_save() {
+    let html = "<ul><li>One<li><a href='/two'>Two</a></ul>";
+    let static_query = query! {
+        all("li", Save::only_outer_html()) => {
+            first("a", Save::only_text_content().with_outer_html()),
+        }
+    };
+    assert_eq!(static_query.queries()[0].save, Save::only_outer_html());
+    assert_eq!(
+        static_query.queries()[1].save,
+        Save::only_text_content().with_outer_html()
+    );
+
+    let queries = [static_query];
+    let store = parse(html, &queries);
+    let items = store.get("li").unwrap().collect::<Vec<_>>();
+    assert_eq!(items[0].outer_html, Some("<li>One"));
+    assert_eq!(items[1].outer_html, Some("<li><a href='/two'>Two</a>"));
+    let a = items[1].get(&store, "a").unwrap().next().unwrap();
+    assert_eq!(a.outer_html, Some("<a href='/two'>Two</a>"));
+}
+
+#[test]
+fn test_macro_next_sibling_query() {
+    let html = "<article><h1>Title</h1><p>Lead</p><p>Body</p></article>";
+    let static_query = query! {
+        all("article", Save::none()) => {
+            first("h1 + p", Save::only_text_content()),
+        }
+    };
+    assert_eq!(static_query.states()[1].guard, scah::Combinator::Descendant);
+    assert_eq!(
+        static_query.states()[2].guard,
+        scah::Combinator::NextSibling
+    );
+
+    let queries = [static_query];
+    let store = parse(html, &queries);
+    let article = store.get("article").unwrap().next().unwrap();
+    let leads = article
+        .get(&store, "h1 + p")
+        .unwrap()
+        .map(|p| p.text_content(&store))
+        .collect::<Vec<_>>();
+
+    assert_eq!(leads, vec![Some("Lead")]);
+}
+
+#[test]
+fn test_macro_selector_list_query() {
+    let html = "<article><h2>Sub</h2><h1>Title</h1></article><section><h1>Other</h1></section>";
+    let static_query = query! {
+        all("article, section", Save::none()) => {
+            all("h1, h2", Save::only_text_content()),
+        }
+    };
+    assert!(!static_query.states()[0].alternative);
+    assert!(static_query.states()[1].alternative);
+    assert!(static_query.states()[3].alternative);
+    let runtime_query = Query::all("article, section", Save::none())
+        .unwrap()
+        .then(|parent| Ok([parent.all("h1, h2", Save::only_text_content())?]))
+        .unwrap()
+        .build();
+    assert_eq!(static_query.states(), &*runtime_query.states);
+    assert_eq!(static_query.queries(), &*runtime_query.queries);
+
+    let queries = [static_query];
+    let store = parse(html, &queries);
+    let headings = store
+        .get("article, section")
+        .unwrap()
+        .map(|parent| {
+            parent
+                .get(&store, "h1, h2")
+                .unwrap()
+                .map(|heading| heading.text_content(&store))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        headings,
+        vec![vec![Some("Sub"), Some("Title")], vec![Some("Other")]]
+    );
+}
+
+/// Checks that `query!` compiles each selector to the same query as
+/// `Query::all`, and that the compiled query matches the elements whose text
+/// is expected.
+macro_rules! macro_matches_runtime_query {
+    ($($name:ident: $selector:tt in $html:expr => $expected:expr;)*) => {$(
+        #[test]
+        fn $name() {
+            let static_query = query! { all($selector, Save::all()) };
+            let runtime_query = Query::all($selector, Save::all()).unwrap().build();
+            assert_eq!(static_query.states(), &*runtime_query.states);
+            assert_eq!(static_query.queries(), &*runtime_query.queries);
+
+            let queries = [static_query];
+            let store = parse($html, &queries);
+            let texts = store
+                .get($selector)
+                .into_iter()
+                .flatten()
+                .map(|element| element.text_content(&store))
+                .collect::<Vec<_>>();
+
+            assert_eq!(texts, $expected);
+        }
+    )*};
+}
+
+macro_matches_runtime_query! {
+    test_macro_flat_selector_list_query: "h1, h2"
+        in "<h2>a</h2><h3>no</h3><h1>b</h1>"
+        => [Some("a"), Some("b")];
+    test_macro_not_pseudo_class_query: "a[href]:not([rel=nofollow], .ad)"
+        in r#"<a href="/1" rel="nofollow">a</a><a href="/2" class="ad">b</a><a href="/3">c</a>"#
+        => [Some("c")];
+    test_macro_is_pseudo_class_query: "article :is(h1, h2, h3) > a"
+        in "<article><h2><a>1</a></h2><h4><a>no</a></h4><h3><a>2</a></h3></article>"
+        => [Some("1"), Some("2")];
+    test_macro_structural_pseudo_class_query: "li:nth-child(2 of .x), li:last-child"
+        in r#"<ul><li class="x">1</li><li>2</li><li class="x">3</li><li>4</li></ul>"#
+        => [Some("3"), Some("4")];
+    test_macro_has_pseudo_class_query: "li:has(> a.download, img)"
+        in r#"<ul><li><a class="download">1</a></li><li><p><img></p>2</li><li>3</li></ul>"#
+        => [Some("1"), Some("2")];
+    test_macro_attribute_case_modifier_query: "button[type=\"submit\" i]"
+        in r#"<button type="Submit">1</button><button type="submit">2</button><button type="reset">3</button>"#
+        => [Some("1"), Some("2")];
+    test_macro_namespaced_query: "svg|rect, * > *|circle"
+        in r#"<svg><rect id="a">a</rect></svg><rect id="b">b</rect>"#
+        => [Some("a")];
+    test_macro_escaped_query: "#\\31 23.md\\:flex > .caf\\e9"
+        in r#"<div class="md:flex" id="123"><p class="café">x</p></div>"#
+        => [Some("x")];
+}
@@ -0,0 +1,203 @@
+//! Runs the tree construction cases in `tests/spec_tree` against
+//! `parse_spec_tree`, and those of an html5lib-tests checkout when
+//! `HTML5LIB_TESTS` names one.
+//!
+//! The cases in `tests/spec_tree` were written for this crate, not copied
+//! from html5lib-tests, but use its `.dat` format. The upstream fixtures
+//! aren't vendored here; run them with
+//! `HTML5LIB_TESTS=path/to/html5lib-tests cargo test --test spec_tree_test`.
+//! Only the elements of each `#document` are compared, with their nesting:
+//! text, comments, attributes and the DOCTYPE aren't part of the events the
+//! parser emits, and errors aren't checked. The cases of the kinds in
+//! [`UNSUPPORTED`] are skipped, and counted in the report.
+#![cfg(debug_assertions)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use scah::debug::TraceEvent;
+use scah::{Query, Save, parse_spec_tree};
+
+const FIXTURES: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/spec_tree/tree-construction"
+);
+
+/// The sections marking the cases `parse_spec_tree` can't run, and why.
+const UNSUPPORTED: [(&str, &str); 2] = [
+    (
+        "#document-fragment",
+        "fragment parsing, which needs a context element to parse in",
+    ),
+    (
+        "#script-on",
+        "scripting, as `<noscript>` is always parsed with scripting off",
+    ),
+];
+
+#[derive(Debug, Default)]
+struct TreeTest {
+    data: String,
+    document: Vec<String>,
+    // The index in `UNSUPPORTED` of the kind of case it is, if it is one.
+    unsupported: Option<usize>,
+}
+
+fn read_tests(path: &Path) -> Vec<TreeTest> {
+    let source = fs::read_to_string(path).unwrap();
+    let mut tests = Vec::new();
+    let mut section = "";
+    let mut data = Vec::new();
+
+    for line in source.lines() {
+        if line == "#data" {
+            tests.push(TreeTest::default());
+            data.clear();
+            section = line;
+            continue;
+        }
+        let Some(test) = tests.last_mut() else {
+            continue;
+        };
+        match line {
+            "#errors" | "#new-errors" | "#document" => {
+                if section == "#data" {
+                    test.data = data.join("\n");
+                }
+                section = line;
+            }
+            "#script-off" => section = line,
+            _ if UNSUPPORTED.iter().any(|(name, _)| *name == line) => {
+                test.unsupported = UNSUPPORTED.iter().position(|(name, _)| *name == line);
+                section = line;
+            }
+            _ if section == "#data" => data.push(line),
+            _ if section == "#document" && line.starts_with("| ") => {
+                test.document.push(line[2..].to_string())
+            }
+            _ => {}
+        }
+    }
+    tests
+}
+
+/// The elements of an expected `#document`, as `depth name` lines. The
+/// contents of a `<template>` are its children.
+fn expected_elements(document: &[String]) -> Vec<String> {
+    let mut elements = Vec::new();
+    // Depths of the `content` lines the following lines are inside of.
+    let mut contents: Vec<usize> = Vec::new();
+
+    for line in document {
+        let indent = line.len() - line.trim_start().len();
+        let depth = indent / 2;
+        while contents.last().is_some_and(|&content| depth <= content) {
+            contents.pop();
+        }
+        let node = line.trim_start();
+        if node == "content" {
+            contents.push(depth);
+            continue;
+        }
+        let Some(name) = node
+            .strip_prefix('<')
+            .and_then(|node| node.strip_suffix('>'))
+            .filter(|name| !name.starts_with('!') && !name.starts_with('?'))
+        else {
+            continue;
+        };
+        let name = name
+            .strip_prefix("svg ")
+            .or_else(|| name.strip_prefix("math "))
+            .unwrap_or(name);
+        elements.push(format!("{} {}", depth - contents.len(), name));
+    }
+    elements
+}
+
+fn actual_elements(html: &str) -> Vec<String> {
+    let queries = &[Query::all("*", Save::none()).unwrap().build()];
+    let store = parse_spec_tree(html, queries);
+    store
+        .trace
+        .events()
+        .iter()
+        .filter_map(|event| match event {
+            TraceEvent::OpenTag { tag, depth, .. } => Some(format!("{} {}", depth - 1, tag)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Runs every `.dat` file in `dir`, and panics with the cases that failed.
+fn run_fixtures(dir: &Path) {
+    let mut paths = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "dat"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert!(!paths.is_empty(), "no fixtures in {}", dir.display());
+
+    let mut failures = Vec::new();
+    let mut run = 0;
+    let mut skipped = [0; UNSUPPORTED.len()];
+    for path in &paths {
+        for (index, test) in read_tests(path).iter().enumerate() {
+            if let Some(kind) = test.unsupported {
+                skipped[kind] += 1;
+                continue;
+            }
+            run += 1;
+            let expected = expected_elements(&test.document);
+            let actual = actual_elements(&test.data);
+            let same = expected.len() == actual.len()
+                && expected
+                    .iter()
+                    .zip(&actual)
+                    .all(|(expected, actual)| expected.eq_ignore_ascii_case(actual));
+            if !same {
+                failures.push(format!(
+                    "{} #{index}: {:?}\n  expected: {expected:?}\n  actual:   {actual:?}",
+                    path.file_name().unwrap().to_string_lossy(),
+                    test.data,
+                ));
+            }
+        }
+    }
+
+    let skipped = UNSUPPORTED
+        .iter()
+        .zip(skipped)
+        .filter(|(_, count)| *count > 0)
+        .map(|((_, reason), count)| format!("{count} skipped for {reason}"))
+        .collect::<Vec<_>>();
+    eprintln!(
+        "{}: {run} tree construction tests run{}",
+        dir.display(),
+        skipped
+            .iter()
+            .map(|skipped| format!(", {skipped}"))
+            .collect::<String>()
+    );
+    assert!(run > 0);
+    assert!(
+        failures.is_empty(),
+        "{} of {run} tree construction tests failed:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn spec_tree_construction() {
+    run_fixtures(Path::new(FIXTURES));
+}
+
+#[test]
+fn html5lib_tree_construction() {
+    let Some(checkout) = std::env::var_os("HTML5LIB_TESTS") else {
+        return;
+    };
+    run_fixtures(&PathBuf::from(checkout).join("tree-construction"));
+}
@@ -0,0 +1,349 @@
+use std::io::{BufReader, ErrorKind, Read};
+use std::ops::Range;
+
+use scah::{
+    Element, OwnedElement, OwnedStore, Query, Save, SourceLocation, Store, StreamingParser, parse,
+    parse_buf_reader, parse_reader,
+};
+
+#[derive(Debug, PartialEq)]
+struct Match {
+    name: String,
+    class: Option<String>,
+    id: Option<String>,
+    attributes: Vec<(String, Option<String>, Option<String>)>,
+    inner_html: Option<String>,
+    outer_html: Option<String>,
+    text_content: Option<String>,
+    start_tag_range: Range<usize>,
+    outer_range: Range<usize>,
+    location: SourceLocation,
+    children: Vec<(String, Vec<Match>)>,
+}
+
+// The same code reads both kinds of store, through the accessors they share.
+macro_rules! collect_matches {
+    ($name:ident, $store:ty, $element:ty) => {
+        fn $name<'a>(
+            store: &'a $store,
+            elements: impl Iterator<Item = &'a $element>,
+            children: &[&str],
+        ) -> Vec<Match> {
+            elements
+                .map(|element| Match {
+                    name: element.name().to_string(),
+                    class: element.class().map(str::to_string),
+                    id: element.id().map(str::to_string),
+                    attributes: element
+                        .attributes(store)
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|attribute| {
+                            (
+                                attribute.key.to_string(),
+                                attribute.value.as_deref().map(str::to_string),
+                                attribute.raw_value.as_deref().map(str::to_string),
+                            )
+                        })
+                        .collect(),
+                    inner_html: element.inner_html(store).map(str::to_string),
+                    outer_html: element.outer_html(store).map(str::to_string),
+                    text_content: element.text_content(store).map(str::to_string),
+                    start_tag_range: element.start_tag_range.clone(),
+                    outer_range: element.outer_range.clone(),
+                    location: element.location(store),
+                    children: children
+                        .iter()
+                        .filter_map(|child| {
+                            let elements = element.get(store, child)?;
+                            Some((child.to_string(), $name(store, elements, &[])))
+                        })
+                        .collect(),
+                })
+                .collect()
+        }
+    };
+}
+
+collect_matches!(from_store, Store<'a, 'a>, Element<'a>);
+collect_matches!(from_owned, OwnedStore, OwnedElement);
+
+const SELECTORS: [&str; 6] = [
+    "article",
+    "article > h2 + p",
+    "li:nth-child(2n)",
+    "section:has(> img)",
+    "script",
+    "textarea",
+];
+const CHILDREN: [&str; 2] = ["a", "span"];
+
+fn queries() -> Vec<Query<'static>> {
+    let mut queries: Vec<_> = SELECTORS[1..]
+        .iter()
+        .map(|selector| Query::all(selector, Save::all()).unwrap().build())
+        .collect();
+    queries.insert(
+        0,
+        Query::all("article", Save::all().with_outer_html())
+            .unwrap()
+            .then(|article| {
+                Ok([
+                    article.all("a", Save::all())?,
+                    article.all("span", Save::none())?,
+                ])
+            })
+            .unwrap()
+            .build(),
+    );
+    queries
+}
+
+fn document(articles: usize) -> String {
+    let mut html =
+        String::from("<!DOCTYPE html>\n<html><head><title>Déjà vu</title></head><body>\n");
+    for i in 0..articles {
+        html += &format!(
+            "<article id='a{i}' class=\"post\">\n  <h2>Post №{i}</h2><p>Hello &amp; <a href=\"/{i}?x=1&amp;y\">wörld</a> <span>😀</span></p>\n</article>\n<ul><li>one<li>two<li>three</ul>\n"
+        );
+        if i % 5 == 0 {
+            html += &format!(
+                "<section><img src=x{i}><!-- a <b> comment --></section><section>no image</section>\n"
+            );
+        }
+        if i % 7 == 0 {
+            html +=
+                "<script>if (a < b && '</scr' + 'ipt>') {}</script><textarea>a &lt; b</textarea>\n";
+        }
+    }
+    html += "<p>unclosed <b>at the end";
+    html
+}
+
+fn streamed(html: &str, queries: &[Query<'static>], chunk_size: usize) -> OwnedStore {
+    let mut parser = StreamingParser::new(queries);
+    for chunk in html.as_bytes().chunks(chunk_size) {
+        parser.feed(chunk);
+    }
+    parser.finish()
+}
+
+/// Reads at most `block` bytes at a time, and fails every other read with
+/// `Interrupted`.
+struct Trickle<'a> {
+    html: &'a [u8],
+    block: usize,
+    interrupt: bool,
+}
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        self.interrupt = !self.interrupt;
+        if self.interrupt {
+            return Err(ErrorKind::Interrupted.into());
+        }
+        let len = self.block.min(buffer.len()).min(self.html.len());
+        buffer[..len].copy_from_slice(&self.html[..len]);
+        self.html = &self.html[len..];
+        Ok(len)
+    }
+}
+
+fn assert_same_matches(html: &str, chunk_sizes: &[usize]) {
+    assert_same_matches_as(html, chunk_sizes, streamed);
+}
+
+fn assert_same_matches_as(
+    html: &str,
+    chunk_sizes: &[usize],
+    read: impl Fn(&str, &[Query<'static>], usize) -> OwnedStore,
+) {
+    let queries = queries();
+    let expected_store = parse(html, &queries);
+    let expected: Vec<_> = SELECTORS
+        .iter()
+        .map(|selector| {
+            let elements = expected_store.get(selector).unwrap();
+            from_store(&expected_store, elements, &CHILDREN)
+        })
+        .collect();
+
+    for &chunk_size in chunk_sizes {
+        let store = read(html, &queries, chunk_size);
+        let found: Vec<_> = SELECTORS
+            .iter()
+            .map(|selector| {
+                let elements = store.get(selector).unwrap();
+                from_owned(&store, elements, &CHILDREN)
+            })
+            .collect();
+        assert_eq!(found, expected, "chunks of {chunk_size} bytes");
+    }
+}
+
+#[test]
+fn test_streaming_matches_parse_on_small_document() {
+    let html = document(3);
+    assert_same_matches(&html, &[1, 2, 3, 7, 64, html.len()]);
+}
+
+#[test]
+fn test_streaming_matches_parse_on_large_document() {
+    let html = document(600);
+    assert!(html.len() > 100 * 1024);
+    assert_same_matches(&html, &[1, 7, 4096, 10_000, html.len()]);
+}
+
+#[test]
+fn test_parse_reader_matches_parse() {
+    let html = document(40);
+    assert_same_matches_as(&html, &[1, 2, 3, 64, 10_000], |html, queries, block| {
+        let reader = Trickle {
+            html: html.as_bytes(),
+            block,
+            interrupt: false,
+        };
+        parse_reader(reader, queries).unwrap()
+    });
+}
+
+#[test]
+fn test_parse_buf_reader_matches_parse() {
+    let html = document(40);
+    assert_same_matches_as(&html, &[1, 2, 3, 64, 10_000], |html, queries, capacity| {
+        let reader = BufReader::with_capacity(capacity, html.as_bytes());
+        parse_buf_reader(reader, queries).unwrap()
+    });
+}
+
+#[test]
+fn test_into_owned_matches_parse() {
+    let html = document(40);
+    assert_same_matches_as(&html, &[html.len()], |html, queries, _| {
+        parse(html, queries).into_owned()
+    });
+}
+
+#[test]
+fn test_into_owned_outlives_source_and_queries() {
+    let store = {
+        let html = String::from("<!DOCTYPE html><p id=a>one</p><p>two</p>");
+        let queries = vec![Query::all("p", Save::all()).unwrap().build()];
+        parse(&html, &queries).into_owned()
+    };
+
+    let texts: Vec<_> = store
+        .get("p")
+        .unwrap()
+        .map(|p| p.text_content(&store).unwrap())
+        .collect();
+    assert_eq!(texts, ["one", "two"]);
+    assert_eq!(store.doctype.unwrap().name.as_deref(), Some("html"));
+    assert_eq!(store.encoding, "UTF-8");
+}
+
+#[test]
+fn test_into_owned_copies_nested_html_once() {
+    let html = "<p>before</p><section><a href='/x'>x</a><a>y</a></section><a>z</a>";
+    let queries = &[
+        Query::all("section", Save::all().with_outer_html())
+            .unwrap()
+            .build(),
+        Query::all("a", Save::all().with_outer_html())
+            .unwrap()
+            .build(),
+    ];
+    let store = parse(html, queries).into_owned();
+
+    let section = store.get("section").unwrap().next().unwrap();
+    let outer_htmls: Vec<_> = store
+        .get("a")
+        .unwrap()
+        .map(|a| a.outer_html(&store).unwrap())
+        .collect();
+    assert_eq!(
+        section.outer_html(&store),
+        Some("<section><a href='/x'>x</a><a>y</a></section>")
+    );
+    assert_eq!(outer_htmls, ["<a href='/x'>x</a>", "<a>y</a>", "<a>z</a>"]);
+    assert_eq!(store.html, &html[13..]);
+}
+
+#[test]
+fn test_parse_reader_returns_read_errors() {
+    struct Failing;
+    impl Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(ErrorKind::InvalidData.into())
+        }
+    }
+
+    let queries = &[Query::all("a", Save::all()).unwrap().build()];
+    let error = parse_reader(Failing, queries).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_parse_reader_stops_once_queries_are_done() {
+    let queries = &[Query::first("a", Save::all()).unwrap().build()];
+    let mut reader = "<a href='/first'>First</a>"
+        .as_bytes()
+        .chain(std::io::repeat(b' '));
+    let store = parse_reader(&mut reader, queries).unwrap();
+
+    let a = store.get("a").unwrap().next().unwrap();
+    assert_eq!(a.attribute(&store, "href"), Some("/first"));
+}
+
+#[test]
+fn test_streaming_keeps_whole_saved_element() {
+    let html = format!("<main>{}</main><p>after</p>", "<i>x</i>".repeat(5000));
+    let queries = &[
+        Query::all("main", Save::all()).unwrap().build(),
+        Query::all("p", Save::all()).unwrap().build(),
+    ];
+    let store = streamed(&html, queries, 100);
+
+    let main = store.get("main").unwrap().next().unwrap();
+    let end = html.find("</main>").unwrap();
+    assert_eq!(main.inner_html(&store), Some(&html[6..end]));
+    assert_eq!(main.outer_range, 0..end + 7);
+    let p = store.get("p").unwrap().next().unwrap();
+    assert_eq!(&html[p.outer_range.clone()], "<p>after</p>");
+}
+
+#[test]
+fn test_streaming_replaces_invalid_utf8() {
+    let queries = &[Query::all("p", Save::all()).unwrap().build()];
+    let mut parser = StreamingParser::new(queries);
+    for chunk in [&b"<p>caf\xC3"[..], b"\xA9 \xFF</p><p>\xE2\x82"] {
+        parser.feed(chunk);
+    }
+    let store = parser.finish();
+
+    let texts: Vec<_> = store
+        .get("p")
+        .unwrap()
+        .map(|p| p.text_content(&store).unwrap())
+        .collect();
+    assert_eq!(texts, ["café \u{FFFD}", "\u{FFFD}"]);
+}
+
+#[test]
+fn test_streaming_stops_once_queries_are_done() {
+    let queries = &[Query::first("a", Save::all()).unwrap().build()];
+    let mut parser = StreamingParser::new(queries);
+    parser.feed(b"<div><a href='/first'>First</a>");
+    let filler = "<p>filler</p>".repeat(1000);
+    parser.feed(filler.as_bytes());
+    assert!(parser.is_done());
+    parser.feed(b"<a href='/second'>Second</a></div>");
+
+    let store = parser.finish();
+    let links: Vec<_> = store
+        .get("a")
+        .unwrap()
+        .map(|a| a.attribute(&store, "href").unwrap())
+        .collect();
+    assert_eq!(links, ["/first"]);
+}